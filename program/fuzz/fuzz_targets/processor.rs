@@ -0,0 +1,215 @@
+//! Runs the processor natively on arbitrary instruction data and account pre-states.
+//! Accounts hold valid store and SPL token layouts with arbitrary fields, keys are taken from
+//! a small pool including the program derived addresses, so that checks are passed often enough
+//! to reach deep instruction paths. CPIs succeed without effect.
+//!
+//! Run with `cargo fuzz run processor` from the `program` directory.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, program_pack::Pack,
+    program_stubs, pubkey::Pubkey, rent::Rent, system_program, sysvar,
+};
+use solana_test::{processor::Processor, state::Store};
+
+#[derive(Arbitrary, Debug)]
+struct FuzzInput {
+    tag: u8,
+    data: Vec<u8>,
+    accounts: Vec<FuzzAccount>,
+}
+
+#[derive(Arbitrary, Debug)]
+struct FuzzAccount {
+    key: u8,
+    is_signer: bool,
+    is_writable: bool,
+    lamports: u64,
+    owner: FuzzOwner,
+    state: FuzzState,
+}
+
+#[derive(Arbitrary, Debug)]
+enum FuzzOwner {
+    Program,
+    Token,
+    System,
+}
+
+#[derive(Arbitrary, Debug)]
+enum FuzzState {
+    Empty,
+    Raw(Vec<u8>),
+    Store {
+        price: u64,
+        owner: u8,
+        payment_tokens: u8,
+        store_tokens: u8,
+        lot_size: u64,
+        cosigner: u8,
+        cosign_threshold: u64,
+        cost_basis_amount: u64,
+        cost_basis: u64,
+    },
+    Token {
+        mint: u8,
+        owner: u8,
+        amount: u64,
+        delegate: Option<u8>,
+        delegated_amount: u64,
+    },
+}
+
+fn program_id() -> Pubkey {
+    Pubkey::new_from_array([7; 32])
+}
+
+fn key_pool() -> Vec<Pubkey> {
+    let mut keys = vec![
+        program_id(),
+        Pubkey::find_program_address(&[b"store"], &program_id()).0,
+        spl_token::id(),
+        system_program::id(),
+        sysvar::rent::id(),
+        sysvar::instructions::id(),
+    ];
+    keys.extend((1..=10).map(|n| Pubkey::new_from_array([n; 32])));
+    keys
+}
+
+fn pick(keys: &[Pubkey], index: u8) -> Pubkey {
+    keys[index as usize % keys.len()]
+}
+
+fn account_data(keys: &[Pubkey], state: &FuzzState) -> Vec<u8> {
+    match *state {
+        FuzzState::Empty => vec![],
+        FuzzState::Raw(ref data) => data.clone(),
+        FuzzState::Store {
+            price,
+            owner,
+            payment_tokens,
+            store_tokens,
+            lot_size,
+            cosigner,
+            cosign_threshold,
+            cost_basis_amount,
+            cost_basis,
+        } => {
+            let store = Store {
+                is_initialized: true,
+                price,
+                owner_pubkey: pick(keys, owner),
+                native_tokens_to_auto_sell_pubkey: pick(keys, payment_tokens),
+                store_tokens_to_auto_buy_pubkey: pick(keys, store_tokens),
+                lot_size,
+                cosigner_pubkey: pick(keys, cosigner),
+                cosign_threshold,
+                cost_basis_amount,
+                cost_basis,
+                ..Store::default()
+            };
+            let mut data = vec![0; Store::LEN];
+            store.pack_into_slice(&mut data);
+            data
+        }
+        FuzzState::Token {
+            mint,
+            owner,
+            amount,
+            delegate,
+            delegated_amount,
+        } => {
+            let token_account = spl_token::state::Account {
+                mint: pick(keys, mint),
+                owner: pick(keys, owner),
+                amount,
+                delegate: delegate.map(|delegate| pick(keys, delegate)).into(),
+                delegated_amount,
+                state: spl_token::state::AccountState::Initialized,
+                ..spl_token::state::Account::default()
+            };
+            let mut data = vec![0; spl_token::state::Account::LEN];
+            token_account.pack_into_slice(&mut data);
+            data
+        }
+    }
+}
+
+/// Serves sysvars, CPIs and return data without a runtime
+struct FuzzStubs;
+
+impl program_stubs::SyscallStubs for FuzzStubs {
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Clock) = Clock::default() };
+        0
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        0
+    }
+}
+
+fn process(input: &FuzzInput) -> ProgramResult {
+    let keys = key_pool();
+    let mut states: Vec<(Pubkey, u64, Vec<u8>, Pubkey)> = input
+        .accounts
+        .iter()
+        .take(16)
+        .map(|account| {
+            let owner = match account.owner {
+                FuzzOwner::Program => program_id(),
+                FuzzOwner::Token => spl_token::id(),
+                FuzzOwner::System => system_program::id(),
+            };
+            (
+                pick(&keys, account.key),
+                account.lamports,
+                account_data(&keys, &account.state),
+                owner,
+            )
+        })
+        .collect();
+    let account_infos: Vec<AccountInfo> = states
+        .iter_mut()
+        .zip(input.accounts.iter())
+        .map(|((key, lamports, data, owner), account)| {
+            AccountInfo::new(
+                key,
+                account.is_signer,
+                account.is_writable,
+                lamports,
+                data,
+                owner,
+                false,
+                0,
+            )
+        })
+        .collect();
+
+    let program_id = program_id();
+    let mut data = vec![input.tag];
+    data.extend_from_slice(&input.data);
+    Processor::process(&program_id, &account_infos, &data)?;
+
+    // a successful instruction must leave every program owned store readable
+    for account in &account_infos {
+        if account.owner == &program_id && account.data_len() == Store::LEN {
+            Store::unpack_unchecked(&account.data.borrow()).expect("store stays readable");
+        }
+    }
+    Ok(())
+}
+
+fuzz_target!(|input: FuzzInput| {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| {
+        program_stubs::set_syscall_stubs(Box::new(FuzzStubs));
+    });
+
+    let _ = process(&input);
+});
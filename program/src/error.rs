@@ -6,6 +6,96 @@ use solana_program::program_error::ProgramError;
 pub enum StoreError {
     #[error("Account Price Mismatch")]
     AccountPriceMismatch,
+    #[error("Amount Is Not Multiple Of Lot Size")]
+    InvalidLotAmount,
+    #[error("Store Migrated")]
+    StoreMigrated,
+    #[error("Store Has Enough Inventory")]
+    InventoryAvailable,
+    #[error("Trade Requires Cosigner")]
+    CosignerRequired,
+    #[error("Session Expired")]
+    SessionExpired,
+    #[error("Session Limit Exceeded")]
+    SessionLimitExceeded,
+    #[error("Invalid Order Signature")]
+    InvalidOrderSignature,
+    #[error("Order Expired")]
+    OrderExpired,
+    #[error("Order Price Limit Exceeded")]
+    OrderPriceLimit,
+    #[error("Order Nonce Already Used")]
+    NonceUsed,
+    #[error("Balance Assertion Failed")]
+    BalanceAssertionFailed,
+    #[error("Trade Requires Membership Token")]
+    MembershipRequired,
+    #[error("Discount Not Applicable")]
+    InvalidDiscount,
+    #[error("Escrowed Buy Must Be Signed By Escrow Payer")]
+    EscrowPayerRequired,
+    #[error("Escrow Refund Window Open")]
+    EscrowLocked,
+    #[error("Refund Window Closed")]
+    RefundWindowClosed,
+    #[error("Invoice Already Paid")]
+    InvoicePaid,
+    #[error("Store Closed")]
+    StoreClosed,
+    #[error("Offer Expired")]
+    OfferExpired,
+    #[error("Token Mint Mismatch")]
+    MintMismatch,
+    #[error("Store Feature Disabled")]
+    FeatureDisabled,
+    #[error("Math Overflow")]
+    MathOverflow,
+    #[error("Outside Trading Hours")]
+    OutsideTradingHours,
+    #[error("Store Paused")]
+    StorePaused,
+    #[error("Non Canonical Instruction Data")]
+    NonCanonicalInstruction,
+    #[error("Zero Amount")]
+    ZeroAmount,
+    #[error("Fee Vault Required")]
+    FeeVaultRequired,
+    #[error("Fee Schedule Full")]
+    FeeScheduleFull,
+    #[error("Store Winding Down")]
+    StoreWindingDown,
+    #[error("Wind Down Ended")]
+    WindDownEnded,
+    #[error("Trade Limit Exceeded")]
+    TradeLimitExceeded,
+    #[error("Inventory Exhausted")]
+    InventoryExhausted,
+    #[error("Store Already Registered")]
+    StoreAlreadyRegistered,
+    #[error("Store Not Registered")]
+    StoreNotRegistered,
+    #[error("Registry Full")]
+    RegistryFull,
+    #[error("Escheat Period Not Elapsed")]
+    EscheatPeriodNotElapsed,
+    #[error("Store Not Deleted")]
+    StoreNotDeleted,
+    #[error("Payment Mint Not Allowed")]
+    PaymentMintNotAllowed,
+    #[error("Wallet Cap Exceeded")]
+    WalletCapExceeded,
+    #[error("Market Closed")]
+    MarketClosed,
+    #[error("Settlement Pending")]
+    SettlementPending,
+    #[error("No Settlement Due")]
+    NoSettlementDue,
+    #[error("Sell Not Supported")]
+    SellNotSupported,
+    #[error("Reserves Required")]
+    ReservesRequired,
+    #[error("Escrow Vault Not Empty")]
+    EscrowVaultNotEmpty,
 }
 
 impl From<StoreError> for ProgramError {
@@ -1,137 +1,4076 @@
-use std::{convert::TryInto, mem::size_of};
+use std::{
+    convert::{TryFrom, TryInto},
+    mem::size_of,
+    slice,
+};
 
+use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
+    bpf_loader_upgradeable, ed25519_program,
     instruction::{AccountMeta, Instruction},
+    message::Message,
     program_error::ProgramError,
+    program_pack::Pack,
     pubkey::Pubkey,
-    sysvar,
+    rent::Rent,
+    system_instruction, system_program, sysvar,
+};
+
+use crate::{
+    error::StoreError,
+    state::{EscrowRecord, Offer, OrderSide, SignedOrder, Store, NONCES_PER_BITMAP},
 };
 
-pub enum StoreInstruction {
-    ///   0. `[signer]` The initializer's account, which will be set as owner of store account
-    ///   0. `[writable]` The store account
-    ///   0. `[writable]` account with payment tokens, to take tokens when sell, (owner will be updated to program)
-    ///   0. `[writable]` account with store tokens, to take tokens when buy, (owner will be updated to program)
-    ///   0. `[]` The token program
-    ///   0. `[]` Rent sysvar
-    InitializeAccount { price: u64 },
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+pub enum BalanceCheck {
+    AtLeast,
+    Exactly,
+}
+
+/// Token account balance asserted by `AssertBalances`
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct BalanceAssertion {
+    pub check: BalanceCheck,
+    pub amount: u64,
+}
+
+impl BalanceAssertion {
+    const LEN: usize = 1 + 8;
+}
+
+/// Max number of stores bought from by one `BatchBuy`
+pub const MAX_BATCH_LEGS: usize = 8;
+
+/// Buy from one store of `BatchBuy`
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct BatchLeg {
+    pub amount: u64,
+    /// max price to buy at, the trade is at the store price
+    pub max_price: u64,
+    /// number of the leg accounts, Buy accounts starting from the store account
+    pub accounts: u8,
+}
+
+impl BatchLeg {
+    const LEN: usize = 8 + 8 + 1;
+}
+
+/// Pricing mode set by `SetPricing`, see `Store::pricing_engine`
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+pub enum Pricing {
+    /// trades at the store buy and sell prices
+    Fixed,
+    /// sells at a price decaying linearly from `start_price` to `floor_price` between `start_slot`
+    /// and `end_slot`, per the store price denominator, sells to the store are rejected
+    DutchAuction {
+        start_price: u64,
+        floor_price: u64,
+        start_slot: u64,
+        end_slot: u64,
+    },
+    /// trades on the constant product curve of the store vault balances with the virtual reserves
+    /// added, see `math::quote_constant_product`. Buy and Sell settle to the store vaults, not
+    /// the proceeds and payout accounts, prices of the instructions bound the average price of
+    /// the trade.
+    ConstantProduct {
+        /// sets the curve price with the vault balances, not zero
+        virtual_payment_reserve: u64,
+        virtual_store_reserve: u64,
+    },
+}
+
+/// Tag of instruction data encoded with Borsh, followed by the Borsh encoded `StoreInstruction`.
+/// Other tags are the hand packed layout, kept for the instructions which already have it.
+pub const BORSH_INSTRUCTION_TAG: u8 = 0xff;
+
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+pub enum StoreInstruction {
+    /// Store token accounts are either given by the owner, or created by the program as PDAs
+    /// if both don't exist yet, then the owner funds them with Deposit.
+    ///
+    ///   0. `[signer, writable]` The initializer's account, which will be set as owner of store account, pays for vaults creation
+    ///   0. `[writable]` The store account
+    ///   0. `[writable]` account with payment tokens, to take tokens when sell, (owner will be updated to the store PDA),
+    ///      or to be created, PDA of `["vault", store account, payment mint]`
+    ///   0. `[writable]` account with store tokens, to take tokens when buy, (owner will be updated to the store PDA),
+    ///      or to be created, PDA of `["vault", store account, store mint]`
+    ///   0. `[]` The token program
+    ///   0. `[]` Rent sysvar
+    ///   0. `[]` The program config, PDA of `["config"]`, may not exist
+    ///   0. `[writable]` The program stats, PDA of `["stats"]`, may not exist. If it does, the store is
+    ///      counted in it and its trades must pass the mint stats
+    ///   0. `[]` optional, fee vault if `fee_bps` is set, account with payment tokens owned by the store PDA
+    ///   0. `[]` payment tokens mint, if vaults are created
+    ///   0. `[]` store tokens mint, if vaults are created
+    ///   0. `[]` The system program, if vaults are created
+    ///   0. `[writable]` mint stats of the payment mint, PDA of `["stats", payment mint]`, if the program
+    ///      stats exist. Created unless it exists, paid by the initializer
+    ///   0. `[]` The system program, if the program stats exist
+    InitializeAccount {
+        price: u64,
+        /// fee charged on trades notional, in basis points
+        fee_bps: Option<u16>,
+        /// first slot of the trading window, Borsh encoded only (0 - open right away)
+        open_at_slot: u64,
+        /// slot the trading window closes at, Borsh encoded only (0 - never closes)
+        close_at_slot: u64,
+    },
+
+    /// Sets both buy and sell price
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    UpdatePrice { price: u64 },
+
+    ///   0. `[signer]` owner of token accounts to transfer
+    ///   0. `[]` The store account
+    ///   0. `[writable]` store account to receive payment tokens (configured proceeds account, or any of the store owner, escrow account if set)
+    ///   0. `[writable]` store account with store tokens (same as in store info account)
+    ///   0. `[writable]` user account to transfer payment tokens from (owner is signer)
+    ///   0. `[writable]` user account for store tokens
+    ///   0. `[]` The PDA account
+    ///   0. `[]` The token program
+    ///   0. `[signer]` optional, cosigner if trade notional is above store threshold
+    ///   0. `[]` optional, user token account of the store gate mint if it's set
+    ///   0. `[writable]` optional, fee vault of the store if it charges a fee, the fee is paid on top of the notional
+    ///   0. `[writable]` optional, escrow account if store has escrow, PDA of `["escrow", store account, store escrow sequence]`
+    ///   0. `[signer, writable]` optional, pays for escrow account creation if store has escrow, gets it back on close
+    ///   0. `[]` optional, system program if store has escrow
+    ///   0. `[writable]` optional, mint stats of the store payment mint if the store tracks stats
+    ///   0. `[writable]` optional, purchase ledger of the wallet receiving store tokens if store caps purchases per wallet,
+    ///      PDA of `["purchases", store account, owner of the user account for store tokens]`
+    ///   0. `[signer, writable]` optional, pays for purchase ledger creation if store caps purchases per wallet
+    ///   0. `[]` optional, system program if store caps purchases per wallet
+    ///   0. `[]` optional, user token account holding NFT of a discounted collection
+    ///   0. `[]` optional, metadata account of the NFT
+    ///   0. `[]` optional, discount account of the NFT collection
+    Buy {
+        amount: u64,
+        /// max price to buy at, the trade is at the store price
+        max_price: u64,
+        /// optional client tag logged with the trade for attribution, doesn't affect settlement
+        tag: Option<u16>,
+    },
+
+    ///   0. `[signer]` owner of store tokens account to sell
+    ///   0. `[]` The store account
+    ///   0. `[writable]` store account with payment tokens for sell payment (same as in store info account)
+    ///   0. `[writable]` account to transfer store tokens to (configured payout account, or any of the store owner)
+    ///   0. `[writable]` user account to transfer payment tokens to
+    ///   0. `[writable]` user account with store tokens to sell (owner is signer)
+    ///   0. `[]` The PDA account
+    ///   0. `[]` The token program
+    ///   0. `[signer]` optional, cosigner if trade notional is above store threshold
+    ///   0. `[]` optional, user token account of the store gate mint if it's set
+    ///   0. `[writable]` optional, fee vault of the store if it charges a fee, the fee is deducted from the notional
+    ///   0. `[writable]` optional, mint stats of the store payment mint if the store tracks stats
+    Sell {
+        amount: u64,
+        /// min price to sell at, the trade is at the store price
+        min_price: u64,
+        /// optional client tag logged with the trade for attribution, doesn't affect settlement
+        tag: Option<u16>,
+    },
+
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    UpdateLotSize {
+        /// trade amounts must be multiple of lot size (0 - any amount)
+        lot_size: u64,
+    },
+
+    /// Snapshots bid and ask prices of the store and the liquidity of both sides to its depth
+    /// account, callable by anyone
+    ///
+    ///   0. `[signer, writable]` The payer for depth account creation
+    ///   0. `[]` The store account
+    ///   0. `[]` store account with store tokens (same as in store info account)
+    ///   0. `[]` store account with payment tokens (same as in store info account)
+    ///   0. `[writable]` The depth account, PDA of `["depth", store account]`
+    ///   0. `[]` The system program
+    PublishDepth,
+
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account to migrate from (trades will be rejected)
+    ///   0. `[]` The store account to migrate to (must have same owner)
+    SetRedirect,
+
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    SetInventoryThresholds {
+        /// warn when store tokens account drops below (0 - disabled)
+        store_tokens: u64,
+        /// warn when payment tokens account drops below (0 - disabled)
+        payment_tokens: u64,
+    },
+
+    /// Joining again updates the amount, keeping the registration slot
+    ///
+    ///   0. `[signer]` The buyer
+    ///   0. `[signer, writable]` The payer for waitlist account creation, may be the buyer
+    ///   0. `[]` The store account
+    ///   0. `[]` store account with store tokens (same as in store info account)
+    ///   0. `[writable]` The waitlist account, PDA of `["waitlist", store account, buyer]`
+    ///   0. `[]` The system program
+    JoinWaitlist {
+        /// store tokens amount buyer wants to buy, must exceed current inventory
+        amount: u64,
+    },
+
+    ///   0. `[signer, writable]` The buyer, receives waitlist account lamports
+    ///   0. `[writable]` The waitlist account
+    LeaveWaitlist,
+
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    ///   0. `[]` account to receive payment tokens on buy
+    ///   0. `[]` account to receive store tokens on sell
+    SetSettlementAccounts,
+
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    ///   0. `[]` The cosigner account
+    SetCosigner {
+        /// trade notional in payment tokens requiring cosigner (0 - disabled)
+        threshold: u64,
+    },
+
+    ///   0. `[signer]` The wallet
+    ///   0. `[signer, writable]` The payer for session account creation, may be the wallet
+    ///   0. `[]` The store account
+    ///   0. `[]` The session key
+    ///   0. `[writable]` The session account, PDA of `["session", store account, wallet, session key]`
+    ///   0. `[]` The system program
+    ///
+    /// Wallet token accounts must approve the session account as delegate to trade
+    CreateSession {
+        /// max total payment tokens amount of session trades
+        max_notional: u64,
+        /// unix timestamp session expires at
+        expires_at: i64,
+    },
+
+    ///   0. `[signer, writable]` The wallet, receives session account lamports
+    ///   0. `[writable]` The session account
+    RevokeSession,
+
+    /// Same as `Buy`, signed by session key and paid from wallet accounts delegated to session
+    ///
+    ///   0. `[signer]` The session key
+    ///   0. `[writable]` The session account
+    ///   0. `[]` The store account
+    ///   0. `[writable]` store account to receive payment tokens (configured proceeds account, or any of the store owner)
+    ///   0. `[writable]` store account with store tokens (same as in store info account)
+    ///   0. `[writable]` wallet account to transfer payment tokens from (session is delegate)
+    ///   0. `[writable]` wallet account for store tokens
+    ///   0. `[]` The PDA account
+    ///   0. `[]` The token program
+    ///   0. `[signer]` optional, cosigner if trade notional is above store threshold
+    SessionBuy { amount: u64, price: u64 },
+
+    /// Same as `Sell`, signed by session key and paid from wallet accounts delegated to session
+    ///
+    ///   0. `[signer]` The session key
+    ///   0. `[writable]` The session account
+    ///   0. `[]` The store account
+    ///   0. `[writable]` store account with payment tokens for sell payment (same as in store info account)
+    ///   0. `[writable]` account to transfer store tokens to (configured payout account, or any of the store owner)
+    ///   0. `[writable]` wallet account to transfer payment tokens to
+    ///   0. `[writable]` wallet account with store tokens to sell (session is delegate)
+    ///   0. `[]` The PDA account
+    ///   0. `[]` The token program
+    ///   0. `[signer]` optional, cosigner if trade notional is above store threshold
+    SessionSell { amount: u64, price: u64 },
+
+    /// Executes order signed off-chain by the wallet, at the store quote within the order limit.
+    /// Must directly follow the ed25519 program instruction verifying the wallet signature
+    /// of the packed `SignedOrder` message. Can be submitted by anyone.
+    ///
+    ///   0. `[]` The instructions sysvar
+    ///   0. `[]` The order authority, PDA of `["order", wallet]`, delegate of wallet token accounts
+    ///   0. `[writable]` The nonce bitmap account, PDA of `["nonces", wallet, order nonce / 2048]`
+    ///   0. `[signer, writable]` The payer for nonce bitmap account creation
+    ///   0. `[]` The system program
+    ///   0. `[]` The store account
+    ///   0. `[writable]` store account to receive tokens from the wallet (same as in Buy/Sell)
+    ///   0. `[writable]` store account to transfer tokens to the wallet from (same as in Buy/Sell)
+    ///   0. `[writable]` wallet account with payment tokens
+    ///   0. `[writable]` wallet account with store tokens
+    ///   0. `[]` The PDA account
+    ///   0. `[]` The token program
+    ///   0. `[signer]` optional, cosigner if trade notional is above store threshold
+    SettleSignedOrder,
+
+    /// Marks signed order nonce used without trading
+    ///
+    ///   0. `[signer]` The wallet
+    ///   0. `[signer, writable]` The payer for nonce bitmap account creation, may be the wallet
+    ///   0. `[writable]` The nonce bitmap account, PDA of `["nonces", wallet, nonce / 2048]`
+    ///   0. `[]` The system program
+    CancelSignedOrder { nonce: u64 },
+
+    /// Returns `PnlReport` of the store as return data, changes nothing
+    ///
+    ///   0. `[]` The store account
+    ///   0. `[]` optional, valuation account of the store, unrealized PnL is at its mark price
+    ReportPnl,
+
+    /// Performs all checks of Buy or Sell without moving tokens,
+    /// returns `TradePreview` as return data
+    ///
+    /// Accounts are the same as Buy or Sell of the side, cosigner is not required
+    PreviewTrade {
+        side: OrderSide,
+        amount: u64,
+        /// max price on buy, min price on sell
+        price: u64,
+    },
+
+    /// Fails unless token accounts hold the asserted amounts,
+    /// to guard previous instructions of the transaction
+    ///
+    ///   0. `[]` token account for each assertion, in the same order
+    AssertBalances { assertions: Vec<BalanceAssertion> },
+
+    /// Grows store account to the current layout size, topping up rent exemption from the owner.
+    /// The data keeps its layout version, MigrateStore upgrades it.
+    ///
+    ///   0. `[signer, writable]` The owner of store account
+    ///   0. `[writable]` The store account
+    ///   0. `[]` The system program
+    Realloc,
+
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    ///   0. `[]` The membership tokens mint, trades are open to anyone if it's the default pubkey
+    SetGateMint,
+
+    ///   0. `[signer, writable]` The owner of store account, pays for discount account creation
+    ///   0. `[]` The store account
+    ///   0. `[]` The collection NFT mint
+    ///   0. `[writable]` The discount account, PDA of `["discount", store account, collection mint]`
+    ///   0. `[]` The system program
+    SetDiscountTier {
+        /// discount of buy notional for collection holders in basis points (0 - disabled)
+        discount_bps: u16,
+    },
+
+    /// Updates prices of several stores of the owner at once, fails for all if any store fails
+    ///
+    ///   0. `[signer]` The owner of store accounts
+    ///   0. `[writable]` store account for each price, in the same order
+    UpdatePricesBatch { prices: Vec<u64> },
+
+    /// Same as `UpdatePrice`, also appending the price to the store price feed hash chain
+    ///
+    ///   0. `[signer, writable]` The owner of store account, pays for feed account creation
+    ///   0. `[writable]` The store account
+    ///   0. `[writable]` The price feed account, PDA of `["feed", store account]`
+    ///   0. `[]` The system program
+    AttestPrice { price: u64 },
+
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    ///   0. `[]` store account to hold buy payments, owned by the PDA (omitted when window is 0)
+    SetEscrow {
+        /// seconds buy payments stay in escrow and can be refunded (0 - disabled)
+        window: i64,
+    },
+
+    /// Refunds escrowed buy within the refund window, the buyer must have approved the PDA
+    /// as delegate of the account which received store tokens. The fee paid on top of the escrowed
+    /// payment is refunded from the fee vault.
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    ///   0. `[writable]` The escrow account
+    ///   0. `[writable]` store account holding the payment
+    ///   0. `[writable]` store account with store tokens (same as in store info account)
+    ///   0. `[writable]` buyer account to refund payment tokens to
+    ///   0. `[writable]` buyer account to return store tokens from
+    ///   0. `[writable]` The escrow payer, receives escrow account lamports
+    ///   0. `[]` The PDA account
+    ///   0. `[]` The token program
+    ///   0. `[writable]` optional, purchase ledger of the owner of the buyer account with store tokens
+    ///      if store caps purchases per wallet
+    ///   0. `[writable]` optional, fee vault of the store if the escrowed buy paid a fee
+    RefundEscrow,
+
+    /// Pays escrowed buy to the store after the refund window, callable by anyone
+    ///
+    ///   0. `[]` The store account
+    ///   0. `[writable]` The escrow account
+    ///   0. `[writable]` store account holding the payment
+    ///   0. `[writable]` store account to receive payment tokens (configured proceeds account, or any of the store owner)
+    ///   0. `[writable]` The escrow payer, receives escrow account lamports
+    ///   0. `[]` The PDA account
+    ///   0. `[]` The token program
+    ReleaseEscrow,
+
+    ///   0. `[signer, writable]` The owner of store account, pays for invoice account creation
+    ///   0. `[]` The store account
+    ///   0. `[writable]` The invoice account, PDA of `["invoice", store account, reference]`
+    ///   0. `[]` The system program
+    CreateInvoice {
+        /// store tokens amount, priced at the current store price
+        amount: u64,
+        /// merchant reference, unique per store
+        reference: [u8; 32],
+    },
+
+    /// Pays the invoice, once. Buys its amount at its notional, otherwise as Buy does: store checks,
+    /// trade limits, membership, cosigner, fee, escrow and purchase ledger apply the same.
+    ///
+    ///   0. `[signer]` owner of token accounts to transfer
+    ///   0. `[writable]` The invoice account
+    ///   0. `[writable]` The store account
+    ///   0. `[writable]` store account to receive payment tokens (configured proceeds account, or any of the store owner, escrow account if set)
+    ///   0. `[writable]` store account with store tokens (same as in store info account)
+    ///   0. `[writable]` user account to transfer payment tokens from (owner is signer)
+    ///   0. `[writable]` user account for store tokens
+    ///   0. `[]` The PDA account
+    ///   0. `[]` The token program
+    PayInvoice,
+
+    /// Proposes new owner of the store, who takes over with `AcceptOwner`
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    ///   0. `[]` The new owner, default pubkey cancels the proposal
+    ProposeOwner,
+
+    ///   0. `[signer]` The proposed owner
+    ///   0. `[writable]` The store account
+    AcceptOwner,
+
+    /// Returns store token accounts to the owner, the store rejects trades afterwards
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    ///   0. `[writable]` account with payment tokens (same as in store info account)
+    ///   0. `[writable]` account with store tokens (same as in store info account)
+    ///   0. `[]` The PDA account
+    ///   0. `[]` The token program
+    ReleaseAccounts,
+
+    /// Deletes the store, leaving a tombstone with the owner so the address can't be initialized again.
+    /// The tombstone keeps its rent exempt balance, the rest of the lamports go to the owner.
+    /// Token accounts are returned to the owner unless already released by ReleaseAccounts, the fee vault
+    /// and escrow account always. Rejected until the wind down deadline if the store winds down, or while
+    /// the escrow account holds payments.
+    ///
+    ///   0. `[signer, writable]` The owner of store account
+    ///   0. `[writable]` The store account
+    ///   0. `[writable]` account with payment tokens (same as in store info account), if not released or any vault below is set
+    ///   0. `[writable]` account with store tokens (same as in store info account), if not released or any vault below is set
+    ///   0. `[]` The PDA account, if not released or any vault below is set
+    ///   0. `[]` The token program, if not released or any vault below is set
+    ///   0. `[writable]` fee vault of the store, if set and not one of the accounts above
+    ///   0. `[writable]` escrow account of the store, if set and not one of the accounts above
+    ///   0. `[writable]` The program stats, if the store tracks stats
+    ///   0. `[writable]` mint stats of the store payment mint, if the store tracks stats
+    CloseStore,
+
+    /// Offers to buy tokens of the mint of the receiving account, the payment is moved
+    /// to the offer vault which is handed over to the offer account until the offer is accepted or canceled
+    ///
+    ///   0. `[signer, writable]` The maker, pays for offer account creation
+    ///   0. `[writable]` The offer account, PDA of `["offer", offer vault]`
+    ///   0. `[writable]` The offer vault, empty maker account with payment tokens
+    ///   0. `[writable]` maker account with payment tokens
+    ///   0. `[]` maker account to receive bought tokens
+    ///   0. `[]` The token program
+    ///   0. `[]` The system program
+    CreateBuyOffer {
+        /// tokens amount
+        amount: u64,
+        /// amount payment tokens per token
+        price: u64,
+        /// unix timestamp offer expires at
+        expires_at: i64,
+    },
+
+    /// Sells the offered amount of tokens to the maker for the escrowed payment
+    ///
+    ///   0. `[signer]` The taker
+    ///   0. `[writable]` The offer account
+    ///   0. `[writable]` The offer vault
+    ///   0. `[writable]` taker account with tokens to sell
+    ///   0. `[writable]` maker account to receive bought tokens (same as in offer)
+    ///   0. `[writable]` taker account to receive payment tokens
+    ///   0. `[writable]` The maker, receives offer and vault accounts lamports, or the vault if it holds more than the escrow
+    ///   0. `[]` The token program
+    AcceptBuyOffer,
+
+    /// Returns the escrowed tokens to the maker and closes the offer, buy or sell
+    ///
+    ///   0. `[signer, writable]` The maker, receives offer and vault accounts lamports
+    ///   0. `[writable]` The offer account
+    ///   0. `[writable]` The offer vault
+    ///   0. `[writable]` maker account to receive escrowed tokens, and any others sent to the vault
+    ///   0. `[]` The token program
+    CancelOffer,
+
+    /// Offers to sell tokens for payment tokens of the mint of the receiving account,
+    /// the tokens are moved to the offer vault handed over to the offer account
+    ///
+    ///   0. `[signer, writable]` The maker, pays for offer account creation
+    ///   0. `[writable]` The offer account, PDA of `["offer", offer vault]`
+    ///   0. `[writable]` The offer vault, empty maker account with tokens to sell
+    ///   0. `[writable]` maker account with tokens to sell
+    ///   0. `[]` maker account to receive payment tokens
+    ///   0. `[]` The token program
+    ///   0. `[]` The system program
+    CreateSellOffer {
+        /// tokens amount
+        amount: u64,
+        /// amount payment tokens per token
+        price: u64,
+        /// unix timestamp offer expires at
+        expires_at: i64,
+    },
+
+    /// Buys the offered tokens from the maker, paying at the offer price
+    ///
+    ///   0. `[signer]` The taker
+    ///   0. `[writable]` The offer account
+    ///   0. `[writable]` The offer vault
+    ///   0. `[writable]` taker account with payment tokens
+    ///   0. `[writable]` maker account to receive payment tokens (same as in offer)
+    ///   0. `[writable]` taker account to receive bought tokens
+    ///   0. `[writable]` The maker, receives offer and vault accounts lamports, or the vault if it holds more than the escrow
+    ///   0. `[]` The token program
+    AcceptSellOffer,
+
+    /// Sets prices users buy and sell at, the sell price can't be above the buy price
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    UpdatePrices { buy_price: u64, sell_price: u64 },
+
+    /// Transfers tokens from one of store token accounts to any account
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[]` The store account
+    ///   0. `[writable]` account with payment tokens or account with store tokens (same as in store info account)
+    ///   0. `[writable]` account to receive tokens
+    ///   0. `[]` The PDA account
+    ///   0. `[]` The token program
+    Withdraw { amount: u64 },
+
+    /// Transfers owner tokens to one of store token accounts
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[]` The store account
+    ///   0. `[writable]` owner account to take tokens from
+    ///   0. `[writable]` account with store tokens for `Buy` side, account with payment tokens for `Sell` side (same as in store info account)
+    ///   0. `[]` The token program
+    Deposit {
+        amount: u64,
+        /// user side the tokens are used for
+        side: OrderSide,
+    },
+
+    /// Sets enabled store features, may be sent along with InitializeAccount.
+    /// Disabling a feature doesn't undo flows configured before.
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    SetFeatures {
+        /// `FEATURE_*` bits
+        features: u32,
+    },
+
+    /// Sets weekly hours the store accepts trades in, see `Store::trading_hours`
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    SetTradingHours {
+        /// a bit per UTC hour of the week starting Monday 00:00, none set - always open
+        hours: [u8; 21],
+    },
+
+    /// Sets the guardian, which can pause the store but not resume it or move any tokens
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    ///   0. `[]` The guardian account, default pubkey to remove the guardian
+    SetGuardian,
+
+    /// Stops trades of the store until the owner resumes it
+    ///
+    ///   0. `[signer]` The owner or the guardian of store account
+    ///   0. `[writable]` The store account
+    Pause,
+
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    Resume,
+
+    /// Same as `Buy`, logging the trade with a reference key to find the transaction by,
+    /// as in Solana Pay
+    ///
+    ///   0. `[]` reference account, any key, usually unique per order
+    ///   0. Accounts of `Buy`
+    BuyWithReference {
+        amount: u64,
+        /// max price to buy at, the trade is at the store price
+        max_price: u64,
+        /// optional client tag, same as in `Buy`
+        tag: Option<u16>,
+    },
+
+    /// Returns `ProgramVersion` as return data, so clients can detect supported instructions
+    GetVersion,
+
+    /// Sets buy and sell prices as payment tokens per `denominator` store tokens,
+    /// to express prices below one or fractional ones. Trade prices are given over it.
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    UpdatePriceRatio {
+        buy_price: u64,
+        sell_price: u64,
+        denominator: u64,
+    },
+
+    /// Records `Valuation` of the store inventory for the current epoch, once per epoch,
+    /// at the price feed price if it's passed or at the store buy price. Callable by anyone.
+    ///
+    ///   0. `[signer, writable]` The payer for valuation account creation
+    ///   0. `[]` The store account
+    ///   0. `[]` store account with store tokens (same as in store info account)
+    ///   0. `[]` store account with payment tokens (same as in store info account)
+    ///   0. `[writable]` The valuation account, PDA of `["valuation", store account, epoch]`
+    ///   0. `[]` The system program
+    ///   0. `[]` optional, price feed account of the store
+    RecordValuation,
+
+    /// Sets the fee charged on trades notional, a fee vault must be set to charge one
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    ///   0. `[]` optional, the fee vault, account with payment tokens owned by the store PDA
+    UpdateFee {
+        /// fee in basis points, 0 - no fee
+        fee_bps: u16,
+    },
+
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[]` The store account
+    ///   0. `[writable]` The fee vault of the store
+    ///   0. `[writable]` account to transfer fees to
+    ///   0. `[]` The PDA account
+    ///   0. `[]` The token program
+    WithdrawFees { amount: u64 },
+
+    /// Plans a fee change, applied by the first trade from `effective_at`, replacing a change
+    /// planned at the same time. A fee vault must be set to charge a fee.
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    ScheduleFee {
+        /// unix timestamp in the future
+        effective_at: i64,
+        /// fee in basis points, 0 - no fee
+        fee_bps: u16,
+    },
+
+    /// Winds the store down: buys are rejected, sells stay open until the deadline so holders
+    /// can exit, after which the store can be closed. The deadline may be extended.
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    BeginWindDown {
+        /// unix timestamp sells are accepted until, in the future
+        deadline: i64,
+    },
+
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    SetTradeLimits {
+        /// max store tokens amount of a buy (0 - unlimited)
+        max_trade_amount: u64,
+        /// store tokens left to sell, decremented on each buy (`None` - unlimited)
+        remaining_inventory: Option<u64>,
+    },
+
+    /// Adds the store to the `StoreRegistry` of its owner, created on first registration
+    ///
+    ///   0. `[signer, writable]` The owner of store account, pays for registry creation
+    ///   0. `[]` The store account
+    ///   0. `[writable]` The registry account, PDA of `["registry", owner]`, or
+    ///      `["registry", store namespace, owner]` if the store namespace is set
+    ///   0. `[]` The system program
+    RegisterStore,
+
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[]` The store account, may be closed
+    ///   0. `[writable]` A registry account of the owner, in any namespace
+    UnregisterStore,
+
+    /// Buys from several stores at once, fails for all if any leg fails
+    ///
+    ///   0. `[signer]` owner of token accounts to transfer
+    ///   0. Accounts of `Buy` after the signer for each leg, including its optional accounts, in the same order
+    BatchBuy { legs: Vec<BatchLeg> },
+
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    SetEscheatPeriod {
+        /// seconds after the refund window unreleased escrows may be swept (0 - disabled)
+        period: i64,
+    },
+
+    /// Pays escrowed buy left unreleased for the escheat period after the refund window to the store,
+    /// the escrow account rent goes to the store owner. Callable by anyone.
+    ///
+    ///   0. `[]` The store account
+    ///   0. `[writable]` The escrow account
+    ///   0. `[writable]` store account holding the payment
+    ///   0. `[writable]` store account to receive payment tokens (configured proceeds account, or any of the store owner)
+    ///   0. `[writable]` The store owner, receives escrow account lamports
+    ///   0. `[]` The PDA account
+    ///   0. `[]` The token program
+    SweepEscrow,
+
+    /// Lifts the tombstone of a store deleted by CloseStore, only its original owner may initialize it again.
+    /// Borsh encoded only.
+    ///
+    ///   0. `[signer]` The original owner of store account
+    ///   0. `[writable]` The store account
+    ReactivateStore,
+
+    /// Upgrades store account of an older layout version, or shorter layout of the current one,
+    /// to the current layout, growing it and topping up rent exemption from the owner.
+    /// Version 1 stores without a sell price get the buy price, and the store PDA nonce is saved.
+    /// Token accounts owned by the legacy PDA of `["store"]` may be handed over to the store PDA.
+    /// Borsh encoded only.
+    ///
+    ///   0. `[signer, writable]` The owner of store account
+    ///   0. `[writable]` The store account
+    ///   0. `[]` The system program
+    ///   0. `[]` optional, the legacy PDA account, PDA of `["store"]`
+    ///   0. `[]` optional, the token program, if the legacy PDA is passed
+    ///   0. `[writable]` store token accounts, escrow account or fee vault owned by the legacy PDA, if it's passed
+    MigrateStore,
+
+    /// Creates the program stats account. Every store initialized afterwards is counted in it
+    /// and tracks stats of its payment mint. Borsh encoded only.
+    ///
+    ///   0. `[signer, writable]` Pays for stats account creation
+    ///   0. `[writable]` The stats account, PDA of `["stats"]`
+    ///   0. `[]` The system program
+    InitializeStats,
+
+    /// Creates the program config with the program upgrade authority as its authority.
+    /// Borsh encoded only.
+    ///
+    ///   0. `[signer, writable]` The upgrade authority, pays for config account creation
+    ///   0. `[writable]` The config account, PDA of `["config"]`
+    ///   0. `[]` The system program
+    ///   0. `[]` The program data account of the program
+    InitializeConfig,
+
+    /// Replaces the payment mints InitializeAccount accepts, any mint if empty. Borsh encoded only.
+    ///
+    ///   0. `[signer]` The config authority
+    ///   0. `[writable]` The config account
+    SetPaymentMints { mints: Vec<Pubkey> },
+
+    /// Caps the store tokens each wallet may buy in total, counted in purchase ledgers
+    /// created on the first buy. Borsh encoded only.
+    ///
+    ///   0. `[signer]` The store owner
+    ///   0. `[writable]` The store account
+    SetWalletCap {
+        /// store tokens each wallet may buy (0 - no cap)
+        cap: u64,
+    },
+
+    /// Moves the store to a partner namespace of a white-label deployment, which keeps
+    /// owner registries of the namespace apart. Borsh encoded only.
+    ///
+    ///   0. `[signer]` The store owner
+    ///   0. `[writable]` The store account
+    SetNamespace {
+        /// partner id (default - none)
+        namespace: Pubkey,
+    },
+
+    /// Sets the slots Buy and Sell are accepted between, adjusting the window InitializeAccount
+    /// opened the store with. Borsh encoded only.
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    SetTradingWindow {
+        /// first slot of the window (0 - open right away)
+        open_at_slot: u64,
+        /// slot the window closes at, after `open_at_slot` (0 - never closes)
+        close_at_slot: u64,
+    },
+
+    /// Sets the daily settlement cutoff of the store. Trades, invoice payments, deposits, token and
+    /// fee withdrawals, escrow payments and closing the store are rejected from the cutoff until
+    /// SettleDay records the day report. Borsh encoded only.
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    SetSettlementCutoff {
+        /// seconds after UTC midnight (`None` - no daily settlement)
+        cutoff: Option<u32>,
+    },
+
+    /// Records `SettlementReport` of the day of the last cutoff and resumes trades, callable by
+    /// anyone once the cutoff passed. Borsh encoded only.
+    ///
+    ///   0. `[signer, writable]` The payer for report account creation
+    ///   0. `[writable]` The store account
+    ///   0. `[]` store account with store tokens (same as in store info account)
+    ///   0. `[]` store account with payment tokens (same as in store info account)
+    ///   0. `[writable]` The report account, PDA of `["settlement", store account, day]`
+    ///   0. `[]` The system program
+    SettleDay,
+
+    /// Sets the pricing mode of the store, fixed price stores keep their prices. Borsh encoded only.
+    ///
+    ///   0. `[signer]` The owner of store account
+    ///   0. `[writable]` The store account
+    SetPricing { pricing: Pricing },
+
+    /// Proposes new authority of the program config, who takes over with AcceptConfigAuthority.
+    /// Borsh encoded only.
+    ///
+    ///   0. `[signer]` The config authority
+    ///   0. `[writable]` The config account
+    ///   0. `[]` The new authority, default pubkey cancels the proposal
+    ProposeConfigAuthority,
+
+    /// Borsh encoded only.
+    ///
+    ///   0. `[signer]` The proposed authority
+    ///   0. `[writable]` The config account
+    AcceptConfigAuthority,
+}
+
+impl StoreInstruction {
+    /// Unpacks instruction data of either encoding, trailing bytes of the hand packed layout are
+    /// ignored
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        Self::decode(input).map(|(instruction, _)| instruction)
+    }
+
+    /// Same as `unpack`, but fails with `NonCanonicalInstruction` on hand packed data with
+    /// trailing bytes. Borsh data is always decoded strictly.
+    pub fn unpack_strict(input: &[u8]) -> Result<Self, ProgramError> {
+        match Self::decode(input)? {
+            (instruction, true) => Ok(instruction),
+            (_, false) => Err(StoreError::NonCanonicalInstruction.into()),
+        }
+    }
+
+    /// Decoded instruction and whether the data is its canonical encoding
+    fn decode(input: &[u8]) -> Result<(Self, bool), ProgramError> {
+        let (tag, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        if *tag == BORSH_INSTRUCTION_TAG {
+            let instruction =
+                Self::try_from_slice(rest).map_err(|_| ProgramError::InvalidInstructionData)?;
+            return Ok((instruction, true));
+        }
+
+        let mut fields = PackedFields::new(rest);
+        let instruction = match tag {
+            0 => Self::InitializeAccount {
+                price: fields.u64(0)?,
+                fee_bps: fields.optional_u16(8)?,
+                open_at_slot: 0,
+                close_at_slot: 0,
+            },
+            1 => Self::UpdatePrice {
+                price: fields.u64(0)?,
+            },
+            2 => Self::Buy {
+                amount: fields.u64(0)?,
+                max_price: fields.u64(8)?,
+                tag: fields.optional_u16(16)?,
+            },
+            3 => Self::Sell {
+                amount: fields.u64(0)?,
+                min_price: fields.u64(8)?,
+                tag: fields.optional_u16(16)?,
+            },
+            4 => Self::UpdateLotSize {
+                lot_size: fields.u64(0)?,
+            },
+            5 => Self::PublishDepth,
+            6 => Self::SetRedirect,
+            7 => Self::SetInventoryThresholds {
+                store_tokens: fields.u64(0)?,
+                payment_tokens: fields.u64(8)?,
+            },
+            8 => Self::JoinWaitlist {
+                amount: fields.u64(0)?,
+            },
+            9 => Self::LeaveWaitlist,
+            10 => Self::SetSettlementAccounts,
+            11 => Self::SetCosigner {
+                threshold: fields.u64(0)?,
+            },
+            12 => Self::CreateSession {
+                max_notional: fields.u64(0)?,
+                expires_at: fields.i64(8)?,
+            },
+            13 => Self::RevokeSession,
+            14 => Self::SessionBuy {
+                amount: fields.u64(0)?,
+                price: fields.u64(8)?,
+            },
+            15 => Self::SessionSell {
+                amount: fields.u64(0)?,
+                price: fields.u64(8)?,
+            },
+            16 => Self::SettleSignedOrder,
+            17 => Self::CancelSignedOrder {
+                nonce: fields.u64(0)?,
+            },
+            18 => Self::ReportPnl,
+            19 => Self::PreviewTrade {
+                side: fields.side(0)?,
+                amount: fields.u64(1)?,
+                price: fields.u64(9)?,
+            },
+            20 => {
+                let count = fields.u8(0)?;
+                let assertions = (0..count as usize)
+                    .map(|i| {
+                        let offset = 1 + i * BalanceAssertion::LEN;
+                        let check = match fields.u8(offset)? {
+                            0 => BalanceCheck::AtLeast,
+                            1 => BalanceCheck::Exactly,
+                            _ => return Err(ProgramError::InvalidInstructionData),
+                        };
+                        Ok(BalanceAssertion {
+                            check,
+                            amount: fields.u64(offset + 1)?,
+                        })
+                    })
+                    .collect::<Result<_, _>>()?;
+                Self::AssertBalances { assertions }
+            }
+            21 => Self::Realloc,
+            22 => Self::SetGateMint,
+            23 => Self::SetDiscountTier {
+                discount_bps: fields.u16(0)?,
+            },
+            24 => {
+                let count = fields.u8(0)?;
+                let prices = (0..count as usize)
+                    .map(|i| fields.u64(1 + i * 8))
+                    .collect::<Result<_, _>>()?;
+                Self::UpdatePricesBatch { prices }
+            }
+            25 => Self::AttestPrice {
+                price: fields.u64(0)?,
+            },
+            26 => Self::SetEscrow {
+                window: fields.i64(0)?,
+            },
+            27 => Self::RefundEscrow,
+            28 => Self::ReleaseEscrow,
+            29 => Self::CreateInvoice {
+                amount: fields.u64(0)?,
+                reference: fields.bytes(8)?,
+            },
+            30 => Self::PayInvoice,
+            31 => Self::ProposeOwner,
+            32 => Self::AcceptOwner,
+            33 => Self::ReleaseAccounts,
+            34 => Self::CloseStore,
+            35 => Self::CreateBuyOffer {
+                amount: fields.u64(0)?,
+                price: fields.u64(8)?,
+                expires_at: fields.i64(16)?,
+            },
+            36 => Self::AcceptBuyOffer,
+            37 => Self::CancelOffer,
+            38 => Self::CreateSellOffer {
+                amount: fields.u64(0)?,
+                price: fields.u64(8)?,
+                expires_at: fields.i64(16)?,
+            },
+            39 => Self::AcceptSellOffer,
+            40 => Self::UpdatePrices {
+                buy_price: fields.u64(0)?,
+                sell_price: fields.u64(8)?,
+            },
+            41 => Self::Withdraw {
+                amount: fields.u64(0)?,
+            },
+            42 => Self::Deposit {
+                amount: fields.u64(0)?,
+                side: fields.side(8)?,
+            },
+            43 => Self::SetFeatures {
+                features: fields.u32(0)?,
+            },
+            44 => Self::SetTradingHours {
+                hours: fields.bytes(0)?,
+            },
+            45 => Self::SetGuardian,
+            46 => Self::Pause,
+            47 => Self::Resume,
+            48 => Self::BuyWithReference {
+                amount: fields.u64(0)?,
+                max_price: fields.u64(8)?,
+                tag: fields.optional_u16(16)?,
+            },
+            49 => Self::GetVersion,
+            50 => Self::UpdatePriceRatio {
+                buy_price: fields.u64(0)?,
+                sell_price: fields.u64(8)?,
+                denominator: fields.u64(16)?,
+            },
+            51 => Self::RecordValuation,
+            52 => Self::UpdateFee {
+                fee_bps: fields.u16(0)?,
+            },
+            53 => Self::WithdrawFees {
+                amount: fields.u64(0)?,
+            },
+            54 => Self::ScheduleFee {
+                effective_at: fields.i64(0)?,
+                fee_bps: fields.u16(8)?,
+            },
+            55 => Self::BeginWindDown {
+                deadline: fields.i64(0)?,
+            },
+            56 => Self::SetTradeLimits {
+                max_trade_amount: fields.u64(0)?,
+                remaining_inventory: fields.optional_u64(8)?,
+            },
+            57 => Self::RegisterStore,
+            58 => Self::UnregisterStore,
+            59 => Self::SetEscheatPeriod {
+                period: fields.i64(0)?,
+            },
+            60 => Self::SweepEscrow,
+            61 => {
+                let count = fields.u8(0)?;
+                let legs = (0..count as usize)
+                    .map(|i| {
+                        let offset = 1 + i * BatchLeg::LEN;
+                        Ok(BatchLeg {
+                            amount: fields.u64(offset)?,
+                            max_price: fields.u64(offset + 8)?,
+                            accounts: fields.u8(offset + 16)?,
+                        })
+                    })
+                    .collect::<Result<_, ProgramError>>()?;
+                Self::BatchBuy { legs }
+            }
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+        Ok((instruction, fields.is_read()))
+    }
+
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(size_of::<Self>());
+        match self {
+            &Self::InitializeAccount {
+                price,
+                fee_bps,
+                open_at_slot: 0,
+                close_at_slot: 0,
+            } => {
+                buf.push(0);
+                buf.extend_from_slice(&price.to_le_bytes());
+                Self::pack_optional_u16(fee_bps, &mut buf);
+            }
+            &Self::UpdatePrice { price } => {
+                buf.push(1);
+                buf.extend_from_slice(&price.to_le_bytes());
+            }
+            &Self::Buy {
+                amount,
+                max_price,
+                tag,
+            } => {
+                buf.push(2);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&max_price.to_le_bytes());
+                Self::pack_optional_u16(tag, &mut buf);
+            }
+            &Self::Sell {
+                amount,
+                min_price,
+                tag,
+            } => {
+                buf.push(3);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&min_price.to_le_bytes());
+                Self::pack_optional_u16(tag, &mut buf);
+            }
+            &Self::UpdateLotSize { lot_size } => {
+                buf.push(4);
+                buf.extend_from_slice(&lot_size.to_le_bytes());
+            }
+            &Self::PublishDepth => {
+                buf.push(5);
+            }
+            &Self::SetRedirect => {
+                buf.push(6);
+            }
+            &Self::SetInventoryThresholds {
+                store_tokens,
+                payment_tokens,
+            } => {
+                buf.push(7);
+                buf.extend_from_slice(&store_tokens.to_le_bytes());
+                buf.extend_from_slice(&payment_tokens.to_le_bytes());
+            }
+            &Self::JoinWaitlist { amount } => {
+                buf.push(8);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            &Self::LeaveWaitlist => {
+                buf.push(9);
+            }
+            &Self::SetSettlementAccounts => {
+                buf.push(10);
+            }
+            &Self::SetCosigner { threshold } => {
+                buf.push(11);
+                buf.extend_from_slice(&threshold.to_le_bytes());
+            }
+            &Self::CreateSession {
+                max_notional,
+                expires_at,
+            } => {
+                buf.push(12);
+                buf.extend_from_slice(&max_notional.to_le_bytes());
+                buf.extend_from_slice(&expires_at.to_le_bytes());
+            }
+            &Self::RevokeSession => {
+                buf.push(13);
+            }
+            &Self::SessionBuy { amount, price } => {
+                buf.push(14);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&price.to_le_bytes());
+            }
+            &Self::SessionSell { amount, price } => {
+                buf.push(15);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&price.to_le_bytes());
+            }
+            &Self::SettleSignedOrder => {
+                buf.push(16);
+            }
+            &Self::CancelSignedOrder { nonce } => {
+                buf.push(17);
+                buf.extend_from_slice(&nonce.to_le_bytes());
+            }
+            &Self::ReportPnl => {
+                buf.push(18);
+            }
+            &Self::PreviewTrade {
+                side,
+                amount,
+                price,
+            } => {
+                buf.push(19);
+                buf.push(side as u8);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&price.to_le_bytes());
+            }
+            Self::AssertBalances { assertions } => {
+                buf.push(20);
+                buf.push(assertions.len() as u8);
+                for assertion in assertions {
+                    buf.push(assertion.check as u8);
+                    buf.extend_from_slice(&assertion.amount.to_le_bytes());
+                }
+            }
+            &Self::Realloc => {
+                buf.push(21);
+            }
+            &Self::SetGateMint => {
+                buf.push(22);
+            }
+            &Self::SetDiscountTier { discount_bps } => {
+                buf.push(23);
+                buf.extend_from_slice(&discount_bps.to_le_bytes());
+            }
+            Self::UpdatePricesBatch { prices } => {
+                buf.push(24);
+                buf.push(prices.len() as u8);
+                for price in prices {
+                    buf.extend_from_slice(&price.to_le_bytes());
+                }
+            }
+            &Self::AttestPrice { price } => {
+                buf.push(25);
+                buf.extend_from_slice(&price.to_le_bytes());
+            }
+            &Self::SetEscrow { window } => {
+                buf.push(26);
+                buf.extend_from_slice(&window.to_le_bytes());
+            }
+            &Self::RefundEscrow => {
+                buf.push(27);
+            }
+            &Self::ReleaseEscrow => {
+                buf.push(28);
+            }
+            &Self::CreateInvoice { amount, reference } => {
+                buf.push(29);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&reference);
+            }
+            &Self::PayInvoice => {
+                buf.push(30);
+            }
+            &Self::ProposeOwner => {
+                buf.push(31);
+            }
+            &Self::AcceptOwner => {
+                buf.push(32);
+            }
+            &Self::ReleaseAccounts => {
+                buf.push(33);
+            }
+            &Self::CloseStore => {
+                buf.push(34);
+            }
+            &Self::CreateBuyOffer {
+                amount,
+                price,
+                expires_at,
+            } => {
+                buf.push(35);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&price.to_le_bytes());
+                buf.extend_from_slice(&expires_at.to_le_bytes());
+            }
+            &Self::AcceptBuyOffer => {
+                buf.push(36);
+            }
+            &Self::CancelOffer => {
+                buf.push(37);
+            }
+            &Self::CreateSellOffer {
+                amount,
+                price,
+                expires_at,
+            } => {
+                buf.push(38);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&price.to_le_bytes());
+                buf.extend_from_slice(&expires_at.to_le_bytes());
+            }
+            &Self::AcceptSellOffer => {
+                buf.push(39);
+            }
+            &Self::UpdatePrices {
+                buy_price,
+                sell_price,
+            } => {
+                buf.push(40);
+                buf.extend_from_slice(&buy_price.to_le_bytes());
+                buf.extend_from_slice(&sell_price.to_le_bytes());
+            }
+            &Self::Withdraw { amount } => {
+                buf.push(41);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            &Self::Deposit { amount, side } => {
+                buf.push(42);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.push(side as u8);
+            }
+            &Self::SetFeatures { features } => {
+                buf.push(43);
+                buf.extend_from_slice(&features.to_le_bytes());
+            }
+            &Self::SetTradingHours { hours } => {
+                buf.push(44);
+                buf.extend_from_slice(&hours);
+            }
+            &Self::SetGuardian => {
+                buf.push(45);
+            }
+            &Self::Pause => {
+                buf.push(46);
+            }
+            &Self::Resume => {
+                buf.push(47);
+            }
+            &Self::BuyWithReference {
+                amount,
+                max_price,
+                tag,
+            } => {
+                buf.push(48);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&max_price.to_le_bytes());
+                Self::pack_optional_u16(tag, &mut buf);
+            }
+            &Self::GetVersion => {
+                buf.push(49);
+            }
+            &Self::UpdatePriceRatio {
+                buy_price,
+                sell_price,
+                denominator,
+            } => {
+                buf.push(50);
+                buf.extend_from_slice(&buy_price.to_le_bytes());
+                buf.extend_from_slice(&sell_price.to_le_bytes());
+                buf.extend_from_slice(&denominator.to_le_bytes());
+            }
+            &Self::RecordValuation => {
+                buf.push(51);
+            }
+            &Self::UpdateFee { fee_bps } => {
+                buf.push(52);
+                buf.extend_from_slice(&fee_bps.to_le_bytes());
+            }
+            &Self::WithdrawFees { amount } => {
+                buf.push(53);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            &Self::ScheduleFee {
+                effective_at,
+                fee_bps,
+            } => {
+                buf.push(54);
+                buf.extend_from_slice(&effective_at.to_le_bytes());
+                buf.extend_from_slice(&fee_bps.to_le_bytes());
+            }
+            &Self::BeginWindDown { deadline } => {
+                buf.push(55);
+                buf.extend_from_slice(&deadline.to_le_bytes());
+            }
+            &Self::SetTradeLimits {
+                max_trade_amount,
+                remaining_inventory,
+            } => {
+                buf.push(56);
+                buf.extend_from_slice(&max_trade_amount.to_le_bytes());
+                if let Some(remaining_inventory) = remaining_inventory {
+                    buf.extend_from_slice(&remaining_inventory.to_le_bytes());
+                }
+            }
+            &Self::RegisterStore => {
+                buf.push(57);
+            }
+            &Self::UnregisterStore => {
+                buf.push(58);
+            }
+            &Self::SetEscheatPeriod { period } => {
+                buf.push(59);
+                buf.extend_from_slice(&period.to_le_bytes());
+            }
+            &Self::SweepEscrow => {
+                buf.push(60);
+            }
+            Self::InitializeAccount { .. }
+            | Self::ReactivateStore
+            | Self::MigrateStore
+            | Self::InitializeStats
+            | Self::InitializeConfig
+            | Self::SetPaymentMints { .. }
+            | Self::SetWalletCap { .. }
+            | Self::SetNamespace { .. }
+            | Self::SetTradingWindow { .. }
+            | Self::SetSettlementCutoff { .. }
+            | Self::SettleDay
+            | Self::SetPricing { .. }
+            | Self::ProposeConfigAuthority
+            | Self::AcceptConfigAuthority => return self.pack_borsh(),
+            Self::BatchBuy { legs } => {
+                buf.push(61);
+                buf.push(legs.len() as u8);
+                for leg in legs {
+                    buf.extend_from_slice(&leg.amount.to_le_bytes());
+                    buf.extend_from_slice(&leg.max_price.to_le_bytes());
+                    buf.push(leg.accounts);
+                }
+            }
+        }
+        buf
+    }
+
+    /// Borsh encoding behind `BORSH_INSTRUCTION_TAG`, accepted for every instruction.
+    /// Instructions without a hand packed layout are only packed this way.
+    pub fn pack_borsh(&self) -> Vec<u8> {
+        let mut buf = vec![BORSH_INSTRUCTION_TAG];
+        // writing to a Vec can't fail
+        self.serialize(&mut buf).unwrap();
+        buf
+    }
+
+    /// Indices of the store accounts the instruction names, for instructions with hand packed
+    /// data, the only ones which can be sent with non canonical data
+    pub fn store_account_indices(&self) -> Vec<usize> {
+        match self {
+            Self::ReportPnl | Self::ReleaseEscrow | Self::SweepEscrow => vec![0],
+            Self::InitializeAccount { .. }
+            | Self::UpdatePrice { .. }
+            | Self::Buy { .. }
+            | Self::Sell { .. }
+            | Self::UpdateLotSize { .. }
+            | Self::PublishDepth
+            | Self::SetRedirect
+            | Self::SetInventoryThresholds { .. }
+            | Self::SetSettlementAccounts
+            | Self::SetCosigner { .. }
+            | Self::PreviewTrade { .. }
+            | Self::Realloc
+            | Self::SetGateMint
+            | Self::SetDiscountTier { .. }
+            | Self::AttestPrice { .. }
+            | Self::SetEscrow { .. }
+            | Self::RefundEscrow
+            | Self::CreateInvoice { .. }
+            | Self::ProposeOwner
+            | Self::AcceptOwner
+            | Self::ReleaseAccounts
+            | Self::CloseStore
+            | Self::UpdatePrices { .. }
+            | Self::Withdraw { .. }
+            | Self::Deposit { .. }
+            | Self::SetFeatures { .. }
+            | Self::SetTradingHours { .. }
+            | Self::SetGuardian
+            | Self::Pause
+            | Self::Resume
+            | Self::UpdatePriceRatio { .. }
+            | Self::RecordValuation
+            | Self::UpdateFee { .. }
+            | Self::WithdrawFees { .. }
+            | Self::ScheduleFee { .. }
+            | Self::BeginWindDown { .. }
+            | Self::SetTradeLimits { .. }
+            | Self::RegisterStore
+            | Self::UnregisterStore
+            | Self::SetEscheatPeriod { .. } => vec![1],
+            Self::JoinWaitlist { .. }
+            | Self::CreateSession { .. }
+            | Self::SessionBuy { .. }
+            | Self::SessionSell { .. }
+            | Self::PayInvoice
+            | Self::BuyWithReference { .. } => vec![2],
+            Self::SettleSignedOrder { .. } => vec![5],
+            Self::UpdatePricesBatch { prices } => (1..=prices.len()).collect(),
+            Self::BatchBuy { legs } => legs
+                .iter()
+                .scan(1, |index, leg| {
+                    let store_index = *index;
+                    *index += leg.accounts as usize;
+                    Some(store_index)
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    fn pack_optional_u16(value: Option<u16>, buf: &mut Vec<u8>) {
+        if let Some(value) = value {
+            buf.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+/// Fields of hand packed instruction data read at their offsets, keeping the end of the furthest
+/// one read to tell trailing bytes apart
+struct PackedFields<'a> {
+    input: &'a [u8],
+    end: usize,
+}
+
+impl<'a> PackedFields<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        Self { input, end: 0 }
+    }
+
+    fn bytes<const N: usize>(&mut self, offset: usize) -> Result<[u8; N], ProgramError> {
+        let bytes = self
+            .input
+            .get(offset..offset + N)
+            .and_then(|slice| slice.try_into().ok())
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        self.end = self.end.max(offset + N);
+        Ok(bytes)
+    }
+
+    fn u8(&mut self, offset: usize) -> Result<u8, ProgramError> {
+        self.bytes(offset).map(|[byte]| byte)
+    }
+
+    fn u16(&mut self, offset: usize) -> Result<u16, ProgramError> {
+        self.bytes(offset).map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self, offset: usize) -> Result<u32, ProgramError> {
+        self.bytes(offset).map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self, offset: usize) -> Result<u64, ProgramError> {
+        self.bytes(offset).map(u64::from_le_bytes)
+    }
+
+    fn i64(&mut self, offset: usize) -> Result<i64, ProgramError> {
+        self.bytes(offset).map(i64::from_le_bytes)
+    }
+
+    /// Trailing value which may be omitted
+    fn optional_u16(&mut self, offset: usize) -> Result<Option<u16>, ProgramError> {
+        if self.input.len() <= offset {
+            return Ok(None);
+        }
+        self.u16(offset).map(Some)
+    }
+
+    /// Trailing value which may be omitted
+    fn optional_u64(&mut self, offset: usize) -> Result<Option<u64>, ProgramError> {
+        if self.input.len() <= offset {
+            return Ok(None);
+        }
+        self.u64(offset).map(Some)
+    }
+
+    fn side(&mut self, offset: usize) -> Result<OrderSide, ProgramError> {
+        match self.u8(offset)? {
+            0 => Ok(OrderSide::Buy),
+            1 => Ok(OrderSide::Sell),
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+
+    /// Whether no bytes follow the fields read
+    fn is_read(&self) -> bool {
+        self.end == self.input.len()
+    }
+}
+
+pub fn initialyze_account_instruction(
+    price: u64,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    account_with_payment_tokens: &Pubkey,
+    account_with_store_tokens: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::InitializeAccount {
+        price,
+        fee_bps: None,
+        open_at_slot: 0,
+        close_at_slot: 0,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new(*account_with_payment_tokens, false),
+        AccountMeta::new(*account_with_store_tokens, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(find_config_address(store_program_id), false),
+        AccountMeta::new(find_stats_address(store_program_id), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Store token account of `mint` created on init, see `initialize_with_vaults_instruction`
+pub fn find_vault_address(
+    store_program_id: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"vault",
+            store_account_pubkey.as_ref(),
+            mint_pubkey.as_ref(),
+        ],
+        store_program_id,
+    )
+    .0
+}
+
+/// Initializes the store creating its token accounts, no token accounts need to be prepared
+pub fn initialize_with_vaults_instruction(
+    price: u64,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    payment_token_mint: &Pubkey,
+    store_token_mint: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = initialyze_account_instruction(
+        price,
+        store_program_id,
+        owner_pubkey,
+        store_account_pubkey,
+        &find_vault_address(store_program_id, store_account_pubkey, payment_token_mint),
+        &find_vault_address(store_program_id, store_account_pubkey, store_token_mint),
+        token_program_id,
+    )?;
+    instruction.accounts.extend_from_slice(&[
+        AccountMeta::new_readonly(*payment_token_mint, false),
+        AccountMeta::new_readonly(*store_token_mint, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]);
+    Ok(instruction)
+}
+
+/// PDA owning token accounts of the store, the `pda` account of store instructions
+pub fn find_store_authority_address(
+    store_program_id: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(&[b"store", store_account_pubkey.as_ref()], store_program_id).0
+}
+
+pub fn update_price_instruction(
+    price: u64,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::UpdatePrice { price }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn update_lot_size_instruction(
+    lot_size: u64,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::UpdateLotSize { lot_size }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_redirect_instruction(
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    new_store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SetRedirect.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new_readonly(*new_store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_inventory_thresholds_instruction(
+    store_tokens: u64,
+    payment_tokens: u64,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SetInventoryThresholds {
+        store_tokens,
+        payment_tokens,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn buy_instruction(
+    amount: u64,
+    max_price: u64,
+    store_program_id: &Pubkey,
+    buyer_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    store_account_with_payment_tokens: &Pubkey,
+    store_account_with_store_tokens: &Pubkey,
+    user_account_with_payment_tokens: &Pubkey,
+    user_account_with_store_tokens: &Pubkey,
+    pda: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::Buy {
+        amount,
+        max_price,
+        tag: None,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*buyer_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new(*store_account_with_payment_tokens, false),
+        AccountMeta::new(*store_account_with_store_tokens, false),
+        AccountMeta::new(*user_account_with_payment_tokens, false),
+        AccountMeta::new(*user_account_with_store_tokens, false),
+        AccountMeta::new_readonly(*pda, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Buy paid with lamports of the buyer, for stores with wrapped SOL payment mint.
+/// Funds `temporary_account` with the notional at `max_price` as a new wrapped SOL account,
+/// buys from it and closes it back to the buyer with what is left. `temporary_account` must sign the transaction.
+#[allow(clippy::too_many_arguments)]
+pub fn buy_with_sol_instructions(
+    amount: u64,
+    max_price: u64,
+    rent: &Rent,
+    store_program_id: &Pubkey,
+    buyer_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    store_account_with_payment_tokens: &Pubkey,
+    store_account_with_store_tokens: &Pubkey,
+    temporary_account_pubkey: &Pubkey,
+    user_account_with_store_tokens: &Pubkey,
+    pda: &Pubkey,
+) -> Result<Vec<Instruction>, ProgramError> {
+    let token_program_id = spl_token::id();
+    let notional = amount
+        .checked_mul(max_price)
+        .ok_or(StoreError::MathOverflow)?;
+    let lamports = rent
+        .minimum_balance(spl_token::state::Account::LEN)
+        .checked_add(notional)
+        .ok_or(StoreError::MathOverflow)?;
+
+    Ok(vec![
+        system_instruction::create_account(
+            buyer_pubkey,
+            temporary_account_pubkey,
+            lamports,
+            spl_token::state::Account::LEN as u64,
+            &token_program_id,
+        ),
+        spl_token::instruction::initialize_account(
+            &token_program_id,
+            temporary_account_pubkey,
+            &spl_token::native_mint::id(),
+            buyer_pubkey,
+        )?,
+        buy_instruction(
+            amount,
+            max_price,
+            store_program_id,
+            buyer_pubkey,
+            store_account_pubkey,
+            store_account_with_payment_tokens,
+            store_account_with_store_tokens,
+            temporary_account_pubkey,
+            user_account_with_store_tokens,
+            pda,
+            &token_program_id,
+        )?,
+        spl_token::instruction::close_account(
+            &token_program_id,
+            temporary_account_pubkey,
+            buyer_pubkey,
+            buyer_pubkey,
+            &[],
+        )?,
+    ])
+}
+
+pub fn sell_instruction(
+    amount: u64,
+    min_price: u64,
+    store_program_id: &Pubkey,
+    buyer_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    store_account_with_payment_tokens: &Pubkey,
+    store_account_with_store_tokens: &Pubkey,
+    user_account_with_payment_tokens: &Pubkey,
+    user_account_with_store_tokens: &Pubkey,
+    pda: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::Sell {
+        amount,
+        min_price,
+        tag: None,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*buyer_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new(*store_account_with_payment_tokens, false),
+        AccountMeta::new(*store_account_with_store_tokens, false),
+        AccountMeta::new(*user_account_with_payment_tokens, false),
+        AccountMeta::new(*user_account_with_store_tokens, false),
+        AccountMeta::new_readonly(*pda, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn find_depth_address(store_program_id: &Pubkey, store_account_pubkey: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"depth", store_account_pubkey.as_ref()], store_program_id).0
+}
+
+pub fn publish_depth_instruction(
+    store_program_id: &Pubkey,
+    payer_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    store_account_with_store_tokens: &Pubkey,
+    store_account_with_payment_tokens: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::PublishDepth.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(*store_account_pubkey, false),
+        AccountMeta::new_readonly(*store_account_with_store_tokens, false),
+        AccountMeta::new_readonly(*store_account_with_payment_tokens, false),
+        AccountMeta::new(
+            find_depth_address(store_program_id, store_account_pubkey),
+            false,
+        ),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn find_waitlist_address(
+    store_program_id: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    buyer_pubkey: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"waitlist",
+            store_account_pubkey.as_ref(),
+            buyer_pubkey.as_ref(),
+        ],
+        store_program_id,
+    )
+    .0
+}
+
+pub fn join_waitlist_instruction(
+    amount: u64,
+    store_program_id: &Pubkey,
+    buyer_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    store_account_with_store_tokens: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::JoinWaitlist { amount }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*buyer_pubkey, true),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(*store_account_pubkey, false),
+        AccountMeta::new_readonly(*store_account_with_store_tokens, false),
+        AccountMeta::new(
+            find_waitlist_address(store_program_id, store_account_pubkey, buyer_pubkey),
+            false,
+        ),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn leave_waitlist_instruction(
+    store_program_id: &Pubkey,
+    buyer_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::LeaveWaitlist.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*buyer_pubkey, true),
+        AccountMeta::new(
+            find_waitlist_address(store_program_id, store_account_pubkey, buyer_pubkey),
+            false,
+        ),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_settlement_accounts_instruction(
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    proceeds_account_pubkey: &Pubkey,
+    payout_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SetSettlementAccounts.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new_readonly(*proceeds_account_pubkey, false),
+        AccountMeta::new_readonly(*payout_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_cosigner_instruction(
+    threshold: u64,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    cosigner_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SetCosigner { threshold }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new_readonly(*cosigner_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Appends cosigner to Buy or Sell instruction, required for trades above store threshold
+pub fn with_cosigner(mut instruction: Instruction, cosigner_pubkey: &Pubkey) -> Instruction {
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(*cosigner_pubkey, true));
+    instruction
+}
+
+pub fn find_session_address(
+    store_program_id: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    wallet_pubkey: &Pubkey,
+    session_pubkey: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"session",
+            store_account_pubkey.as_ref(),
+            wallet_pubkey.as_ref(),
+            session_pubkey.as_ref(),
+        ],
+        store_program_id,
+    )
+    .0
+}
+
+pub fn create_session_instruction(
+    max_notional: u64,
+    expires_at: i64,
+    store_program_id: &Pubkey,
+    wallet_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    session_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::CreateSession {
+        max_notional,
+        expires_at,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*wallet_pubkey, true),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(*store_account_pubkey, false),
+        AccountMeta::new_readonly(*session_pubkey, false),
+        AccountMeta::new(
+            find_session_address(
+                store_program_id,
+                store_account_pubkey,
+                wallet_pubkey,
+                session_pubkey,
+            ),
+            false,
+        ),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Delegates wallet token account to the session, required before session trades
+pub fn approve_session_instruction(
+    amount: u64,
+    store_program_id: &Pubkey,
+    wallet_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    session_pubkey: &Pubkey,
+    wallet_token_account_pubkey: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    spl_token::instruction::approve(
+        token_program_id,
+        wallet_token_account_pubkey,
+        &find_session_address(
+            store_program_id,
+            store_account_pubkey,
+            wallet_pubkey,
+            session_pubkey,
+        ),
+        wallet_pubkey,
+        &[],
+        amount,
+    )
+}
+
+pub fn revoke_session_instruction(
+    store_program_id: &Pubkey,
+    wallet_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    session_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::RevokeSession.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*wallet_pubkey, true),
+        AccountMeta::new(
+            find_session_address(
+                store_program_id,
+                store_account_pubkey,
+                wallet_pubkey,
+                session_pubkey,
+            ),
+            false,
+        ),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn session_buy_instruction(
+    amount: u64,
+    price: u64,
+    store_program_id: &Pubkey,
+    session_pubkey: &Pubkey,
+    wallet_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    store_account_with_payment_tokens: &Pubkey,
+    store_account_with_store_tokens: &Pubkey,
+    user_account_with_payment_tokens: &Pubkey,
+    user_account_with_store_tokens: &Pubkey,
+    pda: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SessionBuy { amount, price }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*session_pubkey, true),
+        AccountMeta::new(
+            find_session_address(
+                store_program_id,
+                store_account_pubkey,
+                wallet_pubkey,
+                session_pubkey,
+            ),
+            false,
+        ),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new(*store_account_with_payment_tokens, false),
+        AccountMeta::new(*store_account_with_store_tokens, false),
+        AccountMeta::new(*user_account_with_payment_tokens, false),
+        AccountMeta::new(*user_account_with_store_tokens, false),
+        AccountMeta::new_readonly(*pda, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn session_sell_instruction(
+    amount: u64,
+    price: u64,
+    store_program_id: &Pubkey,
+    session_pubkey: &Pubkey,
+    wallet_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    store_account_with_payment_tokens: &Pubkey,
+    store_account_with_store_tokens: &Pubkey,
+    user_account_with_payment_tokens: &Pubkey,
+    user_account_with_store_tokens: &Pubkey,
+    pda: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SessionSell { amount, price }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*session_pubkey, true),
+        AccountMeta::new(
+            find_session_address(
+                store_program_id,
+                store_account_pubkey,
+                wallet_pubkey,
+                session_pubkey,
+            ),
+            false,
+        ),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new(*store_account_with_payment_tokens, false),
+        AccountMeta::new(*store_account_with_store_tokens, false),
+        AccountMeta::new(*user_account_with_payment_tokens, false),
+        AccountMeta::new(*user_account_with_store_tokens, false),
+        AccountMeta::new_readonly(*pda, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn find_order_authority_address(store_program_id: &Pubkey, wallet_pubkey: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"order", wallet_pubkey.as_ref()], store_program_id).0
+}
+
+/// Message for the wallet to sign and pass to the ed25519 program instruction.
+/// These are the raw bytes to give wallet adapters `signMessage`, no prefix is added.
+pub fn signed_order_message(order: &SignedOrder) -> Vec<u8> {
+    let mut message = vec![0; SignedOrder::LEN];
+    order.pack_into_slice(&mut message);
+    message
+}
+
+/// Ed25519 program instruction verifying the wallet `signature` of `signed_order_message`,
+/// must directly precede `settle_signed_order_instruction` in the transaction.
+/// Public key, signature and message are all in the instruction data.
+pub fn signed_order_ed25519_instruction(
+    wallet_pubkey: &Pubkey,
+    signature: &[u8; 64],
+    order: &SignedOrder,
+) -> Instruction {
+    const OFFSETS_START: usize = 2;
+    const OFFSETS_LEN: usize = 14;
+    let public_key_offset = OFFSETS_START + OFFSETS_LEN;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + 64;
+    let message = signed_order_message(order);
+
+    let mut data = Vec::with_capacity(message_offset + message.len());
+    data.extend_from_slice(&[1, 0]);
+    for offset in [
+        signature_offset,
+        u16::MAX as usize,
+        public_key_offset,
+        u16::MAX as usize,
+        message_offset,
+        message.len(),
+        u16::MAX as usize,
+    ] {
+        data.extend_from_slice(&(offset as u16).to_le_bytes());
+    }
+    data.extend_from_slice(wallet_pubkey.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(&message);
+
+    Instruction {
+        program_id: ed25519_program::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Delegates wallet token account to the order authority, required before settling signed orders
+pub fn approve_order_authority_instruction(
+    amount: u64,
+    store_program_id: &Pubkey,
+    wallet_pubkey: &Pubkey,
+    wallet_token_account_pubkey: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    spl_token::instruction::approve(
+        token_program_id,
+        wallet_token_account_pubkey,
+        &find_order_authority_address(store_program_id, wallet_pubkey),
+        wallet_pubkey,
+        &[],
+        amount,
+    )
+}
+
+/// Accounts are ordered as for the order side, see `SettleSignedOrder`
+#[allow(clippy::too_many_arguments)]
+pub fn settle_signed_order_instruction(
+    store_program_id: &Pubkey,
+    wallet_pubkey: &Pubkey,
+    nonce: u64,
+    payer_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    store_account_to_receive: &Pubkey,
+    store_account_to_send: &Pubkey,
+    user_account_with_payment_tokens: &Pubkey,
+    user_account_with_store_tokens: &Pubkey,
+    pda: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SettleSignedOrder.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        AccountMeta::new_readonly(
+            find_order_authority_address(store_program_id, wallet_pubkey),
+            false,
+        ),
+        AccountMeta::new(
+            find_nonce_bitmap_address(store_program_id, wallet_pubkey, nonce),
+            false,
+        ),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new(*store_account_to_receive, false),
+        AccountMeta::new(*store_account_to_send, false),
+        AccountMeta::new(*user_account_with_payment_tokens, false),
+        AccountMeta::new(*user_account_with_store_tokens, false),
+        AccountMeta::new_readonly(*pda, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn find_nonce_bitmap_address(
+    store_program_id: &Pubkey,
+    wallet_pubkey: &Pubkey,
+    nonce: u64,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"nonces",
+            wallet_pubkey.as_ref(),
+            &(nonce / NONCES_PER_BITMAP).to_le_bytes(),
+        ],
+        store_program_id,
+    )
+    .0
+}
+
+pub fn cancel_signed_order_instruction(
+    nonce: u64,
+    store_program_id: &Pubkey,
+    wallet_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::CancelSignedOrder { nonce }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*wallet_pubkey, true),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new(
+            find_nonce_bitmap_address(store_program_id, wallet_pubkey, nonce),
+            false,
+        ),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn report_pnl_instruction(
+    store_program_id: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::ReportPnl.pack();
+
+    let accounts = vec![AccountMeta::new_readonly(*store_account_pubkey, false)];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Accounts are the same as for `buy_instruction` or `sell_instruction` of the side
+#[allow(clippy::too_many_arguments)]
+pub fn preview_trade_instruction(
+    side: OrderSide,
+    amount: u64,
+    price: u64,
+    store_program_id: &Pubkey,
+    user_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    store_account_with_payment_tokens: &Pubkey,
+    store_account_with_store_tokens: &Pubkey,
+    user_account_with_payment_tokens: &Pubkey,
+    user_account_with_store_tokens: &Pubkey,
+    pda: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::PreviewTrade {
+        side,
+        amount,
+        price,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*user_pubkey, true),
+        AccountMeta::new_readonly(*store_account_pubkey, false),
+        AccountMeta::new_readonly(*store_account_with_payment_tokens, false),
+        AccountMeta::new_readonly(*store_account_with_store_tokens, false),
+        AccountMeta::new_readonly(*user_account_with_payment_tokens, false),
+        AccountMeta::new_readonly(*user_account_with_store_tokens, false),
+        AccountMeta::new_readonly(*pda, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// `assertions` are pairs of token account and its asserted balance
+pub fn assert_balances_instruction(
+    store_program_id: &Pubkey,
+    assertions: &[(Pubkey, BalanceAssertion)],
+) -> Result<Instruction, ProgramError> {
+    if assertions.len() > u8::MAX as usize {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let data = StoreInstruction::AssertBalances {
+        assertions: assertions.iter().map(|(_, assertion)| *assertion).collect(),
+    }
+    .pack();
+
+    let accounts = assertions
+        .iter()
+        .map(|(account, _)| AccountMeta::new_readonly(*account, false))
+        .collect();
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn realloc_instruction(
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::Realloc.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn migrate_store_instruction(
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction =
+        realloc_instruction(store_program_id, owner_pubkey, store_account_pubkey)?;
+    instruction.data = StoreInstruction::MigrateStore.pack();
+    Ok(instruction)
+}
+
+/// Appends store token accounts owned by the legacy PDA of `["store"]` to MigrateStore instruction,
+/// to hand them over to the store PDA
+pub fn with_legacy_accounts(mut instruction: Instruction, accounts: &[Pubkey]) -> Instruction {
+    let legacy_pda = Pubkey::find_program_address(&[b"store"], &instruction.program_id).0;
+    instruction.accounts.extend_from_slice(&[
+        AccountMeta::new_readonly(legacy_pda, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ]);
+    instruction.accounts.extend(
+        accounts
+            .iter()
+            .map(|account| AccountMeta::new(*account, false)),
+    );
+    instruction
+}
+
+/// Lamports `Realloc` and `MigrateStore` move from the owner for a store account holding `lamports`
+pub fn store_rent_top_up(rent: &Rent, lamports: u64) -> u64 {
+    rent.minimum_balance(Store::LEN).saturating_sub(lamports)
+}
+
+pub fn set_gate_mint_instruction(
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    gate_mint_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SetGateMint.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new_readonly(*gate_mint_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Appends trader's membership token account to Buy or Sell instruction (after cosigner if any),
+/// required by stores with gate mint
+pub fn with_membership(
+    mut instruction: Instruction,
+    membership_account_pubkey: &Pubkey,
+) -> Instruction {
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(*membership_account_pubkey, false));
+    instruction
+}
+
+pub fn find_discount_tier_address(
+    store_program_id: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    collection_pubkey: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"discount",
+            store_account_pubkey.as_ref(),
+            collection_pubkey.as_ref(),
+        ],
+        store_program_id,
+    )
+    .0
+}
+
+pub fn set_discount_tier_instruction(
+    discount_bps: u16,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    collection_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SetDiscountTier { discount_bps }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new_readonly(*store_account_pubkey, false),
+        AccountMeta::new_readonly(*collection_pubkey, false),
+        AccountMeta::new(
+            find_discount_tier_address(store_program_id, store_account_pubkey, collection_pubkey),
+            false,
+        ),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Appends NFT discount accounts to Buy instruction (after cosigner and membership if any),
+/// `holder_account_pubkey` is the buyer's token account holding the NFT of `nft_mint_pubkey`
+pub fn with_discount(
+    mut instruction: Instruction,
+    holder_account_pubkey: &Pubkey,
+    nft_mint_pubkey: &Pubkey,
+    collection_pubkey: &Pubkey,
+) -> Instruction {
+    let store_account_pubkey = instruction.accounts[1].pubkey;
+    let discount_pubkey = find_discount_tier_address(
+        &instruction.program_id,
+        &store_account_pubkey,
+        collection_pubkey,
+    );
+    instruction.accounts.extend_from_slice(&[
+        AccountMeta::new_readonly(*holder_account_pubkey, false),
+        AccountMeta::new_readonly(
+            crate::metadata::find_metadata_address(nft_mint_pubkey),
+            false,
+        ),
+        AccountMeta::new_readonly(discount_pubkey, false),
+    ]);
+    instruction
+}
+
+pub fn update_prices_batch_instruction(
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    prices: &[(Pubkey, u64)],
+) -> Result<Instruction, ProgramError> {
+    if prices.len() > u8::MAX as usize {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let data = StoreInstruction::UpdatePricesBatch {
+        prices: prices.iter().map(|(_, price)| *price).collect(),
+    }
+    .pack();
+
+    let mut accounts = vec![AccountMeta::new(*owner_pubkey, true)];
+    accounts.extend(
+        prices
+            .iter()
+            .map(|(store_account_pubkey, _)| AccountMeta::new(*store_account_pubkey, false)),
+    );
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn find_price_feed_address(store_program_id: &Pubkey, store_account_pubkey: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"feed", store_account_pubkey.as_ref()], store_program_id).0
+}
+
+pub fn attest_price_instruction(
+    price: u64,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::AttestPrice { price }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new(
+            find_price_feed_address(store_program_id, store_account_pubkey),
+            false,
+        ),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_escrow_instruction(
+    window: i64,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    escrow_vault_pubkey: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SetEscrow { window }.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+    if let Some(escrow_vault_pubkey) = escrow_vault_pubkey {
+        accounts.push(AccountMeta::new_readonly(*escrow_vault_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn find_escrow_address(
+    store_program_id: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    sequence: u64,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"escrow",
+            store_account_pubkey.as_ref(),
+            &sequence.to_le_bytes(),
+        ],
+        store_program_id,
+    )
+    .0
+}
+
+/// Appends escrow accounts to Buy instruction of a store with escrow (after cosigner and
+/// membership if any), `sequence` is the store escrow sequence. `payer_pubkey` must sign the
+/// transaction.
+pub fn with_escrow(
+    mut instruction: Instruction,
+    sequence: u64,
+    payer_pubkey: &Pubkey,
+) -> Instruction {
+    let store_account_pubkey = instruction.accounts[1].pubkey;
+    let escrow_pubkey =
+        find_escrow_address(&instruction.program_id, &store_account_pubkey, sequence);
+    instruction.accounts.extend_from_slice(&[
+        AccountMeta::new(escrow_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]);
+    instruction
+}
+
+/// Delegates buyer account with store tokens to the PDA, allowing the owner to refund escrowed buys
+pub fn approve_refund_instruction(
+    amount: u64,
+    buyer_pubkey: &Pubkey,
+    buyer_store_tokens_pubkey: &Pubkey,
+    pda: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    spl_token::instruction::approve(
+        token_program_id,
+        buyer_store_tokens_pubkey,
+        pda,
+        buyer_pubkey,
+        &[],
+        amount,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn refund_escrow_instruction(
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    escrow_pubkey: &Pubkey,
+    escrow: &EscrowRecord,
+    store_account_with_store_tokens: &Pubkey,
+    pda: &Pubkey,
+    token_program_id: &Pubkey,
+    ledger_wallet_pubkey: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::RefundEscrow.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new(*escrow_pubkey, false),
+        AccountMeta::new(escrow.vault_pubkey, false),
+        AccountMeta::new(*store_account_with_store_tokens, false),
+        AccountMeta::new(escrow.refund_pubkey, false),
+        AccountMeta::new(escrow.tokens_pubkey, false),
+        AccountMeta::new(escrow.payer_pubkey, false),
+        AccountMeta::new_readonly(*pda, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    if let Some(ledger_wallet_pubkey) = ledger_wallet_pubkey {
+        accounts.push(AccountMeta::new(
+            find_purchase_ledger_address(
+                store_program_id,
+                store_account_pubkey,
+                ledger_wallet_pubkey,
+            ),
+            false,
+        ));
+    }
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn release_escrow_instruction(
+    store_program_id: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    escrow_pubkey: &Pubkey,
+    escrow: &EscrowRecord,
+    proceeds_account_pubkey: &Pubkey,
+    pda: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::ReleaseEscrow.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*store_account_pubkey, false),
+        AccountMeta::new(*escrow_pubkey, false),
+        AccountMeta::new(escrow.vault_pubkey, false),
+        AccountMeta::new(*proceeds_account_pubkey, false),
+        AccountMeta::new(escrow.payer_pubkey, false),
+        AccountMeta::new_readonly(*pda, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn find_invoice_address(
+    store_program_id: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    reference: &[u8; 32],
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"invoice", store_account_pubkey.as_ref(), reference],
+        store_program_id,
+    )
+    .0
+}
+
+pub fn create_invoice_instruction(
+    amount: u64,
+    reference: [u8; 32],
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::CreateInvoice { amount, reference }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new_readonly(*store_account_pubkey, false),
+        AccountMeta::new(
+            find_invoice_address(store_program_id, store_account_pubkey, &reference),
+            false,
+        ),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn pay_invoice_instruction(
+    store_program_id: &Pubkey,
+    buyer_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    invoice_pubkey: &Pubkey,
+    store_account_with_payment_tokens: &Pubkey,
+    store_account_with_store_tokens: &Pubkey,
+    user_account_with_payment_tokens: &Pubkey,
+    user_account_with_store_tokens: &Pubkey,
+    pda: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::PayInvoice.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*buyer_pubkey, true),
+        AccountMeta::new(*invoice_pubkey, false),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new(*store_account_with_payment_tokens, false),
+        AccountMeta::new(*store_account_with_store_tokens, false),
+        AccountMeta::new(*user_account_with_payment_tokens, false),
+        AccountMeta::new(*user_account_with_store_tokens, false),
+        AccountMeta::new_readonly(*pda, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn propose_owner_instruction(
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    new_owner_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::ProposeOwner.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new_readonly(*new_owner_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn accept_owner_instruction(
+    store_program_id: &Pubkey,
+    new_owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::AcceptOwner.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*new_owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn release_accounts_instruction(
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    account_with_payment_tokens: &Pubkey,
+    account_with_store_tokens: &Pubkey,
+    pda: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::ReleaseAccounts.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new(*account_with_payment_tokens, false),
+        AccountMeta::new(*account_with_store_tokens, false),
+        AccountMeta::new_readonly(*pda, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Store token accounts are passed always, they are ignored for a store released before.
+/// `vaults` are the fee vault and escrow account of the store which are set and differ from the
+/// store token accounts, in this order.
+#[allow(clippy::too_many_arguments)]
+pub fn close_store_instruction(
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    account_with_payment_tokens: &Pubkey,
+    account_with_store_tokens: &Pubkey,
+    pda: &Pubkey,
+    token_program_id: &Pubkey,
+    vaults: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = release_accounts_instruction(
+        store_program_id,
+        owner_pubkey,
+        store_account_pubkey,
+        account_with_payment_tokens,
+        account_with_store_tokens,
+        pda,
+        token_program_id,
+    )?;
+    instruction.data = StoreInstruction::CloseStore.pack();
+    instruction.accounts.extend(
+        vaults
+            .iter()
+            .map(|vault_pubkey| AccountMeta::new(*vault_pubkey, false)),
+    );
+    Ok(instruction)
+}
+
+pub fn reactivate_store_instruction(
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::ReactivateStore.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn find_offer_address(store_program_id: &Pubkey, offer_vault_pubkey: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"offer", offer_vault_pubkey.as_ref()], store_program_id).0
+}
+
+/// `maker_payment_tokens` funds the offer vault, bought tokens go to `maker_receive_tokens`
+#[allow(clippy::too_many_arguments)]
+pub fn create_buy_offer_instruction(
+    amount: u64,
+    price: u64,
+    expires_at: i64,
+    store_program_id: &Pubkey,
+    maker_pubkey: &Pubkey,
+    offer_vault_pubkey: &Pubkey,
+    maker_payment_tokens: &Pubkey,
+    maker_receive_tokens: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::CreateBuyOffer {
+        amount,
+        price,
+        expires_at,
+    }
+    .pack();
+    create_offer_instruction(
+        data,
+        store_program_id,
+        maker_pubkey,
+        offer_vault_pubkey,
+        maker_payment_tokens,
+        maker_receive_tokens,
+        token_program_id,
+    )
+}
+
+/// `maker_store_tokens` funds the offer vault, the payment goes to `maker_receive_tokens`
+#[allow(clippy::too_many_arguments)]
+pub fn create_sell_offer_instruction(
+    amount: u64,
+    price: u64,
+    expires_at: i64,
+    store_program_id: &Pubkey,
+    maker_pubkey: &Pubkey,
+    offer_vault_pubkey: &Pubkey,
+    maker_store_tokens: &Pubkey,
+    maker_receive_tokens: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::CreateSellOffer {
+        amount,
+        price,
+        expires_at,
+    }
+    .pack();
+    create_offer_instruction(
+        data,
+        store_program_id,
+        maker_pubkey,
+        offer_vault_pubkey,
+        maker_store_tokens,
+        maker_receive_tokens,
+        token_program_id,
+    )
+}
+
+fn create_offer_instruction(
+    data: Vec<u8>,
+    store_program_id: &Pubkey,
+    maker_pubkey: &Pubkey,
+    offer_vault_pubkey: &Pubkey,
+    maker_source_tokens: &Pubkey,
+    maker_receive_tokens: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*maker_pubkey, true),
+        AccountMeta::new(
+            find_offer_address(store_program_id, offer_vault_pubkey),
+            false,
+        ),
+        AccountMeta::new(*offer_vault_pubkey, false),
+        AccountMeta::new(*maker_source_tokens, false),
+        AccountMeta::new_readonly(*maker_receive_tokens, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// AcceptBuyOffer or AcceptSellOffer depending on the offer side, the taker pays from
+/// `taker_source_tokens` and receives the escrowed tokens to `taker_receive_tokens`
+pub fn accept_offer_instruction(
+    store_program_id: &Pubkey,
+    taker_pubkey: &Pubkey,
+    offer: &Offer,
+    taker_source_tokens: &Pubkey,
+    taker_receive_tokens: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = match offer.side {
+        OrderSide::Buy => StoreInstruction::AcceptBuyOffer,
+        OrderSide::Sell => StoreInstruction::AcceptSellOffer,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*taker_pubkey, true),
+        AccountMeta::new(
+            find_offer_address(store_program_id, &offer.vault_pubkey),
+            false,
+        ),
+        AccountMeta::new(offer.vault_pubkey, false),
+        AccountMeta::new(*taker_source_tokens, false),
+        AccountMeta::new(offer.receive_pubkey, false),
+        AccountMeta::new(*taker_receive_tokens, false),
+        AccountMeta::new(offer.maker_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn cancel_offer_instruction(
+    store_program_id: &Pubkey,
+    offer: &Offer,
+    maker_refund_tokens: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::CancelOffer.pack();
+
+    let accounts = vec![
+        AccountMeta::new(offer.maker_pubkey, true),
+        AccountMeta::new(
+            find_offer_address(store_program_id, &offer.vault_pubkey),
+            false,
+        ),
+        AccountMeta::new(offer.vault_pubkey, false),
+        AccountMeta::new(*maker_refund_tokens, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn update_prices_instruction(
+    buy_price: u64,
+    sell_price: u64,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::UpdatePrices {
+        buy_price,
+        sell_price,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_instruction(
+    amount: u64,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    store_account_with_tokens: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pda: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::Withdraw { amount }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new_readonly(*store_account_pubkey, false),
+        AccountMeta::new(*store_account_with_tokens, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*pda, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_instruction(
+    amount: u64,
+    side: OrderSide,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    store_account_with_tokens: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::Deposit { amount, side }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new_readonly(*store_account_pubkey, false),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*store_account_with_tokens, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_features_instruction(
+    features: u32,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SetFeatures { features }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_trading_hours_instruction(
+    hours: [u8; 21],
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SetTradingHours { hours }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_guardian_instruction(
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    guardian_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SetGuardian.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new_readonly(*guardian_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// `authority_pubkey` is the owner or the guardian of the store
+pub fn pause_instruction(
+    store_program_id: &Pubkey,
+    authority_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::Pause.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*authority_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn resume_instruction(
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::Resume.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Makes Buy instruction log the trade with `reference_pubkey`, see `BuyWithReference`.
+/// Applied after other `with_` helpers of the Buy instruction, as the reference goes first.
+pub fn with_reference(
+    mut instruction: Instruction,
+    reference_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    instruction.data = match StoreInstruction::unpack(&instruction.data)? {
+        StoreInstruction::Buy {
+            amount,
+            max_price,
+            tag,
+        } => StoreInstruction::BuyWithReference {
+            amount,
+            max_price,
+            tag,
+        },
+        _ => return Err(ProgramError::InvalidArgument),
+    }
+    .pack();
+    instruction
+        .accounts
+        .insert(0, AccountMeta::new_readonly(*reference_pubkey, false));
+    Ok(instruction)
+}
+
+pub fn get_version_instruction(store_program_id: &Pubkey) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::GetVersion.pack();
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts: vec![],
+        data,
+    })
+}
+
+pub fn update_price_ratio_instruction(
+    buy_price: u64,
+    sell_price: u64,
+    denominator: u64,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::UpdatePriceRatio {
+        buy_price,
+        sell_price,
+        denominator,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Tags Buy or Sell instruction (also with reference) with `tag`, logged with the trade
+/// in the `TaggedTrade` event to attribute it to a client or integration
+pub fn with_tag(mut instruction: Instruction, tag: u16) -> Result<Instruction, ProgramError> {
+    instruction.data = match StoreInstruction::unpack(&instruction.data)? {
+        StoreInstruction::Buy {
+            amount, max_price, ..
+        } => StoreInstruction::Buy {
+            amount,
+            max_price,
+            tag: Some(tag),
+        },
+        StoreInstruction::Sell {
+            amount, min_price, ..
+        } => StoreInstruction::Sell {
+            amount,
+            min_price,
+            tag: Some(tag),
+        },
+        StoreInstruction::BuyWithReference {
+            amount, max_price, ..
+        } => StoreInstruction::BuyWithReference {
+            amount,
+            max_price,
+            tag: Some(tag),
+        },
+        _ => return Err(ProgramError::InvalidArgument),
+    }
+    .pack();
+    Ok(instruction)
+}
+
+pub fn find_valuation_address(
+    store_program_id: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    epoch: u64,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"valuation",
+            store_account_pubkey.as_ref(),
+            &epoch.to_le_bytes(),
+        ],
+        store_program_id,
+    )
+    .0
+}
+
+/// `epoch` must be the epoch the transaction lands in, marks at the store price feed
+/// price if `price_feed` is set
+pub fn record_valuation_instruction(
+    epoch: u64,
+    store_program_id: &Pubkey,
+    payer_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    store_account_with_store_tokens: &Pubkey,
+    store_account_with_payment_tokens: &Pubkey,
+    price_feed: bool,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::RecordValuation.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(*store_account_pubkey, false),
+        AccountMeta::new_readonly(*store_account_with_store_tokens, false),
+        AccountMeta::new_readonly(*store_account_with_payment_tokens, false),
+        AccountMeta::new(
+            find_valuation_address(store_program_id, store_account_pubkey, epoch),
+            false,
+        ),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    if price_feed {
+        accounts.push(AccountMeta::new_readonly(
+            find_price_feed_address(store_program_id, store_account_pubkey),
+            false,
+        ));
+    }
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Appends valuation account to ReportPnl instruction, to report unrealized PnL at its mark price
+pub fn with_valuation(mut instruction: Instruction, valuation_pubkey: &Pubkey) -> Instruction {
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(*valuation_pubkey, false));
+    instruction
+}
+
+/// Sets the fee of InitializeAccount instruction, `fee_vault` must be owned by the store PDA
+pub fn with_fee(
+    mut instruction: Instruction,
+    fee_bps: u16,
+    fee_vault_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    instruction.data = match StoreInstruction::unpack(&instruction.data)? {
+        StoreInstruction::InitializeAccount {
+            price,
+            open_at_slot,
+            close_at_slot,
+            ..
+        } => StoreInstruction::InitializeAccount {
+            price,
+            fee_bps: Some(fee_bps),
+            open_at_slot,
+            close_at_slot,
+        },
+        _ => return Err(ProgramError::InvalidArgument),
+    }
+    .pack();
+    // right after the program stats, before the mints of created vaults
+    instruction
+        .accounts
+        .insert(8, AccountMeta::new_readonly(*fee_vault_pubkey, false));
+    Ok(instruction)
+}
+
+/// Sets the trading window of InitializeAccount instruction, so a timed sale needs no
+/// SetTradingWindow along. The instruction is Borsh encoded then.
+pub fn with_trading_window(
+    instruction: Instruction,
+    open_at_slot: u64,
+    close_at_slot: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = match StoreInstruction::unpack(&instruction.data)? {
+        StoreInstruction::InitializeAccount { price, fee_bps, .. } => {
+            StoreInstruction::InitializeAccount {
+                price,
+                fee_bps,
+                open_at_slot,
+                close_at_slot,
+            }
+        }
+        _ => return Err(ProgramError::InvalidArgument),
+    }
+    .pack();
+    Ok(Instruction {
+        data,
+        ..instruction
+    })
+}
+
+/// Appends the store fee vault to Buy or Sell instruction, after cosigner and membership accounts,
+/// or to RefundEscrow instruction of a buy which paid a fee
+pub fn with_fee_vault(mut instruction: Instruction, fee_vault_pubkey: &Pubkey) -> Instruction {
+    instruction
+        .accounts
+        .push(AccountMeta::new(*fee_vault_pubkey, false));
+    instruction
+}
+
+pub fn update_fee_instruction(
+    fee_bps: u16,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    fee_vault_pubkey: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::UpdateFee { fee_bps }.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+    if let Some(fee_vault_pubkey) = fee_vault_pubkey {
+        accounts.push(AccountMeta::new_readonly(*fee_vault_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_fees_instruction(
+    amount: u64,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    fee_vault_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pda: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::WithdrawFees { amount }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new_readonly(*store_account_pubkey, false),
+        AccountMeta::new(*fee_vault_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*pda, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn schedule_fee_instruction(
+    effective_at: i64,
+    fee_bps: u16,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::ScheduleFee {
+        effective_at,
+        fee_bps,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn begin_wind_down_instruction(
+    deadline: i64,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::BeginWindDown { deadline }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// `remaining_inventory` of `None` lifts the inventory limit
+pub fn set_trade_limits_instruction(
+    max_trade_amount: u64,
+    remaining_inventory: Option<u64>,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SetTradeLimits {
+        max_trade_amount,
+        remaining_inventory,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn find_registry_address(store_program_id: &Pubkey, owner_pubkey: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"registry", owner_pubkey.as_ref()], store_program_id).0
+}
+
+/// Registry PDA of the owner in `namespace`, the same as `find_registry_address` for the default one
+pub fn find_namespaced_registry_address(
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    namespace: &Pubkey,
+) -> Pubkey {
+    if *namespace == Pubkey::default() {
+        return find_registry_address(store_program_id, owner_pubkey);
+    }
+    Pubkey::find_program_address(
+        &[b"registry", namespace.as_ref(), owner_pubkey.as_ref()],
+        store_program_id,
+    )
+    .0
+}
+
+/// Replaces the registry account of RegisterStore or UnregisterStore instruction with the owner
+/// registry in `namespace`
+pub fn with_registry_namespace(mut instruction: Instruction, namespace: &Pubkey) -> Instruction {
+    let owner_pubkey = instruction.accounts[0].pubkey;
+    instruction.accounts[2].pubkey =
+        find_namespaced_registry_address(&instruction.program_id, &owner_pubkey, namespace);
+    instruction
+}
+
+pub fn register_store_instruction(
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::RegisterStore.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new_readonly(*store_account_pubkey, false),
+        AccountMeta::new(find_registry_address(store_program_id, owner_pubkey), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn unregister_store_instruction(
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::UnregisterStore.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new_readonly(*store_account_pubkey, false),
+        AccountMeta::new(find_registry_address(store_program_id, owner_pubkey), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_escheat_period_instruction(
+    period: i64,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SetEscheatPeriod { period }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn sweep_escrow_instruction(
+    store_program_id: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    store_owner_pubkey: &Pubkey,
+    escrow_pubkey: &Pubkey,
+    escrow: &EscrowRecord,
+    proceeds_account_pubkey: &Pubkey,
+    pda: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SweepEscrow.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*store_account_pubkey, false),
+        AccountMeta::new(*escrow_pubkey, false),
+        AccountMeta::new(escrow.vault_pubkey, false),
+        AccountMeta::new(*proceeds_account_pubkey, false),
+        AccountMeta::new(*store_owner_pubkey, false),
+        AccountMeta::new_readonly(*pda, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
 
-    ///   0. `[signer]` The owner of store account
-    ///   0. `[writable]` The store account
-    UpdatePrice { price: u64 },
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
 
-    ///   0. `[signer]` owner of token accounts to transfer
-    ///   0. `[]` The store account
-    ///   0. `[writable]` store account with payment tokens (owner must be same as store owner)
-    ///   0. `[writable]` store account with store tokens (same as in store info account)
-    ///   0. `[writable]` user account to transfer payment tokens from (owner is signer)
-    ///   0. `[writable]` user account for store tokens
-    ///   0. `[]` The PDA account
-    ///   0. `[]` The token program
-    Buy {
-        amount: u64,
-        /// price same as in store account
-        price: u64,
-    },
+/// Combines Buy instructions of the same buyer, built with any of their `with_` helpers, into `BatchBuy`
+pub fn batch_buy_instruction(buys: &[Instruction]) -> Result<Instruction, ProgramError> {
+    let first = buys.first().ok_or(ProgramError::InvalidArgument)?;
+    if buys.len() > MAX_BATCH_LEGS {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let buyer = first.accounts[0].clone();
+    let mut legs = Vec::with_capacity(buys.len());
+    let mut accounts = vec![buyer.clone()];
+    for buy in buys {
+        let (amount, max_price) = match StoreInstruction::unpack(&buy.data)? {
+            StoreInstruction::Buy {
+                amount, max_price, ..
+            } => (amount, max_price),
+            _ => return Err(ProgramError::InvalidArgument),
+        };
+        if buy.program_id != first.program_id || buy.accounts[0] != buyer {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let leg_accounts = &buy.accounts[1..];
+        legs.push(BatchLeg {
+            amount,
+            max_price,
+            accounts: u8::try_from(leg_accounts.len())
+                .map_err(|_| ProgramError::InvalidArgument)?,
+        });
+        accounts.extend_from_slice(leg_accounts);
+    }
 
-    ///   0. `[signer]` owner of store tokens account to sell
-    ///   0. `[]` The store account
-    ///   0. `[writable]` store account with payment tokens for sell payment (same as in store info account)
-    ///   0. `[writable]` account to transfer store tokens to (owner must be same as store owner)
-    ///   0. `[writable]` user account to transfer payment tokens to
-    ///   0. `[writable]` user account with store tokens to sell (owner is signer)
-    ///   0. `[]` The PDA account
-    ///   0. `[]` The token program
-    Sell {
-        amount: u64,
-        /// price same as in store account
-        price: u64,
-    },
-    // ReleaseAccounts (close or get back accounts owned by program)
-    // CreateBuyOffer
-    // CreateSellOffer
-    // AcceptBuyOffer
-    // AcceptSellOffer
+    Ok(Instruction {
+        program_id: first.program_id,
+        accounts,
+        data: StoreInstruction::BatchBuy { legs }.pack(),
+    })
 }
 
-impl StoreInstruction {
-    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
-        let (tag, rest) = input
-            .split_first()
-            .ok_or(ProgramError::InvalidInstructionData)?;
+/// Limits a transaction composed of store instructions must fit in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransactionLimits {
+    /// serialized transaction size, signatures included
+    pub max_size: usize,
+    /// distinct accounts, programs and payer included
+    pub max_accounts: usize,
+}
 
-        Ok(match tag {
-            0 => Self::InitializeAccount {
-                price: Self::unpack_u64(0, rest)?,
-            },
-            1 => Self::UpdatePrice {
-                price: Self::unpack_u64(0, rest)?,
-            },
-            2 => Self::Buy {
-                amount: Self::unpack_u64(0, rest)?,
-                price: Self::unpack_u64(8, rest)?,
-            },
-            3 => Self::Sell {
-                amount: Self::unpack_u64(0, rest)?,
-                price: Self::unpack_u64(8, rest)?,
-            },
-            _ => return Err(ProgramError::InvalidInstructionData),
-        })
+impl Default for TransactionLimits {
+    /// Limits of a legacy transaction: packet data size and account locks
+    fn default() -> Self {
+        Self {
+            max_size: 1232,
+            max_accounts: 64,
+        }
     }
+}
 
-    pub fn pack(&self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(size_of::<Self>());
-        match self {
-            &Self::InitializeAccount { price } => {
-                buf.push(0);
-                buf.extend_from_slice(&price.to_le_bytes());
-            }
-            &Self::UpdatePrice { price } => {
-                buf.push(1);
-                buf.extend_from_slice(&price.to_le_bytes());
-            }
-            &Self::Buy { amount, price } => {
-                buf.push(2);
-                buf.extend_from_slice(&amount.to_le_bytes());
-                buf.extend_from_slice(&price.to_le_bytes());
-            }
-            &Self::Sell { amount, price } => {
-                buf.push(3);
-                buf.extend_from_slice(&amount.to_le_bytes());
-                buf.extend_from_slice(&price.to_le_bytes());
+impl TransactionLimits {
+    /// Checks legacy transaction of `instructions` paid by `payer_pubkey` fits the limits
+    pub fn check(
+        &self,
+        instructions: &[Instruction],
+        payer_pubkey: &Pubkey,
+    ) -> Result<(), ProgramError> {
+        let message = Message::new(instructions, Some(payer_pubkey));
+        // compact length of the signatures, then the signatures
+        let signatures = message.header.num_required_signatures as usize;
+        let size = 1 + signatures * 64 + message.serialize().len();
+        if size > self.max_size || message.account_keys.len() > self.max_accounts {
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(())
+    }
+}
+
+/// Same as `batch_buy_instruction`, splitting the buys into as few `BatchBuy` instructions
+/// as needed for each to fit a transaction of its own
+pub fn batch_buy_instructions(
+    buys: &[Instruction],
+    payer_pubkey: &Pubkey,
+    limits: &TransactionLimits,
+) -> Result<Vec<Instruction>, ProgramError> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    while start < buys.len() {
+        let mut end = start + 1;
+        let mut batch = batch_buy_instruction(&buys[start..end])?;
+        limits.check(slice::from_ref(&batch), payer_pubkey)?;
+        while end < buys.len() && end - start < MAX_BATCH_LEGS {
+            let larger = batch_buy_instruction(&buys[start..end + 1])?;
+            if limits
+                .check(slice::from_ref(&larger), payer_pubkey)
+                .is_err()
+            {
+                break;
             }
+            batch = larger;
+            end += 1;
         }
-        buf
+        batches.push(batch);
+        start = end;
     }
+    Ok(batches)
+}
 
-    fn unpack_u64(offset: usize, input: &[u8]) -> Result<u64, ProgramError> {
-        let price = input
-            .get(offset..offset + 8)
-            .and_then(|slice| slice.try_into().ok())
-            .map(u64::from_le_bytes)
-            .ok_or(ProgramError::InvalidInstructionData)?;
-        Ok(price)
+pub fn find_stats_address(store_program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"stats"], store_program_id).0
+}
+
+pub fn initialize_stats_instruction(
+    store_program_id: &Pubkey,
+    payer_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::InitializeStats.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new(find_stats_address(store_program_id), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Mint stats of stores with `payment_mint`, created by the first InitializeAccount counted in the
+/// program stats
+pub fn find_mint_stats_address(store_program_id: &Pubkey, payment_mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"stats", payment_mint.as_ref()], store_program_id).0
+}
+
+/// Appends stats accounts of a store with `payment_mint`, required once the program stats exist:
+/// the mint stats and the system program to InitializeAccount instruction (after the rest of its
+/// accounts), the program stats and the mint stats to CloseStore instruction, or the mint stats
+/// to Buy or Sell instruction (after the fee vault and escrow accounts if any, before discount
+/// accounts)
+pub fn with_stats(mut instruction: Instruction, payment_mint: &Pubkey) -> Instruction {
+    let program_id = instruction.program_id;
+    let mint_stats = AccountMeta::new(find_mint_stats_address(&program_id, payment_mint), false);
+    match StoreInstruction::unpack(&instruction.data) {
+        Ok(StoreInstruction::InitializeAccount { .. }) => instruction.accounts.extend([
+            mint_stats,
+            AccountMeta::new_readonly(system_program::id(), false),
+        ]),
+        Ok(StoreInstruction::CloseStore) => instruction.accounts.extend([
+            AccountMeta::new(find_stats_address(&program_id), false),
+            mint_stats,
+        ]),
+        _ => instruction.accounts.push(mint_stats),
     }
+    instruction
 }
 
-pub fn initialyze_account_instruction(
-    price: u64,
+pub fn find_config_address(store_program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"config"], store_program_id).0
+}
+
+/// Program data account of the program deployed with the upgradeable loader
+pub fn find_program_data_address(store_program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[store_program_id.as_ref()], &bpf_loader_upgradeable::id()).0
+}
+
+pub fn initialize_config_instruction(
+    store_program_id: &Pubkey,
+    authority_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::InitializeConfig.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*authority_pubkey, true),
+        AccountMeta::new(find_config_address(store_program_id), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_program_data_address(store_program_id), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_payment_mints_instruction(
+    mints: &[Pubkey],
+    store_program_id: &Pubkey,
+    authority_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SetPaymentMints {
+        mints: mints.to_vec(),
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*authority_pubkey, true),
+        AccountMeta::new(find_config_address(store_program_id), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn propose_config_authority_instruction(
+    store_program_id: &Pubkey,
+    authority_pubkey: &Pubkey,
+    new_authority_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::ProposeConfigAuthority.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*authority_pubkey, true),
+        AccountMeta::new(find_config_address(store_program_id), false),
+        AccountMeta::new_readonly(*new_authority_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn accept_config_authority_instruction(
+    store_program_id: &Pubkey,
+    new_authority_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::AcceptConfigAuthority.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*new_authority_pubkey, true),
+        AccountMeta::new(find_config_address(store_program_id), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn find_purchase_ledger_address(
+    store_program_id: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    buyer_pubkey: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"purchases",
+            store_account_pubkey.as_ref(),
+            buyer_pubkey.as_ref(),
+        ],
+        store_program_id,
+    )
+    .0
+}
+
+pub fn set_wallet_cap_instruction(
+    cap: u64,
     store_program_id: &Pubkey,
     owner_pubkey: &Pubkey,
     store_account_pubkey: &Pubkey,
-    account_with_payment_tokens: &Pubkey,
-    account_with_store_tokens: &Pubkey,
-    token_program_id: &Pubkey,
 ) -> Result<Instruction, ProgramError> {
-    let data = StoreInstruction::InitializeAccount { price }.pack();
+    let data = StoreInstruction::SetWalletCap { cap }.pack();
 
     let accounts = vec![
-        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new_readonly(*owner_pubkey, true),
         AccountMeta::new(*store_account_pubkey, false),
-        AccountMeta::new(*account_with_payment_tokens, false),
-        AccountMeta::new(*account_with_store_tokens, false),
-        AccountMeta::new_readonly(*token_program_id, false),
-        AccountMeta::new_readonly(sysvar::rent::id(), false),
     ];
 
     Ok(Instruction {
@@ -141,16 +4080,41 @@ pub fn initialyze_account_instruction(
     })
 }
 
-pub fn update_price_instruction(
-    price: u64,
+/// Appends the purchase ledger accounts of `wallet_pubkey`, the owner of the user account for
+/// store tokens, to Buy instruction of a store capping purchases per wallet (after the stats
+/// account if any, before discount accounts). `payer_pubkey` must sign the transaction.
+pub fn with_purchase_ledger(
+    mut instruction: Instruction,
+    wallet_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+) -> Instruction {
+    let store_account_pubkey = instruction.accounts[1].pubkey;
+    let ledger_pubkey = find_purchase_ledger_address(
+        &instruction.program_id,
+        &store_account_pubkey,
+        wallet_pubkey,
+    );
+    instruction.accounts.extend_from_slice(&[
+        AccountMeta::new(ledger_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]);
+    instruction
+}
+
+pub fn set_namespace_instruction(
+    namespace: &Pubkey,
     store_program_id: &Pubkey,
     owner_pubkey: &Pubkey,
     store_account_pubkey: &Pubkey,
 ) -> Result<Instruction, ProgramError> {
-    let data = StoreInstruction::UpdatePrice { price }.pack();
+    let data = StoreInstruction::SetNamespace {
+        namespace: *namespace,
+    }
+    .pack();
 
     let accounts = vec![
-        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new_readonly(*owner_pubkey, true),
         AccountMeta::new(*store_account_pubkey, false),
     ];
 
@@ -161,30 +4125,22 @@ pub fn update_price_instruction(
     })
 }
 
-pub fn buy_instruction(
-    amount: u64,
-    price: u64,
+pub fn set_trading_window_instruction(
+    open_at_slot: u64,
+    close_at_slot: u64,
     store_program_id: &Pubkey,
-    buyer_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
     store_account_pubkey: &Pubkey,
-    store_account_with_payment_tokens: &Pubkey,
-    store_account_with_store_tokens: &Pubkey,
-    user_account_with_payment_tokens: &Pubkey,
-    user_account_with_store_tokens: &Pubkey,
-    pda: &Pubkey,
-    token_program_id: &Pubkey,
 ) -> Result<Instruction, ProgramError> {
-    let data = StoreInstruction::Buy { amount, price }.pack();
+    let data = StoreInstruction::SetTradingWindow {
+        open_at_slot,
+        close_at_slot,
+    }
+    .pack();
 
     let accounts = vec![
-        AccountMeta::new(*buyer_pubkey, true),
+        AccountMeta::new_readonly(*owner_pubkey, true),
         AccountMeta::new(*store_account_pubkey, false),
-        AccountMeta::new(*store_account_with_payment_tokens, false),
-        AccountMeta::new(*store_account_with_store_tokens, false),
-        AccountMeta::new(*user_account_with_payment_tokens, false),
-        AccountMeta::new(*user_account_with_store_tokens, false),
-        AccountMeta::new_readonly(*pda, false),
-        AccountMeta::new_readonly(*token_program_id, false),
     ];
 
     Ok(Instruction {
@@ -193,30 +4149,85 @@ pub fn buy_instruction(
         data,
     })
 }
-pub fn sell_instruction(
-    amount: u64,
-    price: u64,
+
+pub fn set_settlement_cutoff_instruction(
+    cutoff: Option<u32>,
     store_program_id: &Pubkey,
-    buyer_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
     store_account_pubkey: &Pubkey,
-    store_account_with_payment_tokens: &Pubkey,
-    store_account_with_store_tokens: &Pubkey,
-    user_account_with_payment_tokens: &Pubkey,
-    user_account_with_store_tokens: &Pubkey,
-    pda: &Pubkey,
-    token_program_id: &Pubkey,
 ) -> Result<Instruction, ProgramError> {
-    let data = StoreInstruction::Sell { amount, price }.pack();
+    let data = StoreInstruction::SetSettlementCutoff { cutoff }.pack();
 
     let accounts = vec![
-        AccountMeta::new(*buyer_pubkey, true),
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// `day` is in days since the unix epoch, of its cutoff
+pub fn find_settlement_report_address(
+    store_program_id: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    day: i64,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"settlement",
+            store_account_pubkey.as_ref(),
+            &day.to_le_bytes(),
+        ],
+        store_program_id,
+    )
+    .0
+}
+
+/// Settles `day`, the day of the last cutoff of the store
+pub fn settle_day_instruction(
+    day: i64,
+    store_program_id: &Pubkey,
+    payer_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+    store_store_tokens_pubkey: &Pubkey,
+    store_payment_tokens_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SettleDay.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new(*store_account_pubkey, false),
+        AccountMeta::new_readonly(*store_store_tokens_pubkey, false),
+        AccountMeta::new_readonly(*store_payment_tokens_pubkey, false),
+        AccountMeta::new(
+            find_settlement_report_address(store_program_id, store_account_pubkey, day),
+            false,
+        ),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *store_program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_pricing_instruction(
+    pricing: Pricing,
+    store_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    store_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = StoreInstruction::SetPricing { pricing }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner_pubkey, true),
         AccountMeta::new(*store_account_pubkey, false),
-        AccountMeta::new(*store_account_with_payment_tokens, false),
-        AccountMeta::new(*store_account_with_store_tokens, false),
-        AccountMeta::new(*user_account_with_payment_tokens, false),
-        AccountMeta::new(*user_account_with_store_tokens, false),
-        AccountMeta::new_readonly(*pda, false),
-        AccountMeta::new_readonly(*token_program_id, false),
     ];
 
     Ok(Instruction {
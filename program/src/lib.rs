@@ -1,7 +1,10 @@
 pub mod error;
 pub mod instruction;
+pub mod math;
+pub mod metadata;
 pub mod processor;
 pub mod state;
+pub mod store_interface;
 
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
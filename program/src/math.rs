@@ -0,0 +1,54 @@
+//! Constant product curve of `PRICING_CONSTANT_PRODUCT` stores, shared by the processor and
+//! clients quoting trades off-chain
+
+use std::convert::TryFrom;
+
+use crate::state::{OrderSide, Store};
+
+/// Curve reserves of a store holding `payment_balance` and `store_balance` in its vaults
+pub fn curve_reserves(
+    store: &Store,
+    payment_balance: u64,
+    store_balance: u64,
+) -> Option<(u64, u64)> {
+    Some((
+        payment_balance.checked_add(store.curve_virtual_payment_reserve)?,
+        store_balance.checked_add(store.curve_virtual_store_reserve)?,
+    ))
+}
+
+/// Payment tokens the user pays for `amount` store tokens taken out of the reserves, rounded up,
+/// `None` if the reserves can't give `amount`
+pub fn constant_product_buy(payment_reserve: u64, store_reserve: u64, amount: u64) -> Option<u64> {
+    if amount >= store_reserve {
+        return None;
+    }
+    let remaining = (store_reserve - amount) as u128;
+    let product = payment_reserve as u128 * amount as u128;
+    u64::try_from(product.div_ceil(remaining)).ok()
+}
+
+/// Payment tokens the user receives for `amount` store tokens put into the reserves, rounded down
+pub fn constant_product_sell(payment_reserve: u64, store_reserve: u64, amount: u64) -> Option<u64> {
+    let reserve = store_reserve as u128 + amount as u128;
+    if reserve == 0 {
+        return None;
+    }
+    u64::try_from(payment_reserve as u128 * amount as u128 / reserve).ok()
+}
+
+/// Notional of Buy or Sell of `amount` store tokens with a constant product store holding
+/// `payment_balance` and `store_balance` in its vaults, fees excluded
+pub fn quote_constant_product(
+    store: &Store,
+    payment_balance: u64,
+    store_balance: u64,
+    side: OrderSide,
+    amount: u64,
+) -> Option<u64> {
+    let (payment_reserve, store_reserve) = curve_reserves(store, payment_balance, store_balance)?;
+    match side {
+        OrderSide::Buy => constant_product_buy(payment_reserve, store_reserve, amount),
+        OrderSide::Sell => constant_product_sell(payment_reserve, store_reserve, amount),
+    }
+}
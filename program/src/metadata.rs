@@ -0,0 +1,74 @@
+//! Minimal reader of Metaplex token metadata accounts, enough to check NFT collections
+//! without depending on the metadata program crate
+
+use std::convert::TryInto;
+
+use solana_program::pubkey::Pubkey;
+
+solana_program::declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+/// Metadata account of `mint`, PDA of `["metadata", metadata program, mint]`
+pub fn find_metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"metadata", id().as_ref(), mint.as_ref()], &id()).0
+}
+
+/// Verified collection of metadata account with `data` describing `mint`
+pub fn verified_collection(data: &[u8], mint: &Pubkey) -> Option<Pubkey> {
+    let mut reader = Reader { data, offset: 0 };
+    reader.skip(1 + 32)?; // key, update authority
+    if reader.pubkey()? != *mint {
+        return None;
+    }
+    for _string in 0..3 {
+        // name, symbol, uri
+        let len = reader.u32()?;
+        reader.skip(len as usize)?;
+    }
+    reader.skip(2)?; // seller fee basis points
+    if reader.u8()? == 1 {
+        // creators: address, verified, share
+        let len = reader.u32()?;
+        reader.skip(len as usize * (32 + 1 + 1))?;
+    }
+    reader.skip(1 + 1)?; // primary sale happened, is mutable
+    for _option in 0..2 {
+        // edition nonce, token standard
+        if reader.u8()? == 1 {
+            reader.skip(1)?;
+        }
+    }
+    if reader.u8()? != 1 || reader.u8()? != 1 {
+        return None;
+    }
+    reader.pubkey()
+}
+
+/// Borsh fields reader
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.offset..self.offset.checked_add(len)?)?;
+        self.offset += len;
+        Some(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.take(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn pubkey(&mut self) -> Option<Pubkey> {
+        Some(Pubkey::new_from_array(self.take(32)?.try_into().ok()?))
+    }
+}
@@ -0,0 +1,722 @@
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction, system_program,
+    sysvar::Sysvar,
+};
+
+use super::{
+    set_token_account_owner_signed,
+    settlement::check_settled,
+    stats::untrack_store,
+    transfer, transfer_signed,
+    validation::{SignerAccount, StoreAccount},
+    StoreAuthority,
+};
+use crate::{
+    error::StoreError,
+    state::{OrderSide, Store, FEATURE_MEMBERSHIP, STORE_VERSION},
+};
+
+pub(super) fn process_update_lot_size(
+    accounts: &[AccountInfo],
+    lot_size: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+
+    store.info.lot_size = lot_size;
+    store.save()
+}
+
+pub(super) fn process_set_redirect(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+        new_store: store_owned(owner),
+    });
+    if new_store.key() == store.key() {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    store.info.redirect_pubkey = *new_store.key();
+    store.save()
+}
+
+pub(super) fn process_set_inventory_thresholds(
+    accounts: &[AccountInfo],
+    store_tokens: u64,
+    payment_tokens: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+
+    store.info.store_tokens_warning_threshold = store_tokens;
+    store.info.payment_tokens_warning_threshold = payment_tokens;
+    store.save()
+}
+
+/// Sets the max amount of a buy and the inventory left to sell, `None` lifts the inventory limit
+pub(super) fn process_set_trade_limits(
+    accounts: &[AccountInfo],
+    max_trade_amount: u64,
+    remaining_inventory: Option<u64>,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+
+    store.info.max_trade_amount = max_trade_amount;
+    store.info.is_inventory_limited = remaining_inventory.is_some();
+    store.info.remaining_inventory = remaining_inventory.unwrap_or(0);
+    store.save()
+}
+
+pub(super) fn process_set_settlement_accounts(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+        proceeds_account: vault,
+        payout_account: vault,
+    });
+
+    store.info.proceeds_pubkey = *proceeds_account.key();
+    store.info.payout_pubkey = *payout_account.key();
+    store.save()
+}
+
+pub(super) fn process_set_cosigner(
+    accounts: &[AccountInfo],
+    threshold: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+        cosigner: any,
+    });
+
+    store.info.cosigner_pubkey = *cosigner.key;
+    store.info.cosign_threshold = threshold;
+    store.save()
+}
+
+pub(super) fn process_set_gate_mint(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+        gate_mint: any,
+    });
+    if *gate_mint.key != Pubkey::default() {
+        store.check_feature(FEATURE_MEMBERSHIP)?;
+    }
+
+    store.info.gate_mint_pubkey = *gate_mint.key;
+    store.save()
+}
+
+pub(super) fn process_propose_owner(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+        new_owner: any,
+    });
+
+    store.info.pending_owner_pubkey = *new_owner.key;
+    store.save()
+}
+
+pub(super) fn process_accept_owner(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        new_owner: signer,
+        mut store: store,
+    });
+    if store.info.pending_owner_pubkey == Pubkey::default()
+        || store.info.pending_owner_pubkey != *new_owner.key()
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    msg!(
+        "OwnerChanged: store {} from {} to {}",
+        store.key(),
+        store.info.owner_pubkey,
+        new_owner.key()
+    );
+    store.info.owner_pubkey = *new_owner.key();
+    store.info.pending_owner_pubkey = Pubkey::default();
+    store.save()
+}
+
+/// Returns store token accounts to the owner and closes the store
+pub(super) fn process_release_accounts(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+    check_wind_down_ended(&store)?;
+    release_store_accounts(account_info_iter, &store, &owner, program_id)?;
+
+    store.info.is_closed = true;
+    store.save()?;
+    msg!("StoreClosed: store {}", store.key());
+    Ok(())
+}
+
+/// Deletes the store, returning its lamports above the rent exempt balance to the owner. Token accounts
+/// are returned to the owner as well unless the store was closed by ReleaseAccounts before, and so are
+/// the fee vault and the escrow account, which the tombstone doesn't keep. Rejected while the escrow
+/// account holds payments.
+/// Wiped data keeps the closed flag and the owner as a tombstone, so the account can't be initialized
+/// again unless the owner reactivates it.
+pub(super) fn process_close_store(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        store: store_owned(owner),
+    });
+    let vaults = fee_and_escrow_vaults(&store);
+    if !store.info.is_closed {
+        check_wind_down_ended(&store)?;
+    }
+    check_settled(&store)?;
+    if !store.info.is_closed || !vaults.is_empty() {
+        accounts!(account_info_iter, program_id, {
+            native_tokens_account: vault(&store.info.native_tokens_to_auto_sell_pubkey),
+            store_tokens_account: vault(&store.info.store_tokens_to_auto_buy_pubkey),
+            pda_account: any,
+            token_program: program(&spl_token::id()),
+        });
+        let mut token_accounts = Vec::new();
+        if !store.info.is_closed {
+            token_accounts.extend([native_tokens_account.account, store_tokens_account.account]);
+        }
+        for vault_pubkey in &vaults {
+            accounts!(account_info_iter, program_id, {
+                vault: vault(vault_pubkey),
+            });
+            if *vault_pubkey == store.info.escrow_pubkey && vault.info.amount != 0 {
+                return Err(StoreError::EscrowVaultNotEmpty.into());
+            }
+            token_accounts.push(vault.account);
+        }
+
+        let authority = StoreAuthority::of(&store, program_id)?;
+        for token_account in token_accounts {
+            set_token_account_owner_signed(
+                token_program,
+                token_account,
+                pda_account,
+                &authority,
+                owner.key(),
+            )?;
+        }
+        if !store.info.is_closed {
+            msg!("StoreClosed: store {}", store.key());
+        }
+    }
+
+    if store.info.is_stats_tracked {
+        untrack_store(account_info_iter, &store.info, program_id)?;
+    }
+
+    let rent_exempt = Rent::get()?.minimum_balance(store.account.data_len());
+    let refund = store.account.lamports().saturating_sub(rent_exempt);
+    **owner.account.lamports.borrow_mut() = owner
+        .account
+        .lamports()
+        .checked_add(refund)
+        .ok_or(StoreError::MathOverflow)?;
+    **store.account.lamports.borrow_mut() -= refund;
+    let tombstone = Store {
+        is_closed: true,
+        owner_pubkey: store.info.owner_pubkey,
+        ..Store::default()
+    };
+    Store::pack(tombstone, &mut store.account.data.borrow_mut())?;
+    msg!("StoreDeleted: store {}", store.key());
+    Ok(())
+}
+
+/// Lifts the tombstone left by CloseStore, keeping the owner so that only the owner
+/// can initialize the store again
+pub(super) fn process_reactivate_store(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        store_account: any,
+    });
+    if store_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let store_info = Store::unpack_unchecked(&store_account.data.borrow())?;
+    if store_info.is_initialized || !store_info.is_closed {
+        return Err(StoreError::StoreNotDeleted.into());
+    }
+    if store_info.owner_pubkey != *owner.key() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let reserved = Store {
+        owner_pubkey: store_info.owner_pubkey,
+        ..Store::default()
+    };
+    Store::pack(reserved, &mut store_account.data.borrow_mut())?;
+    msg!(
+        "StoreReactivated: store {} owner {}",
+        store_account.key,
+        owner.key()
+    );
+    Ok(())
+}
+
+/// Stops buys, leaving sells open until `deadline` so holders can exit, the store can't be closed before it.
+/// The deadline may only be extended.
+pub(super) fn process_begin_wind_down(
+    accounts: &[AccountInfo],
+    deadline: i64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+    if deadline <= Clock::get()?.unix_timestamp || deadline < store.info.wind_down_deadline {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    store.info.wind_down_deadline = deadline;
+    store.save()?;
+    msg!("WindDownBegun: store {} deadline {}", store.key(), deadline);
+    Ok(())
+}
+
+/// Fails if the store is winding down and its deadline has not passed
+fn check_wind_down_ended(store: &StoreAccount) -> ProgramResult {
+    if store.info.is_winding_down() && Clock::get()?.unix_timestamp <= store.info.wind_down_deadline
+    {
+        return Err(StoreError::StoreWindingDown.into());
+    }
+    Ok(())
+}
+
+/// Fee vault and escrow account of the store if set, unless they are the store token accounts
+fn fee_and_escrow_vaults(store: &StoreAccount) -> Vec<Pubkey> {
+    let mut vaults: Vec<Pubkey> = Vec::new();
+    for vault in [store.info.fee_vault_pubkey, store.info.escrow_pubkey] {
+        if vault != Pubkey::default()
+            && vault != store.info.native_tokens_to_auto_sell_pubkey
+            && vault != store.info.store_tokens_to_auto_buy_pubkey
+            && !vaults.contains(&vault)
+        {
+            vaults.push(vault);
+        }
+    }
+    vaults
+}
+
+/// Sets the owner of store token accounts, the rest of ReleaseAccounts accounts, back to the store owner
+fn release_store_accounts<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    account_info_iter: &mut I,
+    store: &StoreAccount,
+    owner: &SignerAccount,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    accounts!(account_info_iter, program_id, {
+        native_tokens_account: vault(&store.info.native_tokens_to_auto_sell_pubkey),
+        store_tokens_account: vault(&store.info.store_tokens_to_auto_buy_pubkey),
+        pda_account: any,
+        token_program: program(&spl_token::id()),
+    });
+
+    let authority = StoreAuthority::of(store, program_id)?;
+    for token_account in [native_tokens_account.account, store_tokens_account.account] {
+        set_token_account_owner_signed(
+            token_program,
+            token_account,
+            pda_account,
+            &authority,
+            owner.key(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Moves tokens out of the store token accounts without releasing them
+pub(super) fn process_withdraw(
+    accounts: &[AccountInfo],
+    amount: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        store: store_owned(owner),
+        vault: vault,
+        destination: any,
+        pda_account: any,
+        token_program: program(&spl_token::id()),
+    });
+    if *vault.key() != store.info.native_tokens_to_auto_sell_pubkey
+        && *vault.key() != store.info.store_tokens_to_auto_buy_pubkey
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    check_settled(&store)?;
+
+    msg!("Calling the token program to withdraw tokens...");
+    transfer_signed(
+        token_program,
+        vault.account,
+        destination,
+        pda_account,
+        &StoreAuthority::of(&store, program_id)?,
+        amount,
+    )?;
+    msg!(
+        "Withdrawn: store {} account {} amount {}",
+        store.key(),
+        vault.key(),
+        amount
+    );
+    Ok(())
+}
+
+/// Restocks store token accounts from an owner account of the same mint
+pub(super) fn process_deposit(
+    accounts: &[AccountInfo],
+    amount: u64,
+    side: OrderSide,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        store: store_owned(owner),
+        source: vault,
+    });
+    let vault_pubkey = match side {
+        OrderSide::Buy => store.info.store_tokens_to_auto_buy_pubkey,
+        OrderSide::Sell => store.info.native_tokens_to_auto_sell_pubkey,
+    };
+    accounts!(account_info_iter, program_id, {
+        vault: vault(&vault_pubkey),
+        token_program: program(&spl_token::id()),
+    });
+    if source.info.mint != vault.info.mint {
+        return Err(StoreError::MintMismatch.into());
+    }
+    check_settled(&store)?;
+
+    msg!("Calling the token program to deposit tokens...");
+    transfer(
+        token_program,
+        source.account,
+        vault.account,
+        owner.account,
+        &[],
+        amount,
+    )?;
+    msg!(
+        "Deposited: store {} account {} amount {}",
+        store.key(),
+        vault.key(),
+        amount
+    );
+    Ok(())
+}
+
+pub(super) fn process_set_features(
+    accounts: &[AccountInfo],
+    features: u32,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+
+    store.info.features = features;
+    store.save()
+}
+
+pub(super) fn process_set_trading_hours(
+    accounts: &[AccountInfo],
+    hours: [u8; 21],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+
+    store.info.trading_hours = hours;
+    store.save()
+}
+
+pub(super) fn process_set_trading_window(
+    accounts: &[AccountInfo],
+    open_at_slot: u64,
+    close_at_slot: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+    store.info.set_trading_window(open_at_slot, close_at_slot)?;
+    store.save()?;
+    msg!(
+        "TradingWindowSet: store {} open {} close {}",
+        store.key(),
+        open_at_slot,
+        close_at_slot
+    );
+    Ok(())
+}
+
+/// Grows store account created with an older, shorter layout to the current length,
+/// topping up lamports from the owner to keep it rent exempt. The data keeps its layout,
+/// MigrateStore rewrites it.
+pub(super) fn process_realloc(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        store_account: any,
+        system_program: program(&system_program::id()),
+    });
+    grow_store_account(&owner, store_account, system_program, program_id)?;
+    Ok(())
+}
+
+/// Upgrades store account created with an older layout to the current one, growing it like Realloc.
+/// Fields version 1 stores may lack are mapped from the ones they had, and the nonce of the store
+/// PDA is derived again, stores from before it have their token accounts owned by the legacy PDA
+/// of `["store"]`. Those given in the rest of the accounts are handed over to the store PDA.
+pub(super) fn process_migrate_store(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        store_account: any,
+        system_program: program(&system_program::id()),
+    });
+    let mut store_info = grow_store_account(&owner, store_account, system_program, program_id)?;
+    if store_info.version == 1 {
+        store_info.upgrade_from_v1();
+    }
+    let authority = StoreAuthority::find(program_id, store_account.key);
+    store_info.authority_nonce = authority.nonce;
+
+    if let Some(legacy_pda) = account_info_iter.next() {
+        accounts!(account_info_iter, program_id, {
+            token_program: program(&spl_token::id()),
+        });
+        let (legacy_pubkey, legacy_nonce) = Pubkey::find_program_address(&[b"store"], program_id);
+        if legacy_pubkey != *legacy_pda.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        for vault in account_info_iter {
+            let mint = spl_token::state::Account::unpack(&vault.data.borrow())?.mint;
+            if *vault.key == store_info.native_tokens_to_auto_sell_pubkey {
+                if store_info.payment_token_mint == Pubkey::default() {
+                    store_info.payment_token_mint = mint;
+                }
+            } else if *vault.key == store_info.store_tokens_to_auto_buy_pubkey {
+                if store_info.store_token_mint == Pubkey::default() {
+                    store_info.store_token_mint = mint;
+                }
+            } else if *vault.key != store_info.escrow_pubkey
+                && *vault.key != store_info.fee_vault_pubkey
+            {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let owner_change_ix = spl_token::instruction::set_authority(
+                token_program.key,
+                vault.key,
+                Some(&authority.pubkey),
+                spl_token::instruction::AuthorityType::AccountOwner,
+                legacy_pda.key,
+                &[legacy_pda.key],
+            )?;
+            msg!("Calling the token program to hand over legacy store account...");
+            invoke_signed(
+                &owner_change_ix,
+                &[vault.clone(), legacy_pda.clone(), token_program.clone()],
+                &[&[&b"store"[..], &[legacy_nonce]]],
+            )?;
+            msg!(
+                "LegacyAccountMoved: store {} account {}",
+                store_account.key,
+                vault.key
+            );
+        }
+    }
+
+    Store::pack(store_info, &mut store_account.data.borrow_mut())?;
+    if store_info.version != STORE_VERSION {
+        msg!(
+            "StoreMigrated: store {} from version {} to {}",
+            store_account.key,
+            store_info.version,
+            STORE_VERSION
+        );
+    }
+    Ok(())
+}
+
+/// Grows store account of the owner to the current length if shorter, returns the store read
+/// in the layout of its version
+fn grow_store_account<'a>(
+    owner: &SignerAccount<'_, 'a>,
+    store_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    program_id: &Pubkey,
+) -> Result<Store, ProgramError> {
+    if store_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if store_account.data_len() > Store::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    // fields are only ever appended within a version, so older layouts padded with zeros
+    // are read as the padded layout of their version
+    let mut data = store_account.data.borrow().to_vec();
+    data.resize(Store::LEN, 0);
+    let store_info = Store::unpack(&data)?;
+    if store_info.owner_pubkey != *owner.key() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let top_up = Rent::get()?
+        .minimum_balance(Store::LEN)
+        .saturating_sub(store_account.lamports());
+    if top_up > 0 {
+        msg!("Calling the system program to top up store rent...");
+        invoke(
+            &system_instruction::transfer(owner.key(), store_account.key, top_up),
+            &[
+                owner.account.clone(),
+                store_account.clone(),
+                system_program.clone(),
+            ],
+        )?;
+    }
+    store_account.realloc(Store::LEN, true)?;
+    Ok(store_info)
+}
+
+pub(super) fn process_set_guardian(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+        guardian: any,
+    });
+
+    store.info.guardian_pubkey = *guardian.key;
+    store.save()
+}
+
+/// Pauses trades, signed by the owner or the guardian
+pub(super) fn process_pause(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        authority: signer,
+        mut store: store,
+    });
+    if *authority.key() != store.info.owner_pubkey
+        && (*authority.key() != store.info.guardian_pubkey
+            || store.info.guardian_pubkey == Pubkey::default())
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    store.info.is_paused = true;
+    store.save()?;
+    msg!("StorePaused: store {} by {}", store.key(), authority.key());
+    Ok(())
+}
+
+pub(super) fn process_resume(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+
+    store.info.is_paused = false;
+    store.save()?;
+    msg!("StoreResumed: store {}", store.key());
+    Ok(())
+}
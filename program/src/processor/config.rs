@@ -0,0 +1,189 @@
+use solana_program::{
+    account_info::AccountInfo,
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    program_pack::Pack,
+    program_utils::limited_deserialize,
+    pubkey::Pubkey,
+    system_program,
+};
+
+use super::create_pda_account;
+use crate::{error::StoreError, instruction::find_program_data_address, state::ProgramConfig};
+
+/// Creates the program config with the program upgrade authority as its authority
+pub(super) fn process_initialize_config(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        authority: signer,
+        config_account: any,
+        system_program: program(&system_program::id()),
+        program_data: any,
+    });
+    let config_nonce = config_nonce(config_account, program_id)?;
+    // the first to initialize the config would otherwise take it
+    if upgrade_authority(program_data, program_id)? != Some(*authority.key()) {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    create_pda_account(
+        authority.account,
+        config_account,
+        system_program,
+        ProgramConfig::LEN,
+        program_id,
+        &[&b"config"[..], &[config_nonce]],
+    )?;
+    let config = ProgramConfig {
+        is_initialized: true,
+        authority_pubkey: *authority.key(),
+        ..ProgramConfig::default()
+    };
+    ProgramConfig::pack(config, &mut config_account.data.borrow_mut())?;
+    msg!(
+        "ConfigInitialized: account {} authority {}",
+        config_account.key,
+        authority.key()
+    );
+    Ok(())
+}
+
+/// Replaces the payment mints new stores may use, any mint is allowed if empty
+pub(super) fn process_set_payment_mints(
+    accounts: &[AccountInfo],
+    mints: &[Pubkey],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        authority: signer,
+        config_account: any,
+    });
+    let mut config = load_config(config_account, program_id)?;
+    if config.authority_pubkey != *authority.key() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    config.set_payment_mints(mints)?;
+    ProgramConfig::pack(config, &mut config_account.data.borrow_mut())?;
+    msg!("PaymentMintsSet: count {}", mints.len());
+    Ok(())
+}
+
+pub(super) fn process_propose_config_authority(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        authority: signer,
+        config_account: any,
+        new_authority: any,
+    });
+    let mut config = load_config(config_account, program_id)?;
+    if config.authority_pubkey != *authority.key() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    config.pending_authority_pubkey = *new_authority.key;
+    ProgramConfig::pack(config, &mut config_account.data.borrow_mut())
+}
+
+pub(super) fn process_accept_config_authority(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        new_authority: signer,
+        config_account: any,
+    });
+    let mut config = load_config(config_account, program_id)?;
+    if config.pending_authority_pubkey == Pubkey::default()
+        || config.pending_authority_pubkey != *new_authority.key()
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    msg!(
+        "ConfigAuthorityChanged: from {} to {}",
+        config.authority_pubkey,
+        new_authority.key()
+    );
+    config.authority_pubkey = *new_authority.key();
+    config.pending_authority_pubkey = Pubkey::default();
+    ProgramConfig::pack(config, &mut config_account.data.borrow_mut())
+}
+
+/// Fails if the program config restricts payment mints and `payment_mint` is not one of them.
+/// The config account may not exist, then any mint is allowed.
+pub(super) fn check_payment_mint(
+    config_account: &AccountInfo,
+    payment_mint: &Pubkey,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    config_nonce(config_account, program_id)?;
+    if config_account.owner != program_id {
+        return Ok(());
+    }
+    let config = ProgramConfig::unpack(&config_account.data.borrow())?;
+    if !config.is_payment_mint_allowed(payment_mint) {
+        return Err(StoreError::PaymentMintNotAllowed.into());
+    }
+    Ok(())
+}
+
+/// Checks the config account is the config PDA, returns its nonce
+fn config_nonce(config_account: &AccountInfo, program_id: &Pubkey) -> Result<u8, ProgramError> {
+    let (config_pda, config_nonce) = Pubkey::find_program_address(&[b"config"], program_id);
+    if config_pda != *config_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(config_nonce)
+}
+
+fn load_config(
+    config_account: &AccountInfo,
+    program_id: &Pubkey,
+) -> Result<ProgramConfig, ProgramError> {
+    config_nonce(config_account, program_id)?;
+    if config_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    ProgramConfig::unpack(&config_account.data.borrow())
+}
+
+/// Upgrade authority recorded in the program data account of the program, `None` if the
+/// program is immutable
+fn upgrade_authority(
+    program_data: &AccountInfo,
+    program_id: &Pubkey,
+) -> Result<Option<Pubkey>, ProgramError> {
+    if find_program_data_address(program_id) != *program_data.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if *program_data.owner != bpf_loader_upgradeable::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let metadata_len = UpgradeableLoaderState::size_of_programdata_metadata();
+    let data = program_data.data.borrow();
+    let metadata = data
+        .get(..metadata_len)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    match limited_deserialize(metadata, metadata_len as u64) {
+        Ok(UpgradeableLoaderState::ProgramData {
+            upgrade_authority_address,
+            ..
+        }) => Ok(upgrade_authority_address),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
@@ -0,0 +1,58 @@
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult,
+    program_error::ProgramError, program_pack::Pack, pubkey::Pubkey, system_program,
+    sysvar::Sysvar,
+};
+
+use super::{create_pda_account, pricing::PricingEngine};
+use crate::state::Depth;
+
+pub(super) fn process_publish_depth(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        payer: signer,
+        store: store,
+        store_store_tokens: vault(&store.info.store_tokens_to_auto_buy_pubkey),
+        store_payment_tokens: vault(&store.info.native_tokens_to_auto_sell_pubkey),
+        depth_account: any,
+        system_program: program(&system_program::id()),
+    });
+    let (depth_pda, depth_nonce) =
+        Pubkey::find_program_address(&[b"depth", store.key().as_ref()], program_id);
+    if depth_pda != *depth_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if depth_account.lamports() == 0 {
+        create_pda_account(
+            payer.account,
+            depth_account,
+            system_program,
+            Depth::LEN,
+            program_id,
+            &[&b"depth"[..], store.key().as_ref(), &[depth_nonce]],
+        )?;
+    }
+    if depth_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let pricing =
+        PricingEngine::of_reserves(&store.info, &store_payment_tokens, &store_store_tokens)?;
+    let (bid_price, ask_price) = pricing.quotes()?;
+    let depth_info = Depth {
+        is_initialized: true,
+        store_pubkey: *store.key(),
+        bid_price,
+        ask_price,
+        price_denominator: pricing.denominator(),
+        ask_liquidity: store_store_tokens.info.amount,
+        bid_liquidity: store_payment_tokens.info.amount,
+        slot: Clock::get()?.slot,
+    };
+    Depth::pack(depth_info, &mut depth_account.data.borrow_mut())
+}
@@ -0,0 +1,116 @@
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    program_pack::Pack, pubkey::Pubkey, system_program,
+};
+
+use super::{create_pda_account, validation::StoreAccount};
+use crate::{
+    error::StoreError,
+    metadata,
+    state::{DiscountTier, FEATURE_DISCOUNTS},
+};
+
+const MAX_DISCOUNT_BPS: u16 = 10_000;
+
+pub(super) fn process_set_discount_tier(
+    accounts: &[AccountInfo],
+    discount_bps: u16,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        store: store_owned(owner),
+        collection: any,
+        discount_account: any,
+        system_program: program(&system_program::id()),
+    });
+    store.check_feature(FEATURE_DISCOUNTS)?;
+    if discount_bps > MAX_DISCOUNT_BPS {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let (discount_pda, discount_nonce) = Pubkey::find_program_address(
+        &[b"discount", store.key().as_ref(), collection.key.as_ref()],
+        program_id,
+    );
+    if discount_pda != *discount_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if discount_account.lamports() == 0 {
+        create_pda_account(
+            owner.account,
+            discount_account,
+            system_program,
+            DiscountTier::LEN,
+            program_id,
+            &[
+                &b"discount"[..],
+                store.key().as_ref(),
+                collection.key.as_ref(),
+                &[discount_nonce],
+            ],
+        )?;
+    }
+    if discount_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let discount_info = DiscountTier {
+        is_initialized: true,
+        store_pubkey: *store.key(),
+        collection_pubkey: *collection.key,
+        discount_bps,
+    };
+    DiscountTier::pack(discount_info, &mut discount_account.data.borrow_mut())
+}
+
+/// Discount of buy notional from the next accounts if present: token account of the `recipient`
+/// holding an NFT, metadata account of the NFT and discount account of its verified collection
+pub(super) fn next_discount_bps<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    store: &StoreAccount,
+    iter: &mut I,
+    recipient: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<u16, ProgramError> {
+    let holder_account = match iter.next() {
+        Some(account) => account,
+        None => return Ok(0),
+    };
+    let metadata_account = iter.next().ok_or(StoreError::InvalidDiscount)?;
+    let discount_account = iter.next().ok_or(StoreError::InvalidDiscount)?;
+
+    if *holder_account.owner != spl_token::id() {
+        return Err(StoreError::InvalidDiscount.into());
+    }
+    let holder = spl_token::state::Account::unpack(&holder_account.data.borrow())
+        .map_err(|_| StoreError::InvalidDiscount)?;
+    if holder.owner != *recipient || holder.amount < 1 {
+        return Err(StoreError::InvalidDiscount.into());
+    }
+
+    if *metadata_account.owner != metadata::id()
+        || *metadata_account.key != metadata::find_metadata_address(&holder.mint)
+    {
+        return Err(StoreError::InvalidDiscount.into());
+    }
+    let collection = metadata::verified_collection(&metadata_account.data.borrow(), &holder.mint)
+        .ok_or(StoreError::InvalidDiscount)?;
+
+    if discount_account.owner != program_id {
+        return Err(StoreError::InvalidDiscount.into());
+    }
+    let discount = DiscountTier::unpack(&discount_account.data.borrow())?;
+    if discount.store_pubkey != *store.key() || discount.collection_pubkey != collection {
+        return Err(StoreError::InvalidDiscount.into());
+    }
+    Ok(discount.discount_bps)
+}
+
+/// `notional` reduced by `discount_bps`
+pub(super) fn discounted(notional: u64, discount_bps: u16) -> u64 {
+    let remaining_bps = MAX_DISCOUNT_BPS.saturating_sub(discount_bps);
+    (notional as u128 * remaining_bps as u128 / MAX_DISCOUNT_BPS as u128) as u64
+}
@@ -0,0 +1,367 @@
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, msg,
+    program_error::ProgramError, program_pack::Pack, pubkey::Pubkey, sysvar::Sysvar,
+};
+
+use super::{
+    close_account, create_pda_account,
+    ledger::refund_purchase,
+    settlement::check_settled,
+    trade::check_settlement_account,
+    transfer_signed,
+    validation::{StoreAccount, VaultAccount},
+    StoreAuthority,
+};
+use crate::{
+    error::StoreError,
+    state::{EscrowRecord, FEATURE_ESCROW},
+};
+
+pub(super) fn process_set_escrow(
+    accounts: &[AccountInfo],
+    window: i64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+    if window < 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if window == 0 {
+        store.info.escrow_pubkey = Pubkey::default();
+    } else {
+        store.check_feature(FEATURE_ESCROW)?;
+        accounts!(account_info_iter, program_id, {
+            escrow_vault: vault,
+        });
+        if escrow_vault.info.owner != StoreAuthority::of(&store, program_id)?.pubkey {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        store.info.escrow_pubkey = *escrow_vault.key();
+    }
+    store.info.escrow_window = window;
+    store.save()
+}
+
+pub(super) fn process_set_escheat_period(
+    accounts: &[AccountInfo],
+    period: i64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+    if period < 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    store.info.escheat_period = period;
+    store.save()
+}
+
+/// Creates escrow record of the buy paid to the store escrow account, `payer` pays the rent,
+/// so buys by a program authority such as a session can be escrowed
+#[allow(clippy::too_many_arguments)]
+pub(super) fn open_escrow<'a>(
+    store: &mut StoreAccount,
+    escrow_account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    refund: &Pubkey,
+    tokens: &VaultAccount,
+    amount: u64,
+    notional: u64,
+    fee: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    if !payer.is_signer {
+        return Err(StoreError::EscrowPayerRequired.into());
+    }
+    let sequence = store.info.escrow_sequence.to_le_bytes();
+    let (escrow_pda, escrow_nonce) =
+        Pubkey::find_program_address(&[b"escrow", store.key().as_ref(), &sequence], program_id);
+    if escrow_pda != *escrow_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    create_pda_account(
+        payer,
+        escrow_account,
+        system_program,
+        EscrowRecord::LEN,
+        program_id,
+        &[
+            &b"escrow"[..],
+            store.key().as_ref(),
+            &sequence,
+            &[escrow_nonce],
+        ],
+    )?;
+
+    let release_at = Clock::get()?
+        .unix_timestamp
+        .checked_add(store.info.escrow_window)
+        .ok_or(StoreError::MathOverflow)?;
+    let escrow_info = EscrowRecord {
+        is_initialized: true,
+        store_pubkey: *store.key(),
+        payer_pubkey: *payer.key,
+        vault_pubkey: store.info.escrow_pubkey,
+        refund_pubkey: *refund,
+        tokens_pubkey: *tokens.key(),
+        amount,
+        notional,
+        fee,
+        release_at,
+    };
+    EscrowRecord::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;
+    store.info.escrow_sequence += 1;
+    msg!(
+        "EscrowOpened: store {} escrow {} buyer {} payer {} amount {} notional {} fee {} release at {}",
+        store.key(),
+        escrow_account.key,
+        tokens.info.owner,
+        payer.key,
+        amount,
+        notional,
+        fee,
+        release_at
+    );
+    Ok(())
+}
+
+/// Returns the payment with its fee to the buyer and store tokens to the store within the refund window.
+/// The refund is accounted as the store buying the tokens back, which go back to the store
+/// inventory and off the purchase ledger of the buyer.
+pub(super) fn process_refund_escrow(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+        escrow_account: any,
+    });
+    let escrow_info = load_escrow(&store, escrow_account, program_id)?;
+    accounts!(account_info_iter, program_id, {
+        escrow_vault: vault(&escrow_info.vault_pubkey),
+        store_store_tokens: vault(&store.info.store_tokens_to_auto_buy_pubkey),
+        refund_account: vault(&escrow_info.refund_pubkey),
+        tokens_account: vault(&escrow_info.tokens_pubkey),
+        payer: any,
+        pda_account: any,
+        token_program: program(&spl_token::id()),
+    });
+    if escrow_info.release_at <= Clock::get()?.unix_timestamp {
+        return Err(StoreError::RefundWindowClosed.into());
+    }
+    check_settled(&store)?;
+    if *payer.key != escrow_info.payer_pubkey {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if store.info.per_wallet_cap != 0 {
+        accounts!(account_info_iter, program_id, {
+            ledger_account: any,
+        });
+        refund_purchase(
+            &store,
+            ledger_account,
+            &tokens_account.info.owner,
+            escrow_info.amount,
+            program_id,
+        )?;
+    }
+    let fee_vault = if escrow_info.fee != 0 {
+        accounts!(account_info_iter, program_id, {
+            fee_vault: vault(&store.info.fee_vault_pubkey),
+        });
+        Some(fee_vault)
+    } else {
+        None
+    };
+
+    let authority = StoreAuthority::of(&store, program_id)?;
+    msg!("Calling the token program to refund the payment...");
+    transfer_signed(
+        token_program,
+        escrow_vault.account,
+        refund_account.account,
+        pda_account,
+        &authority,
+        escrow_info.notional,
+    )?;
+    if let Some(fee_vault) = fee_vault {
+        msg!("Calling the token program to refund the fee...");
+        transfer_signed(
+            token_program,
+            fee_vault.account,
+            refund_account.account,
+            pda_account,
+            &authority,
+            escrow_info.fee,
+        )?;
+        store.info.refund_fee(escrow_info.fee)?;
+    }
+    msg!("Calling the token program to return store tokens...");
+    transfer_signed(
+        token_program,
+        tokens_account.account,
+        store_store_tokens.account,
+        pda_account,
+        &authority,
+        escrow_info.amount,
+    )?;
+
+    store
+        .info
+        .record_bought(escrow_info.amount, escrow_info.notional)?;
+    store.info.restore_inventory(escrow_info.amount)?;
+    store.save()?;
+    msg!(
+        "EscrowRefunded: store {} escrow {} amount {} notional {} fee {}",
+        store.key(),
+        escrow_account.key,
+        escrow_info.amount,
+        escrow_info.notional,
+        escrow_info.fee
+    );
+    close_account(escrow_account, payer)
+}
+
+/// Pays the escrowed payment to the store after the refund window, callable by anyone
+pub(super) fn process_release_escrow(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        store: store,
+        escrow_account: any,
+    });
+    let escrow_info = load_escrow(&store, escrow_account, program_id)?;
+    accounts!(account_info_iter, program_id, {
+        escrow_vault: vault(&escrow_info.vault_pubkey),
+        proceeds_account: vault,
+        payer: any,
+        pda_account: any,
+        token_program: program(&spl_token::id()),
+    });
+    if escrow_info.release_at > Clock::get()?.unix_timestamp {
+        return Err(StoreError::EscrowLocked.into());
+    }
+    if *payer.key != escrow_info.payer_pubkey {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    pay_escrow(
+        &store,
+        &escrow_info,
+        &escrow_vault,
+        &proceeds_account,
+        pda_account,
+        token_program,
+        program_id,
+    )?;
+    msg!(
+        "EscrowReleased: store {} escrow {} notional {}",
+        store.key(),
+        escrow_account.key,
+        escrow_info.notional
+    );
+    close_account(escrow_account, payer)
+}
+
+/// Pays an escrow left unreleased for the store escheat period after its refund window to the store,
+/// the escrow account rent goes to the store owner instead of the buyer. Callable by anyone.
+pub(super) fn process_sweep_escrow(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        store: store,
+        escrow_account: any,
+    });
+    let escrow_info = load_escrow(&store, escrow_account, program_id)?;
+    accounts!(account_info_iter, program_id, {
+        escrow_vault: vault(&escrow_info.vault_pubkey),
+        proceeds_account: vault,
+        owner: any,
+        pda_account: any,
+        token_program: program(&spl_token::id()),
+    });
+    let escheat_at = escrow_info
+        .release_at
+        .checked_add(store.info.escheat_period)
+        .ok_or(StoreError::MathOverflow)?;
+    if store.info.escheat_period == 0 || escheat_at > Clock::get()?.unix_timestamp {
+        return Err(StoreError::EscheatPeriodNotElapsed.into());
+    }
+    if *owner.key != store.info.owner_pubkey {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    pay_escrow(
+        &store,
+        &escrow_info,
+        &escrow_vault,
+        &proceeds_account,
+        pda_account,
+        token_program,
+        program_id,
+    )?;
+    msg!(
+        "EscrowEscheated: store {} escrow {} payer {} notional {} rent {}",
+        store.key(),
+        escrow_account.key,
+        escrow_info.payer_pubkey,
+        escrow_info.notional,
+        escrow_account.lamports()
+    );
+    close_account(escrow_account, owner)
+}
+
+/// Moves the escrowed payment to the store proceeds account
+fn pay_escrow<'a>(
+    store: &StoreAccount,
+    escrow_info: &EscrowRecord,
+    escrow_vault: &VaultAccount<'_, 'a>,
+    proceeds_account: &VaultAccount<'_, 'a>,
+    pda_account: &AccountInfo<'a>,
+    token_program: &AccountInfo<'a>,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    check_settlement_account(store, proceeds_account, &store.info.proceeds_pubkey)?;
+    check_settled(store)?;
+    msg!("Calling the token program to release the payment...");
+    transfer_signed(
+        token_program,
+        escrow_vault.account,
+        proceeds_account.account,
+        pda_account,
+        &StoreAuthority::of(store, program_id)?,
+        escrow_info.notional,
+    )
+}
+
+fn load_escrow(
+    store: &StoreAccount,
+    escrow_account: &AccountInfo,
+    program_id: &Pubkey,
+) -> Result<EscrowRecord, ProgramError> {
+    if escrow_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let escrow_info = EscrowRecord::unpack(&escrow_account.data.borrow())?;
+    if escrow_info.store_pubkey != *store.key() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(escrow_info)
+}
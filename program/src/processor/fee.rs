@@ -0,0 +1,168 @@
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, msg,
+    program_error::ProgramError, pubkey::Pubkey, sysvar::Sysvar,
+};
+
+use super::{
+    settlement::check_settled,
+    trade::TradeMode,
+    transfer_signed,
+    validation::{StoreAccount, VaultAccount},
+    StoreAuthority,
+};
+use crate::{
+    error::StoreError,
+    state::{Store, MAX_FEE_BPS},
+};
+
+pub(super) fn process_update_fee(
+    accounts: &[AccountInfo],
+    fee_bps: u16,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+    let fee_vault = match account_info_iter.next() {
+        Some(account) => Some(VaultAccount::load(account)?),
+        None => None,
+    };
+    let authority = StoreAuthority::of(&store, program_id)?;
+
+    configure_fee(
+        &mut store.info,
+        fee_bps,
+        fee_vault.as_ref(),
+        &authority.pubkey,
+    )?;
+    store.save()
+}
+
+/// Plans a fee change from `effective_at`, so takers see it coming, a fee vault must be set
+/// to charge one
+pub(super) fn process_schedule_fee(
+    accounts: &[AccountInfo],
+    effective_at: i64,
+    fee_bps: u16,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+    if fee_bps > MAX_FEE_BPS || effective_at <= Clock::get()?.unix_timestamp {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if fee_bps > 0 && store.info.fee_vault_pubkey == Pubkey::default() {
+        return Err(StoreError::FeeVaultRequired.into());
+    }
+
+    store.info.fee_schedule.schedule(effective_at, fee_bps)?;
+    store.save()?;
+    msg!(
+        "FeeScheduled: store {} fee {} from {}",
+        store.key(),
+        fee_bps,
+        effective_at
+    );
+    Ok(())
+}
+
+/// Sets the fee of the latest scheduled change in effect, trades charge it from then on
+pub(super) fn apply_fee_schedule(store: &mut StoreAccount) -> ProgramResult {
+    if let Some(fee_bps) = store
+        .info
+        .fee_schedule
+        .take_due(Clock::get()?.unix_timestamp)
+    {
+        store.info.fee_bps = fee_bps;
+        msg!("FeeChanged: store {} fee {}", store.key(), fee_bps);
+    }
+    Ok(())
+}
+
+/// Sets the store fee and its vault if given, the vault must be a payment tokens account
+/// owned by the store PDA. A fee can't be charged without a vault.
+pub(super) fn configure_fee(
+    store_info: &mut Store,
+    fee_bps: u16,
+    fee_vault: Option<&VaultAccount>,
+    authority: &Pubkey,
+) -> ProgramResult {
+    if fee_bps > MAX_FEE_BPS {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if let Some(fee_vault) = fee_vault {
+        if fee_vault.info.owner != *authority {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if store_info.payment_token_mint != Pubkey::default()
+            && fee_vault.info.mint != store_info.payment_token_mint
+        {
+            return Err(StoreError::MintMismatch.into());
+        }
+        store_info.fee_vault_pubkey = *fee_vault.key();
+    }
+    if fee_bps > 0 && store_info.fee_vault_pubkey == Pubkey::default() {
+        return Err(StoreError::FeeVaultRequired.into());
+    }
+    store_info.fee_bps = fee_bps;
+    Ok(())
+}
+
+/// Next account is the store fee vault if the store charges a fee, not passed in preview mode
+pub(super) fn next_fee_vault<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    store: &StoreAccount,
+    iter: &mut I,
+    mode: TradeMode,
+) -> Result<Option<VaultAccount<'a, 'b>>, ProgramError> {
+    if store.info.fee_bps == 0 || mode == TradeMode::Preview {
+        return Ok(None);
+    }
+    let account = iter.next().ok_or(StoreError::FeeVaultRequired)?;
+    if *account.key != store.info.fee_vault_pubkey {
+        return Err(StoreError::FeeVaultRequired.into());
+    }
+    VaultAccount::load(account).map(Some)
+}
+
+/// Moves collected fees out of the fee vault
+pub(super) fn process_withdraw_fees(
+    accounts: &[AccountInfo],
+    amount: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        store: store_owned(owner),
+        fee_vault: vault(&store.info.fee_vault_pubkey),
+        destination: any,
+        pda_account: any,
+        token_program: program(&spl_token::id()),
+    });
+    check_settled(&store)?;
+
+    msg!("Calling the token program to withdraw fees...");
+    transfer_signed(
+        token_program,
+        fee_vault.account,
+        destination,
+        pda_account,
+        &StoreAuthority::of(&store, program_id)?,
+        amount,
+    )?;
+    msg!(
+        "FeesWithdrawn: store {} account {} amount {}",
+        store.key(),
+        fee_vault.key(),
+        amount
+    );
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, msg};
+
+use super::validation::VaultAccount;
+use crate::{
+    error::StoreError,
+    instruction::{BalanceAssertion, BalanceCheck},
+};
+
+pub(super) fn process_assert_balances(
+    accounts: &[AccountInfo],
+    assertions: &[BalanceAssertion],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    for assertion in assertions {
+        let vault = VaultAccount::next(account_info_iter)?;
+        let holds = match assertion.check {
+            BalanceCheck::AtLeast => vault.info.amount >= assertion.amount,
+            BalanceCheck::Exactly => vault.info.amount == assertion.amount,
+        };
+        if !holds {
+            msg!(
+                "Account {} holds {}, asserted {:?} {}",
+                vault.key(),
+                vault.info.amount,
+                assertion.check,
+                assertion.amount
+            );
+            return Err(StoreError::BalanceAssertionFailed.into());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,208 @@
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction, system_program,
+    sysvar::{self, Sysvar},
+};
+
+use super::{
+    config::check_payment_mint, fee::configure_fee, set_token_account_owner, stats::track_store,
+    validation::VaultAccount, StoreAuthority,
+};
+use crate::{error::StoreError, state::Store};
+
+pub(super) fn process_init_store(
+    accounts: &[AccountInfo],
+    price: u64,
+    fee_bps: Option<u16>,
+    open_at_slot: u64,
+    close_at_slot: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        store_account: any,
+        native_tokens_account: any,
+        store_tokens_account: any,
+        token_program: program(&spl_token::id()),
+        rent_account: program(&sysvar::rent::id()),
+        config_account: any,
+        stats_account: any,
+    });
+    // the fee vault is owned by the store PDA already, since the owner can't sign for it afterwards
+    let fee_vault = match fee_bps {
+        Some(fee_bps) if fee_bps > 0 => Some(VaultAccount::load(
+            account_info_iter
+                .next()
+                .ok_or(StoreError::FeeVaultRequired)?,
+        )?),
+        _ => None,
+    };
+    let authority = StoreAuthority::find(program_id, store_account.key);
+    if native_tokens_account.lamports() == 0 && store_tokens_account.lamports() == 0 {
+        accounts!(account_info_iter, program_id, {
+            payment_token_mint: any,
+            store_token_mint: any,
+            system_program: program(&system_program::id()),
+        });
+        let rent = Rent::from_account_info(rent_account)?;
+        for (vault, mint) in [
+            (native_tokens_account, payment_token_mint),
+            (store_tokens_account, store_token_mint),
+        ] {
+            create_vault(
+                owner.account,
+                vault,
+                mint,
+                store_account.key,
+                &authority.pubkey,
+                token_program,
+                rent_account,
+                system_program,
+                &rent,
+                program_id,
+            )?;
+        }
+    } else {
+        if *store_tokens_account.owner != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if *native_tokens_account.owner != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        set_token_account_owner(
+            token_program,
+            store_tokens_account,
+            owner.account,
+            &authority.pubkey,
+        )?;
+        set_token_account_owner(
+            token_program,
+            native_tokens_account,
+            owner.account,
+            &authority.pubkey,
+        )?;
+    }
+    {
+        let rent = &Rent::from_account_info(rent_account)?;
+        if !rent.is_exempt(store_account.lamports(), store_account.data_len()) {
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+        if store_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+    }
+    {
+        let mut store_info = Store::unpack_unchecked(&store_account.data.borrow())?;
+        if store_info.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        if store_info.is_closed {
+            return Err(StoreError::StoreClosed.into());
+        }
+        // a reactivated store is reserved for its original owner
+        if store_info.owner_pubkey != Pubkey::default() && store_info.owner_pubkey != *owner.key() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        store_info.is_initialized = true;
+        store_info.set_price(price);
+        store_info.price_denominator = 1;
+        store_info.owner_pubkey = *owner.key();
+        store_info.native_tokens_to_auto_sell_pubkey = *native_tokens_account.key;
+        store_info.store_tokens_to_auto_buy_pubkey = *store_tokens_account.key;
+        store_info.authority_nonce = authority.nonce;
+        store_info.set_trading_window(open_at_slot, close_at_slot)?;
+        store_info.payment_token_mint = token_account_mint(native_tokens_account)?;
+        store_info.store_token_mint = token_account_mint(store_tokens_account)?;
+        check_payment_mint(config_account, &store_info.payment_token_mint, program_id)?;
+        configure_fee(
+            &mut store_info,
+            fee_bps.unwrap_or(0),
+            fee_vault.as_ref(),
+            &authority.pubkey,
+        )?;
+        track_store(
+            &owner,
+            stats_account,
+            account_info_iter,
+            &mut store_info,
+            program_id,
+        )?;
+
+        Store::pack(store_info, &mut store_account.data.borrow_mut())?;
+    }
+    Ok(())
+}
+
+/// Creates the store token account of `mint` at its PDA of `["vault", store, mint]`,
+/// owned by the store PDA
+#[allow(clippy::too_many_arguments)]
+fn create_vault<'a>(
+    payer: &AccountInfo<'a>,
+    vault: &AccountInfo<'a>,
+    mint: &AccountInfo<'a>,
+    store_key: &Pubkey,
+    authority: &Pubkey,
+    token_program: &AccountInfo<'a>,
+    rent_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    rent: &Rent,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let (vault_pda, vault_nonce) = Pubkey::find_program_address(
+        &[b"vault", store_key.as_ref(), mint.key.as_ref()],
+        program_id,
+    );
+    if vault_pda != *vault.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let len = spl_token::state::Account::LEN;
+    let create_ix = system_instruction::create_account(
+        payer.key,
+        vault.key,
+        rent.minimum_balance(len),
+        len as u64,
+        token_program.key,
+    );
+    msg!("Calling the system program to create vault account...");
+    invoke_signed(
+        &create_ix,
+        &[payer.clone(), vault.clone(), system_program.clone()],
+        &[&[
+            &b"vault"[..],
+            store_key.as_ref(),
+            mint.key.as_ref(),
+            &[vault_nonce],
+        ]],
+    )?;
+
+    let init_ix = spl_token::instruction::initialize_account2(
+        token_program.key,
+        vault.key,
+        mint.key,
+        authority,
+    )?;
+    msg!("Calling the token program to initialize vault account...");
+    invoke(
+        &init_ix,
+        &[
+            vault.clone(),
+            mint.clone(),
+            rent_account.clone(),
+            token_program.clone(),
+        ],
+    )
+}
+
+fn token_account_mint(account: &AccountInfo) -> Result<Pubkey, ProgramError> {
+    Ok(spl_token::state::Account::unpack(&account.data.borrow())?.mint)
+}
@@ -0,0 +1,129 @@
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::ProgramError,
+    program_pack::Pack, pubkey::Pubkey, system_program,
+};
+
+use super::{
+    create_pda_account,
+    pricing::PricingEngine,
+    trade::{buy_quoted, BuyQuote, TradeMode},
+};
+use crate::{
+    error::StoreError,
+    state::{Invoice, FEATURE_INVOICES},
+};
+
+pub(super) fn process_create_invoice(
+    accounts: &[AccountInfo],
+    amount: u64,
+    reference: [u8; 32],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        store: store_owned(owner),
+        invoice_account: any,
+        system_program: program(&system_program::id()),
+    });
+    store.check_feature(FEATURE_INVOICES)?;
+    if store.info.is_migrated() {
+        return Err(StoreError::StoreMigrated.into());
+    }
+    if !store.info.is_valid_amount(amount) {
+        return Err(StoreError::InvalidLotAmount.into());
+    }
+    // the inventory is taken on payment, an invoice over the limits could never be paid
+    store.info.check_inventory(amount)?;
+    let notional = PricingEngine::of(&store.info)?.quote_buy(amount, u64::MAX)?;
+
+    let (invoice_pda, invoice_nonce) =
+        Pubkey::find_program_address(&[b"invoice", store.key().as_ref(), &reference], program_id);
+    if invoice_pda != *invoice_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if invoice_account.lamports() != 0 {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    create_pda_account(
+        owner.account,
+        invoice_account,
+        system_program,
+        Invoice::LEN,
+        program_id,
+        &[
+            &b"invoice"[..],
+            store.key().as_ref(),
+            &reference,
+            &[invoice_nonce],
+        ],
+    )?;
+
+    let invoice_info = Invoice {
+        is_initialized: true,
+        store_pubkey: *store.key(),
+        reference,
+        amount,
+        notional,
+        payer_pubkey: Pubkey::default(),
+    };
+    Invoice::pack(invoice_info, &mut invoice_account.data.borrow_mut())?;
+    msg!(
+        "InvoiceCreated: store {} invoice {} amount {} notional {}",
+        store.key(),
+        invoice_account.key,
+        amount,
+        notional
+    );
+
+    Ok(())
+}
+
+/// Buys the invoice amount at its notional, regardless of the current store price. Otherwise it's
+/// a buy like any other, the rest of the accounts are Buy accounts from the store account on.
+pub(super) fn process_pay_invoice(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        buyer: signer,
+        invoice_account: any,
+    });
+    if invoice_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut invoice_info = Invoice::unpack(&invoice_account.data.borrow())?;
+    let store_account = account_info_iter
+        .as_slice()
+        .first()
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+    if invoice_info.store_pubkey != *store_account.key {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if invoice_info.is_paid() {
+        return Err(StoreError::InvoicePaid.into());
+    }
+
+    let settlement = buy_quoted(
+        account_info_iter,
+        buyer.account,
+        &[],
+        invoice_info.amount,
+        BuyQuote::Notional(invoice_info.notional),
+        TradeMode::Execute,
+        program_id,
+    )?;
+    invoice_info.payer_pubkey = *buyer.key();
+    Invoice::pack(invoice_info, &mut invoice_account.data.borrow_mut())?;
+    msg!(
+        "InvoicePaid: store {} invoice {} payer {} amount {} notional {} fee {}",
+        settlement.store,
+        invoice_account.key,
+        invoice_info.payer_pubkey,
+        settlement.amount,
+        settlement.notional,
+        settlement.fee
+    );
+
+    Ok(())
+}
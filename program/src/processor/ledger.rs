@@ -0,0 +1,108 @@
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::ProgramError,
+    program_pack::Pack, pubkey::Pubkey,
+};
+
+use super::{create_pda_account, validation::StoreAccount};
+use crate::{error::StoreError, state::PurchaseLedger};
+
+pub(super) fn process_set_wallet_cap(
+    accounts: &[AccountInfo],
+    cap: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+
+    store.info.per_wallet_cap = cap;
+    store.save()?;
+    msg!("WalletCapSet: store {} cap {}", store.key(), cap);
+    Ok(())
+}
+
+/// Adds `amount` bought for `wallet`, the owner of the account receiving store tokens, to its
+/// purchase ledger, creating the ledger paid by `payer` on the first buy. Fails if the total
+/// goes over the store per wallet cap.
+pub(super) fn record_purchase<'a>(
+    store: &StoreAccount,
+    ledger_account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    wallet: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let ledger_nonce = ledger_nonce(store, ledger_account, wallet, program_id)?;
+    let mut ledger = if ledger_account.owner != program_id {
+        if !payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        create_pda_account(
+            payer,
+            ledger_account,
+            system_program,
+            PurchaseLedger::LEN,
+            program_id,
+            &[
+                &b"purchases"[..],
+                store.key().as_ref(),
+                wallet.as_ref(),
+                &[ledger_nonce],
+            ],
+        )?;
+        PurchaseLedger {
+            is_initialized: true,
+            store_pubkey: *store.key(),
+            buyer_pubkey: *wallet,
+            amount: 0,
+        }
+    } else {
+        PurchaseLedger::unpack(&ledger_account.data.borrow())?
+    };
+
+    ledger.amount = ledger
+        .amount
+        .checked_add(amount)
+        .ok_or(StoreError::MathOverflow)?;
+    if ledger.amount > store.info.per_wallet_cap {
+        return Err(StoreError::WalletCapExceeded.into());
+    }
+    PurchaseLedger::pack(ledger, &mut ledger_account.data.borrow_mut())
+}
+
+/// Takes `amount` of a refunded buy off the purchase ledger of `wallet`, if it has one
+pub(super) fn refund_purchase(
+    store: &StoreAccount,
+    ledger_account: &AccountInfo,
+    wallet: &Pubkey,
+    amount: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    ledger_nonce(store, ledger_account, wallet, program_id)?;
+    if ledger_account.owner != program_id {
+        return Ok(());
+    }
+    let mut ledger = PurchaseLedger::unpack(&ledger_account.data.borrow())?;
+    ledger.amount = ledger.amount.saturating_sub(amount);
+    PurchaseLedger::pack(ledger, &mut ledger_account.data.borrow_mut())
+}
+
+fn ledger_nonce(
+    store: &StoreAccount,
+    ledger_account: &AccountInfo,
+    wallet: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<u8, ProgramError> {
+    let (ledger_pda, ledger_nonce) = Pubkey::find_program_address(
+        &[b"purchases", store.key().as_ref(), wallet.as_ref()],
+        program_id,
+    );
+    if ledger_pda != *ledger_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(ledger_nonce)
+}
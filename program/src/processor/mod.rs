@@ -0,0 +1,564 @@
+#[macro_use]
+mod profile;
+#[macro_use]
+mod validation;
+
+mod admin;
+mod config;
+mod depth;
+mod discount;
+mod escrow;
+mod fee;
+mod guard;
+mod init;
+mod invoice;
+mod ledger;
+mod offer;
+mod order;
+mod price;
+mod pricing;
+mod registry;
+mod report;
+mod session;
+mod settlement;
+mod stats;
+mod trade;
+mod valuation;
+mod waitlist;
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar::Sysvar,
+};
+
+use self::validation::StoreAccount;
+use crate::{
+    error::StoreError,
+    instruction::StoreInstruction,
+    state::{OrderSide, Store, FEATURE_STRICT_DECODING},
+};
+
+pub struct Processor;
+impl Processor {
+    pub fn process(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instruction_data: &[u8],
+    ) -> ProgramResult {
+        cu_checkpoint!("start");
+        let instruction = match StoreInstruction::unpack_strict(instruction_data) {
+            Err(error) if error == StoreError::NonCanonicalInstruction.into() => {
+                let instruction = StoreInstruction::unpack(instruction_data)?;
+                check_lenient_decoding(&instruction, accounts, program_id)?;
+                instruction
+            }
+            result => result?,
+        };
+        match instruction {
+            StoreInstruction::InitializeAccount {
+                price,
+                fee_bps,
+                open_at_slot,
+                close_at_slot,
+            } => init::process_init_store(
+                accounts,
+                price,
+                fee_bps,
+                open_at_slot,
+                close_at_slot,
+                program_id,
+            ),
+            StoreInstruction::UpdatePrice { price } => {
+                price::process_update_price(accounts, price, program_id)
+            }
+            StoreInstruction::Buy {
+                amount,
+                max_price,
+                tag,
+            } => trade::process_buy(accounts, amount, max_price, tag, program_id),
+            StoreInstruction::Sell {
+                amount,
+                min_price,
+                tag,
+            } => trade::process_sell(accounts, amount, min_price, tag, program_id),
+            StoreInstruction::UpdateLotSize { lot_size } => {
+                admin::process_update_lot_size(accounts, lot_size, program_id)
+            }
+            StoreInstruction::PublishDepth => depth::process_publish_depth(accounts, program_id),
+            StoreInstruction::SetRedirect => admin::process_set_redirect(accounts, program_id),
+            StoreInstruction::SetInventoryThresholds {
+                store_tokens,
+                payment_tokens,
+            } => admin::process_set_inventory_thresholds(
+                accounts,
+                store_tokens,
+                payment_tokens,
+                program_id,
+            ),
+            StoreInstruction::JoinWaitlist { amount } => {
+                waitlist::process_join_waitlist(accounts, amount, program_id)
+            }
+            StoreInstruction::LeaveWaitlist => {
+                waitlist::process_leave_waitlist(accounts, program_id)
+            }
+            StoreInstruction::SetSettlementAccounts => {
+                admin::process_set_settlement_accounts(accounts, program_id)
+            }
+            StoreInstruction::SetCosigner { threshold } => {
+                admin::process_set_cosigner(accounts, threshold, program_id)
+            }
+            StoreInstruction::CreateSession {
+                max_notional,
+                expires_at,
+            } => session::process_create_session(accounts, max_notional, expires_at, program_id),
+            StoreInstruction::RevokeSession => {
+                session::process_revoke_session(accounts, program_id)
+            }
+            StoreInstruction::SessionBuy { amount, price } => {
+                session::process_session_buy(accounts, amount, price, program_id)
+            }
+            StoreInstruction::SessionSell { amount, price } => {
+                session::process_session_sell(accounts, amount, price, program_id)
+            }
+            StoreInstruction::SettleSignedOrder => {
+                order::process_settle_signed_order(accounts, program_id)
+            }
+            StoreInstruction::CancelSignedOrder { nonce } => {
+                order::process_cancel_signed_order(accounts, nonce, program_id)
+            }
+            StoreInstruction::ReportPnl => report::process_report_pnl(accounts, program_id),
+            StoreInstruction::PreviewTrade {
+                side,
+                amount,
+                price,
+            } => trade::process_preview_trade(accounts, side, amount, price, program_id),
+            StoreInstruction::AssertBalances { assertions } => {
+                guard::process_assert_balances(accounts, &assertions)
+            }
+            StoreInstruction::Realloc => admin::process_realloc(accounts, program_id),
+            StoreInstruction::SetGateMint => admin::process_set_gate_mint(accounts, program_id),
+            StoreInstruction::SetDiscountTier { discount_bps } => {
+                discount::process_set_discount_tier(accounts, discount_bps, program_id)
+            }
+            StoreInstruction::UpdatePricesBatch { prices } => {
+                price::process_update_prices_batch(accounts, &prices, program_id)
+            }
+            StoreInstruction::AttestPrice { price } => {
+                price::process_attest_price(accounts, price, program_id)
+            }
+            StoreInstruction::SetEscrow { window } => {
+                escrow::process_set_escrow(accounts, window, program_id)
+            }
+            StoreInstruction::RefundEscrow => escrow::process_refund_escrow(accounts, program_id),
+            StoreInstruction::ReleaseEscrow => escrow::process_release_escrow(accounts, program_id),
+            StoreInstruction::CreateInvoice { amount, reference } => {
+                invoice::process_create_invoice(accounts, amount, reference, program_id)
+            }
+            StoreInstruction::PayInvoice => invoice::process_pay_invoice(accounts, program_id),
+            StoreInstruction::ProposeOwner => admin::process_propose_owner(accounts, program_id),
+            StoreInstruction::AcceptOwner => admin::process_accept_owner(accounts, program_id),
+            StoreInstruction::ReleaseAccounts => {
+                admin::process_release_accounts(accounts, program_id)
+            }
+            StoreInstruction::CloseStore => admin::process_close_store(accounts, program_id),
+            StoreInstruction::CreateBuyOffer {
+                amount,
+                price,
+                expires_at,
+            } => offer::process_create_offer(
+                accounts,
+                OrderSide::Buy,
+                amount,
+                price,
+                expires_at,
+                program_id,
+            ),
+            StoreInstruction::AcceptBuyOffer => {
+                offer::process_accept_offer(accounts, OrderSide::Buy, program_id)
+            }
+            StoreInstruction::CancelOffer => offer::process_cancel_offer(accounts, program_id),
+            StoreInstruction::CreateSellOffer {
+                amount,
+                price,
+                expires_at,
+            } => offer::process_create_offer(
+                accounts,
+                OrderSide::Sell,
+                amount,
+                price,
+                expires_at,
+                program_id,
+            ),
+            StoreInstruction::AcceptSellOffer => {
+                offer::process_accept_offer(accounts, OrderSide::Sell, program_id)
+            }
+            StoreInstruction::UpdatePrices {
+                buy_price,
+                sell_price,
+            } => price::process_update_prices(accounts, buy_price, sell_price, program_id),
+            StoreInstruction::Withdraw { amount } => {
+                admin::process_withdraw(accounts, amount, program_id)
+            }
+            StoreInstruction::Deposit { amount, side } => {
+                admin::process_deposit(accounts, amount, side, program_id)
+            }
+            StoreInstruction::SetFeatures { features } => {
+                admin::process_set_features(accounts, features, program_id)
+            }
+            StoreInstruction::SetTradingHours { hours } => {
+                admin::process_set_trading_hours(accounts, hours, program_id)
+            }
+            StoreInstruction::SetGuardian => admin::process_set_guardian(accounts, program_id),
+            StoreInstruction::Pause => admin::process_pause(accounts, program_id),
+            StoreInstruction::Resume => admin::process_resume(accounts, program_id),
+            StoreInstruction::BuyWithReference {
+                amount,
+                max_price,
+                tag,
+            } => trade::process_buy_with_reference(accounts, amount, max_price, tag, program_id),
+            StoreInstruction::GetVersion => report::process_get_version(),
+            StoreInstruction::UpdatePriceRatio {
+                buy_price,
+                sell_price,
+                denominator,
+            } => price::process_update_price_ratio(
+                accounts,
+                buy_price,
+                sell_price,
+                denominator,
+                program_id,
+            ),
+            StoreInstruction::RecordValuation => {
+                valuation::process_record_valuation(accounts, program_id)
+            }
+            StoreInstruction::UpdateFee { fee_bps } => {
+                fee::process_update_fee(accounts, fee_bps, program_id)
+            }
+            StoreInstruction::WithdrawFees { amount } => {
+                fee::process_withdraw_fees(accounts, amount, program_id)
+            }
+            StoreInstruction::ScheduleFee {
+                effective_at,
+                fee_bps,
+            } => fee::process_schedule_fee(accounts, effective_at, fee_bps, program_id),
+            StoreInstruction::BeginWindDown { deadline } => {
+                admin::process_begin_wind_down(accounts, deadline, program_id)
+            }
+            StoreInstruction::SetTradeLimits {
+                max_trade_amount,
+                remaining_inventory,
+            } => admin::process_set_trade_limits(
+                accounts,
+                max_trade_amount,
+                remaining_inventory,
+                program_id,
+            ),
+            StoreInstruction::RegisterStore => {
+                registry::process_register_store(accounts, program_id)
+            }
+            StoreInstruction::UnregisterStore => {
+                registry::process_unregister_store(accounts, program_id)
+            }
+            StoreInstruction::SetEscheatPeriod { period } => {
+                escrow::process_set_escheat_period(accounts, period, program_id)
+            }
+            StoreInstruction::SweepEscrow => escrow::process_sweep_escrow(accounts, program_id),
+            StoreInstruction::BatchBuy { legs } => {
+                trade::process_batch_buy(accounts, &legs, program_id)
+            }
+            StoreInstruction::ReactivateStore => {
+                admin::process_reactivate_store(accounts, program_id)
+            }
+            StoreInstruction::MigrateStore => admin::process_migrate_store(accounts, program_id),
+            StoreInstruction::InitializeStats => {
+                stats::process_initialize_stats(accounts, program_id)
+            }
+            StoreInstruction::InitializeConfig => {
+                config::process_initialize_config(accounts, program_id)
+            }
+            StoreInstruction::SetPaymentMints { mints } => {
+                config::process_set_payment_mints(accounts, &mints, program_id)
+            }
+            StoreInstruction::SetWalletCap { cap } => {
+                ledger::process_set_wallet_cap(accounts, cap, program_id)
+            }
+            StoreInstruction::SetNamespace { namespace } => {
+                registry::process_set_namespace(accounts, &namespace, program_id)
+            }
+            StoreInstruction::SetTradingWindow {
+                open_at_slot,
+                close_at_slot,
+            } => {
+                admin::process_set_trading_window(accounts, open_at_slot, close_at_slot, program_id)
+            }
+            StoreInstruction::SetSettlementCutoff { cutoff } => {
+                settlement::process_set_settlement_cutoff(accounts, cutoff, program_id)
+            }
+            StoreInstruction::SettleDay => settlement::process_settle_day(accounts, program_id),
+            StoreInstruction::SetPricing { pricing } => {
+                price::process_set_pricing(accounts, pricing, program_id)
+            }
+            StoreInstruction::ProposeConfigAuthority => {
+                config::process_propose_config_authority(accounts, program_id)
+            }
+            StoreInstruction::AcceptConfigAuthority => {
+                config::process_accept_config_authority(accounts, program_id)
+            }
+        }
+    }
+}
+
+/// Fails if any store named by an instruction sent with non canonical data, such as
+/// trailing bytes, opted into strict decoding
+fn check_lenient_decoding(
+    instruction: &StoreInstruction,
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    for index in instruction.store_account_indices() {
+        let Some(account) = accounts.get(index) else {
+            continue;
+        };
+        if account.owner != program_id {
+            continue;
+        }
+        if let Ok(store_info) = Store::unpack(&account.data.borrow()) {
+            if store_info.has_feature(FEATURE_STRICT_DECODING) {
+                return Err(StoreError::NonCanonicalInstruction.into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// PDA owning token accounts handed over to the program, one per store and one per offer
+struct StoreAuthority {
+    pubkey: Pubkey,
+    prefix: &'static [u8],
+    key: Pubkey,
+    nonce: u8,
+}
+
+impl StoreAuthority {
+    /// Looks up the PDA of the store, the found nonce is saved in the store on init
+    fn find(program_id: &Pubkey, store_key: &Pubkey) -> Self {
+        Self::find_with_prefix(b"store", program_id, store_key)
+    }
+
+    /// PDA of initialized store, derived from the nonce saved in it
+    fn of(store: &StoreAccount, program_id: &Pubkey) -> Result<Self, ProgramError> {
+        let nonce = store.info.authority_nonce;
+        let pubkey =
+            Pubkey::create_program_address(&[b"store", store.key().as_ref(), &[nonce]], program_id)
+                .map_err(|_| ProgramError::InvalidSeeds)?;
+        Ok(Self {
+            pubkey,
+            prefix: b"store",
+            key: *store.key(),
+            nonce,
+        })
+    }
+
+    /// PDA of the offer vault, same address as the offer account
+    fn offer(program_id: &Pubkey, vault_key: &Pubkey) -> Self {
+        Self::find_with_prefix(b"offer", program_id, vault_key)
+    }
+
+    fn find_with_prefix(prefix: &'static [u8], program_id: &Pubkey, key: &Pubkey) -> Self {
+        let (pubkey, nonce) = Pubkey::find_program_address(&[prefix, key.as_ref()], program_id);
+        Self {
+            pubkey,
+            prefix,
+            key: *key,
+            nonce,
+        }
+    }
+
+    fn seeds(&self) -> [&[u8]; 3] {
+        [
+            self.prefix,
+            self.key.as_ref(),
+            std::slice::from_ref(&self.nonce),
+        ]
+    }
+}
+
+fn set_token_account_owner<'a>(
+    token_program: &AccountInfo<'a>,
+    token_account: &AccountInfo<'a>,
+    current_owner: &AccountInfo<'a>,
+    new_owner: &Pubkey,
+) -> ProgramResult {
+    let owner_change_ix = spl_token::instruction::set_authority(
+        token_program.key,
+        token_account.key,
+        Some(new_owner),
+        spl_token::instruction::AuthorityType::AccountOwner,
+        current_owner.key,
+        &[current_owner.key],
+    )?;
+
+    msg!("Calling the token program to transfer token account ownership...");
+    invoke(
+        &owner_change_ix,
+        &[
+            token_account.clone(),
+            current_owner.clone(),
+            token_program.clone(),
+        ],
+    )
+}
+
+/// Same as `set_token_account_owner` for token account owned by the PDA
+fn set_token_account_owner_signed<'a>(
+    token_program: &AccountInfo<'a>,
+    token_account: &AccountInfo<'a>,
+    pda_account: &AccountInfo<'a>,
+    authority: &StoreAuthority,
+    new_owner: &Pubkey,
+) -> ProgramResult {
+    let owner_change_ix = spl_token::instruction::set_authority(
+        token_program.key,
+        token_account.key,
+        Some(new_owner),
+        spl_token::instruction::AuthorityType::AccountOwner,
+        &authority.pubkey,
+        &[&authority.pubkey],
+    )?;
+
+    msg!("Calling the token program to transfer token account ownership...");
+    invoke_signed(
+        &owner_change_ix,
+        &[
+            token_account.clone(),
+            pda_account.clone(),
+            token_program.clone(),
+        ],
+        &[&authority.seeds()],
+    )
+}
+
+/// Transfers tokens by `authority`, signing with `authority_seeds` if it's a PDA
+fn transfer<'a>(
+    token_program: &AccountInfo<'a>,
+    source: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    authority_seeds: &[&[u8]],
+    amount: u64,
+) -> ProgramResult {
+    let transfer_ix = spl_token::instruction::transfer(
+        token_program.key,
+        source.key,
+        destination.key,
+        authority.key,
+        &[authority.key],
+        amount,
+    )?;
+    let account_infos = [
+        source.clone(),
+        destination.clone(),
+        authority.clone(),
+        token_program.clone(),
+    ];
+    if authority_seeds.is_empty() {
+        invoke(&transfer_ix, &account_infos)
+    } else {
+        invoke_signed(&transfer_ix, &account_infos, &[authority_seeds])
+    }
+}
+
+fn transfer_signed<'a>(
+    token_program: &AccountInfo<'a>,
+    source: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    pda_account: &AccountInfo<'a>,
+    authority: &StoreAuthority,
+    amount: u64,
+) -> ProgramResult {
+    let transfer_ix = spl_token::instruction::transfer(
+        token_program.key,
+        source.key,
+        destination.key,
+        &authority.pubkey,
+        &[&authority.pubkey],
+        amount,
+    )?;
+    invoke_signed(
+        &transfer_ix,
+        &[
+            source.clone(),
+            destination.clone(),
+            pda_account.clone(),
+            token_program.clone(),
+        ],
+        &[&authority.seeds()],
+    )
+}
+
+/// Creates program owned account at PDA address, `seeds` must include the nonce.
+/// Lamports sent to the address beforehand don't block the creation, only the rest of the rent is paid.
+fn create_pda_account<'a>(
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    len: usize,
+    program_id: &Pubkey,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let rent = Rent::get()?;
+    if account.lamports() == 0 {
+        let create_ix = system_instruction::create_account(
+            payer.key,
+            account.key,
+            rent.minimum_balance(len),
+            len as u64,
+            program_id,
+        );
+        msg!("Calling the system program to create program account...");
+        return invoke_signed(
+            &create_ix,
+            &[payer.clone(), account.clone(), system_program.clone()],
+            &[seeds],
+        );
+    }
+
+    let top_up = rent.minimum_balance(len).saturating_sub(account.lamports());
+    if top_up > 0 {
+        msg!("Calling the system program to top up program account rent...");
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, top_up),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    msg!("Calling the system program to allocate program account...");
+    invoke_signed(
+        &system_instruction::allocate(account.key, len as u64),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account.key, program_id),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )
+}
+
+/// Moves all lamports of program owned account to `destination` and wipes its data
+fn close_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+    **destination.lamports.borrow_mut() = destination
+        .lamports()
+        .checked_add(account.lamports())
+        .ok_or(StoreError::MathOverflow)?;
+    **account.lamports.borrow_mut() = 0;
+    account.data.borrow_mut().fill(0);
+    Ok(())
+}
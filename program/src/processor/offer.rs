@@ -0,0 +1,260 @@
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, msg,
+    program::invoke_signed, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+    system_program, sysvar::Sysvar,
+};
+
+use super::{
+    close_account, create_pda_account, set_token_account_owner, set_token_account_owner_signed,
+    transfer, transfer_signed, StoreAuthority,
+};
+use crate::{
+    error::StoreError,
+    state::{Offer, OrderSide},
+};
+
+/// Moves the maker side tokens to the offer vault and hands the vault over to the offer account
+pub(super) fn process_create_offer(
+    accounts: &[AccountInfo],
+    side: OrderSide,
+    amount: u64,
+    price: u64,
+    expires_at: i64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        maker: signer,
+        offer_account: any,
+        offer_vault: vault,
+        source_account: any,
+        receive_account: vault,
+        token_program: program(&spl_token::id()),
+        system_program: program(&system_program::id()),
+    });
+    if amount == 0 || price == 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if expires_at <= Clock::get()?.unix_timestamp {
+        return Err(StoreError::OfferExpired.into());
+    }
+    // closing the vault once the offer is settled needs the offer account to be its close authority
+    if offer_vault.info.owner != *maker.key() || offer_vault.info.close_authority.is_some() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    // the vault holds exactly the escrow, anything else would be taken by the taker
+    if offer_vault.info.amount != 0 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let (offer_pda, offer_nonce) =
+        Pubkey::find_program_address(&[b"offer", offer_vault.key().as_ref()], program_id);
+    if offer_pda != *offer_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    create_pda_account(
+        maker.account,
+        offer_account,
+        system_program,
+        Offer::LEN,
+        program_id,
+        &[&b"offer"[..], offer_vault.key().as_ref(), &[offer_nonce]],
+    )?;
+
+    let offer_info = Offer {
+        is_initialized: true,
+        side,
+        maker_pubkey: *maker.key(),
+        mint_pubkey: receive_account.info.mint,
+        vault_pubkey: *offer_vault.key(),
+        receive_pubkey: *receive_account.key(),
+        amount,
+        price,
+        expires_at,
+    };
+    msg!("Calling the token program to escrow maker tokens...");
+    transfer(
+        token_program,
+        source_account,
+        offer_vault.account,
+        maker.account,
+        &[],
+        offer_info.escrowed()?,
+    )?;
+    set_token_account_owner(
+        token_program,
+        offer_vault.account,
+        maker.account,
+        offer_account.key,
+    )?;
+
+    Offer::pack(offer_info, &mut offer_account.data.borrow_mut())?;
+    msg!(
+        "OfferCreated: offer {} side {:?} maker {} mint {} amount {} price {} expires at {}",
+        offer_account.key,
+        side,
+        maker.key(),
+        offer_info.mint_pubkey,
+        amount,
+        price,
+        expires_at
+    );
+    Ok(())
+}
+
+/// Swaps taker tokens for the escrowed ones, the maker gets back the rent of both accounts,
+/// or the vault itself if it holds more than the escrow. `side` is the maker side expected by
+/// the instruction.
+pub(super) fn process_accept_offer(
+    accounts: &[AccountInfo],
+    side: OrderSide,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        taker: signer,
+        offer_account: any,
+    });
+    let offer_info = load_offer(offer_account, program_id)?;
+    accounts!(account_info_iter, program_id, {
+        offer_vault: vault(&offer_info.vault_pubkey),
+        taker_source: any,
+        receive_account: vault(&offer_info.receive_pubkey),
+        taker_receive: vault,
+        maker: any,
+        token_program: program(&spl_token::id()),
+    });
+    if offer_info.expires_at <= Clock::get()?.unix_timestamp {
+        return Err(StoreError::OfferExpired.into());
+    }
+    if offer_info.side != side || *maker.key != offer_info.maker_pubkey {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    msg!("Calling the token program to transfer taker tokens to the maker...");
+    transfer(
+        token_program,
+        taker_source,
+        receive_account.account,
+        taker.account,
+        &[],
+        offer_info.taker_pays()?,
+    )?;
+    let authority = StoreAuthority::offer(program_id, offer_vault.key());
+    msg!("Calling the token program to transfer escrowed tokens to the taker...");
+    transfer_signed(
+        token_program,
+        offer_vault.account,
+        taker_receive.account,
+        offer_account,
+        &authority,
+        offer_info.escrowed()?,
+    )?;
+    // tokens sent to the vault after it was created go back to the maker with the vault
+    if offer_vault.info.amount > offer_info.escrowed()? {
+        set_token_account_owner_signed(
+            token_program,
+            offer_vault.account,
+            offer_account,
+            &authority,
+            maker.key,
+        )?;
+    } else {
+        close_vault(
+            token_program,
+            offer_vault.account,
+            maker,
+            offer_account,
+            &authority,
+        )?;
+    }
+
+    msg!(
+        "OfferAccepted: offer {} side {:?} maker {} taker {} amount {} price {}",
+        offer_account.key,
+        side,
+        maker.key,
+        taker.key(),
+        offer_info.amount,
+        offer_info.price
+    );
+    close_account(offer_account, maker)
+}
+
+/// Returns the vault tokens to the maker, allowed before and after expiration
+pub(super) fn process_cancel_offer(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        maker: signer,
+        offer_account: any,
+    });
+    let offer_info = load_offer(offer_account, program_id)?;
+    accounts!(account_info_iter, program_id, {
+        offer_vault: vault(&offer_info.vault_pubkey),
+        refund_account: vault,
+        token_program: program(&spl_token::id()),
+    });
+    if *maker.key() != offer_info.maker_pubkey {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let authority = StoreAuthority::offer(program_id, offer_vault.key());
+    msg!("Calling the token program to return escrowed tokens...");
+    // the whole balance, tokens sent to the vault after creation would keep it from closing
+    transfer_signed(
+        token_program,
+        offer_vault.account,
+        refund_account.account,
+        offer_account,
+        &authority,
+        offer_vault.info.amount,
+    )?;
+    close_vault(
+        token_program,
+        offer_vault.account,
+        maker.account,
+        offer_account,
+        &authority,
+    )?;
+
+    msg!("OfferCanceled: offer {}", offer_account.key);
+    close_account(offer_account, maker.account)
+}
+
+fn load_offer(offer_account: &AccountInfo, program_id: &Pubkey) -> Result<Offer, ProgramError> {
+    if offer_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Offer::unpack(&offer_account.data.borrow())
+}
+
+/// Closes empty vault owned by the offer account, its lamports go to `destination`
+fn close_vault<'a>(
+    token_program: &AccountInfo<'a>,
+    vault: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    offer_account: &AccountInfo<'a>,
+    authority: &StoreAuthority,
+) -> ProgramResult {
+    let close_ix = spl_token::instruction::close_account(
+        token_program.key,
+        vault.key,
+        destination.key,
+        &authority.pubkey,
+        &[&authority.pubkey],
+    )?;
+    msg!("Calling the token program to close the vault...");
+    invoke_signed(
+        &close_ix,
+        &[
+            vault.clone(),
+            destination.clone(),
+            offer_account.clone(),
+            token_program.clone(),
+        ],
+        &[&authority.seeds()],
+    )
+}
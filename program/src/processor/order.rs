@@ -0,0 +1,212 @@
+use std::convert::TryFrom;
+
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, ed25519_program, entrypoint::ProgramResult, msg,
+    program_error::ProgramError, program_pack::Pack, pubkey::Pubkey, system_program,
+    sysvar::instructions, sysvar::Sysvar,
+};
+
+use super::{create_pda_account, trade, validation::StoreAccount};
+use crate::{
+    error::StoreError,
+    state::{NonceBitmap, OrderSide, SignedOrder, NONCES_PER_BITMAP},
+};
+
+/// Size of ed25519 signature offsets entry of the ed25519 program instruction
+const ED25519_OFFSETS_LEN: usize = 14;
+
+pub(super) fn process_settle_signed_order(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        instructions_sysvar: program(&instructions::id()),
+        order_authority: any,
+        nonce_account: any,
+        payer: signer,
+        system_program: program(&system_program::id()),
+    });
+    let (wallet, order) = load_signed_order(instructions_sysvar)?;
+    if order.expires_at <= Clock::get()?.unix_timestamp {
+        return Err(StoreError::OrderExpired.into());
+    }
+    use_nonce(
+        &wallet,
+        order.nonce,
+        nonce_account,
+        payer.account,
+        system_program,
+        program_id,
+    )?;
+
+    let (order_authority_pda, order_authority_nonce) =
+        Pubkey::find_program_address(&[b"order", wallet.as_ref()], program_id);
+    if order_authority_pda != *order_authority.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    // the store quotes the trade through its pricing engine, within the order limit price
+    let store = StoreAccount::load(
+        account_info_iter
+            .as_slice()
+            .first()
+            .ok_or(ProgramError::NotEnoughAccountKeys)?,
+        program_id,
+    )?;
+    if *store.key() != order.store_pubkey {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let order_authority_seeds = [&b"order"[..], wallet.as_ref(), &[order_authority_nonce]];
+    let settlement = match order.side {
+        OrderSide::Buy => trade::buy(
+            account_info_iter,
+            order_authority,
+            &order_authority_seeds,
+            order.amount,
+            order.limit_price,
+            trade::TradeMode::Execute,
+            program_id,
+        )?,
+        OrderSide::Sell => trade::sell(
+            account_info_iter,
+            order_authority,
+            &order_authority_seeds,
+            order.amount,
+            order.limit_price,
+            trade::TradeMode::Execute,
+            program_id,
+        )?,
+    };
+    if settlement.recipient != wallet {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    msg!(
+        "SignedOrderSettled: store {} wallet {} nonce {} amount {} notional {}",
+        store.key(),
+        wallet,
+        order.nonce,
+        order.amount,
+        settlement.notional
+    );
+
+    Ok(())
+}
+
+pub(super) fn process_cancel_signed_order(
+    accounts: &[AccountInfo],
+    nonce: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        wallet: signer,
+        payer: signer,
+        nonce_account: any,
+        system_program: program(&system_program::id()),
+    });
+    use_nonce(
+        wallet.key(),
+        nonce,
+        nonce_account,
+        payer.account,
+        system_program,
+        program_id,
+    )?;
+    msg!(
+        "SignedOrderCancelled: wallet {} nonce {}",
+        wallet.key(),
+        nonce
+    );
+
+    Ok(())
+}
+
+/// Marks `nonce` of the wallet used, creating its bitmap account if needed
+fn use_nonce<'a>(
+    wallet: &Pubkey,
+    nonce: u64,
+    nonce_account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let page = (nonce / NONCES_PER_BITMAP).to_le_bytes();
+    let (nonce_pda, nonce_pda_nonce) =
+        Pubkey::find_program_address(&[b"nonces", wallet.as_ref(), &page], program_id);
+    if nonce_pda != *nonce_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if nonce_account.lamports() == 0 {
+        create_pda_account(
+            payer,
+            nonce_account,
+            system_program,
+            NonceBitmap::LEN,
+            program_id,
+            &[&b"nonces"[..], wallet.as_ref(), &page, &[nonce_pda_nonce]],
+        )?;
+    }
+    if nonce_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut bitmap = NonceBitmap::unpack_unchecked(&nonce_account.data.borrow())?;
+    if !bitmap.is_initialized {
+        bitmap.is_initialized = true;
+        bitmap.wallet_pubkey = *wallet;
+        bitmap.page = nonce / NONCES_PER_BITMAP;
+    }
+    if bitmap.is_used(nonce) {
+        return Err(StoreError::NonceUsed.into());
+    }
+    bitmap.mark_used(nonce);
+    NonceBitmap::pack(bitmap, &mut nonce_account.data.borrow_mut())
+}
+
+/// Reads wallet and order verified by the ed25519 program instruction preceding this one
+fn load_signed_order(
+    instructions_sysvar: &AccountInfo,
+) -> Result<(Pubkey, SignedOrder), ProgramError> {
+    let current_index = instructions::load_current_index_checked(instructions_sysvar)?;
+    let ed25519_index = current_index
+        .checked_sub(1)
+        .ok_or(StoreError::InvalidOrderSignature)?;
+    let ed25519_ix =
+        instructions::load_instruction_at_checked(ed25519_index as usize, instructions_sysvar)?;
+    if ed25519_ix.program_id != ed25519_program::id() {
+        return Err(StoreError::InvalidOrderSignature.into());
+    }
+
+    let data = &ed25519_ix.data;
+    if data.len() < 2 + ED25519_OFFSETS_LEN || data[0] != 1 {
+        return Err(StoreError::InvalidOrderSignature.into());
+    }
+    let offset = |i: usize| u16::from_le_bytes([data[2 + i * 2], data[3 + i * 2]]);
+    let (public_key_offset, message_offset, message_size) = (offset(2), offset(4), offset(5));
+    // signature, public key and message must all be in the ed25519 instruction itself
+    for instruction_index in [offset(1), offset(3), offset(6)] {
+        if instruction_index != u16::MAX && instruction_index != ed25519_index {
+            return Err(StoreError::InvalidOrderSignature.into());
+        }
+    }
+
+    let public_key = data
+        .get(public_key_offset as usize..public_key_offset as usize + 32)
+        .ok_or(StoreError::InvalidOrderSignature)?;
+    let message = data
+        .get(message_offset as usize..message_offset as usize + message_size as usize)
+        .ok_or(StoreError::InvalidOrderSignature)?;
+    if message.len() != SignedOrder::LEN {
+        return Err(StoreError::InvalidOrderSignature.into());
+    }
+
+    Ok((
+        Pubkey::try_from(public_key).map_err(|_| StoreError::InvalidOrderSignature)?,
+        SignedOrder::unpack_from_slice(message)?,
+    ))
+}
@@ -0,0 +1,199 @@
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, hash::Hash, msg,
+    program_error::ProgramError, program_pack::Pack, pubkey::Pubkey, system_program,
+    sysvar::Sysvar,
+};
+
+use super::create_pda_account;
+use crate::{
+    instruction::Pricing,
+    state::{PriceFeed, PRICING_CONSTANT_PRODUCT, PRICING_DUTCH_AUCTION, PRICING_FIXED},
+};
+
+pub(super) fn process_update_price(
+    accounts: &[AccountInfo],
+    price: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+
+    store.info.set_price(price);
+    store.save()
+}
+
+/// Sets separate buy and sell prices, a sell price above the buy price would
+/// let users drain the store by buying and selling back
+pub(super) fn process_update_prices(
+    accounts: &[AccountInfo],
+    buy_price: u64,
+    sell_price: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+    if sell_price > buy_price {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    store.info.buy_price = buy_price;
+    store.info.sell_price = sell_price;
+    store.save()
+}
+
+/// Same as `process_update_prices`, also changing how many store tokens the prices are for
+pub(super) fn process_update_price_ratio(
+    accounts: &[AccountInfo],
+    buy_price: u64,
+    sell_price: u64,
+    denominator: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+    if denominator == 0 || sell_price > buy_price {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    store.info.buy_price = buy_price;
+    store.info.sell_price = sell_price;
+    store.info.price_denominator = denominator;
+    store.save()
+}
+
+pub(super) fn process_set_pricing(
+    accounts: &[AccountInfo],
+    pricing: Pricing,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+
+    match pricing {
+        Pricing::Fixed => store.info.pricing_engine = PRICING_FIXED,
+        Pricing::DutchAuction {
+            start_price,
+            floor_price,
+            start_slot,
+            end_slot,
+        } => {
+            if floor_price > start_price || start_slot >= end_slot {
+                return Err(ProgramError::InvalidArgument);
+            }
+            store.info.pricing_engine = PRICING_DUTCH_AUCTION;
+            store.info.auction_start_price = start_price;
+            store.info.auction_floor_price = floor_price;
+            store.info.auction_start_slot = start_slot;
+            store.info.auction_end_slot = end_slot;
+        }
+        Pricing::ConstantProduct {
+            virtual_payment_reserve,
+            virtual_store_reserve,
+        } => {
+            if virtual_payment_reserve == 0 {
+                return Err(ProgramError::InvalidArgument);
+            }
+            store.info.pricing_engine = PRICING_CONSTANT_PRODUCT;
+            store.info.curve_virtual_payment_reserve = virtual_payment_reserve;
+            store.info.curve_virtual_store_reserve = virtual_store_reserve;
+        }
+    }
+    store.save()?;
+    msg!(
+        "PricingSet: store {} engine {}",
+        store.key(),
+        store.info.pricing_engine
+    );
+    Ok(())
+}
+
+pub(super) fn process_update_prices_batch(
+    accounts: &[AccountInfo],
+    prices: &[u64],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+    });
+    for &price in prices {
+        accounts!(account_info_iter, program_id, {
+            mut store: store_owned(owner),
+        });
+        store.info.set_price(price);
+        store.save()?;
+    }
+    Ok(())
+}
+
+pub(super) fn process_attest_price(
+    accounts: &[AccountInfo],
+    price: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+        feed_account: any,
+        system_program: program(&system_program::id()),
+    });
+    let (feed_pda, feed_nonce) =
+        Pubkey::find_program_address(&[b"feed", store.key().as_ref()], program_id);
+    if feed_pda != *feed_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if feed_account.lamports() == 0 {
+        create_pda_account(
+            owner.account,
+            feed_account,
+            system_program,
+            PriceFeed::LEN,
+            program_id,
+            &[&b"feed"[..], store.key().as_ref(), &[feed_nonce]],
+        )?;
+    }
+    if feed_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut feed_info = PriceFeed::unpack_unchecked(&feed_account.data.borrow())?;
+    if !feed_info.is_initialized {
+        feed_info.is_initialized = true;
+        feed_info.store_pubkey = *store.key();
+    }
+    let previous_hash = feed_info.hash;
+    feed_info.attest(price, Clock::get()?.slot);
+    PriceFeed::pack(feed_info, &mut feed_account.data.borrow_mut())?;
+
+    store.info.set_price(price);
+    store.save()?;
+    msg!(
+        "PriceAttested: store {} price {} sequence {} hash {} previous {}",
+        store.key(),
+        price,
+        feed_info.sequence,
+        Hash::new_from_array(feed_info.hash),
+        Hash::new_from_array(previous_hash)
+    );
+    Ok(())
+}
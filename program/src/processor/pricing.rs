@@ -0,0 +1,240 @@
+use std::convert::TryFrom;
+
+use solana_program::{clock::Clock, program_error::ProgramError, sysvar::Sysvar};
+
+use super::validation::VaultAccount;
+use crate::{
+    error::StoreError,
+    math::{constant_product_buy, constant_product_sell, curve_reserves},
+    state::{OrderSide, Store, PRICING_CONSTANT_PRODUCT, PRICING_DUTCH_AUCTION, PRICING_FIXED},
+};
+
+/// Quotes trades of a store, selected by `Store::pricing_engine`.
+/// Settlement only moves the quoted notional, new pricing modes are added here.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum PricingEngine {
+    /// Trades at owner set prices per `denominator` store tokens,
+    /// within the price bound of the instruction
+    FixedPrice {
+        buy_price: u64,
+        sell_price: u64,
+        denominator: u64,
+    },
+    /// Sells at `start_price` decaying linearly to `floor_price` between `start_slot` and
+    /// `end_slot`, per `denominator` store tokens, doesn't buy
+    DutchAuction {
+        start_price: u64,
+        floor_price: u64,
+        start_slot: u64,
+        end_slot: u64,
+        denominator: u64,
+        /// current slot
+        slot: u64,
+    },
+    /// Trades on the constant product curve of the reserves, vault balances of the store with
+    /// the curve virtual reserves, prices bound the average price of the trade
+    ConstantProduct {
+        payment_reserve: u64,
+        store_reserve: u64,
+        denominator: u64,
+    },
+}
+
+impl PricingEngine {
+    pub(super) fn of(store: &Store) -> Result<Self, ProgramError> {
+        match store.pricing_engine {
+            PRICING_FIXED => Ok(Self::FixedPrice {
+                buy_price: store.buy_price,
+                sell_price: store.sell_price,
+                denominator: store.denominator(),
+            }),
+            PRICING_DUTCH_AUCTION => Ok(Self::DutchAuction {
+                start_price: store.auction_start_price,
+                floor_price: store.auction_floor_price,
+                start_slot: store.auction_start_slot,
+                end_slot: store.auction_end_slot,
+                denominator: store.denominator(),
+                slot: Clock::get()?.slot,
+            }),
+            PRICING_CONSTANT_PRODUCT => Err(StoreError::ReservesRequired.into()),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    /// Same as `of`, constant product stores quote by the balances of the store vaults,
+    /// trades must settle to them
+    pub(super) fn of_reserves(
+        store: &Store,
+        payment_vault: &VaultAccount,
+        store_vault: &VaultAccount,
+    ) -> Result<Self, ProgramError> {
+        if store.pricing_engine != PRICING_CONSTANT_PRODUCT {
+            return Self::of(store);
+        }
+        if *payment_vault.key() != store.native_tokens_to_auto_sell_pubkey
+            || *store_vault.key() != store.store_tokens_to_auto_buy_pubkey
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (payment_reserve, store_reserve) =
+            curve_reserves(store, payment_vault.info.amount, store_vault.info.amount)
+                .ok_or(StoreError::MathOverflow)?;
+        Ok(Self::ConstantProduct {
+            payment_reserve,
+            store_reserve,
+            denominator: store.denominator(),
+        })
+    }
+
+    /// Store tokens amount prices are quoted per
+    pub(super) fn denominator(&self) -> u64 {
+        match *self {
+            Self::FixedPrice { denominator, .. }
+            | Self::DutchAuction { denominator, .. }
+            | Self::ConstantProduct { denominator, .. } => denominator,
+        }
+    }
+
+    /// Prices per `denominator` store tokens at the moment: the bid the store pays users selling
+    /// to it (0 - doesn't buy) and the ask users buying from it pay. The spot price of the curve
+    /// for constant product stores.
+    pub(super) fn quotes(&self) -> Result<(u64, u64), ProgramError> {
+        match *self {
+            Self::FixedPrice {
+                buy_price,
+                sell_price,
+                ..
+            } => Ok((sell_price, buy_price)),
+            Self::DutchAuction {
+                start_price,
+                floor_price,
+                start_slot,
+                end_slot,
+                slot,
+                ..
+            } => Ok((
+                0,
+                auction_price(start_price, floor_price, start_slot, end_slot, slot),
+            )),
+            Self::ConstantProduct {
+                payment_reserve,
+                store_reserve,
+                denominator,
+            } => {
+                let spot = (payment_reserve as u128 * denominator as u128)
+                    .checked_div(store_reserve as u128)
+                    .and_then(|spot| u64::try_from(spot).ok())
+                    .ok_or(StoreError::MathOverflow)?;
+                Ok((spot, spot))
+            }
+        }
+    }
+
+    /// Payment tokens the user pays for `amount` store tokens, `max_price` is the most user agreed to
+    pub(super) fn quote_buy(&self, amount: u64, max_price: u64) -> Result<u64, ProgramError> {
+        match *self {
+            Self::FixedPrice {
+                buy_price,
+                denominator,
+                ..
+            } => {
+                if buy_price > max_price {
+                    return Err(StoreError::AccountPriceMismatch.into());
+                }
+                notional(amount, buy_price, denominator, OrderSide::Buy)
+            }
+            Self::DutchAuction {
+                start_price,
+                floor_price,
+                start_slot,
+                end_slot,
+                denominator,
+                slot,
+            } => {
+                let price = auction_price(start_price, floor_price, start_slot, end_slot, slot);
+                if price > max_price {
+                    return Err(StoreError::AccountPriceMismatch.into());
+                }
+                notional(amount, price, denominator, OrderSide::Buy)
+            }
+            Self::ConstantProduct {
+                payment_reserve,
+                store_reserve,
+                denominator,
+            } => {
+                let notional = constant_product_buy(payment_reserve, store_reserve, amount)
+                    .ok_or(ProgramError::InsufficientFunds)?;
+                if notional as u128 * denominator as u128 > amount as u128 * max_price as u128 {
+                    return Err(StoreError::AccountPriceMismatch.into());
+                }
+                Ok(notional)
+            }
+        }
+    }
+
+    /// Payment tokens the user receives for `amount` store tokens, `min_price` is the least user agreed to
+    pub(super) fn quote_sell(&self, amount: u64, min_price: u64) -> Result<u64, ProgramError> {
+        match *self {
+            Self::FixedPrice {
+                sell_price,
+                denominator,
+                ..
+            } => {
+                if sell_price < min_price {
+                    return Err(StoreError::AccountPriceMismatch.into());
+                }
+                notional(amount, sell_price, denominator, OrderSide::Sell)
+            }
+            Self::DutchAuction { .. } => Err(StoreError::SellNotSupported.into()),
+            Self::ConstantProduct {
+                payment_reserve,
+                store_reserve,
+                denominator,
+            } => {
+                let notional = constant_product_sell(payment_reserve, store_reserve, amount)
+                    .ok_or(StoreError::MathOverflow)?;
+                if (notional as u128 * denominator as u128) < amount as u128 * min_price as u128 {
+                    return Err(StoreError::AccountPriceMismatch.into());
+                }
+                Ok(notional)
+            }
+        }
+    }
+}
+
+/// `amount * price / denominator`, rounded up when the user pays (buy) and down when
+/// the user receives (sell), so that rounding never favors the user
+fn notional(
+    amount: u64,
+    price: u64,
+    denominator: u64,
+    side: OrderSide,
+) -> Result<u64, ProgramError> {
+    let product = amount as u128 * price as u128;
+    let denominator = denominator as u128;
+    let notional = match side {
+        OrderSide::Buy => product.div_ceil(denominator),
+        OrderSide::Sell => product / denominator,
+    };
+    u64::try_from(notional).map_err(|_| StoreError::MathOverflow.into())
+}
+
+/// Dutch auction price at `slot`, the decay is rounded down so the price never gets below
+/// the linear one
+fn auction_price(
+    start_price: u64,
+    floor_price: u64,
+    start_slot: u64,
+    end_slot: u64,
+    slot: u64,
+) -> u64 {
+    if slot <= start_slot {
+        return start_price;
+    }
+    if slot >= end_slot {
+        return floor_price;
+    }
+    let decay = (start_price - floor_price) as u128 * (slot - start_slot) as u128
+        / (end_slot - start_slot) as u128;
+    start_price - decay as u64
+}
@@ -0,0 +1,11 @@
+/// Logs remaining compute units at a named checkpoint when built with the `cu-profile` feature,
+/// the units spent between consecutive checkpoints are reported by `cargo xtask cu-report`
+macro_rules! cu_checkpoint {
+    ($name:expr) => {
+        #[cfg(feature = "cu-profile")]
+        {
+            ::solana_program::msg!("CU checkpoint: {}", $name);
+            ::solana_program::log::sol_log_compute_units();
+        }
+    };
+}
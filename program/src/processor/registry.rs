@@ -0,0 +1,124 @@
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::ProgramError,
+    program_pack::Pack, pubkey::Pubkey, system_program,
+};
+
+use super::create_pda_account;
+use crate::state::StoreRegistry;
+
+/// Moves the store to a partner namespace, it's registered in the owner registry of the namespace
+/// afterwards. Registrations in the former namespace stay until unregistered.
+pub(super) fn process_set_namespace(
+    accounts: &[AccountInfo],
+    namespace: &Pubkey,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+
+    store.info.namespace = *namespace;
+    store.save()?;
+    msg!(
+        "NamespaceSet: store {} namespace {}",
+        store.key(),
+        namespace
+    );
+    Ok(())
+}
+
+/// Adds the store to the registry of its owner in the store namespace, creating the registry on first use
+pub(super) fn process_register_store(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        store: store_owned(owner),
+        registry_account: any,
+        system_program: program(&system_program::id()),
+    });
+    let mut seeds = registry_seeds(owner.key(), &store.info.namespace);
+    let (registry_pda, registry_nonce) = Pubkey::find_program_address(&seeds, program_id);
+    if registry_pda != *registry_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if registry_account.lamports() == 0 {
+        let nonce = [registry_nonce];
+        seeds.push(&nonce);
+        create_pda_account(
+            owner.account,
+            registry_account,
+            system_program,
+            StoreRegistry::LEN,
+            program_id,
+            &seeds,
+        )?;
+        let registry_info = StoreRegistry {
+            is_initialized: true,
+            owner_pubkey: *owner.key(),
+            ..StoreRegistry::default()
+        };
+        StoreRegistry::pack(registry_info, &mut registry_account.data.borrow_mut())?;
+    }
+    if registry_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut registry_info = StoreRegistry::unpack(&registry_account.data.borrow())?;
+    registry_info.register(store.key())?;
+    StoreRegistry::pack(registry_info, &mut registry_account.data.borrow_mut())?;
+    msg!(
+        "StoreRegistered: store {} owner {}",
+        store.key(),
+        owner.key()
+    );
+    Ok(())
+}
+
+/// Removes the store from a registry of the owner in any namespace, the store may be closed already
+pub(super) fn process_unregister_store(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        store_account: any,
+        registry_account: any,
+    });
+    if registry_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut registry_info = StoreRegistry::unpack(&registry_account.data.borrow())?;
+    if registry_info.owner_pubkey != *owner.key() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    registry_info.unregister(store_account.key)?;
+    StoreRegistry::pack(registry_info, &mut registry_account.data.borrow_mut())?;
+    msg!(
+        "StoreUnregistered: store {} owner {}",
+        store_account.key,
+        owner.key()
+    );
+    Ok(())
+}
+
+/// Seeds of the registry PDA of `owner`, `["registry", owner]` or `["registry", namespace, owner]`
+/// if the namespace is set
+fn registry_seeds<'a>(owner: &'a Pubkey, namespace: &'a Pubkey) -> Vec<&'a [u8]> {
+    let mut seeds = vec![&b"registry"[..]];
+    if *namespace != Pubkey::default() {
+        seeds.push(namespace.as_ref());
+    }
+    seeds.push(owner.as_ref());
+    seeds
+}
@@ -0,0 +1,41 @@
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program::set_return_data,
+    program_pack::Pack, pubkey::Pubkey,
+};
+
+use super::valuation::load_valuation;
+use crate::state::{PnlReport, ProgramVersion};
+
+/// Unrealized PnL is at the store buy price, or at the mark price of the valuation if it's passed
+pub(super) fn process_report_pnl(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        store: store,
+    });
+    let mark_price = match account_info_iter.next() {
+        Some(valuation_account) => load_valuation(&store, valuation_account, program_id)?.price,
+        None => store.info.buy_price,
+    };
+    let report = PnlReport {
+        realized_pnl: store.info.realized_pnl,
+        unrealized_pnl: store.info.unrealized_pnl(mark_price)?,
+        bought_volume: store.info.bought_volume,
+        sold_volume: store.info.sold_volume,
+        cost_basis_amount: store.info.cost_basis_amount,
+        cost_basis: store.info.cost_basis,
+    };
+
+    let mut data = vec![0; PnlReport::LEN];
+    report.pack_into_slice(&mut data);
+    set_return_data(&data);
+    Ok(())
+}
+
+/// Returns `ProgramVersion` of the deployed program as return data, takes no accounts
+pub(super) fn process_get_version() -> ProgramResult {
+    let mut data = vec![0; ProgramVersion::LEN];
+    ProgramVersion::current().pack_into_slice(&mut data);
+    set_return_data(&data);
+    Ok(())
+}
@@ -0,0 +1,208 @@
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, msg,
+    program_error::ProgramError, program_pack::Pack, pubkey::Pubkey, system_program,
+    sysvar::Sysvar,
+};
+
+use super::{close_account, create_pda_account, trade};
+use crate::{
+    error::StoreError,
+    state::{Session, FEATURE_SESSIONS},
+};
+
+pub(super) fn process_create_session(
+    accounts: &[AccountInfo],
+    max_notional: u64,
+    expires_at: i64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        wallet: signer,
+        payer: signer,
+        store: store,
+        session_key: any,
+        session_account: any,
+        system_program: program(&system_program::id()),
+    });
+    store.check_feature(FEATURE_SESSIONS)?;
+    if expires_at <= Clock::get()?.unix_timestamp {
+        return Err(StoreError::SessionExpired.into());
+    }
+
+    let (session_pda, session_nonce) = Pubkey::find_program_address(
+        &[
+            b"session",
+            store.key().as_ref(),
+            wallet.key().as_ref(),
+            session_key.key.as_ref(),
+        ],
+        program_id,
+    );
+    if session_pda != *session_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if session_account.lamports() != 0 {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    create_pda_account(
+        payer.account,
+        session_account,
+        system_program,
+        Session::LEN,
+        program_id,
+        &[
+            &b"session"[..],
+            store.key().as_ref(),
+            wallet.key().as_ref(),
+            session_key.key.as_ref(),
+            &[session_nonce],
+        ],
+    )?;
+
+    let session_info = Session {
+        is_initialized: true,
+        wallet_pubkey: *wallet.key(),
+        session_pubkey: *session_key.key,
+        store_pubkey: *store.key(),
+        max_notional,
+        spent_notional: 0,
+        expires_at,
+        nonce: session_nonce,
+    };
+    Session::pack(session_info, &mut session_account.data.borrow_mut())?;
+    msg!(
+        "SessionCreated: store {} wallet {} session {} max notional {} expires at {}",
+        store.key(),
+        wallet.key(),
+        session_key.key,
+        max_notional,
+        expires_at
+    );
+
+    Ok(())
+}
+
+pub(super) fn process_revoke_session(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        wallet: signer,
+        session_account: any,
+    });
+    if session_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let session_info = Session::unpack(&session_account.data.borrow())?;
+    if session_info.wallet_pubkey != *wallet.key() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    close_account(session_account, wallet.account)
+}
+
+pub(super) fn process_session_buy(
+    accounts: &[AccountInfo],
+    amount: u64,
+    price: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        session_key: signer,
+        session_account: any,
+    });
+    let mut session_info = load_session(session_account, session_key.key(), program_id)?;
+    let settlement = trade::buy(
+        account_info_iter,
+        session_account,
+        &[
+            &b"session"[..],
+            session_info.store_pubkey.as_ref(),
+            session_info.wallet_pubkey.as_ref(),
+            session_info.session_pubkey.as_ref(),
+            &[session_info.nonce],
+        ],
+        amount,
+        price,
+        trade::TradeMode::Execute,
+        program_id,
+    )?;
+    spend_session(&mut session_info, &settlement)?;
+    Session::pack(session_info, &mut session_account.data.borrow_mut())
+}
+
+pub(super) fn process_session_sell(
+    accounts: &[AccountInfo],
+    amount: u64,
+    price: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        session_key: signer,
+        session_account: any,
+    });
+    let mut session_info = load_session(session_account, session_key.key(), program_id)?;
+    let settlement = trade::sell(
+        account_info_iter,
+        session_account,
+        &[
+            &b"session"[..],
+            session_info.store_pubkey.as_ref(),
+            session_info.wallet_pubkey.as_ref(),
+            session_info.session_pubkey.as_ref(),
+            &[session_info.nonce],
+        ],
+        amount,
+        price,
+        trade::TradeMode::Execute,
+        program_id,
+    )?;
+    spend_session(&mut session_info, &settlement)?;
+    Session::pack(session_info, &mut session_account.data.borrow_mut())
+}
+
+/// Unpacks session of `session_key`, checking it's not expired
+fn load_session(
+    session_account: &AccountInfo,
+    session_key: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<Session, ProgramError> {
+    if session_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let session_info = Session::unpack(&session_account.data.borrow())?;
+    if session_info.session_pubkey != *session_key {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if session_info.expires_at <= Clock::get()?.unix_timestamp {
+        return Err(StoreError::SessionExpired.into());
+    }
+    Ok(session_info)
+}
+
+/// Accounts the trade against session store, wallet and notional limit
+fn spend_session(session_info: &mut Session, settlement: &trade::Settlement) -> ProgramResult {
+    if settlement.store != session_info.store_pubkey
+        || settlement.recipient != session_info.wallet_pubkey
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let spent_notional = session_info
+        .spent_notional
+        .checked_add(settlement.notional)
+        .ok_or(StoreError::MathOverflow)?;
+    if spent_notional > session_info.max_notional {
+        return Err(StoreError::SessionLimitExceeded.into());
+    }
+    session_info.spent_notional = spent_notional;
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, hash::hashv, msg,
+    program_error::ProgramError, program_pack::Pack, pubkey::Pubkey, system_program,
+    sysvar::Sysvar,
+};
+
+use super::{create_pda_account, validation::StoreAccount};
+use crate::{
+    error::StoreError,
+    state::{SettlementReport, DAY_SECONDS},
+};
+
+/// Sets the daily settlement cutoff, or stops daily settlement if `cutoff` is `None`.
+/// The cutoff passed already today is not settled, a pending settlement must be settled first.
+pub(super) fn process_set_settlement_cutoff(
+    accounts: &[AccountInfo],
+    cutoff: Option<u32>,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        owner: signer,
+        mut store: store_owned(owner),
+    });
+    check_settled(&store)?;
+    let now = Clock::get()?.unix_timestamp;
+
+    match cutoff {
+        Some(cutoff) if cutoff as i64 >= DAY_SECONDS => return Err(ProgramError::InvalidArgument),
+        Some(cutoff) => {
+            store.info.is_settling_daily = true;
+            store.info.settlement_cutoff = cutoff;
+            store.info.settled_day = store.info.settlement_day(now);
+        }
+        None => store.info.is_settling_daily = false,
+    }
+    store.save()?;
+    msg!(
+        "SettlementCutoffSet: store {} cutoff {:?}",
+        store.key(),
+        cutoff
+    );
+    Ok(())
+}
+
+/// Fails while the day of the last cutoff waits for its settlement: store vault balances are frozen
+/// from the cutoff for SettleDay to snapshot them
+pub(super) fn check_settled(store: &StoreAccount) -> ProgramResult {
+    if store
+        .info
+        .pending_settlement(Clock::get()?.unix_timestamp)
+        .is_some()
+    {
+        return Err(StoreError::SettlementPending.into());
+    }
+    Ok(())
+}
+
+/// Settles the day of the last cutoff, callable by anyone: snapshots store balances and trade
+/// totals to the day report, logs its hash and resumes trades frozen since the cutoff
+pub(super) fn process_settle_day(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        payer: signer,
+        mut store: store,
+        store_store_tokens: vault(&store.info.store_tokens_to_auto_buy_pubkey),
+        store_payment_tokens: vault(&store.info.native_tokens_to_auto_sell_pubkey),
+        report_account: any,
+        system_program: program(&system_program::id()),
+    });
+    let clock = Clock::get()?;
+    let day = store
+        .info
+        .pending_settlement(clock.unix_timestamp)
+        .ok_or(StoreError::NoSettlementDue)?;
+
+    let day_bytes = day.to_le_bytes();
+    let (report_pda, report_nonce) = Pubkey::find_program_address(
+        &[b"settlement", store.key().as_ref(), &day_bytes],
+        program_id,
+    );
+    if report_pda != *report_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    create_pda_account(
+        payer.account,
+        report_account,
+        system_program,
+        SettlementReport::LEN,
+        program_id,
+        &[
+            &b"settlement"[..],
+            store.key().as_ref(),
+            &day_bytes,
+            &[report_nonce],
+        ],
+    )?;
+
+    let report = SettlementReport {
+        is_initialized: true,
+        store_pubkey: *store.key(),
+        day,
+        previous_day: store.info.settled_day,
+        slot: clock.slot,
+        unix_timestamp: clock.unix_timestamp,
+        store_tokens: store_store_tokens.info.amount,
+        payment_tokens: store_payment_tokens.info.amount,
+        total_buy_volume: store.info.total_buy_volume,
+        total_sell_volume: store.info.total_sell_volume,
+        trade_count: store.info.trade_count,
+        fees_collected: store.info.fees_collected,
+    };
+    SettlementReport::pack(report, &mut report_account.data.borrow_mut())?;
+    let report_hash = hashv(&[&report_account.data.borrow()]);
+
+    store.info.settled_day = day;
+    store.save()?;
+    msg!(
+        "DaySettled: store {} day {} store tokens {} payment tokens {} trades {} report {} hash {}",
+        store.key(),
+        day,
+        report.store_tokens,
+        report.payment_tokens,
+        report.trade_count,
+        report_account.key,
+        report_hash
+    );
+    Ok(())
+}
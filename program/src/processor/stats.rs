@@ -0,0 +1,187 @@
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::ProgramError,
+    program_pack::Pack, pubkey::Pubkey, system_program,
+};
+
+use super::{
+    create_pda_account,
+    trade::TradeMode,
+    validation::{SignerAccount, StoreAccount},
+};
+use crate::{
+    error::StoreError,
+    state::{MintStats, ProgramStats, Store},
+};
+
+/// Creates the program stats account, paid by anyone
+pub(super) fn process_initialize_stats(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        payer: signer,
+        stats_account: any,
+        system_program: program(&system_program::id()),
+    });
+    let stats_nonce = check_stats_address(stats_account, &[b"stats"], program_id)?;
+
+    create_pda_account(
+        payer.account,
+        stats_account,
+        system_program,
+        ProgramStats::LEN,
+        program_id,
+        &[&b"stats"[..], &[stats_nonce]],
+    )?;
+    let stats = ProgramStats {
+        is_initialized: true,
+        ..ProgramStats::default()
+    };
+    ProgramStats::pack(stats, &mut stats_account.data.borrow_mut())?;
+    msg!("StatsInitialized: account {}", stats_account.key);
+    Ok(())
+}
+
+/// Counts the store being initialized in the program stats if they exist, its trades update
+/// the stats of its payment mint afterwards. The mint stats account is created if it doesn't exist,
+/// paid by the store owner.
+pub(super) fn track_store<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    owner: &SignerAccount<'a, 'b>,
+    stats_account: &AccountInfo<'b>,
+    account_info_iter: &mut I,
+    store_info: &mut Store,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    check_stats_address(stats_account, &[b"stats"], program_id)?;
+    if stats_account.owner != program_id {
+        return Ok(());
+    }
+    accounts!(account_info_iter, program_id, {
+        mint_stats_account: any,
+        system_program: program(&system_program::id()),
+    });
+    let mint = store_info.payment_token_mint;
+    let mint_stats_nonce =
+        check_stats_address(mint_stats_account, &[b"stats", mint.as_ref()], program_id)?;
+    if mint_stats_account.lamports() == 0 {
+        create_pda_account(
+            owner.account,
+            mint_stats_account,
+            system_program,
+            MintStats::LEN,
+            program_id,
+            &[&b"stats"[..], mint.as_ref(), &[mint_stats_nonce]],
+        )?;
+        let mint_stats = MintStats {
+            is_initialized: true,
+            mint,
+            ..MintStats::default()
+        };
+        MintStats::pack(mint_stats, &mut mint_stats_account.data.borrow_mut())?;
+        msg!(
+            "MintStatsInitialized: account {} mint {}",
+            mint_stats_account.key,
+            mint
+        );
+    } else if mint_stats_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut stats = ProgramStats::unpack(&stats_account.data.borrow())?;
+    stats.store_count = stats
+        .store_count
+        .checked_add(1)
+        .ok_or(StoreError::MathOverflow)?;
+    ProgramStats::pack(stats, &mut stats_account.data.borrow_mut())?;
+    let mut mint_stats = MintStats::unpack(&mint_stats_account.data.borrow())?;
+    mint_stats.store_count = mint_stats
+        .store_count
+        .checked_add(1)
+        .ok_or(StoreError::MathOverflow)?;
+    MintStats::pack(mint_stats, &mut mint_stats_account.data.borrow_mut())?;
+    store_info.is_stats_tracked = true;
+    Ok(())
+}
+
+/// Stops counting the store being deleted, with the program stats and mint stats accounts next
+pub(super) fn untrack_store<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    account_info_iter: &mut I,
+    store_info: &Store,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    accounts!(account_info_iter, program_id, {
+        stats_account: any,
+        mint_stats_account: any,
+    });
+    check_stats_account(stats_account, &[b"stats"], program_id)?;
+    check_stats_account(
+        mint_stats_account,
+        &[b"stats", store_info.payment_token_mint.as_ref()],
+        program_id,
+    )?;
+
+    let mut stats = ProgramStats::unpack(&stats_account.data.borrow())?;
+    stats.store_count = stats.store_count.saturating_sub(1);
+    ProgramStats::pack(stats, &mut stats_account.data.borrow_mut())?;
+    let mut mint_stats = MintStats::unpack(&mint_stats_account.data.borrow())?;
+    mint_stats.store_count = mint_stats.store_count.saturating_sub(1);
+    MintStats::pack(mint_stats, &mut mint_stats_account.data.borrow_mut())
+}
+
+/// Next account is the stats of the store payment mint if the store tracks stats, not passed
+/// in preview mode
+pub(super) fn next_stats_account<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    store: &StoreAccount,
+    iter: &mut I,
+    mode: TradeMode,
+    program_id: &Pubkey,
+) -> Result<Option<&'a AccountInfo<'b>>, ProgramError> {
+    if !store.info.is_stats_tracked || mode == TradeMode::Preview {
+        return Ok(None);
+    }
+    let account = iter.next().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    check_stats_account(
+        account,
+        &[b"stats", store.info.payment_token_mint.as_ref()],
+        program_id,
+    )?;
+    Ok(Some(account))
+}
+
+/// Adds a trade of the store to the stats of its payment mint
+pub(super) fn record_trade_stats(
+    mint_stats_account: &AccountInfo,
+    notional: u64,
+    fee: u64,
+) -> ProgramResult {
+    let mut mint_stats = MintStats::unpack(&mint_stats_account.data.borrow())?;
+    mint_stats.record_trade(notional, fee)?;
+    MintStats::pack(mint_stats, &mut mint_stats_account.data.borrow_mut())
+}
+
+/// Checks the account is the stats PDA of `seeds`, returns its nonce
+fn check_stats_address(
+    account: &AccountInfo,
+    seeds: &[&[u8]],
+    program_id: &Pubkey,
+) -> Result<u8, ProgramError> {
+    let (pda, nonce) = Pubkey::find_program_address(seeds, program_id);
+    if pda != *account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(nonce)
+}
+
+fn check_stats_account(
+    account: &AccountInfo,
+    seeds: &[&[u8]],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    check_stats_address(account, seeds, program_id)?;
+    if account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
@@ -0,0 +1,736 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program::set_return_data,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+    sysvar::Sysvar,
+};
+
+use super::{
+    discount::{discounted, next_discount_bps},
+    escrow::open_escrow,
+    fee::{apply_fee_schedule, next_fee_vault},
+    ledger::record_purchase,
+    pricing::PricingEngine,
+    settlement::check_settled,
+    stats::{next_stats_account, record_trade_stats},
+    transfer, transfer_signed,
+    validation::{next_program_account, StoreAccount, VaultAccount},
+    StoreAuthority,
+};
+use crate::{
+    error::StoreError,
+    instruction::{BatchLeg, MAX_BATCH_LEGS},
+    state::{OrderSide, TradePreview, FEATURE_STRICT_DECODING, PRICING_CONSTANT_PRODUCT},
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum TradeMode {
+    Execute,
+    /// performs all checks without moving tokens or updating the store
+    Preview,
+}
+
+/// Price terms of a buy
+#[derive(Clone, Copy)]
+pub(super) enum BuyQuote {
+    /// quoted by the store pricing engine, at most this price
+    MaxPrice(u64),
+    /// notional of the whole amount agreed before, as of an invoice
+    Notional(u64),
+}
+
+/// Trade executed against the store
+pub(super) struct Settlement {
+    pub store: Pubkey,
+    /// store tokens amount
+    pub amount: u64,
+    /// payment tokens amount
+    pub notional: u64,
+    /// payment tokens fee, paid by the user on top of the notional on buy and deducted from it on sell
+    pub fee: u64,
+    /// trade needs cosigner, only reported in preview mode
+    pub cosigner_required: bool,
+    /// owner of the user account receiving tokens from the store
+    pub recipient: Pubkey,
+}
+
+pub(super) fn process_buy(
+    accounts: &[AccountInfo],
+    amount: u64,
+    max_price: u64,
+    tag: Option<u16>,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let settlement = signed_buy(&mut accounts.iter(), amount, max_price, program_id)?;
+    log_tagged_trade(&settlement, OrderSide::Buy, tag);
+    Ok(())
+}
+
+/// Buy logged with a reference key, the Solana Pay convention to find the transaction by
+pub(super) fn process_buy_with_reference(
+    accounts: &[AccountInfo],
+    amount: u64,
+    max_price: u64,
+    tag: Option<u16>,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        reference: any,
+    });
+    let settlement = signed_buy(account_info_iter, amount, max_price, program_id)?;
+    msg!(
+        "ReferencedTrade: store {} side buy amount {} notional {} reference {}",
+        settlement.store,
+        settlement.amount,
+        settlement.notional,
+        reference.key
+    );
+    log_tagged_trade(&settlement, OrderSide::Buy, tag);
+    Ok(())
+}
+
+/// Buys from each store with its own accounts, so optional accounts of a leg can't be taken by another
+pub(super) fn process_batch_buy(
+    accounts: &[AccountInfo],
+    legs: &[BatchLeg],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    if legs.is_empty() || legs.len() > MAX_BATCH_LEGS {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (buyer, mut rest) = accounts
+        .split_first()
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+    if !buyer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let (mut amount, mut notional) = (0u64, 0u64);
+    for leg in legs {
+        if rest.len() < leg.accounts as usize {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let (leg_accounts, next) = rest.split_at(leg.accounts as usize);
+        let settlement = buy(
+            &mut leg_accounts.iter(),
+            buyer,
+            &[],
+            leg.amount,
+            leg.max_price,
+            TradeMode::Execute,
+            program_id,
+        )?;
+        amount = amount
+            .checked_add(settlement.amount)
+            .ok_or(StoreError::MathOverflow)?;
+        notional = notional
+            .checked_add(settlement.notional)
+            .ok_or(StoreError::MathOverflow)?;
+        rest = next;
+    }
+    msg!(
+        "BatchBuy: buyer {} legs {} amount {} notional {}",
+        buyer.key,
+        legs.len(),
+        amount,
+        notional
+    );
+    Ok(())
+}
+
+fn signed_buy<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    account_info_iter: &mut I,
+    amount: u64,
+    max_price: u64,
+    program_id: &Pubkey,
+) -> Result<Settlement, ProgramError> {
+    accounts!(account_info_iter, program_id, {
+        buyer: signer,
+    });
+    buy(
+        account_info_iter,
+        buyer.account,
+        &[],
+        amount,
+        max_price,
+        TradeMode::Execute,
+        program_id,
+    )
+}
+
+pub(super) fn process_sell(
+    accounts: &[AccountInfo],
+    amount: u64,
+    min_price: u64,
+    tag: Option<u16>,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        seller: signer,
+    });
+    let settlement = sell(
+        account_info_iter,
+        seller.account,
+        &[],
+        amount,
+        min_price,
+        TradeMode::Execute,
+        program_id,
+    )?;
+    log_tagged_trade(&settlement, OrderSide::Sell, tag);
+
+    Ok(())
+}
+
+pub(super) fn process_preview_trade(
+    accounts: &[AccountInfo],
+    side: OrderSide,
+    amount: u64,
+    price: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        user: signer,
+    });
+    let settlement = match side {
+        OrderSide::Buy => buy(
+            account_info_iter,
+            user.account,
+            &[],
+            amount,
+            price,
+            TradeMode::Preview,
+            program_id,
+        )?,
+        OrderSide::Sell => sell(
+            account_info_iter,
+            user.account,
+            &[],
+            amount,
+            price,
+            TradeMode::Preview,
+            program_id,
+        )?,
+    };
+    let (user_pays, user_receives) = match side {
+        OrderSide::Buy => (
+            settlement
+                .notional
+                .checked_add(settlement.fee)
+                .ok_or(StoreError::MathOverflow)?,
+            settlement.amount,
+        ),
+        OrderSide::Sell => (settlement.amount, settlement.notional - settlement.fee),
+    };
+
+    let preview = TradePreview {
+        user_pays,
+        user_receives,
+        cosigner_required: settlement.cosigner_required,
+    };
+    let mut data = vec![0; TradePreview::LEN];
+    preview.pack_into_slice(&mut data);
+    set_return_data(&data);
+    Ok(())
+}
+
+/// Executes buy with the rest of Buy accounts, starting from the store account.
+/// User tokens are moved by `user_authority`, signed with `user_authority_seeds` if it's a PDA.
+pub(super) fn buy<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    account_info_iter: &mut I,
+    user_authority: &'a AccountInfo<'b>,
+    user_authority_seeds: &[&[u8]],
+    amount: u64,
+    max_price: u64,
+    mode: TradeMode,
+    program_id: &Pubkey,
+) -> Result<Settlement, ProgramError> {
+    buy_quoted(
+        account_info_iter,
+        user_authority,
+        user_authority_seeds,
+        amount,
+        BuyQuote::MaxPrice(max_price),
+        mode,
+        program_id,
+    )
+}
+
+/// Same as `buy` on given price terms, all store checks, limits and fees apply as to any buy
+pub(super) fn buy_quoted<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    account_info_iter: &mut I,
+    user_authority: &'a AccountInfo<'b>,
+    user_authority_seeds: &[&[u8]],
+    amount: u64,
+    quote: BuyQuote,
+    mode: TradeMode,
+    program_id: &Pubkey,
+) -> Result<Settlement, ProgramError> {
+    accounts!(account_info_iter, program_id, {
+        mut store: store,
+        store_payment_tokens: vault,
+        store_store_tokens: vault(&store.info.store_tokens_to_auto_buy_pubkey),
+        user_account_payment_tokens: vault,
+        user_account_store_tokens: vault,
+        pda_account: any,
+        token_program: program(&spl_token::id()),
+    });
+    check_trade(&store, OrderSide::Buy, amount)?;
+    apply_fee_schedule(&mut store)?;
+    store.info.take_inventory(amount)?;
+    store.check_mints(
+        &[&store_payment_tokens, &user_account_payment_tokens],
+        &[&store_store_tokens, &user_account_store_tokens],
+    )?;
+    // constant product stores check the reserve vaults whatever the price terms
+    let pricing =
+        PricingEngine::of_reserves(&store.info, &store_payment_tokens, &store_store_tokens)?;
+    let full_notional = match quote {
+        BuyQuote::MaxPrice(max_price) => pricing.quote_buy(amount, max_price)?,
+        BuyQuote::Notional(notional) => notional,
+    };
+    let cosigner_required = check_cosigner(&store, account_info_iter, full_notional, mode)?;
+    check_membership(
+        &store,
+        account_info_iter,
+        &user_account_store_tokens.info.owner,
+        mode,
+    )?;
+    let fee_vault = next_fee_vault(&store, account_info_iter, mode)?;
+    let escrow_accounts = match mode {
+        TradeMode::Execute if store.info.escrow_pubkey != Pubkey::default() => Some((
+            next_account_info(account_info_iter)?,
+            next_account_info(account_info_iter)?,
+            next_program_account(account_info_iter, &system_program::id())?,
+        )),
+        _ => None,
+    };
+    let stats_account = next_stats_account(&store, account_info_iter, mode, program_id)?;
+    let ledger_accounts = match mode {
+        TradeMode::Execute if store.info.per_wallet_cap != 0 => Some((
+            next_account_info(account_info_iter)?,
+            next_account_info(account_info_iter)?,
+            next_program_account(account_info_iter, &system_program::id())?,
+        )),
+        _ => None,
+    };
+    let discount_bps = match mode {
+        TradeMode::Execute => next_discount_bps(
+            &store,
+            account_info_iter,
+            &user_account_store_tokens.info.owner,
+            program_id,
+        )?,
+        TradeMode::Preview => 0,
+    };
+    let notional = discounted(full_notional, discount_bps);
+    let fee = store.info.fee(notional);
+    // constant product stores settle to the reserve vaults checked when quoting
+    if store.info.escrow_pubkey == Pubkey::default() {
+        if store.info.pricing_engine != PRICING_CONSTANT_PRODUCT {
+            check_settlement_account(&store, &store_payment_tokens, &store.info.proceeds_pubkey)?;
+        }
+    } else if *store_payment_tokens.key() != store.info.escrow_pubkey {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    cu_checkpoint!("buy: validation");
+
+    let settlement = Settlement {
+        store: *store.key(),
+        amount,
+        notional,
+        fee,
+        cosigner_required,
+        recipient: user_account_store_tokens.info.owner,
+    };
+    if mode == TradeMode::Preview {
+        if store_store_tokens.info.amount < amount {
+            return Err(ProgramError::InsufficientFunds);
+        }
+        return Ok(settlement);
+    }
+
+    let authority = StoreAuthority::of(&store, program_id)?;
+
+    msg!("Calling the token program to transfer tokens to the store's owner...");
+    transfer(
+        token_program,
+        user_account_payment_tokens.account,
+        store_payment_tokens.account,
+        user_authority,
+        user_authority_seeds,
+        notional,
+    )?;
+    if let Some(fee_vault) = &fee_vault {
+        msg!("Calling the token program to transfer the fee...");
+        transfer(
+            token_program,
+            user_account_payment_tokens.account,
+            fee_vault.account,
+            user_authority,
+            user_authority_seeds,
+            fee,
+        )?;
+    }
+    msg!("Calling the token program to transfer tokens to the user...");
+    transfer_signed(
+        token_program,
+        store_store_tokens.account,
+        user_account_store_tokens.account,
+        pda_account,
+        &authority,
+        amount,
+    )?;
+
+    cu_checkpoint!("buy: transfers");
+    log_fill(
+        &settlement,
+        OrderSide::Buy,
+        &store_payment_tokens,
+        &store_store_tokens,
+        fee_vault.as_ref(),
+    );
+    store.info.record_sold(amount, notional)?;
+    store.info.record_fee(fee)?;
+    store
+        .info
+        .record_trade(OrderSide::Buy, notional, Clock::get()?.slot)?;
+    if let Some(stats_account) = stats_account {
+        record_trade_stats(stats_account, notional, fee)?;
+    }
+    if let Some((escrow_account, payer, system_program)) = escrow_accounts {
+        open_escrow(
+            &mut store,
+            escrow_account,
+            payer,
+            system_program,
+            user_account_payment_tokens.key(),
+            &user_account_store_tokens,
+            amount,
+            notional,
+            fee,
+            program_id,
+        )?;
+    }
+    if let Some((ledger_account, payer, system_program)) = ledger_accounts {
+        record_purchase(
+            &store,
+            ledger_account,
+            payer,
+            system_program,
+            &user_account_store_tokens.info.owner,
+            amount,
+            program_id,
+        )?;
+    }
+    store.save()?;
+    cu_checkpoint!("buy: state");
+
+    check_inventory_threshold(
+        &store,
+        &store_store_tokens,
+        amount,
+        store.info.store_tokens_warning_threshold,
+    );
+
+    Ok(settlement)
+}
+
+/// Executes sell with the rest of Sell accounts, starting from the store account.
+/// User tokens are moved by `user_authority`, signed with `user_authority_seeds` if it's a PDA.
+pub(super) fn sell<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    account_info_iter: &mut I,
+    user_authority: &'a AccountInfo<'b>,
+    user_authority_seeds: &[&[u8]],
+    amount: u64,
+    min_price: u64,
+    mode: TradeMode,
+    program_id: &Pubkey,
+) -> Result<Settlement, ProgramError> {
+    accounts!(account_info_iter, program_id, {
+        mut store: store,
+        store_payment_tokens: vault(&store.info.native_tokens_to_auto_sell_pubkey),
+        store_store_tokens: vault,
+        user_account_payment_tokens: vault,
+        user_account_store_tokens: vault,
+        pda_account: any,
+        token_program: program(&spl_token::id()),
+    });
+    check_trade(&store, OrderSide::Sell, amount)?;
+    apply_fee_schedule(&mut store)?;
+    store.check_mints(
+        &[&store_payment_tokens, &user_account_payment_tokens],
+        &[&store_store_tokens, &user_account_store_tokens],
+    )?;
+    let notional =
+        PricingEngine::of_reserves(&store.info, &store_payment_tokens, &store_store_tokens)?
+            .quote_sell(amount, min_price)?;
+    let cosigner_required = check_cosigner(&store, account_info_iter, notional, mode)?;
+    check_membership(
+        &store,
+        account_info_iter,
+        &user_account_payment_tokens.info.owner,
+        mode,
+    )?;
+    let fee_vault = next_fee_vault(&store, account_info_iter, mode)?;
+    let stats_account = next_stats_account(&store, account_info_iter, mode, program_id)?;
+    let fee = store.info.fee(notional);
+    // constant product stores settle to the reserve vaults checked when quoting
+    if store.info.pricing_engine != PRICING_CONSTANT_PRODUCT {
+        check_settlement_account(&store, &store_store_tokens, &store.info.payout_pubkey)?;
+    }
+    cu_checkpoint!("sell: validation");
+
+    let settlement = Settlement {
+        store: *store.key(),
+        amount,
+        notional,
+        fee,
+        cosigner_required,
+        recipient: user_account_payment_tokens.info.owner,
+    };
+    if mode == TradeMode::Preview {
+        if store_payment_tokens.info.amount < notional {
+            return Err(ProgramError::InsufficientFunds);
+        }
+        return Ok(settlement);
+    }
+
+    let authority = StoreAuthority::of(&store, program_id)?;
+
+    msg!("Calling the token program to transfer tokens to the store owner...");
+    transfer(
+        token_program,
+        user_account_store_tokens.account,
+        store_store_tokens.account,
+        user_authority,
+        user_authority_seeds,
+        amount,
+    )?;
+    msg!("Calling the token program to transfer tokens to the user...");
+    transfer_signed(
+        token_program,
+        store_payment_tokens.account,
+        user_account_payment_tokens.account,
+        pda_account,
+        &authority,
+        notional - fee,
+    )?;
+    if let Some(fee_vault) = &fee_vault {
+        msg!("Calling the token program to transfer the fee...");
+        transfer_signed(
+            token_program,
+            store_payment_tokens.account,
+            fee_vault.account,
+            pda_account,
+            &authority,
+            fee,
+        )?;
+    }
+
+    cu_checkpoint!("sell: transfers");
+    log_fill(
+        &settlement,
+        OrderSide::Sell,
+        &store_payment_tokens,
+        &store_store_tokens,
+        fee_vault.as_ref(),
+    );
+    store.info.record_bought(amount, notional)?;
+    store.info.record_fee(fee)?;
+    store
+        .info
+        .record_trade(OrderSide::Sell, notional, Clock::get()?.slot)?;
+    if let Some(stats_account) = stats_account {
+        record_trade_stats(stats_account, notional, fee)?;
+    }
+    store.save()?;
+    cu_checkpoint!("sell: state");
+
+    check_inventory_threshold(
+        &store,
+        &store_payment_tokens,
+        notional,
+        store.info.payment_tokens_warning_threshold,
+    );
+
+    Ok(settlement)
+}
+
+/// Checks the store accepts trades on `side` with given amount, the price is checked by the pricing engine
+fn check_trade(store: &StoreAccount, side: OrderSide, amount: u64) -> ProgramResult {
+    if store.info.is_closed {
+        return Err(StoreError::StoreClosed.into());
+    }
+    if store.info.is_paused {
+        return Err(StoreError::StorePaused.into());
+    }
+    if store.info.is_migrated() {
+        return Err(StoreError::StoreMigrated.into());
+    }
+    if amount == 0 && store.info.has_feature(FEATURE_STRICT_DECODING) {
+        return Err(StoreError::ZeroAmount.into());
+    }
+    if !store.info.is_valid_amount(amount) {
+        return Err(StoreError::InvalidLotAmount.into());
+    }
+    let now = Clock::get()?.unix_timestamp;
+    if store.info.is_winding_down() {
+        // holders may only exit until the deadline
+        if side == OrderSide::Buy {
+            return Err(StoreError::StoreWindingDown.into());
+        }
+        if now > store.info.wind_down_deadline {
+            return Err(StoreError::WindDownEnded.into());
+        }
+    }
+    if !store.info.is_trading_hour(now) {
+        return Err(StoreError::OutsideTradingHours.into());
+    }
+    check_settled(store)?;
+    if !store.info.is_open_at_slot(Clock::get()?.slot) {
+        return Err(StoreError::MarketClosed.into());
+    }
+    Ok(())
+}
+
+/// Checks the next account is a signing cosigner if the trade notional requires it,
+/// in preview mode only reports whether it's required
+fn check_cosigner<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    store: &StoreAccount,
+    iter: &mut I,
+    notional: u64,
+    mode: TradeMode,
+) -> Result<bool, ProgramError> {
+    if store.info.cosign_threshold == 0 || notional <= store.info.cosign_threshold {
+        return Ok(false);
+    }
+    if mode == TradeMode::Preview {
+        return Ok(true);
+    }
+    let cosigner = iter.next().ok_or(StoreError::CosignerRequired)?;
+    if !cosigner.is_signer || *cosigner.key != store.info.cosigner_pubkey {
+        return Err(StoreError::CosignerRequired.into());
+    }
+    Ok(true)
+}
+
+/// Checks the next account is a token account of the gate mint with at least one token,
+/// owned by the `recipient` of store tokens. Not checked in preview mode.
+fn check_membership<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    store: &StoreAccount,
+    iter: &mut I,
+    recipient: &Pubkey,
+    mode: TradeMode,
+) -> ProgramResult {
+    if store.info.gate_mint_pubkey == Pubkey::default() || mode == TradeMode::Preview {
+        return Ok(());
+    }
+    let membership = iter.next().ok_or(StoreError::MembershipRequired)?;
+    if *membership.owner != spl_token::id() {
+        return Err(StoreError::MembershipRequired.into());
+    }
+    let account = spl_token::state::Account::unpack(&membership.data.borrow())
+        .map_err(|_| StoreError::MembershipRequired)?;
+    if account.mint != store.info.gate_mint_pubkey
+        || account.owner != *recipient
+        || account.amount < 1
+    {
+        return Err(StoreError::MembershipRequired.into());
+    }
+    Ok(())
+}
+
+/// Checks account receiving tokens from the user is the configured one,
+/// or any account of the store owner if it's not configured
+pub(super) fn check_settlement_account(
+    store: &StoreAccount,
+    account: &VaultAccount,
+    expected: &Pubkey,
+) -> ProgramResult {
+    if *expected == Pubkey::default() {
+        if account.info.owner != store.info.owner_pubkey {
+            return Err(ProgramError::InvalidAccountData);
+        }
+    } else if account.key() != expected {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+/// Logs `InventoryLow` event when the trade moved account balance below the threshold
+fn check_inventory_threshold(
+    store: &StoreAccount,
+    vault: &VaultAccount,
+    spent: u64,
+    threshold: u64,
+) {
+    let before = vault.info.amount;
+    let remaining = before.saturating_sub(spent);
+    if threshold != 0 && remaining < threshold && before >= threshold {
+        msg!(
+            "InventoryLow: store {} account {} amount {} threshold {}",
+            store.key(),
+            vault.key(),
+            remaining,
+            threshold
+        );
+    }
+}
+
+/// Logs `Fill` event of an executed trade with the store accounts it moved tokens of, so indexers
+/// can book each fill: on buy the payment vault receives the notional and the store vault sends
+/// the amount, on sell the other way around. The fee vault is the default pubkey without a fee.
+fn log_fill(
+    settlement: &Settlement,
+    side: OrderSide,
+    payment_vault: &VaultAccount,
+    store_vault: &VaultAccount,
+    fee_vault: Option<&VaultAccount>,
+) {
+    msg!(
+        "Fill: store {} side {} amount {} notional {} fee {} payment_vault {} store_vault {} fee_vault {} user {}",
+        settlement.store,
+        side_name(side),
+        settlement.amount,
+        settlement.notional,
+        settlement.fee,
+        payment_vault.key(),
+        store_vault.key(),
+        fee_vault.map_or(Pubkey::default(), |vault| *vault.key()),
+        settlement.recipient
+    );
+}
+
+fn side_name(side: OrderSide) -> &'static str {
+    match side {
+        OrderSide::Buy => "buy",
+        OrderSide::Sell => "sell",
+    }
+}
+
+/// Logs `TaggedTrade` event for trades carrying a client tag, for attribution by indexers
+fn log_tagged_trade(settlement: &Settlement, side: OrderSide, tag: Option<u16>) {
+    if let Some(tag) = tag {
+        msg!(
+            "TaggedTrade: store {} side {} amount {} notional {} tag {}",
+            settlement.store,
+            side_name(side),
+            settlement.amount,
+            settlement.notional,
+            tag
+        );
+    }
+}
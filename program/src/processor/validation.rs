@@ -0,0 +1,216 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+};
+
+use crate::{error::StoreError, state::Store};
+
+/// Declares instruction accounts in order together with their constraints,
+/// extracting and validating each one from the accounts iterator:
+///
+/// ```ignore
+/// accounts!(account_info_iter, program_id, {
+///     owner: signer,
+///     mut store: store_owned(owner),
+///     vault: vault(&store.info.store_tokens_to_auto_buy_pubkey),
+///     token_program: program(&spl_token::id()),
+///     other: any,
+/// });
+/// ```
+///
+/// - `signer` - [`SignerAccount`]
+/// - `store` - [`StoreAccount`] owned by the program
+/// - `store_owned(owner)` - [`StoreAccount`] owned by the program and `owner` signer
+/// - `vault` - [`VaultAccount`], `vault(key)` additionally checks the account key
+/// - `program(id)` - account with given key
+/// - `any` - account without constraints
+macro_rules! accounts {
+    ($iter:ident, $program_id:expr, { $($body:tt)* }) => {
+        accounts!(@munch $iter, $program_id; $($body)*);
+    };
+
+    (@munch $iter:ident, $program_id:expr;) => {};
+    (@munch $iter:ident, $program_id:expr;
+        mut $name:ident : $kind:ident $(( $($arg:expr),* ))? $(, $($rest:tt)*)?
+    ) => {
+        let mut $name = accounts!(@next $iter, $program_id, $kind $(, $($arg),*)?);
+        accounts!(@munch $iter, $program_id; $($($rest)*)?);
+    };
+    (@munch $iter:ident, $program_id:expr;
+        $name:ident : $kind:ident $(( $($arg:expr),* ))? $(, $($rest:tt)*)?
+    ) => {
+        let $name = accounts!(@next $iter, $program_id, $kind $(, $($arg),*)?);
+        accounts!(@munch $iter, $program_id; $($($rest)*)?);
+    };
+
+    (@next $iter:ident, $program_id:expr, signer) => {
+        $crate::processor::validation::SignerAccount::next($iter)?
+    };
+    (@next $iter:ident, $program_id:expr, store) => {
+        $crate::processor::validation::StoreAccount::next($iter, $program_id)?
+    };
+    (@next $iter:ident, $program_id:expr, store_owned, $owner:expr) => {
+        $crate::processor::validation::StoreAccount::next_owned($iter, &$owner, $program_id)?
+    };
+    (@next $iter:ident, $program_id:expr, vault) => {
+        $crate::processor::validation::VaultAccount::next($iter)?
+    };
+    (@next $iter:ident, $program_id:expr, vault, $expected:expr) => {
+        $crate::processor::validation::VaultAccount::next_expected($iter, $expected)?
+    };
+    (@next $iter:ident, $program_id:expr, program, $id:expr) => {
+        $crate::processor::validation::next_program_account($iter, $id)?
+    };
+    (@next $iter:ident, $program_id:expr, any) => {
+        ::solana_program::account_info::next_account_info($iter)?
+    };
+}
+
+/// Next account which key must be equal to `id`
+pub fn next_program_account<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    iter: &mut I,
+    id: &Pubkey,
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    let account = next_account_info(iter)?;
+    if account.key != id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(account)
+}
+
+/// Account which must sign the transaction
+pub struct SignerAccount<'a, 'b> {
+    pub account: &'a AccountInfo<'b>,
+}
+
+impl<'a, 'b> SignerAccount<'a, 'b> {
+    pub fn next<I: Iterator<Item = &'a AccountInfo<'b>>>(
+        iter: &mut I,
+    ) -> Result<Self, ProgramError> {
+        let account = next_account_info(iter)?;
+        if !account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(Self { account })
+    }
+
+    pub fn key(&self) -> &Pubkey {
+        self.account.key
+    }
+}
+
+/// Initialized store account owned by the program, unpacked once per instruction
+pub struct StoreAccount<'a, 'b> {
+    pub account: &'a AccountInfo<'b>,
+    pub info: Store,
+}
+
+impl<'a, 'b> StoreAccount<'a, 'b> {
+    /// Checks program ownership and unpacks initialized store
+    pub fn load(account: &'a AccountInfo<'b>, program_id: &Pubkey) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let info = Store::unpack_unchecked(&account.data.borrow())?;
+        if !info.is_initialized() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        Ok(Self { account, info })
+    }
+
+    pub fn next<I: Iterator<Item = &'a AccountInfo<'b>>>(
+        iter: &mut I,
+        program_id: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        Self::load(next_account_info(iter)?, program_id)
+    }
+
+    /// Same as `next`, additionally checks that `owner` owns the store
+    pub fn next_owned<I: Iterator<Item = &'a AccountInfo<'b>>>(
+        iter: &mut I,
+        owner: &SignerAccount,
+        program_id: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        let store = Self::next(iter, program_id)?;
+        if store.info.owner_pubkey != *owner.key() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(store)
+    }
+
+    pub fn key(&self) -> &Pubkey {
+        self.account.key
+    }
+
+    /// Fails unless the owner enabled `feature` for the store
+    pub fn check_feature(&self, feature: u32) -> ProgramResult {
+        if !self.info.has_feature(feature) {
+            return Err(StoreError::FeatureDisabled.into());
+        }
+        Ok(())
+    }
+
+    /// Fails unless token accounts are of the store mints. Stores initialized before
+    /// the mints were recorded have none and skip the check.
+    pub fn check_mints(
+        &self,
+        payment_tokens: &[&VaultAccount],
+        store_tokens: &[&VaultAccount],
+    ) -> ProgramResult {
+        let mismatch = |accounts: &[&VaultAccount], mint: &Pubkey| {
+            *mint != Pubkey::default() && accounts.iter().any(|account| account.info.mint != *mint)
+        };
+        if mismatch(payment_tokens, &self.info.payment_token_mint)
+            || mismatch(store_tokens, &self.info.store_token_mint)
+        {
+            return Err(StoreError::MintMismatch.into());
+        }
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<(), ProgramError> {
+        Store::pack(self.info, &mut self.account.data.borrow_mut())
+    }
+}
+
+/// SPL token account, unpacked once per instruction
+pub struct VaultAccount<'a, 'b> {
+    pub account: &'a AccountInfo<'b>,
+    pub info: spl_token::state::Account,
+}
+
+impl<'a, 'b> VaultAccount<'a, 'b> {
+    /// Checks token program ownership and unpacks token account
+    pub fn load(account: &'a AccountInfo<'b>) -> Result<Self, ProgramError> {
+        if *account.owner != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let info = spl_token::state::Account::unpack_unchecked(&account.data.borrow())?;
+        Ok(Self { account, info })
+    }
+
+    pub fn next<I: Iterator<Item = &'a AccountInfo<'b>>>(
+        iter: &mut I,
+    ) -> Result<Self, ProgramError> {
+        Self::load(next_account_info(iter)?)
+    }
+
+    /// Same as `next`, additionally checks account is the expected one
+    pub fn next_expected<I: Iterator<Item = &'a AccountInfo<'b>>>(
+        iter: &mut I,
+        expected: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        let vault = Self::next(iter)?;
+        if vault.key() != expected {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(vault)
+    }
+
+    pub fn key(&self) -> &Pubkey {
+        self.account.key
+    }
+}
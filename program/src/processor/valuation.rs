@@ -0,0 +1,116 @@
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, msg,
+    program_error::ProgramError, program_pack::Pack, pubkey::Pubkey, system_program,
+    sysvar::Sysvar,
+};
+
+use super::{create_pda_account, validation::StoreAccount};
+use crate::state::{PriceFeed, Valuation, VALUATION_PRICE_FEED, VALUATION_STORE_PRICE};
+
+/// Records mark-to-market value of the store inventory for the current epoch, callable by anyone.
+/// Marks at the attested price of the store price feed if it's passed, at the store buy price otherwise.
+pub(super) fn process_record_valuation(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        payer: signer,
+        store: store,
+        store_store_tokens: vault(&store.info.store_tokens_to_auto_buy_pubkey),
+        store_payment_tokens: vault(&store.info.native_tokens_to_auto_sell_pubkey),
+        valuation_account: any,
+        system_program: program(&system_program::id()),
+    });
+    let (price, source) = match account_info_iter.next() {
+        Some(feed_account) => (
+            attested_price(&store, feed_account, program_id)?,
+            VALUATION_PRICE_FEED,
+        ),
+        None => (store.info.buy_price, VALUATION_STORE_PRICE),
+    };
+
+    let clock = Clock::get()?;
+    let epoch = clock.epoch.to_le_bytes();
+    let (valuation_pda, valuation_nonce) =
+        Pubkey::find_program_address(&[b"valuation", store.key().as_ref(), &epoch], program_id);
+    if valuation_pda != *valuation_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    // one valuation per epoch, so that reports of the epoch agree
+    if valuation_account.lamports() != 0 {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    create_pda_account(
+        payer.account,
+        valuation_account,
+        system_program,
+        Valuation::LEN,
+        program_id,
+        &[
+            &b"valuation"[..],
+            store.key().as_ref(),
+            &epoch,
+            &[valuation_nonce],
+        ],
+    )?;
+
+    let store_tokens = store_store_tokens.info.amount;
+    let valuation = Valuation {
+        is_initialized: true,
+        store_pubkey: *store.key(),
+        epoch: clock.epoch,
+        slot: clock.slot,
+        price,
+        denominator: store.info.denominator(),
+        source,
+        store_tokens,
+        payment_tokens: store_payment_tokens.info.amount,
+        inventory_value: store.info.value_at(store_tokens, price)?,
+        unrealized_pnl: store.info.unrealized_pnl(price)?,
+    };
+    Valuation::pack(valuation, &mut valuation_account.data.borrow_mut())?;
+    msg!(
+        "InventoryValued: store {} epoch {} price {} store tokens {} value {} payment tokens {}",
+        store.key(),
+        valuation.epoch,
+        valuation.price,
+        valuation.store_tokens,
+        valuation.inventory_value,
+        valuation.payment_tokens
+    );
+    Ok(())
+}
+
+/// Last price of the store price feed
+fn attested_price(
+    store: &StoreAccount,
+    feed_account: &AccountInfo,
+    program_id: &Pubkey,
+) -> Result<u64, ProgramError> {
+    if feed_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let feed_info = PriceFeed::unpack(&feed_account.data.borrow())?;
+    if feed_info.store_pubkey != *store.key() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(feed_info.price)
+}
+
+/// Valuation of the store passed as the optional account of `ReportPnl`
+pub(super) fn load_valuation(
+    store: &StoreAccount,
+    valuation_account: &AccountInfo,
+    program_id: &Pubkey,
+) -> Result<Valuation, ProgramError> {
+    if valuation_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let valuation = Valuation::unpack(&valuation_account.data.borrow())?;
+    if valuation.store_pubkey != *store.key() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(valuation)
+}
@@ -0,0 +1,98 @@
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, msg,
+    program_error::ProgramError, program_pack::Pack, pubkey::Pubkey, system_program,
+    sysvar::Sysvar,
+};
+
+use super::{close_account, create_pda_account};
+use crate::{error::StoreError, state::WaitlistEntry};
+
+pub(super) fn process_join_waitlist(
+    accounts: &[AccountInfo],
+    amount: u64,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        buyer: signer,
+        payer: signer,
+        store: store,
+        store_store_tokens: vault(&store.info.store_tokens_to_auto_buy_pubkey),
+        waitlist_account: any,
+        system_program: program(&system_program::id()),
+    });
+    if store_store_tokens.info.amount >= amount {
+        return Err(StoreError::InventoryAvailable.into());
+    }
+
+    let (waitlist_pda, waitlist_nonce) = Pubkey::find_program_address(
+        &[b"waitlist", store.key().as_ref(), buyer.key().as_ref()],
+        program_id,
+    );
+    if waitlist_pda != *waitlist_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    if waitlist_account.lamports() == 0 {
+        create_pda_account(
+            payer.account,
+            waitlist_account,
+            system_program,
+            WaitlistEntry::LEN,
+            program_id,
+            &[
+                &b"waitlist"[..],
+                store.key().as_ref(),
+                buyer.key().as_ref(),
+                &[waitlist_nonce],
+            ],
+        )?;
+    }
+    if waitlist_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // joining again only updates the amount, the buyer keeps its place in the fill order
+    let slot = match WaitlistEntry::unpack(&waitlist_account.data.borrow()) {
+        Ok(waitlist_info) => waitlist_info.slot,
+        Err(_) => Clock::get()?.slot,
+    };
+    let waitlist_info = WaitlistEntry {
+        is_initialized: true,
+        store_pubkey: *store.key(),
+        buyer_pubkey: *buyer.key(),
+        amount,
+        slot,
+    };
+    WaitlistEntry::pack(waitlist_info, &mut waitlist_account.data.borrow_mut())?;
+    msg!(
+        "WaitlistJoined: store {} buyer {} amount {}",
+        store.key(),
+        buyer.key(),
+        amount
+    );
+
+    Ok(())
+}
+
+pub(super) fn process_leave_waitlist(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    accounts!(account_info_iter, program_id, {
+        buyer: signer,
+        waitlist_account: any,
+    });
+    if waitlist_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let waitlist_info = WaitlistEntry::unpack(&waitlist_account.data.borrow())?;
+    if waitlist_info.buyer_pubkey != *buyer.key() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    close_account(waitlist_account, buyer.account)
+}
@@ -1,23 +1,441 @@
+use std::convert::TryFrom;
+
 use solana_program::{
+    hash::hashv,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
 };
 
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use borsh::{BorshDeserialize, BorshSerialize};
 
-#[derive(Clone, Copy, Debug, Default, PartialEq)]
+use crate::error::StoreError;
+
+/// `Store::pricing_engine` of stores trading at owner set buy and sell prices
+pub const PRICING_FIXED: u8 = 0;
+/// `Store::pricing_engine` of stores selling at a price decaying over the `auction_*` slots
+pub const PRICING_DUTCH_AUCTION: u8 = 1;
+/// `Store::pricing_engine` of stores quoting on a constant product curve of their vault balances
+pub const PRICING_CONSTANT_PRODUCT: u8 = 2;
+
+/// `Store::features` bits, each enables configuring the flow for the store
+pub const FEATURE_ESCROW: u32 = 1 << 0;
+pub const FEATURE_MEMBERSHIP: u32 = 1 << 1;
+pub const FEATURE_DISCOUNTS: u32 = 1 << 2;
+pub const FEATURE_INVOICES: u32 = 1 << 3;
+pub const FEATURE_SESSIONS: u32 = 1 << 4;
+/// rejects instruction data with trailing or non canonical bytes and trades of zero amount
+pub const FEATURE_STRICT_DECODING: u32 = 1 << 5;
+
+/// Max `Store::fee_bps`, the whole notional
+pub const MAX_FEE_BPS: u16 = 10_000;
+
+/// Hours in `Store::trading_hours` week
+pub const WEEK_HOURS: i64 = 7 * 24;
+
+/// Seconds in a settlement day, `Store::settlement_cutoff` is within it
+pub const DAY_SECONDS: i64 = 24 * 60 * 60;
+
+/// Version of the store layout written by the program. Version 1 is the layout before
+/// the version header, it starts right at `is_initialized`, so its first byte is 1 for an initialized store.
+pub const STORE_VERSION: u8 = 2;
+
+/// Offset of `Store::namespace` in the store layout, for account discovery filters of a namespace.
+/// Counted back from the end, over the trading window, settlement, auction and curve fields.
+pub const STORE_NAMESPACE_OFFSET: usize =
+    Store::LEN - 32 - (8 + 8) - (1 + 4 + 8) - (8 * 4) - (8 * 2);
+
+/// Borsh encoding of a store of the current version is the same as its packed layout
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
 pub struct Store {
+    /// version of the layout the store was read from, 0 for a new account.
+    /// Stores are always written with `STORE_VERSION`
+    pub version: u8,
     pub is_initialized: bool,
 
-    /// amount native tokens per store token
-    pub price: u64,
+    /// amount native tokens per store token paid by users on buy
+    pub buy_price: u64,
     pub owner_pubkey: Pubkey,
 
     /// account to take tokens when sell
     pub native_tokens_to_auto_sell_pubkey: Pubkey,
     /// account to take tokens when buy
     pub store_tokens_to_auto_buy_pubkey: Pubkey,
+
+    /// trade amounts must be multiple of lot size (0 - any amount)
+    pub lot_size: u64,
+
+    /// store this one was migrated to (default - not migrated)
+    pub redirect_pubkey: Pubkey,
+
+    /// log warning when store tokens account drops below (0 - disabled)
+    pub store_tokens_warning_threshold: u64,
+    /// log warning when payment tokens account drops below (0 - disabled)
+    pub payment_tokens_warning_threshold: u64,
+
+    /// account to receive payment tokens on buy (default - any account of the store owner)
+    pub proceeds_pubkey: Pubkey,
+    /// account to receive store tokens on sell (default - any account of the store owner)
+    pub payout_pubkey: Pubkey,
+
+    /// additional signer required for trades above the threshold
+    pub cosigner_pubkey: Pubkey,
+    /// trade notional in payment tokens requiring cosigner (0 - disabled)
+    pub cosign_threshold: u64,
+
+    /// store tokens bought from users
+    pub bought_volume: u64,
+    /// store tokens sold to users
+    pub sold_volume: u64,
+    /// store tokens bought from users and not yet sold, at average cost
+    pub cost_basis_amount: u64,
+    /// payment tokens paid for `cost_basis_amount`
+    pub cost_basis: u64,
+    /// payment tokens earned selling tokens over their average cost
+    pub realized_pnl: i64,
+
+    /// only owners of this mint tokens may receive store trades (default - anyone)
+    pub gate_mint_pubkey: Pubkey,
+
+    /// account holding buy payments during the refund window (default - escrow disabled)
+    pub escrow_pubkey: Pubkey,
+    /// seconds buy payments stay in escrow
+    pub escrow_window: i64,
+    /// number of escrowed buys, seeds the next escrow account
+    pub escrow_sequence: u64,
+
+    /// owner proposed by the current one, until accepted (default - none)
+    pub pending_owner_pubkey: Pubkey,
+
+    /// accounts were released to the owner, trades are rejected
+    pub is_closed: bool,
+
+    /// amount payment tokens per store token paid to users on sell, not above buy price
+    pub sell_price: u64,
+
+    /// pricing engine trades are quoted by, `PRICING_*` (0 - fixed price)
+    pub pricing_engine: u8,
+
+    /// enabled opt-in features, `FEATURE_*` bits
+    pub features: u32,
+
+    /// nonce of the store PDA owning its token accounts, seeds are store and the store account key
+    pub authority_nonce: u8,
+
+    /// weekly UTC hours trades are accepted in, a bit per hour starting Monday 00:00, no bits set - always open
+    pub trading_hours: [u8; 21],
+
+    /// account allowed to pause the store besides the owner (default - none)
+    pub guardian_pubkey: Pubkey,
+    /// trades are rejected until the owner resumes the store
+    pub is_paused: bool,
+
+    /// mint of store tokens, read from the store token account on init
+    pub store_token_mint: Pubkey,
+    /// mint of payment tokens, read from the payment token account on init
+    pub payment_token_mint: Pubkey,
+
+    /// buy and sell prices are payment tokens per this many store tokens (0 - 1, stores created before it)
+    pub price_denominator: u64,
+
+    /// fee in basis points of trade notional, paid by users on top on buy and deducted on sell (0 - no fee)
+    pub fee_bps: u16,
+    /// payment tokens account owned by the store PDA collecting fees (default - not set)
+    pub fee_vault_pubkey: Pubkey,
+    /// payment tokens collected as fees
+    pub fees_collected: u64,
+
+    /// planned fee changes by effective time, applied by the first trade after it
+    pub fee_schedule: FeeSchedule,
+
+    /// unix timestamp sells are accepted until while the store winds down, buys are rejected (0 - trading normally)
+    pub wind_down_deadline: i64,
+
+    /// max store tokens sold to a user in one buy (0 - unlimited)
+    pub max_trade_amount: u64,
+    /// buys are limited by `remaining_inventory`
+    pub is_inventory_limited: bool,
+    /// store tokens left to sell while inventory is limited
+    pub remaining_inventory: u64,
+
+    /// seconds after the refund window an unreleased escrow is swept to the store, along with its rent (0 - disabled)
+    pub escheat_period: i64,
+
+    /// payment tokens paid by users buying from the store, fees excluded
+    pub total_buy_volume: u64,
+    /// payment tokens paid to users selling to the store, fees included
+    pub total_sell_volume: u64,
+    /// number of buys and sells
+    pub trade_count: u64,
+    /// slot of the last buy or sell (0 - none)
+    pub last_trade_slot: u64,
+
+    /// trades update the mint stats account of the payment mint, required by Buy and Sell
+    pub is_stats_tracked: bool,
+
+    /// store tokens each buyer may buy in total, counted in their purchase ledger (0 - no cap)
+    pub per_wallet_cap: u64,
+
+    /// partner namespace of a white-label store, namespaces its owner registry (default - none)
+    pub namespace: Pubkey,
+
+    /// slot trades open at (0 - open since init)
+    pub open_at_slot: u64,
+    /// slot trades close at, exclusive (0 - never close)
+    pub close_at_slot: u64,
+
+    /// trades and other store vault movements freeze at the daily cutoff until SettleDay records the day report
+    pub is_settling_daily: bool,
+    /// seconds after UTC midnight of the daily settlement cutoff
+    pub settlement_cutoff: u32,
+    /// last settled day, in days since the unix epoch, of its cutoff
+    pub settled_day: i64,
+
+    // pricing parameters of `pricing_engine` modes other than the fixed price
+    /// Dutch auction buy price per `denominator` store tokens until `auction_start_slot`
+    pub auction_start_price: u64,
+    /// buy price from `auction_end_slot`, the price decays linearly to it from the start price
+    pub auction_floor_price: u64,
+    /// slot the price starts decaying at
+    pub auction_start_slot: u64,
+    /// slot the price reaches the floor at
+    pub auction_end_slot: u64,
+    /// constant product payment reserve on top of the payment vault balance, sets the curve price
+    pub curve_virtual_payment_reserve: u64,
+    /// constant product store reserve on top of the store vault balance
+    pub curve_virtual_store_reserve: u64,
+}
+
+impl Store {
+    /// Maps fields a version 1 store may have been written without, read as zeros, from the
+    /// ones it had: stores from before separate sell prices sold and bought at the one price,
+    /// and prices were per store token
+    pub fn upgrade_from_v1(&mut self) {
+        if self.sell_price == 0 {
+            self.sell_price = self.buy_price;
+        }
+        if self.price_denominator == 0 {
+            self.price_denominator = 1;
+        }
+    }
+
+    pub fn is_valid_amount(&self, amount: u64) -> bool {
+        self.lot_size == 0 || amount.is_multiple_of(self.lot_size)
+    }
+
+    pub fn is_migrated(&self) -> bool {
+        self.redirect_pubkey != Pubkey::default()
+    }
+
+    pub fn is_winding_down(&self) -> bool {
+        self.wind_down_deadline != 0
+    }
+
+    /// Checks a buy of `amount` is within trade limits and the remaining inventory
+    pub fn check_inventory(&self, amount: u64) -> Result<(), ProgramError> {
+        if self.max_trade_amount != 0 && amount > self.max_trade_amount {
+            return Err(StoreError::TradeLimitExceeded.into());
+        }
+        if self.is_inventory_limited && amount > self.remaining_inventory {
+            return Err(StoreError::InventoryExhausted.into());
+        }
+        Ok(())
+    }
+
+    /// Checks a buy of `amount` is within trade limits, taking it from the remaining inventory
+    pub fn take_inventory(&mut self, amount: u64) -> Result<(), ProgramError> {
+        self.check_inventory(amount)?;
+        if self.is_inventory_limited {
+            self.remaining_inventory -= amount;
+        }
+        Ok(())
+    }
+
+    /// Puts store tokens of a refunded buy back to the inventory
+    pub fn restore_inventory(&mut self, amount: u64) -> Result<(), ProgramError> {
+        if self.is_inventory_limited {
+            self.remaining_inventory = self
+                .remaining_inventory
+                .checked_add(amount)
+                .ok_or(StoreError::MathOverflow)?;
+        }
+        Ok(())
+    }
+
+    /// Records store tokens bought from a user, adding them to the cost basis
+    pub fn record_bought(&mut self, amount: u64, notional: u64) -> Result<(), ProgramError> {
+        self.bought_volume = self
+            .bought_volume
+            .checked_add(amount)
+            .ok_or(StoreError::MathOverflow)?;
+        self.cost_basis_amount = self
+            .cost_basis_amount
+            .checked_add(amount)
+            .ok_or(StoreError::MathOverflow)?;
+        self.cost_basis = self
+            .cost_basis
+            .checked_add(notional)
+            .ok_or(StoreError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Records store tokens sold to a user, realizing PnL of the part covered by the cost basis.
+    /// Tokens above the cost basis (deposited by the owner) have unknown cost and are not counted.
+    pub fn record_sold(&mut self, amount: u64, notional: u64) -> Result<(), ProgramError> {
+        self.sold_volume = self
+            .sold_volume
+            .checked_add(amount)
+            .ok_or(StoreError::MathOverflow)?;
+
+        let matched = amount.min(self.cost_basis_amount);
+        if matched == 0 {
+            return Ok(());
+        }
+        let cost =
+            (self.cost_basis as u128 * matched as u128 / self.cost_basis_amount as u128) as u64;
+        let proceeds = notional as u128 * matched as u128 / amount as u128;
+        let pnl =
+            i64::try_from(proceeds as i128 - cost as i128).map_err(|_| StoreError::MathOverflow)?;
+        self.realized_pnl = self
+            .realized_pnl
+            .checked_add(pnl)
+            .ok_or(StoreError::MathOverflow)?;
+        self.cost_basis_amount -= matched;
+        self.cost_basis -= cost;
+        Ok(())
+    }
+
+    /// Records trade statistics of a user trade on `side` at `slot`
+    pub fn record_trade(
+        &mut self,
+        side: OrderSide,
+        notional: u64,
+        slot: u64,
+    ) -> Result<(), ProgramError> {
+        let volume = match side {
+            OrderSide::Buy => &mut self.total_buy_volume,
+            OrderSide::Sell => &mut self.total_sell_volume,
+        };
+        *volume = volume
+            .checked_add(notional)
+            .ok_or(StoreError::MathOverflow)?;
+        self.trade_count = self
+            .trade_count
+            .checked_add(1)
+            .ok_or(StoreError::MathOverflow)?;
+        self.last_trade_slot = slot;
+        Ok(())
+    }
+
+    pub fn has_feature(&self, feature: u32) -> bool {
+        self.features & feature == feature
+    }
+
+    /// Sets the trading window, the window closes after it opens unless it never closes
+    pub fn set_trading_window(
+        &mut self,
+        open_at_slot: u64,
+        close_at_slot: u64,
+    ) -> Result<(), ProgramError> {
+        if close_at_slot != 0 && close_at_slot <= open_at_slot {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.open_at_slot = open_at_slot;
+        self.close_at_slot = close_at_slot;
+        Ok(())
+    }
+
+    /// Whether `slot` is within the `open_at_slot`..`close_at_slot` trading window
+    pub fn is_open_at_slot(&self, slot: u64) -> bool {
+        slot >= self.open_at_slot && (self.close_at_slot == 0 || slot < self.close_at_slot)
+    }
+
+    /// Day of the last settlement cutoff passed at unix timestamp `now`, in days since the unix epoch
+    pub fn settlement_day(&self, now: i64) -> i64 {
+        (now - self.settlement_cutoff as i64).div_euclid(DAY_SECONDS)
+    }
+
+    /// Day waiting for its settlement at unix timestamp `now`, store vaults are frozen until it's settled
+    pub fn pending_settlement(&self, now: i64) -> Option<i64> {
+        let day = self.settlement_day(now);
+        if self.is_settling_daily && day > self.settled_day {
+            Some(day)
+        } else {
+            None
+        }
+    }
+
+    /// Whether `trading_hours` allow trades at unix timestamp `now`
+    pub fn is_trading_hour(&self, now: i64) -> bool {
+        if self.trading_hours == [0; 21] {
+            return true;
+        }
+        // the unix epoch is Thursday 00:00
+        let hour = (now.div_euclid(3600) + 3 * 24).rem_euclid(WEEK_HOURS) as usize;
+        self.trading_hours[hour / 8] & (1 << (hour % 8)) != 0
+    }
+
+    /// Store tokens amount buy and sell prices are for
+    pub fn denominator(&self) -> u64 {
+        self.price_denominator.max(1)
+    }
+
+    /// Price users trade at on `side`, per `denominator` store tokens
+    pub fn price(&self, side: OrderSide) -> u64 {
+        match side {
+            OrderSide::Buy => self.buy_price,
+            OrderSide::Sell => self.sell_price,
+        }
+    }
+
+    /// Sets the same price for both sides
+    pub fn set_price(&mut self, price: u64) {
+        self.buy_price = price;
+        self.sell_price = price;
+    }
+
+    /// Payment tokens `amount` store tokens are worth at `price`
+    pub fn value_at(&self, amount: u64, price: u64) -> Result<u64, ProgramError> {
+        let value = amount as u128 * price as u128 / self.denominator() as u128;
+        u64::try_from(value).map_err(|_| StoreError::MathOverflow.into())
+    }
+
+    /// Fee of a trade with `notional` payment tokens, rounded down
+    pub fn fee(&self, notional: u64) -> u64 {
+        (notional as u128 * self.fee_bps as u128 / MAX_FEE_BPS as u128) as u64
+    }
+
+    pub fn record_fee(&mut self, fee: u64) -> Result<(), ProgramError> {
+        self.fees_collected = self
+            .fees_collected
+            .checked_add(fee)
+            .ok_or(StoreError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Takes the fee of a refunded buy off the collected fees
+    pub fn refund_fee(&mut self, fee: u64) -> Result<(), ProgramError> {
+        self.fees_collected = self
+            .fees_collected
+            .checked_sub(fee)
+            .ok_or(StoreError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// PnL of the cost basis if sold at `price`
+    pub fn unrealized_pnl(&self, price: u64) -> Result<i64, ProgramError> {
+        let value = self.cost_basis_amount as i128 * price as i128 / self.denominator() as i128;
+        i64::try_from(value - self.cost_basis as i128).map_err(|_| StoreError::MathOverflow.into())
+    }
+}
+
+fn unpack_bool(src: &[u8; 1]) -> Result<bool, ProgramError> {
+    match src {
+        [0] => Ok(false),
+        [1] => Ok(true),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
 }
 
 impl Sealed for Store {}
@@ -29,11 +447,144 @@ impl IsInitialized for Store {
 }
 
 impl Pack for Store {
-    const LEN: usize = 1 + 8 + 32 + 32 + 32;
+    const LEN: usize = 1
+        + 1
+        + 8
+        + 32
+        + 32
+        + 32
+        + 8
+        + 32
+        + 8
+        + 8
+        + 32
+        + 32
+        + 32
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 32
+        + 32
+        + 8
+        + 8
+        + 32
+        + 1
+        + 8
+        + 1
+        + 4
+        + 1
+        + 21
+        + 32
+        + 1
+        + 32
+        + 32
+        + 8
+        + 2
+        + 32
+        + 8
+        + 40
+        + 8
+        + 8
+        + 1
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 1
+        + 8
+        + 32
+        + 8
+        + 8
+        + 1
+        + 4
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
         let src = array_ref![src, 0, Store::LEN];
-        let (is_initialized, price, initializer_pubkey, native_tokens_pubkey, store_tokens_pubkey) =
-            array_refs![src, 1, 8, 32, 32, 32];
+        let (version, src) = match src[0] {
+            // a new account, or the current layout
+            0 | STORE_VERSION => (src[0], array_ref![src, 1, Store::LEN - 1]),
+            // version 1 layout padded to the current length, as read by MigrateStore
+            1 => (1, array_ref![src, 0, Store::LEN - 1]),
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let (
+            is_initialized,
+            buy_price,
+            initializer_pubkey,
+            native_tokens_pubkey,
+            store_tokens_pubkey,
+            lot_size,
+            redirect_pubkey,
+            store_tokens_warning_threshold,
+            payment_tokens_warning_threshold,
+            proceeds_pubkey,
+            payout_pubkey,
+            cosigner_pubkey,
+            cosign_threshold,
+            bought_volume,
+            sold_volume,
+            cost_basis_amount,
+            cost_basis,
+            realized_pnl,
+            gate_mint_pubkey,
+            escrow_pubkey,
+            escrow_window,
+            escrow_sequence,
+            pending_owner_pubkey,
+            is_closed,
+            sell_price,
+            pricing_engine,
+            features,
+            authority_nonce,
+            trading_hours,
+            guardian_pubkey,
+            is_paused,
+            store_token_mint,
+            payment_token_mint,
+            price_denominator,
+            fee_bps,
+            fee_vault_pubkey,
+            fees_collected,
+            fee_schedule,
+            wind_down_deadline,
+            max_trade_amount,
+            is_inventory_limited,
+            remaining_inventory,
+            escheat_period,
+            total_buy_volume,
+            total_sell_volume,
+            trade_count,
+            last_trade_slot,
+            is_stats_tracked,
+            per_wallet_cap,
+            namespace,
+            open_at_slot,
+            close_at_slot,
+            is_settling_daily,
+            settlement_cutoff,
+            settled_day,
+            auction_start_price,
+            auction_floor_price,
+            auction_start_slot,
+            auction_end_slot,
+            curve_virtual_payment_reserve,
+            curve_virtual_store_reserve,
+        ) = array_refs![
+            src, 1, 8, 32, 32, 32, 8, 32, 8, 8, 32, 32, 32, 8, 8, 8, 8, 8, 8, 32, 32, 8, 8, 32, 1,
+            8, 1, 4, 1, 21, 32, 1, 32, 32, 8, 2, 32, 8, 40, 8, 8, 1, 8, 8, 8, 8, 8, 8, 1, 8, 32, 8,
+            8, 1, 4, 8, 8, 8, 8, 8, 8, 8
+        ];
         let is_initialized = match is_initialized {
             [0] => false,
             [1] => true,
@@ -41,36 +592,2114 @@ impl Pack for Store {
         };
 
         Ok(Store {
+            version,
             is_initialized,
-            price: u64::from_le_bytes(*price),
+            buy_price: u64::from_le_bytes(*buy_price),
             owner_pubkey: Pubkey::new_from_array(*initializer_pubkey),
             native_tokens_to_auto_sell_pubkey: Pubkey::new_from_array(*native_tokens_pubkey),
             store_tokens_to_auto_buy_pubkey: Pubkey::new_from_array(*store_tokens_pubkey),
+            lot_size: u64::from_le_bytes(*lot_size),
+            redirect_pubkey: Pubkey::new_from_array(*redirect_pubkey),
+            store_tokens_warning_threshold: u64::from_le_bytes(*store_tokens_warning_threshold),
+            payment_tokens_warning_threshold: u64::from_le_bytes(*payment_tokens_warning_threshold),
+            proceeds_pubkey: Pubkey::new_from_array(*proceeds_pubkey),
+            payout_pubkey: Pubkey::new_from_array(*payout_pubkey),
+            cosigner_pubkey: Pubkey::new_from_array(*cosigner_pubkey),
+            cosign_threshold: u64::from_le_bytes(*cosign_threshold),
+            bought_volume: u64::from_le_bytes(*bought_volume),
+            sold_volume: u64::from_le_bytes(*sold_volume),
+            cost_basis_amount: u64::from_le_bytes(*cost_basis_amount),
+            cost_basis: u64::from_le_bytes(*cost_basis),
+            realized_pnl: i64::from_le_bytes(*realized_pnl),
+            gate_mint_pubkey: Pubkey::new_from_array(*gate_mint_pubkey),
+            escrow_pubkey: Pubkey::new_from_array(*escrow_pubkey),
+            escrow_window: i64::from_le_bytes(*escrow_window),
+            escrow_sequence: u64::from_le_bytes(*escrow_sequence),
+            pending_owner_pubkey: Pubkey::new_from_array(*pending_owner_pubkey),
+            is_closed: unpack_bool(is_closed)?,
+            sell_price: u64::from_le_bytes(*sell_price),
+            pricing_engine: pricing_engine[0],
+            features: u32::from_le_bytes(*features),
+            authority_nonce: authority_nonce[0],
+            trading_hours: *trading_hours,
+            guardian_pubkey: Pubkey::new_from_array(*guardian_pubkey),
+            is_paused: unpack_bool(is_paused)?,
+            store_token_mint: Pubkey::new_from_array(*store_token_mint),
+            payment_token_mint: Pubkey::new_from_array(*payment_token_mint),
+            price_denominator: u64::from_le_bytes(*price_denominator),
+            fee_bps: u16::from_le_bytes(*fee_bps),
+            fee_vault_pubkey: Pubkey::new_from_array(*fee_vault_pubkey),
+            fees_collected: u64::from_le_bytes(*fees_collected),
+            fee_schedule: FeeSchedule::from_le_bytes(*fee_schedule),
+            wind_down_deadline: i64::from_le_bytes(*wind_down_deadline),
+            max_trade_amount: u64::from_le_bytes(*max_trade_amount),
+            is_inventory_limited: unpack_bool(is_inventory_limited)?,
+            remaining_inventory: u64::from_le_bytes(*remaining_inventory),
+            escheat_period: i64::from_le_bytes(*escheat_period),
+            total_buy_volume: u64::from_le_bytes(*total_buy_volume),
+            total_sell_volume: u64::from_le_bytes(*total_sell_volume),
+            trade_count: u64::from_le_bytes(*trade_count),
+            last_trade_slot: u64::from_le_bytes(*last_trade_slot),
+            is_stats_tracked: unpack_bool(is_stats_tracked)?,
+            per_wallet_cap: u64::from_le_bytes(*per_wallet_cap),
+            namespace: Pubkey::new_from_array(*namespace),
+            open_at_slot: u64::from_le_bytes(*open_at_slot),
+            close_at_slot: u64::from_le_bytes(*close_at_slot),
+            is_settling_daily: unpack_bool(is_settling_daily)?,
+            settlement_cutoff: u32::from_le_bytes(*settlement_cutoff),
+            settled_day: i64::from_le_bytes(*settled_day),
+            auction_start_price: u64::from_le_bytes(*auction_start_price),
+            auction_floor_price: u64::from_le_bytes(*auction_floor_price),
+            auction_start_slot: u64::from_le_bytes(*auction_start_slot),
+            auction_end_slot: u64::from_le_bytes(*auction_end_slot),
+            curve_virtual_payment_reserve: u64::from_le_bytes(*curve_virtual_payment_reserve),
+            curve_virtual_store_reserve: u64::from_le_bytes(*curve_virtual_store_reserve),
         })
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
         let dst = array_mut_ref![dst, 0, Store::LEN];
+        let (version_dst, dst) = mut_array_refs![dst, 1, Store::LEN - 1];
+        version_dst[0] = STORE_VERSION;
         let (
             is_initialized_dst,
-            price_dst,
+            buy_price_dst,
             initializer_pubkey_dst,
             native_tokens_pubkey_dst,
             store_tokens_pubkey_dst,
-        ) = mut_array_refs![dst, 1, 8, 32, 32, 32];
+            lot_size_dst,
+            redirect_pubkey_dst,
+            store_tokens_warning_threshold_dst,
+            payment_tokens_warning_threshold_dst,
+            proceeds_pubkey_dst,
+            payout_pubkey_dst,
+            cosigner_pubkey_dst,
+            cosign_threshold_dst,
+            bought_volume_dst,
+            sold_volume_dst,
+            cost_basis_amount_dst,
+            cost_basis_dst,
+            realized_pnl_dst,
+            gate_mint_pubkey_dst,
+            escrow_pubkey_dst,
+            escrow_window_dst,
+            escrow_sequence_dst,
+            pending_owner_pubkey_dst,
+            is_closed_dst,
+            sell_price_dst,
+            pricing_engine_dst,
+            features_dst,
+            authority_nonce_dst,
+            trading_hours_dst,
+            guardian_pubkey_dst,
+            is_paused_dst,
+            store_token_mint_dst,
+            payment_token_mint_dst,
+            price_denominator_dst,
+            fee_bps_dst,
+            fee_vault_pubkey_dst,
+            fees_collected_dst,
+            fee_schedule_dst,
+            wind_down_deadline_dst,
+            max_trade_amount_dst,
+            is_inventory_limited_dst,
+            remaining_inventory_dst,
+            escheat_period_dst,
+            total_buy_volume_dst,
+            total_sell_volume_dst,
+            trade_count_dst,
+            last_trade_slot_dst,
+            is_stats_tracked_dst,
+            per_wallet_cap_dst,
+            namespace_dst,
+            open_at_slot_dst,
+            close_at_slot_dst,
+            is_settling_daily_dst,
+            settlement_cutoff_dst,
+            settled_day_dst,
+            auction_start_price_dst,
+            auction_floor_price_dst,
+            auction_start_slot_dst,
+            auction_end_slot_dst,
+            curve_virtual_payment_reserve_dst,
+            curve_virtual_store_reserve_dst,
+        ) = mut_array_refs![
+            dst, 1, 8, 32, 32, 32, 8, 32, 8, 8, 32, 32, 32, 8, 8, 8, 8, 8, 8, 32, 32, 8, 8, 32, 1,
+            8, 1, 4, 1, 21, 32, 1, 32, 32, 8, 2, 32, 8, 40, 8, 8, 1, 8, 8, 8, 8, 8, 8, 1, 8, 32, 8,
+            8, 1, 4, 8, 8, 8, 8, 8, 8, 8
+        ];
 
         let Store {
+            version: _,
             is_initialized,
-            price,
+            buy_price,
             owner_pubkey,
             native_tokens_to_auto_sell_pubkey,
             store_tokens_to_auto_buy_pubkey,
+            lot_size,
+            redirect_pubkey,
+            store_tokens_warning_threshold,
+            payment_tokens_warning_threshold,
+            proceeds_pubkey,
+            payout_pubkey,
+            cosigner_pubkey,
+            cosign_threshold,
+            bought_volume,
+            sold_volume,
+            cost_basis_amount,
+            cost_basis,
+            realized_pnl,
+            gate_mint_pubkey,
+            escrow_pubkey,
+            escrow_window,
+            escrow_sequence,
+            pending_owner_pubkey,
+            is_closed,
+            sell_price,
+            pricing_engine,
+            features,
+            authority_nonce,
+            trading_hours,
+            guardian_pubkey,
+            is_paused,
+            store_token_mint,
+            payment_token_mint,
+            price_denominator,
+            fee_bps,
+            fee_vault_pubkey,
+            fees_collected,
+            fee_schedule,
+            wind_down_deadline,
+            max_trade_amount,
+            is_inventory_limited,
+            remaining_inventory,
+            escheat_period,
+            total_buy_volume,
+            total_sell_volume,
+            trade_count,
+            last_trade_slot,
+            is_stats_tracked,
+            per_wallet_cap,
+            namespace,
+            open_at_slot,
+            close_at_slot,
+            is_settling_daily,
+            settlement_cutoff,
+            settled_day,
+            auction_start_price,
+            auction_floor_price,
+            auction_start_slot,
+            auction_end_slot,
+            curve_virtual_payment_reserve,
+            curve_virtual_store_reserve,
         } = self;
 
         is_initialized_dst[0] = *is_initialized as u8;
-        *price_dst = price.to_le_bytes();
+        *buy_price_dst = buy_price.to_le_bytes();
         initializer_pubkey_dst.copy_from_slice(owner_pubkey.as_ref());
         native_tokens_pubkey_dst.copy_from_slice(native_tokens_to_auto_sell_pubkey.as_ref());
         store_tokens_pubkey_dst.copy_from_slice(store_tokens_to_auto_buy_pubkey.as_ref());
+        *lot_size_dst = lot_size.to_le_bytes();
+        redirect_pubkey_dst.copy_from_slice(redirect_pubkey.as_ref());
+        *store_tokens_warning_threshold_dst = store_tokens_warning_threshold.to_le_bytes();
+        *payment_tokens_warning_threshold_dst = payment_tokens_warning_threshold.to_le_bytes();
+        proceeds_pubkey_dst.copy_from_slice(proceeds_pubkey.as_ref());
+        payout_pubkey_dst.copy_from_slice(payout_pubkey.as_ref());
+        cosigner_pubkey_dst.copy_from_slice(cosigner_pubkey.as_ref());
+        *cosign_threshold_dst = cosign_threshold.to_le_bytes();
+        *bought_volume_dst = bought_volume.to_le_bytes();
+        *sold_volume_dst = sold_volume.to_le_bytes();
+        *cost_basis_amount_dst = cost_basis_amount.to_le_bytes();
+        *cost_basis_dst = cost_basis.to_le_bytes();
+        *realized_pnl_dst = realized_pnl.to_le_bytes();
+        gate_mint_pubkey_dst.copy_from_slice(gate_mint_pubkey.as_ref());
+        escrow_pubkey_dst.copy_from_slice(escrow_pubkey.as_ref());
+        *escrow_window_dst = escrow_window.to_le_bytes();
+        *escrow_sequence_dst = escrow_sequence.to_le_bytes();
+        pending_owner_pubkey_dst.copy_from_slice(pending_owner_pubkey.as_ref());
+        is_closed_dst[0] = *is_closed as u8;
+        *sell_price_dst = sell_price.to_le_bytes();
+        pricing_engine_dst[0] = *pricing_engine;
+        *features_dst = features.to_le_bytes();
+        authority_nonce_dst[0] = *authority_nonce;
+        *trading_hours_dst = *trading_hours;
+        guardian_pubkey_dst.copy_from_slice(guardian_pubkey.as_ref());
+        is_paused_dst[0] = *is_paused as u8;
+        store_token_mint_dst.copy_from_slice(store_token_mint.as_ref());
+        payment_token_mint_dst.copy_from_slice(payment_token_mint.as_ref());
+        *price_denominator_dst = price_denominator.to_le_bytes();
+        *fee_bps_dst = fee_bps.to_le_bytes();
+        fee_vault_pubkey_dst.copy_from_slice(fee_vault_pubkey.as_ref());
+        *fees_collected_dst = fees_collected.to_le_bytes();
+        *fee_schedule_dst = fee_schedule.to_le_bytes();
+        *wind_down_deadline_dst = wind_down_deadline.to_le_bytes();
+        *max_trade_amount_dst = max_trade_amount.to_le_bytes();
+        is_inventory_limited_dst[0] = *is_inventory_limited as u8;
+        *remaining_inventory_dst = remaining_inventory.to_le_bytes();
+        *escheat_period_dst = escheat_period.to_le_bytes();
+        *total_buy_volume_dst = total_buy_volume.to_le_bytes();
+        *total_sell_volume_dst = total_sell_volume.to_le_bytes();
+        *trade_count_dst = trade_count.to_le_bytes();
+        *last_trade_slot_dst = last_trade_slot.to_le_bytes();
+        is_stats_tracked_dst[0] = *is_stats_tracked as u8;
+        *per_wallet_cap_dst = per_wallet_cap.to_le_bytes();
+        namespace_dst.copy_from_slice(namespace.as_ref());
+        *open_at_slot_dst = open_at_slot.to_le_bytes();
+        *close_at_slot_dst = close_at_slot.to_le_bytes();
+        is_settling_daily_dst[0] = *is_settling_daily as u8;
+        *settlement_cutoff_dst = settlement_cutoff.to_le_bytes();
+        *settled_day_dst = settled_day.to_le_bytes();
+        *auction_start_price_dst = auction_start_price.to_le_bytes();
+        *auction_floor_price_dst = auction_floor_price.to_le_bytes();
+        *auction_start_slot_dst = auction_start_slot.to_le_bytes();
+        *auction_end_slot_dst = auction_end_slot.to_le_bytes();
+        *curve_virtual_payment_reserve_dst = curve_virtual_payment_reserve.to_le_bytes();
+        *curve_virtual_store_reserve_dst = curve_virtual_store_reserve.to_le_bytes();
+    }
+}
+
+/// Max number of planned changes in `FeeSchedule`
+pub const MAX_FEE_CHANGES: usize = 4;
+
+/// Fee of a store from `effective_at` on
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct FeeChange {
+    /// unix timestamp (0 - empty entry)
+    pub effective_at: i64,
+    pub fee_bps: u16,
+}
+
+impl FeeChange {
+    const LEN: usize = 8 + 2;
+}
+
+/// Planned fee changes of a store sorted by effective time, empty entries last
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct FeeSchedule {
+    pub changes: [FeeChange; MAX_FEE_CHANGES],
+}
+
+impl FeeSchedule {
+    /// Planned changes, soonest first
+    pub fn changes(&self) -> &[FeeChange] {
+        let count = self
+            .changes
+            .iter()
+            .take_while(|change| change.effective_at != 0)
+            .count();
+        &self.changes[..count]
+    }
+
+    /// Plans `fee_bps` from `effective_at`, replacing a change planned at the same time
+    pub fn schedule(&mut self, effective_at: i64, fee_bps: u16) -> Result<(), ProgramError> {
+        let count = self.changes().len();
+        let index = self
+            .changes()
+            .iter()
+            .position(|change| change.effective_at >= effective_at)
+            .unwrap_or(count);
+        if index < count && self.changes[index].effective_at == effective_at {
+            self.changes[index].fee_bps = fee_bps;
+            return Ok(());
+        }
+        if count == MAX_FEE_CHANGES {
+            return Err(StoreError::FeeScheduleFull.into());
+        }
+        self.changes.copy_within(index..count, index + 1);
+        self.changes[index] = FeeChange {
+            effective_at,
+            fee_bps,
+        };
+        Ok(())
+    }
+
+    /// Removes changes effective at unix timestamp `now`, returns the fee of the latest one
+    pub fn take_due(&mut self, now: i64) -> Option<u16> {
+        let due = self
+            .changes()
+            .iter()
+            .take_while(|change| change.effective_at <= now)
+            .count();
+        let fee_bps = self.changes[..due].last()?.fee_bps;
+        self.changes.copy_within(due.., 0);
+        for change in &mut self.changes[MAX_FEE_CHANGES - due..] {
+            *change = FeeChange::default();
+        }
+        Some(fee_bps)
+    }
+
+    fn from_le_bytes(src: [u8; MAX_FEE_CHANGES * FeeChange::LEN]) -> Self {
+        let mut schedule = Self::default();
+        for (change, src) in schedule
+            .changes
+            .iter_mut()
+            .zip(src.chunks_exact(FeeChange::LEN))
+        {
+            let (effective_at, fee_bps) = array_refs![array_ref![src, 0, FeeChange::LEN], 8, 2];
+            *change = FeeChange {
+                effective_at: i64::from_le_bytes(*effective_at),
+                fee_bps: u16::from_le_bytes(*fee_bps),
+            };
+        }
+        schedule
+    }
+
+    fn to_le_bytes(self) -> [u8; MAX_FEE_CHANGES * FeeChange::LEN] {
+        let mut dst = [0; MAX_FEE_CHANGES * FeeChange::LEN];
+        for (change, dst) in self
+            .changes
+            .iter()
+            .zip(dst.chunks_exact_mut(FeeChange::LEN))
+        {
+            let (effective_at_dst, fee_bps_dst) =
+                mut_array_refs![array_mut_ref![dst, 0, FeeChange::LEN], 8, 2];
+            *effective_at_dst = change.effective_at.to_le_bytes();
+            *fee_bps_dst = change.fee_bps.to_le_bytes();
+        }
+        dst
+    }
+}
+
+/// Compact summary of the store liquidity, readable by other programs
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Depth {
+    pub is_initialized: bool,
+    pub store_pubkey: Pubkey,
+
+    /// price the store pays for `price_denominator` store tokens sold to it at the moment of
+    /// snapshot (0 - doesn't buy)
+    pub bid_price: u64,
+    /// price of `price_denominator` store tokens bought from the store at the moment of snapshot
+    pub ask_price: u64,
+    /// store tokens amount prices are quoted per
+    pub price_denominator: u64,
+    /// store tokens available for buy
+    pub ask_liquidity: u64,
+    /// payment tokens available to pay for sell
+    pub bid_liquidity: u64,
+    /// slot of the snapshot
+    pub slot: u64,
+}
+
+impl Sealed for Depth {}
+
+impl IsInitialized for Depth {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Depth {
+    const LEN: usize = 1 + 32 + 8 + 8 + 8 + 8 + 8 + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Depth::LEN];
+        let (
+            is_initialized,
+            store_pubkey,
+            bid_price,
+            ask_price,
+            price_denominator,
+            ask_liquidity,
+            bid_liquidity,
+            slot,
+        ) = array_refs![src, 1, 32, 8, 8, 8, 8, 8, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(Depth {
+            is_initialized,
+            store_pubkey: Pubkey::new_from_array(*store_pubkey),
+            bid_price: u64::from_le_bytes(*bid_price),
+            ask_price: u64::from_le_bytes(*ask_price),
+            price_denominator: u64::from_le_bytes(*price_denominator),
+            ask_liquidity: u64::from_le_bytes(*ask_liquidity),
+            bid_liquidity: u64::from_le_bytes(*bid_liquidity),
+            slot: u64::from_le_bytes(*slot),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Depth::LEN];
+        let (
+            is_initialized_dst,
+            store_pubkey_dst,
+            bid_price_dst,
+            ask_price_dst,
+            price_denominator_dst,
+            ask_dst,
+            bid_dst,
+            slot_dst,
+        ) = mut_array_refs![dst, 1, 32, 8, 8, 8, 8, 8, 8];
+
+        let Depth {
+            is_initialized,
+            store_pubkey,
+            bid_price,
+            ask_price,
+            price_denominator,
+            ask_liquidity,
+            bid_liquidity,
+            slot,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        store_pubkey_dst.copy_from_slice(store_pubkey.as_ref());
+        *bid_price_dst = bid_price.to_le_bytes();
+        *ask_price_dst = ask_price.to_le_bytes();
+        *price_denominator_dst = price_denominator.to_le_bytes();
+        *ask_dst = ask_liquidity.to_le_bytes();
+        *bid_dst = bid_liquidity.to_le_bytes();
+        *slot_dst = slot.to_le_bytes();
+    }
+}
+
+/// Buyer registration for sold-out store, PDA of `["waitlist", store, buyer]`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WaitlistEntry {
+    pub is_initialized: bool,
+    pub store_pubkey: Pubkey,
+    pub buyer_pubkey: Pubkey,
+
+    /// store tokens amount buyer wants to buy
+    pub amount: u64,
+    /// slot of the registration
+    pub slot: u64,
+}
+
+impl Sealed for WaitlistEntry {}
+
+impl IsInitialized for WaitlistEntry {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for WaitlistEntry {
+    const LEN: usize = 1 + 32 + 32 + 8 + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, WaitlistEntry::LEN];
+        let (is_initialized, store_pubkey, buyer_pubkey, amount, slot) =
+            array_refs![src, 1, 32, 32, 8, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(WaitlistEntry {
+            is_initialized,
+            store_pubkey: Pubkey::new_from_array(*store_pubkey),
+            buyer_pubkey: Pubkey::new_from_array(*buyer_pubkey),
+            amount: u64::from_le_bytes(*amount),
+            slot: u64::from_le_bytes(*slot),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, WaitlistEntry::LEN];
+        let (is_initialized_dst, store_pubkey_dst, buyer_pubkey_dst, amount_dst, slot_dst) =
+            mut_array_refs![dst, 1, 32, 32, 8, 8];
+
+        let WaitlistEntry {
+            is_initialized,
+            store_pubkey,
+            buyer_pubkey,
+            amount,
+            slot,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        store_pubkey_dst.copy_from_slice(store_pubkey.as_ref());
+        buyer_pubkey_dst.copy_from_slice(buyer_pubkey.as_ref());
+        *amount_dst = amount.to_le_bytes();
+        *slot_dst = slot.to_le_bytes();
+    }
+}
+
+/// Short-lived trading authority of a wallet for one store,
+/// PDA of `["session", store, wallet, session key]`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Session {
+    pub is_initialized: bool,
+    pub wallet_pubkey: Pubkey,
+    /// key allowed to sign trades on behalf of the wallet
+    pub session_pubkey: Pubkey,
+    pub store_pubkey: Pubkey,
+
+    /// max total payment tokens amount of session trades
+    pub max_notional: u64,
+    /// payment tokens amount of session trades
+    pub spent_notional: u64,
+    /// unix timestamp session expires at
+    pub expires_at: i64,
+    pub nonce: u8,
+}
+
+impl Sealed for Session {}
+
+impl IsInitialized for Session {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Session {
+    const LEN: usize = 1 + 32 + 32 + 32 + 8 + 8 + 8 + 1;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Session::LEN];
+        let (
+            is_initialized,
+            wallet_pubkey,
+            session_pubkey,
+            store_pubkey,
+            max_notional,
+            spent_notional,
+            expires_at,
+            nonce,
+        ) = array_refs![src, 1, 32, 32, 32, 8, 8, 8, 1];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(Session {
+            is_initialized,
+            wallet_pubkey: Pubkey::new_from_array(*wallet_pubkey),
+            session_pubkey: Pubkey::new_from_array(*session_pubkey),
+            store_pubkey: Pubkey::new_from_array(*store_pubkey),
+            max_notional: u64::from_le_bytes(*max_notional),
+            spent_notional: u64::from_le_bytes(*spent_notional),
+            expires_at: i64::from_le_bytes(*expires_at),
+            nonce: nonce[0],
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Session::LEN];
+        let (
+            is_initialized_dst,
+            wallet_pubkey_dst,
+            session_pubkey_dst,
+            store_pubkey_dst,
+            max_notional_dst,
+            spent_notional_dst,
+            expires_at_dst,
+            nonce_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 8, 8, 8, 1];
+
+        let Session {
+            is_initialized,
+            wallet_pubkey,
+            session_pubkey,
+            store_pubkey,
+            max_notional,
+            spent_notional,
+            expires_at,
+            nonce,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        wallet_pubkey_dst.copy_from_slice(wallet_pubkey.as_ref());
+        session_pubkey_dst.copy_from_slice(session_pubkey.as_ref());
+        store_pubkey_dst.copy_from_slice(store_pubkey.as_ref());
+        *max_notional_dst = max_notional.to_le_bytes();
+        *spent_notional_dst = spent_notional.to_le_bytes();
+        *expires_at_dst = expires_at.to_le_bytes();
+        nonce_dst[0] = *nonce;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+pub enum OrderSide {
+    Buy,
+    Sell,
+}
+
+/// Order message signed off-chain by the wallet, settled by `SettleSignedOrder`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SignedOrder {
+    pub store_pubkey: Pubkey,
+    pub side: OrderSide,
+    pub amount: u64,
+    /// max price to buy at, min price to sell at
+    pub limit_price: u64,
+    /// unix timestamp order expires at
+    pub expires_at: i64,
+    pub nonce: u64,
+}
+
+impl Sealed for SignedOrder {}
+
+impl Pack for SignedOrder {
+    const LEN: usize = 32 + 1 + 8 + 8 + 8 + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, SignedOrder::LEN];
+        let (store_pubkey, side, amount, limit_price, expires_at, nonce) =
+            array_refs![src, 32, 1, 8, 8, 8, 8];
+        let side = match side {
+            [0] => OrderSide::Buy,
+            [1] => OrderSide::Sell,
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+
+        Ok(SignedOrder {
+            store_pubkey: Pubkey::new_from_array(*store_pubkey),
+            side,
+            amount: u64::from_le_bytes(*amount),
+            limit_price: u64::from_le_bytes(*limit_price),
+            expires_at: i64::from_le_bytes(*expires_at),
+            nonce: u64::from_le_bytes(*nonce),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, SignedOrder::LEN];
+        let (store_pubkey_dst, side_dst, amount_dst, limit_price_dst, expires_at_dst, nonce_dst) =
+            mut_array_refs![dst, 32, 1, 8, 8, 8, 8];
+
+        let SignedOrder {
+            store_pubkey,
+            side,
+            amount,
+            limit_price,
+            expires_at,
+            nonce,
+        } = self;
+
+        store_pubkey_dst.copy_from_slice(store_pubkey.as_ref());
+        side_dst[0] = *side as u8;
+        *amount_dst = amount.to_le_bytes();
+        *limit_price_dst = limit_price.to_le_bytes();
+        *expires_at_dst = expires_at.to_le_bytes();
+        *nonce_dst = nonce.to_le_bytes();
+    }
+}
+
+/// Number of signed order nonces tracked by one `NonceBitmap` account
+pub const NONCES_PER_BITMAP: u64 = 2048;
+const NONCE_BITMAP_BYTES: usize = NONCES_PER_BITMAP as usize / 8;
+
+/// Used signed order nonces of a wallet, PDA of `["nonces", wallet, page]`,
+/// where page is `nonce / NONCES_PER_BITMAP` in little endian
+#[derive(Clone, Copy)]
+pub struct NonceBitmap {
+    pub is_initialized: bool,
+    pub wallet_pubkey: Pubkey,
+    pub page: u64,
+    pub bits: [u8; NONCE_BITMAP_BYTES],
+}
+
+impl NonceBitmap {
+    fn position(nonce: u64) -> (usize, u8) {
+        let bit = nonce % NONCES_PER_BITMAP;
+        ((bit / 8) as usize, 1 << (bit % 8))
+    }
+
+    pub fn is_used(&self, nonce: u64) -> bool {
+        let (byte, mask) = Self::position(nonce);
+        self.bits[byte] & mask != 0
+    }
+
+    pub fn mark_used(&mut self, nonce: u64) {
+        let (byte, mask) = Self::position(nonce);
+        self.bits[byte] |= mask;
+    }
+}
+
+impl Sealed for NonceBitmap {}
+
+impl IsInitialized for NonceBitmap {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for NonceBitmap {
+    const LEN: usize = 1 + 32 + 8 + NONCE_BITMAP_BYTES;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, NonceBitmap::LEN];
+        let (is_initialized, wallet_pubkey, page, bits) =
+            array_refs![src, 1, 32, 8, NONCE_BITMAP_BYTES];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(NonceBitmap {
+            is_initialized,
+            wallet_pubkey: Pubkey::new_from_array(*wallet_pubkey),
+            page: u64::from_le_bytes(*page),
+            bits: *bits,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, NonceBitmap::LEN];
+        let (is_initialized_dst, wallet_pubkey_dst, page_dst, bits_dst) =
+            mut_array_refs![dst, 1, 32, 8, NONCE_BITMAP_BYTES];
+
+        let NonceBitmap {
+            is_initialized,
+            wallet_pubkey,
+            page,
+            bits,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        wallet_pubkey_dst.copy_from_slice(wallet_pubkey.as_ref());
+        *page_dst = page.to_le_bytes();
+        bits_dst.copy_from_slice(bits);
+    }
+}
+
+/// Return data of `ReportPnl`, in payment tokens
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PnlReport {
+    pub realized_pnl: i64,
+    /// PnL of the cost basis at current store price
+    pub unrealized_pnl: i64,
+    pub bought_volume: u64,
+    pub sold_volume: u64,
+    pub cost_basis_amount: u64,
+    pub cost_basis: u64,
+}
+
+impl Sealed for PnlReport {}
+
+impl Pack for PnlReport {
+    const LEN: usize = 8 + 8 + 8 + 8 + 8 + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, PnlReport::LEN];
+        let (
+            realized_pnl,
+            unrealized_pnl,
+            bought_volume,
+            sold_volume,
+            cost_basis_amount,
+            cost_basis,
+        ) = array_refs![src, 8, 8, 8, 8, 8, 8];
+
+        Ok(PnlReport {
+            realized_pnl: i64::from_le_bytes(*realized_pnl),
+            unrealized_pnl: i64::from_le_bytes(*unrealized_pnl),
+            bought_volume: u64::from_le_bytes(*bought_volume),
+            sold_volume: u64::from_le_bytes(*sold_volume),
+            cost_basis_amount: u64::from_le_bytes(*cost_basis_amount),
+            cost_basis: u64::from_le_bytes(*cost_basis),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, PnlReport::LEN];
+        let (
+            realized_pnl_dst,
+            unrealized_pnl_dst,
+            bought_volume_dst,
+            sold_volume_dst,
+            cost_basis_amount_dst,
+            cost_basis_dst,
+        ) = mut_array_refs![dst, 8, 8, 8, 8, 8, 8];
+
+        let PnlReport {
+            realized_pnl,
+            unrealized_pnl,
+            bought_volume,
+            sold_volume,
+            cost_basis_amount,
+            cost_basis,
+        } = self;
+
+        *realized_pnl_dst = realized_pnl.to_le_bytes();
+        *unrealized_pnl_dst = unrealized_pnl.to_le_bytes();
+        *bought_volume_dst = bought_volume.to_le_bytes();
+        *sold_volume_dst = sold_volume.to_le_bytes();
+        *cost_basis_amount_dst = cost_basis_amount.to_le_bytes();
+        *cost_basis_dst = cost_basis.to_le_bytes();
+    }
+}
+
+/// Return data of `PreviewTrade`, from the user's side
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TradePreview {
+    /// payment tokens on buy, store tokens on sell
+    pub user_pays: u64,
+    /// store tokens on buy, payment tokens on sell
+    pub user_receives: u64,
+    pub cosigner_required: bool,
+}
+
+impl Sealed for TradePreview {}
+
+impl Pack for TradePreview {
+    const LEN: usize = 8 + 8 + 1;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, TradePreview::LEN];
+        let (user_pays, user_receives, cosigner_required) = array_refs![src, 8, 8, 1];
+        let cosigner_required = match cosigner_required {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(TradePreview {
+            user_pays: u64::from_le_bytes(*user_pays),
+            user_receives: u64::from_le_bytes(*user_receives),
+            cosigner_required,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, TradePreview::LEN];
+        let (user_pays_dst, user_receives_dst, cosigner_required_dst) =
+            mut_array_refs![dst, 8, 8, 1];
+
+        let TradePreview {
+            user_pays,
+            user_receives,
+            cosigner_required,
+        } = self;
+
+        *user_pays_dst = user_pays.to_le_bytes();
+        *user_receives_dst = user_receives.to_le_bytes();
+        cosigner_required_dst[0] = *cosigner_required as u8;
+    }
+}
+
+/// `ProgramVersion::capabilities` bits, instruction sets supported by the deployed program
+pub const CAPABILITY_PER_STORE_PDA: u64 = 1 << 0;
+pub const CAPABILITY_OFFERS: u64 = 1 << 1;
+pub const CAPABILITY_BUY_SELL_PRICES: u64 = 1 << 2;
+pub const CAPABILITY_WITHDRAW_DEPOSIT: u64 = 1 << 3;
+pub const CAPABILITY_FEATURES: u64 = 1 << 4;
+pub const CAPABILITY_TRADING_HOURS: u64 = 1 << 5;
+pub const CAPABILITY_GUARDIAN: u64 = 1 << 6;
+pub const CAPABILITY_PRICE_DENOMINATOR: u64 = 1 << 7;
+pub const CAPABILITY_PRICE_LIMITS: u64 = 1 << 8;
+pub const CAPABILITY_TRADE_TAGS: u64 = 1 << 9;
+pub const CAPABILITY_VALUATION: u64 = 1 << 10;
+pub const CAPABILITY_FEES: u64 = 1 << 11;
+pub const CAPABILITY_WIND_DOWN: u64 = 1 << 12;
+pub const CAPABILITY_TRADE_LIMITS: u64 = 1 << 13;
+pub const CAPABILITY_REGISTRY: u64 = 1 << 14;
+pub const CAPABILITY_ESCHEAT: u64 = 1 << 15;
+pub const CAPABILITY_VAULT_CREATION: u64 = 1 << 16;
+pub const CAPABILITY_BATCH_BUY: u64 = 1 << 17;
+pub const CAPABILITY_BORSH: u64 = 1 << 18;
+pub const CAPABILITY_STORE_REACTIVATION: u64 = 1 << 19;
+pub const CAPABILITY_STORE_MIGRATION: u64 = 1 << 20;
+pub const CAPABILITY_PROGRAM_STATS: u64 = 1 << 21;
+pub const CAPABILITY_PROGRAM_CONFIG: u64 = 1 << 22;
+pub const CAPABILITY_WALLET_CAP: u64 = 1 << 23;
+pub const CAPABILITY_NAMESPACE: u64 = 1 << 24;
+pub const CAPABILITY_TRADING_WINDOW: u64 = 1 << 25;
+pub const CAPABILITY_SETTLEMENT: u64 = 1 << 26;
+pub const CAPABILITY_DUTCH_AUCTION: u64 = 1 << 27;
+pub const CAPABILITY_CONSTANT_PRODUCT: u64 = 1 << 28;
+
+/// Return data of `GetVersion`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProgramVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+    /// `CAPABILITY_*` bits
+    pub capabilities: u64,
+}
+
+impl ProgramVersion {
+    /// Version of this build
+    pub fn current() -> Self {
+        Self {
+            major: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or_default(),
+            minor: env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or_default(),
+            patch: env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or_default(),
+            capabilities: CAPABILITY_PER_STORE_PDA
+                | CAPABILITY_OFFERS
+                | CAPABILITY_BUY_SELL_PRICES
+                | CAPABILITY_WITHDRAW_DEPOSIT
+                | CAPABILITY_FEATURES
+                | CAPABILITY_TRADING_HOURS
+                | CAPABILITY_GUARDIAN
+                | CAPABILITY_PRICE_DENOMINATOR
+                | CAPABILITY_PRICE_LIMITS
+                | CAPABILITY_TRADE_TAGS
+                | CAPABILITY_VALUATION
+                | CAPABILITY_FEES
+                | CAPABILITY_WIND_DOWN
+                | CAPABILITY_TRADE_LIMITS
+                | CAPABILITY_REGISTRY
+                | CAPABILITY_ESCHEAT
+                | CAPABILITY_VAULT_CREATION
+                | CAPABILITY_BATCH_BUY
+                | CAPABILITY_BORSH
+                | CAPABILITY_STORE_REACTIVATION
+                | CAPABILITY_STORE_MIGRATION
+                | CAPABILITY_PROGRAM_STATS
+                | CAPABILITY_PROGRAM_CONFIG
+                | CAPABILITY_WALLET_CAP
+                | CAPABILITY_NAMESPACE
+                | CAPABILITY_TRADING_WINDOW
+                | CAPABILITY_SETTLEMENT
+                | CAPABILITY_DUTCH_AUCTION
+                | CAPABILITY_CONSTANT_PRODUCT,
+        }
+    }
+
+    pub fn has_capability(&self, capability: u64) -> bool {
+        self.capabilities & capability == capability
+    }
+}
+
+impl Sealed for ProgramVersion {}
+
+impl Pack for ProgramVersion {
+    const LEN: usize = 2 + 2 + 2 + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, ProgramVersion::LEN];
+        let (major, minor, patch, capabilities) = array_refs![src, 2, 2, 2, 8];
+
+        Ok(ProgramVersion {
+            major: u16::from_le_bytes(*major),
+            minor: u16::from_le_bytes(*minor),
+            patch: u16::from_le_bytes(*patch),
+            capabilities: u64::from_le_bytes(*capabilities),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, ProgramVersion::LEN];
+        let (major_dst, minor_dst, patch_dst, capabilities_dst) = mut_array_refs![dst, 2, 2, 2, 8];
+
+        let ProgramVersion {
+            major,
+            minor,
+            patch,
+            capabilities,
+        } = self;
+
+        *major_dst = major.to_le_bytes();
+        *minor_dst = minor.to_le_bytes();
+        *patch_dst = patch.to_le_bytes();
+        *capabilities_dst = capabilities.to_le_bytes();
+    }
+}
+
+/// Price discount for holders of NFTs of a verified Metaplex collection,
+/// PDA of `["discount", store, collection]`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DiscountTier {
+    pub is_initialized: bool,
+    pub store_pubkey: Pubkey,
+    /// collection NFT mint
+    pub collection_pubkey: Pubkey,
+    /// discount of buy notional in basis points (0 - disabled)
+    pub discount_bps: u16,
+}
+
+impl Sealed for DiscountTier {}
+
+impl IsInitialized for DiscountTier {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for DiscountTier {
+    const LEN: usize = 1 + 32 + 32 + 2;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, DiscountTier::LEN];
+        let (is_initialized, store_pubkey, collection_pubkey, discount_bps) =
+            array_refs![src, 1, 32, 32, 2];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(DiscountTier {
+            is_initialized,
+            store_pubkey: Pubkey::new_from_array(*store_pubkey),
+            collection_pubkey: Pubkey::new_from_array(*collection_pubkey),
+            discount_bps: u16::from_le_bytes(*discount_bps),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, DiscountTier::LEN];
+        let (is_initialized_dst, store_pubkey_dst, collection_pubkey_dst, discount_bps_dst) =
+            mut_array_refs![dst, 1, 32, 32, 2];
+
+        let DiscountTier {
+            is_initialized,
+            store_pubkey,
+            collection_pubkey,
+            discount_bps,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        store_pubkey_dst.copy_from_slice(store_pubkey.as_ref());
+        collection_pubkey_dst.copy_from_slice(collection_pubkey.as_ref());
+        *discount_bps_dst = discount_bps.to_le_bytes();
+    }
+}
+
+/// Hash chain of attested store prices, PDA of `["feed", store]`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PriceFeed {
+    pub is_initialized: bool,
+    pub store_pubkey: Pubkey,
+    /// last attested price
+    pub price: u64,
+    /// slot of the last attestation
+    pub slot: u64,
+    /// number of attestations
+    pub sequence: u64,
+    /// `hashv([previous hash, store, price, slot, sequence])` of the last attestation
+    pub hash: [u8; 32],
+}
+
+impl PriceFeed {
+    /// Appends attestation of `price` at `slot` to the chain
+    pub fn attest(&mut self, price: u64, slot: u64) {
+        self.sequence += 1;
+        self.hash = hashv(&[
+            &self.hash,
+            self.store_pubkey.as_ref(),
+            &price.to_le_bytes(),
+            &slot.to_le_bytes(),
+            &self.sequence.to_le_bytes(),
+        ])
+        .to_bytes();
+        self.price = price;
+        self.slot = slot;
+    }
+}
+
+impl Sealed for PriceFeed {}
+
+impl IsInitialized for PriceFeed {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for PriceFeed {
+    const LEN: usize = 1 + 32 + 8 + 8 + 8 + 32;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, PriceFeed::LEN];
+        let (is_initialized, store_pubkey, price, slot, sequence, hash) =
+            array_refs![src, 1, 32, 8, 8, 8, 32];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(PriceFeed {
+            is_initialized,
+            store_pubkey: Pubkey::new_from_array(*store_pubkey),
+            price: u64::from_le_bytes(*price),
+            slot: u64::from_le_bytes(*slot),
+            sequence: u64::from_le_bytes(*sequence),
+            hash: *hash,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, PriceFeed::LEN];
+        let (is_initialized_dst, store_pubkey_dst, price_dst, slot_dst, sequence_dst, hash_dst) =
+            mut_array_refs![dst, 1, 32, 8, 8, 8, 32];
+
+        let PriceFeed {
+            is_initialized,
+            store_pubkey,
+            price,
+            slot,
+            sequence,
+            hash,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        store_pubkey_dst.copy_from_slice(store_pubkey.as_ref());
+        *price_dst = price.to_le_bytes();
+        *slot_dst = slot.to_le_bytes();
+        *sequence_dst = sequence.to_le_bytes();
+        hash_dst.copy_from_slice(hash);
+    }
+}
+
+/// `Valuation::source` of marks at the store buy price
+pub const VALUATION_STORE_PRICE: u8 = 0;
+/// `Valuation::source` of marks at the last attested `PriceFeed` price
+pub const VALUATION_PRICE_FEED: u8 = 1;
+
+/// Mark-to-market value of store inventory, recorded once per epoch,
+/// PDA of `["valuation", store, epoch]` with epoch in little endian
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Valuation {
+    pub is_initialized: bool,
+    pub store_pubkey: Pubkey,
+    pub epoch: u64,
+    /// slot of the recording
+    pub slot: u64,
+    /// mark price, payment tokens per `denominator` store tokens
+    pub price: u64,
+    pub denominator: u64,
+    /// where the mark price comes from, `VALUATION_*`
+    pub source: u8,
+    /// store tokens held by the store
+    pub store_tokens: u64,
+    /// payment tokens held by the store
+    pub payment_tokens: u64,
+    /// `store_tokens` at the mark price, in payment tokens
+    pub inventory_value: u64,
+    /// PnL of the cost basis at the mark price
+    pub unrealized_pnl: i64,
+}
+
+impl Sealed for Valuation {}
+
+impl IsInitialized for Valuation {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Valuation {
+    const LEN: usize = 1 + 32 + 8 + 8 + 8 + 8 + 1 + 8 + 8 + 8 + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Valuation::LEN];
+        let (
+            is_initialized,
+            store_pubkey,
+            epoch,
+            slot,
+            price,
+            denominator,
+            source,
+            store_tokens,
+            payment_tokens,
+            inventory_value,
+            unrealized_pnl,
+        ) = array_refs![src, 1, 32, 8, 8, 8, 8, 1, 8, 8, 8, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(Valuation {
+            is_initialized,
+            store_pubkey: Pubkey::new_from_array(*store_pubkey),
+            epoch: u64::from_le_bytes(*epoch),
+            slot: u64::from_le_bytes(*slot),
+            price: u64::from_le_bytes(*price),
+            denominator: u64::from_le_bytes(*denominator),
+            source: source[0],
+            store_tokens: u64::from_le_bytes(*store_tokens),
+            payment_tokens: u64::from_le_bytes(*payment_tokens),
+            inventory_value: u64::from_le_bytes(*inventory_value),
+            unrealized_pnl: i64::from_le_bytes(*unrealized_pnl),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Valuation::LEN];
+        let (
+            is_initialized_dst,
+            store_pubkey_dst,
+            epoch_dst,
+            slot_dst,
+            price_dst,
+            denominator_dst,
+            source_dst,
+            store_tokens_dst,
+            payment_tokens_dst,
+            inventory_value_dst,
+            unrealized_pnl_dst,
+        ) = mut_array_refs![dst, 1, 32, 8, 8, 8, 8, 1, 8, 8, 8, 8];
+
+        let Valuation {
+            is_initialized,
+            store_pubkey,
+            epoch,
+            slot,
+            price,
+            denominator,
+            source,
+            store_tokens,
+            payment_tokens,
+            inventory_value,
+            unrealized_pnl,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        store_pubkey_dst.copy_from_slice(store_pubkey.as_ref());
+        *epoch_dst = epoch.to_le_bytes();
+        *slot_dst = slot.to_le_bytes();
+        *price_dst = price.to_le_bytes();
+        *denominator_dst = denominator.to_le_bytes();
+        source_dst[0] = *source;
+        *store_tokens_dst = store_tokens.to_le_bytes();
+        *payment_tokens_dst = payment_tokens.to_le_bytes();
+        *inventory_value_dst = inventory_value.to_le_bytes();
+        *unrealized_pnl_dst = unrealized_pnl.to_le_bytes();
+    }
+}
+
+/// Daily settlement report of a store, recorded by SettleDay at the cutoff while trades are frozen,
+/// PDA of `["settlement", store, day]` with day in little endian
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SettlementReport {
+    pub is_initialized: bool,
+    pub store_pubkey: Pubkey,
+    /// settled day, in days since the unix epoch, of its cutoff
+    pub day: i64,
+    /// day of the report before, days in between were not settled
+    pub previous_day: i64,
+    /// slot of the recording
+    pub slot: u64,
+    pub unix_timestamp: i64,
+    /// store tokens held by the store
+    pub store_tokens: u64,
+    /// payment tokens held by the store
+    pub payment_tokens: u64,
+    /// `Store` trade totals at the recording, daily figures are the differences of consecutive reports
+    pub total_buy_volume: u64,
+    pub total_sell_volume: u64,
+    pub trade_count: u64,
+    pub fees_collected: u64,
+}
+
+impl Sealed for SettlementReport {}
+
+impl IsInitialized for SettlementReport {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for SettlementReport {
+    const LEN: usize = 1 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, SettlementReport::LEN];
+        let (
+            is_initialized,
+            store_pubkey,
+            day,
+            previous_day,
+            slot,
+            unix_timestamp,
+            store_tokens,
+            payment_tokens,
+            total_buy_volume,
+            total_sell_volume,
+            trade_count,
+            fees_collected,
+        ) = array_refs![src, 1, 32, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(SettlementReport {
+            is_initialized,
+            store_pubkey: Pubkey::new_from_array(*store_pubkey),
+            day: i64::from_le_bytes(*day),
+            previous_day: i64::from_le_bytes(*previous_day),
+            slot: u64::from_le_bytes(*slot),
+            unix_timestamp: i64::from_le_bytes(*unix_timestamp),
+            store_tokens: u64::from_le_bytes(*store_tokens),
+            payment_tokens: u64::from_le_bytes(*payment_tokens),
+            total_buy_volume: u64::from_le_bytes(*total_buy_volume),
+            total_sell_volume: u64::from_le_bytes(*total_sell_volume),
+            trade_count: u64::from_le_bytes(*trade_count),
+            fees_collected: u64::from_le_bytes(*fees_collected),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, SettlementReport::LEN];
+        let (
+            is_initialized_dst,
+            store_pubkey_dst,
+            day_dst,
+            previous_day_dst,
+            slot_dst,
+            unix_timestamp_dst,
+            store_tokens_dst,
+            payment_tokens_dst,
+            total_buy_volume_dst,
+            total_sell_volume_dst,
+            trade_count_dst,
+            fees_collected_dst,
+        ) = mut_array_refs![dst, 1, 32, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8];
+
+        let SettlementReport {
+            is_initialized,
+            store_pubkey,
+            day,
+            previous_day,
+            slot,
+            unix_timestamp,
+            store_tokens,
+            payment_tokens,
+            total_buy_volume,
+            total_sell_volume,
+            trade_count,
+            fees_collected,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        store_pubkey_dst.copy_from_slice(store_pubkey.as_ref());
+        *day_dst = day.to_le_bytes();
+        *previous_day_dst = previous_day.to_le_bytes();
+        *slot_dst = slot.to_le_bytes();
+        *unix_timestamp_dst = unix_timestamp.to_le_bytes();
+        *store_tokens_dst = store_tokens.to_le_bytes();
+        *payment_tokens_dst = payment_tokens.to_le_bytes();
+        *total_buy_volume_dst = total_buy_volume.to_le_bytes();
+        *total_sell_volume_dst = total_sell_volume.to_le_bytes();
+        *trade_count_dst = trade_count.to_le_bytes();
+        *fees_collected_dst = fees_collected.to_le_bytes();
+    }
+}
+
+/// Buy payment held in escrow until `release_at`, PDA of `["escrow", store, sequence]`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EscrowRecord {
+    pub is_initialized: bool,
+    pub store_pubkey: Pubkey,
+    /// wallet which paid the account rent, receives it back
+    pub payer_pubkey: Pubkey,
+    /// store account holding the payment
+    pub vault_pubkey: Pubkey,
+    /// buyer account the payment is refunded to
+    pub refund_pubkey: Pubkey,
+    /// buyer account which received store tokens, returned on refund
+    pub tokens_pubkey: Pubkey,
+    /// store tokens amount
+    pub amount: u64,
+    /// payment tokens amount
+    pub notional: u64,
+    /// fee paid to the store fee vault on top of the notional, in payment tokens
+    pub fee: u64,
+    /// unix timestamp the refund window ends at
+    pub release_at: i64,
+}
+
+impl Sealed for EscrowRecord {}
+
+impl IsInitialized for EscrowRecord {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for EscrowRecord {
+    const LEN: usize = 1 + 32 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, EscrowRecord::LEN];
+        let (
+            is_initialized,
+            store_pubkey,
+            payer_pubkey,
+            vault_pubkey,
+            refund_pubkey,
+            tokens_pubkey,
+            amount,
+            notional,
+            fee,
+            release_at,
+        ) = array_refs![src, 1, 32, 32, 32, 32, 32, 8, 8, 8, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(EscrowRecord {
+            is_initialized,
+            store_pubkey: Pubkey::new_from_array(*store_pubkey),
+            payer_pubkey: Pubkey::new_from_array(*payer_pubkey),
+            vault_pubkey: Pubkey::new_from_array(*vault_pubkey),
+            refund_pubkey: Pubkey::new_from_array(*refund_pubkey),
+            tokens_pubkey: Pubkey::new_from_array(*tokens_pubkey),
+            amount: u64::from_le_bytes(*amount),
+            notional: u64::from_le_bytes(*notional),
+            fee: u64::from_le_bytes(*fee),
+            release_at: i64::from_le_bytes(*release_at),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, EscrowRecord::LEN];
+        let (
+            is_initialized_dst,
+            store_pubkey_dst,
+            payer_pubkey_dst,
+            vault_pubkey_dst,
+            refund_pubkey_dst,
+            tokens_pubkey_dst,
+            amount_dst,
+            notional_dst,
+            fee_dst,
+            release_at_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 32, 32, 8, 8, 8, 8];
+
+        let EscrowRecord {
+            is_initialized,
+            store_pubkey,
+            payer_pubkey,
+            vault_pubkey,
+            refund_pubkey,
+            tokens_pubkey,
+            amount,
+            notional,
+            fee,
+            release_at,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        store_pubkey_dst.copy_from_slice(store_pubkey.as_ref());
+        payer_pubkey_dst.copy_from_slice(payer_pubkey.as_ref());
+        vault_pubkey_dst.copy_from_slice(vault_pubkey.as_ref());
+        refund_pubkey_dst.copy_from_slice(refund_pubkey.as_ref());
+        tokens_pubkey_dst.copy_from_slice(tokens_pubkey.as_ref());
+        *amount_dst = amount.to_le_bytes();
+        *notional_dst = notional.to_le_bytes();
+        *fee_dst = fee.to_le_bytes();
+        *release_at_dst = release_at.to_le_bytes();
+    }
+}
+
+/// Store tokens offer at fixed notional paid once, PDA of `["invoice", store, reference]`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Invoice {
+    pub is_initialized: bool,
+    pub store_pubkey: Pubkey,
+    /// merchant reference to reconcile the payment with
+    pub reference: [u8; 32],
+    /// store tokens amount
+    pub amount: u64,
+    /// payment tokens amount, at store price when issued
+    pub notional: u64,
+    /// wallet which paid the invoice (default - not paid)
+    pub payer_pubkey: Pubkey,
+}
+
+impl Invoice {
+    pub fn is_paid(&self) -> bool {
+        self.payer_pubkey != Pubkey::default()
+    }
+}
+
+impl Sealed for Invoice {}
+
+impl IsInitialized for Invoice {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Invoice {
+    const LEN: usize = 1 + 32 + 32 + 8 + 8 + 32;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Invoice::LEN];
+        let (is_initialized, store_pubkey, reference, amount, notional, payer_pubkey) =
+            array_refs![src, 1, 32, 32, 8, 8, 32];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(Invoice {
+            is_initialized,
+            store_pubkey: Pubkey::new_from_array(*store_pubkey),
+            reference: *reference,
+            amount: u64::from_le_bytes(*amount),
+            notional: u64::from_le_bytes(*notional),
+            payer_pubkey: Pubkey::new_from_array(*payer_pubkey),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Invoice::LEN];
+        let (
+            is_initialized_dst,
+            store_pubkey_dst,
+            reference_dst,
+            amount_dst,
+            notional_dst,
+            payer_pubkey_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 8, 8, 32];
+
+        let Invoice {
+            is_initialized,
+            store_pubkey,
+            reference,
+            amount,
+            notional,
+            payer_pubkey,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        store_pubkey_dst.copy_from_slice(store_pubkey.as_ref());
+        reference_dst.copy_from_slice(reference);
+        *amount_dst = amount.to_le_bytes();
+        *notional_dst = notional.to_le_bytes();
+        payer_pubkey_dst.copy_from_slice(payer_pubkey.as_ref());
+    }
+}
+
+/// Offer to trade `amount` tokens at `price` payment tokens each, the maker side tokens
+/// are held in `vault_pubkey` owned by the PDA. PDA of `["offer", vault]`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Offer {
+    pub is_initialized: bool,
+    /// maker side, buy offers hold the payment, sell offers hold the tokens
+    pub side: OrderSide,
+    /// maker wallet, paid the account rent
+    pub maker_pubkey: Pubkey,
+    /// mint of tokens the maker receives
+    pub mint_pubkey: Pubkey,
+    /// account holding the maker side tokens
+    pub vault_pubkey: Pubkey,
+    /// maker account receiving the taker side tokens
+    pub receive_pubkey: Pubkey,
+    /// tokens amount
+    pub amount: u64,
+    /// amount payment tokens per token
+    pub price: u64,
+    /// unix timestamp the offer expires at
+    pub expires_at: i64,
+}
+
+impl Offer {
+    /// Payment tokens amount of the trade
+    pub fn notional(&self) -> Result<u64, ProgramError> {
+        self.amount
+            .checked_mul(self.price)
+            .ok_or_else(|| StoreError::MathOverflow.into())
+    }
+
+    /// Amount held in the vault
+    pub fn escrowed(&self) -> Result<u64, ProgramError> {
+        match self.side {
+            OrderSide::Buy => self.notional(),
+            OrderSide::Sell => Ok(self.amount),
+        }
+    }
+
+    /// Amount the taker pays to the maker
+    pub fn taker_pays(&self) -> Result<u64, ProgramError> {
+        match self.side {
+            OrderSide::Buy => Ok(self.amount),
+            OrderSide::Sell => self.notional(),
+        }
+    }
+}
+
+impl Sealed for Offer {}
+
+impl IsInitialized for Offer {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Offer {
+    const LEN: usize = 1 + 1 + 32 + 32 + 32 + 32 + 8 + 8 + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Offer::LEN];
+        let (
+            is_initialized,
+            side,
+            maker_pubkey,
+            mint_pubkey,
+            vault_pubkey,
+            receive_pubkey,
+            amount,
+            price,
+            expires_at,
+        ) = array_refs![src, 1, 1, 32, 32, 32, 32, 8, 8, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let side = match side {
+            [0] => OrderSide::Buy,
+            [1] => OrderSide::Sell,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(Offer {
+            is_initialized,
+            side,
+            maker_pubkey: Pubkey::new_from_array(*maker_pubkey),
+            mint_pubkey: Pubkey::new_from_array(*mint_pubkey),
+            vault_pubkey: Pubkey::new_from_array(*vault_pubkey),
+            receive_pubkey: Pubkey::new_from_array(*receive_pubkey),
+            amount: u64::from_le_bytes(*amount),
+            price: u64::from_le_bytes(*price),
+            expires_at: i64::from_le_bytes(*expires_at),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Offer::LEN];
+        let (
+            is_initialized_dst,
+            side_dst,
+            maker_pubkey_dst,
+            mint_pubkey_dst,
+            vault_pubkey_dst,
+            receive_pubkey_dst,
+            amount_dst,
+            price_dst,
+            expires_at_dst,
+        ) = mut_array_refs![dst, 1, 1, 32, 32, 32, 32, 8, 8, 8];
+
+        let Offer {
+            is_initialized,
+            side,
+            maker_pubkey,
+            mint_pubkey,
+            vault_pubkey,
+            receive_pubkey,
+            amount,
+            price,
+            expires_at,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        side_dst[0] = *side as u8;
+        maker_pubkey_dst.copy_from_slice(maker_pubkey.as_ref());
+        mint_pubkey_dst.copy_from_slice(mint_pubkey.as_ref());
+        vault_pubkey_dst.copy_from_slice(vault_pubkey.as_ref());
+        receive_pubkey_dst.copy_from_slice(receive_pubkey.as_ref());
+        *amount_dst = amount.to_le_bytes();
+        *price_dst = price.to_le_bytes();
+        *expires_at_dst = expires_at.to_le_bytes();
+    }
+}
+
+/// Max number of stores tracked by one `StoreRegistry`
+pub const MAX_REGISTERED_STORES: usize = 32;
+
+/// Stores run by an owner, so clients can list them without scanning program accounts.
+/// PDA of `["registry", owner]`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StoreRegistry {
+    pub is_initialized: bool,
+    pub owner_pubkey: Pubkey,
+    /// number of used entries of `stores`
+    pub count: u8,
+    pub stores: [Pubkey; MAX_REGISTERED_STORES],
+}
+
+impl Default for StoreRegistry {
+    fn default() -> Self {
+        Self {
+            is_initialized: false,
+            owner_pubkey: Pubkey::default(),
+            count: 0,
+            stores: [Pubkey::default(); MAX_REGISTERED_STORES],
+        }
+    }
+}
+
+impl StoreRegistry {
+    /// Registered stores in registration order
+    pub fn stores(&self) -> &[Pubkey] {
+        &self.stores[..self.count as usize]
+    }
+
+    pub fn register(&mut self, store: &Pubkey) -> Result<(), ProgramError> {
+        if self.stores().contains(store) {
+            return Err(StoreError::StoreAlreadyRegistered.into());
+        }
+        if self.count as usize == MAX_REGISTERED_STORES {
+            return Err(StoreError::RegistryFull.into());
+        }
+        self.stores[self.count as usize] = *store;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Removes the store, keeping the order of the rest
+    pub fn unregister(&mut self, store: &Pubkey) -> Result<(), ProgramError> {
+        let index = self
+            .stores()
+            .iter()
+            .position(|registered| registered == store)
+            .ok_or(StoreError::StoreNotRegistered)?;
+        let count = self.count as usize;
+        self.stores.copy_within(index + 1..count, index);
+        self.stores[count - 1] = Pubkey::default();
+        self.count -= 1;
+        Ok(())
+    }
+}
+
+impl Sealed for StoreRegistry {}
+
+impl IsInitialized for StoreRegistry {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for StoreRegistry {
+    const LEN: usize = 1 + 32 + 1 + 32 * MAX_REGISTERED_STORES;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, StoreRegistry::LEN];
+        let (is_initialized, owner_pubkey, count, stores_src) =
+            array_refs![src, 1, 32, 1, 32 * MAX_REGISTERED_STORES];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        if count[0] as usize > MAX_REGISTERED_STORES {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut stores = [Pubkey::default(); MAX_REGISTERED_STORES];
+        for (store, chunk) in stores.iter_mut().zip(stores_src.chunks_exact(32)) {
+            *store = Pubkey::new_from_array(*array_ref![chunk, 0, 32]);
+        }
+
+        Ok(StoreRegistry {
+            is_initialized,
+            owner_pubkey: Pubkey::new_from_array(*owner_pubkey),
+            count: count[0],
+            stores,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, StoreRegistry::LEN];
+        let (is_initialized_dst, owner_pubkey_dst, count_dst, stores_dst) =
+            mut_array_refs![dst, 1, 32, 1, 32 * MAX_REGISTERED_STORES];
+
+        let StoreRegistry {
+            is_initialized,
+            owner_pubkey,
+            count,
+            stores,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        owner_pubkey_dst.copy_from_slice(owner_pubkey.as_ref());
+        count_dst[0] = *count;
+        for (chunk, store) in stores_dst.chunks_exact_mut(32).zip(stores.iter()) {
+            chunk.copy_from_slice(store.as_ref());
+        }
+    }
+}
+
+/// Activity of stores in one payment mint, updated by trades of stores tracking stats.
+/// PDA of `["stats", payment mint]`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MintStats {
+    pub is_initialized: bool,
+    pub mint: Pubkey,
+    /// stores counted in the program stats with this payment mint
+    pub store_count: u64,
+    /// notional of buys and sells, in payment tokens
+    pub volume: u64,
+    /// fees charged, in payment tokens
+    pub fees: u64,
+}
+
+impl MintStats {
+    pub fn record_trade(&mut self, notional: u64, fee: u64) -> Result<(), ProgramError> {
+        self.volume = self
+            .volume
+            .checked_add(notional)
+            .ok_or(StoreError::MathOverflow)?;
+        self.fees = self.fees.checked_add(fee).ok_or(StoreError::MathOverflow)?;
+        Ok(())
+    }
+}
+
+impl Sealed for MintStats {}
+
+impl IsInitialized for MintStats {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for MintStats {
+    const LEN: usize = 1 + 32 + 8 + 8 + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, MintStats::LEN];
+        let (is_initialized, mint, store_count, volume, fees) = array_refs![src, 1, 32, 8, 8, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(MintStats {
+            is_initialized,
+            mint: Pubkey::new_from_array(*mint),
+            store_count: u64::from_le_bytes(*store_count),
+            volume: u64::from_le_bytes(*volume),
+            fees: u64::from_le_bytes(*fees),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, MintStats::LEN];
+        let (is_initialized_dst, mint_dst, store_count_dst, volume_dst, fees_dst) =
+            mut_array_refs![dst, 1, 32, 8, 8, 8];
+
+        let MintStats {
+            is_initialized,
+            mint,
+            store_count,
+            volume,
+            fees,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        mint_dst.copy_from_slice(mint.as_ref());
+        *store_count_dst = store_count.to_le_bytes();
+        *volume_dst = volume.to_le_bytes();
+        *fees_dst = fees.to_le_bytes();
+    }
+}
+
+/// Program stats, once created every store initialized is counted and tracks stats of its payment
+/// mint in `MintStats`. PDA of `["stats"]`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProgramStats {
+    pub is_initialized: bool,
+    /// stores tracking stats, not closed
+    pub store_count: u64,
+}
+
+impl Sealed for ProgramStats {}
+
+impl IsInitialized for ProgramStats {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for ProgramStats {
+    const LEN: usize = 1 + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, ProgramStats::LEN];
+        let (is_initialized, store_count) = array_refs![src, 1, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(ProgramStats {
+            is_initialized,
+            store_count: u64::from_le_bytes(*store_count),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, ProgramStats::LEN];
+        let (is_initialized_dst, store_count_dst) = mut_array_refs![dst, 1, 8];
+
+        is_initialized_dst[0] = self.is_initialized as u8;
+        *store_count_dst = self.store_count.to_le_bytes();
+    }
+}
+
+pub const MAX_PAYMENT_MINTS: usize = 8;
+
+/// Deployment wide settings, PDA of `["config"]`. The program upgrade authority initializes it and
+/// becomes its authority.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProgramConfig {
+    pub is_initialized: bool,
+    pub authority_pubkey: Pubkey,
+    /// number of used entries of `payment_mints`, new stores may use any payment mint if 0
+    pub payment_mint_count: u8,
+    pub payment_mints: [Pubkey; MAX_PAYMENT_MINTS],
+    /// proposed authority that has to accept the config, default if none
+    pub pending_authority_pubkey: Pubkey,
+}
+
+impl Default for ProgramConfig {
+    fn default() -> Self {
+        Self {
+            is_initialized: false,
+            authority_pubkey: Pubkey::default(),
+            payment_mint_count: 0,
+            payment_mints: [Pubkey::default(); MAX_PAYMENT_MINTS],
+            pending_authority_pubkey: Pubkey::default(),
+        }
+    }
+}
+
+impl ProgramConfig {
+    /// Payment mints new stores may use
+    pub fn payment_mints(&self) -> &[Pubkey] {
+        &self.payment_mints[..self.payment_mint_count as usize]
+    }
+
+    pub fn set_payment_mints(&mut self, mints: &[Pubkey]) -> Result<(), ProgramError> {
+        if mints.len() > MAX_PAYMENT_MINTS {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.payment_mints = [Pubkey::default(); MAX_PAYMENT_MINTS];
+        self.payment_mints[..mints.len()].copy_from_slice(mints);
+        self.payment_mint_count = mints.len() as u8;
+        Ok(())
+    }
+
+    pub fn is_payment_mint_allowed(&self, mint: &Pubkey) -> bool {
+        self.payment_mint_count == 0 || self.payment_mints().contains(mint)
+    }
+}
+
+impl Sealed for ProgramConfig {}
+
+impl IsInitialized for ProgramConfig {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for ProgramConfig {
+    const LEN: usize = 1 + 32 + 1 + 32 * MAX_PAYMENT_MINTS + 32;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, ProgramConfig::LEN];
+        let (
+            is_initialized,
+            authority_pubkey,
+            payment_mint_count,
+            payment_mints_src,
+            pending_authority_pubkey,
+        ) = array_refs![src, 1, 32, 1, 32 * MAX_PAYMENT_MINTS, 32];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        if payment_mint_count[0] as usize > MAX_PAYMENT_MINTS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut payment_mints = [Pubkey::default(); MAX_PAYMENT_MINTS];
+        for (mint, chunk) in payment_mints
+            .iter_mut()
+            .zip(payment_mints_src.chunks_exact(32))
+        {
+            *mint = Pubkey::new_from_array(*array_ref![chunk, 0, 32]);
+        }
+
+        Ok(ProgramConfig {
+            is_initialized,
+            authority_pubkey: Pubkey::new_from_array(*authority_pubkey),
+            payment_mint_count: payment_mint_count[0],
+            payment_mints,
+            pending_authority_pubkey: Pubkey::new_from_array(*pending_authority_pubkey),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, ProgramConfig::LEN];
+        let (
+            is_initialized_dst,
+            authority_pubkey_dst,
+            payment_mint_count_dst,
+            payment_mints_dst,
+            pending_authority_pubkey_dst,
+        ) = mut_array_refs![dst, 1, 32, 1, 32 * MAX_PAYMENT_MINTS, 32];
+
+        let ProgramConfig {
+            is_initialized,
+            authority_pubkey,
+            payment_mint_count,
+            payment_mints,
+            pending_authority_pubkey,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        authority_pubkey_dst.copy_from_slice(authority_pubkey.as_ref());
+        payment_mint_count_dst[0] = *payment_mint_count;
+        for (chunk, mint) in payment_mints_dst
+            .chunks_exact_mut(32)
+            .zip(payment_mints.iter())
+        {
+            chunk.copy_from_slice(mint.as_ref());
+        }
+        pending_authority_pubkey_dst.copy_from_slice(pending_authority_pubkey.as_ref());
+    }
+}
+
+/// Store tokens bought by a wallet from a store with a per wallet cap,
+/// PDA of `["purchases", store, buyer]`, created on the first buy
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PurchaseLedger {
+    pub is_initialized: bool,
+    pub store_pubkey: Pubkey,
+    /// owner of the accounts receiving the bought store tokens
+    pub buyer_pubkey: Pubkey,
+    /// store tokens bought in total
+    pub amount: u64,
+}
+
+impl Sealed for PurchaseLedger {}
+
+impl IsInitialized for PurchaseLedger {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for PurchaseLedger {
+    const LEN: usize = 1 + 32 + 32 + 8;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, PurchaseLedger::LEN];
+        let (is_initialized, store_pubkey, buyer_pubkey, amount) = array_refs![src, 1, 32, 32, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(PurchaseLedger {
+            is_initialized,
+            store_pubkey: Pubkey::new_from_array(*store_pubkey),
+            buyer_pubkey: Pubkey::new_from_array(*buyer_pubkey),
+            amount: u64::from_le_bytes(*amount),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, PurchaseLedger::LEN];
+        let (is_initialized_dst, store_pubkey_dst, buyer_pubkey_dst, amount_dst) =
+            mut_array_refs![dst, 1, 32, 32, 8];
+
+        let PurchaseLedger {
+            is_initialized,
+            store_pubkey,
+            buyer_pubkey,
+            amount,
+        } = self;
+
+        is_initialized_dst[0] = *is_initialized as u8;
+        store_pubkey_dst.copy_from_slice(store_pubkey.as_ref());
+        buyer_pubkey_dst.copy_from_slice(buyer_pubkey.as_ref());
+        *amount_dst = amount.to_le_bytes();
     }
 }
@@ -0,0 +1,92 @@
+//! Read helpers for other on-chain programs given a store account,
+//! to use the store as a price source or trading venue without copying its layout.
+//!
+//! The program is deployed as a separate instance per cluster,
+//! so callers pass the id of the instance they trust.
+
+use solana_program::{
+    account_info::AccountInfo, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+};
+
+use crate::{error::StoreError, state::Store};
+
+/// Current store quote
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Price {
+    /// payment tokens per `price_denominator` store tokens paid by users on buy
+    pub price: u64,
+    /// payment tokens per `price_denominator` store tokens paid to users on sell
+    pub sell_price: u64,
+    /// store tokens amount the prices are for
+    pub price_denominator: u64,
+    /// trade amounts must be multiple of lot size (0 - any amount)
+    pub lot_size: u64,
+}
+
+/// Reads initialized store account owned by `program_id`
+pub fn load_store(program_id: &Pubkey, store_account: &AccountInfo) -> Result<Store, ProgramError> {
+    if store_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Store::unpack(&store_account.data.borrow())
+}
+
+/// Price the store trades at, fails for stores migrated to another one
+pub fn get_price(program_id: &Pubkey, store_account: &AccountInfo) -> Result<Price, ProgramError> {
+    let store = load_store(program_id, store_account)?;
+    if store.is_migrated() {
+        return Err(StoreError::StoreMigrated.into());
+    }
+    Ok(Price {
+        price: store.buy_price,
+        sell_price: store.sell_price,
+        price_denominator: store.denominator(),
+        lot_size: store.lot_size,
+    })
+}
+
+/// Store account with store tokens, sold to users on buy
+pub fn get_store_tokens_vault(
+    program_id: &Pubkey,
+    store_account: &AccountInfo,
+) -> Result<Pubkey, ProgramError> {
+    Ok(load_store(program_id, store_account)?.store_tokens_to_auto_buy_pubkey)
+}
+
+/// Store account with payment tokens, paid to users on sell
+pub fn get_payment_tokens_vault(
+    program_id: &Pubkey,
+    store_account: &AccountInfo,
+) -> Result<Pubkey, ProgramError> {
+    Ok(load_store(program_id, store_account)?.native_tokens_to_auto_sell_pubkey)
+}
+
+/// Mint of store tokens, `vault_account` is the store tokens vault
+pub fn get_store_mint(
+    program_id: &Pubkey,
+    store_account: &AccountInfo,
+    vault_account: &AccountInfo,
+) -> Result<Pubkey, ProgramError> {
+    let vault = get_store_tokens_vault(program_id, store_account)?;
+    vault_mint(&vault, vault_account)
+}
+
+/// Mint of payment tokens, `vault_account` is the payment tokens vault
+pub fn get_payment_mint(
+    program_id: &Pubkey,
+    store_account: &AccountInfo,
+    vault_account: &AccountInfo,
+) -> Result<Pubkey, ProgramError> {
+    let vault = get_payment_tokens_vault(program_id, store_account)?;
+    vault_mint(&vault, vault_account)
+}
+
+fn vault_mint(expected: &Pubkey, vault_account: &AccountInfo) -> Result<Pubkey, ProgramError> {
+    if vault_account.key != expected {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if *vault_account.owner != spl_token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(spl_token::state::Account::unpack(&vault_account.data.borrow())?.mint)
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::Market;
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_test::{
+    instruction::{self, Pricing},
+    state::{PRICING_DUTCH_AUCTION, PRICING_FIXED},
+};
+
+#[tokio::test]
+async fn test_dutch_auction_pricing() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let set_pricing = |market: &Market, pricing| {
+        instruction::set_pricing_instruction(
+            pricing,
+            &market.program_id,
+            &owner.pubkey(),
+            &market.store,
+        )
+        .unwrap()
+    };
+    let auction = |floor_price, end_slot| Pricing::DutchAuction {
+        start_price: 20,
+        floor_price,
+        start_slot: 100,
+        end_slot,
+    };
+    // the price decays down to the floor
+    let ix = set_pricing(&market, auction(21, 200));
+    assert!(!market.send(&[ix], &owner).await);
+    let ix = set_pricing(&market, auction(10, 100));
+    assert!(!market.send(&[ix], &owner).await);
+    let ix = set_pricing(&market, auction(10, 200));
+    assert!(market.send(&[ix], &owner).await);
+    assert_eq!(
+        market.store_info().await.pricing_engine,
+        PRICING_DUTCH_AUCTION
+    );
+
+    let user_payment_tokens = market.users[0].payment_tokens;
+    market.warp_to_slot(50);
+    assert!(!market.buy(0, 10, 19).await);
+    assert!(market.buy(0, 10, 20).await);
+    assert_eq!(
+        market.token_balance(&user_payment_tokens).await,
+        1_000 - 10 * 20
+    );
+
+    market.warp_to_slot(150);
+    assert!(!market.buy(0, 10, 14).await);
+    assert!(market.buy(0, 10, 15).await);
+    assert_eq!(
+        market.token_balance(&user_payment_tokens).await,
+        1_000 - 10 * 20 - 10 * 15
+    );
+
+    market.warp_to_slot(300);
+    assert!(market.buy(0, 10, 10).await);
+    assert_eq!(
+        market.token_balance(&user_payment_tokens).await,
+        1_000 - 10 * 20 - 10 * 15 - 10 * 10
+    );
+    // the auction only sells
+    assert!(!market.sell(0, 10, 0).await);
+
+    let ix = set_pricing(&market, Pricing::Fixed);
+    assert!(market.send(&[ix], &owner).await);
+    assert_eq!(market.store_info().await.pricing_engine, PRICING_FIXED);
+    assert!(market.buy(0, 10, PRICE).await);
+    assert!(market.sell(0, 10, PRICE).await);
+}
@@ -12,7 +12,6 @@ use spl_token::state::{Account as SplAccount, AccountState as SplAccountState};
 #[tokio::test]
 async fn test_one() {
     let program_id = Pubkey::new_unique();
-    let (pda, _nonce) = Pubkey::find_program_address(&[b"store"], &program_id);
 
     let store_owner_keypair = Keypair::new();
     let store_payment_tokens_account_pubkey = Pubkey::new_unique();
@@ -25,6 +24,8 @@ async fn test_one() {
     let user_store_tokens_account_pubkey = Pubkey::new_unique();
 
     let store_account_keypair = Keypair::new();
+    let pda =
+        instruction::find_store_authority_address(&program_id, &store_account_keypair.pubkey());
     let store_token_mint_pubkey = Pubkey::new_unique();
     let payment_token_mint_pubkey = Pubkey::new_unique();
 
@@ -326,7 +327,7 @@ async fn assert_store_account(
 
     let sa = state::Store::unpack_unchecked(&a.data).unwrap();
     if let Some(price) = price {
-        assert_eq!(sa.price, price);
+        assert_eq!(sa.buy_price, price);
     }
     if let Some(owner) = owner {
         assert_eq!(sa.owner_pubkey, owner);
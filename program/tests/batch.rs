@@ -0,0 +1,107 @@
+mod common;
+
+use common::Market;
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_test::instruction::{self, StoreInstruction, TransactionLimits, MAX_BATCH_LEGS};
+
+#[tokio::test]
+async fn test_batch_buy_across_stores() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let (second_store, _, second_store_tokens) = market.add_store(1_000, PRICE + 1).await;
+    let user = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let (user_payment_tokens, user_store_tokens) =
+        (market.users[0].payment_tokens, market.users[0].store_tokens);
+
+    let buy = |amount, price, store, store_store_tokens| {
+        instruction::buy_instruction(
+            amount,
+            price,
+            &market.program_id,
+            &user.pubkey(),
+            store,
+            &market.proceeds,
+            store_store_tokens,
+            &user_payment_tokens,
+            &user_store_tokens,
+            &instruction::find_store_authority_address(&market.program_id, store),
+            &spl_token::id(),
+        )
+        .unwrap()
+    };
+    let legs = [
+        buy(10, PRICE, &market.store, &market.store_store_tokens),
+        buy(20, PRICE + 1, &second_store, &second_store_tokens),
+    ];
+    // one leg over its max price fails the whole batch
+    let over_price = [
+        buy(10, PRICE, &market.store, &market.store_store_tokens),
+        buy(20, PRICE, &second_store, &second_store_tokens),
+    ];
+    let batch = instruction::batch_buy_instruction(&legs).unwrap();
+    let failing = instruction::batch_buy_instruction(&over_price).unwrap();
+
+    let before = market.token_balance(&user_payment_tokens).await;
+    assert!(!market.send(&[failing], &user).await);
+    assert_eq!(market.token_balance(&user_payment_tokens).await, before);
+
+    assert!(market.send(&[batch], &user).await);
+    assert_eq!(
+        market.token_balance(&user_payment_tokens).await,
+        before - 10 * PRICE - 20 * (PRICE + 1)
+    );
+    assert_eq!(market.token_balance(&user_store_tokens).await, 1_000 + 30);
+    assert_eq!(market.token_balance(&second_store_tokens).await, 1_000 - 20);
+}
+
+#[test]
+fn test_batch_buy_split_to_transaction_limits() {
+    let program_id = Pubkey::new_unique();
+    let user = Pubkey::new_unique();
+    let (proceeds, user_payment_tokens, user_store_tokens) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let buys: Vec<_> = (0..MAX_BATCH_LEGS + 2)
+        .map(|_| {
+            let store = Pubkey::new_unique();
+            instruction::buy_instruction(
+                10,
+                5,
+                &program_id,
+                &user,
+                &store,
+                &proceeds,
+                &Pubkey::new_unique(),
+                &user_payment_tokens,
+                &user_store_tokens,
+                &instruction::find_store_authority_address(&program_id, &store),
+                &spl_token::id(),
+            )
+            .unwrap()
+        })
+        .collect();
+    let limits = TransactionLimits::default();
+    // every leg brings three new accounts, a full batch doesn't fit a transaction
+    let all = instruction::batch_buy_instruction(&buys[..MAX_BATCH_LEGS]).unwrap();
+    assert!(limits.check(&[all], &user).is_err());
+
+    let batches = instruction::batch_buy_instructions(&buys, &user, &limits).unwrap();
+    for batch in &batches {
+        limits.check(&[batch.clone()], &user).unwrap();
+    }
+    // the first batch is as large as fits, the rest goes to the second
+    let legs: Vec<usize> = batches
+        .iter()
+        .map(
+            |batch| match StoreInstruction::unpack(&batch.data).unwrap() {
+                StoreInstruction::BatchBuy { legs } => legs.len(),
+                _ => unreachable!(),
+            },
+        )
+        .collect();
+    assert_eq!(legs, vec![MAX_BATCH_LEGS - 1, 3]);
+}
@@ -0,0 +1,63 @@
+use borsh::{to_vec, BorshDeserialize};
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+use solana_test::{
+    instruction::{BatchLeg, StoreInstruction, BORSH_INSTRUCTION_TAG},
+    state::{Store, STORE_VERSION},
+};
+
+#[test]
+fn test_borsh_instruction_round_trip() {
+    let instructions = [
+        StoreInstruction::Buy {
+            amount: 10,
+            max_price: 5,
+            tag: Some(7),
+        },
+        StoreInstruction::SetTradeLimits {
+            max_trade_amount: 100,
+            remaining_inventory: None,
+        },
+        StoreInstruction::BatchBuy {
+            legs: vec![BatchLeg {
+                amount: 1,
+                max_price: 2,
+                accounts: 8,
+            }],
+        },
+        StoreInstruction::InitializeAccount {
+            price: 5,
+            fee_bps: Some(30),
+            open_at_slot: 100,
+            close_at_slot: 200,
+        },
+    ];
+    for instruction in instructions {
+        let data = instruction.pack_borsh();
+        assert_eq!(data[0], BORSH_INSTRUCTION_TAG);
+        assert_eq!(StoreInstruction::unpack(&data).unwrap(), instruction);
+        // the hand packed layout is still understood
+        assert_eq!(
+            StoreInstruction::unpack(&instruction.pack()).unwrap(),
+            instruction
+        );
+    }
+    assert!(StoreInstruction::unpack(&[BORSH_INSTRUCTION_TAG, 0xfe]).is_err());
+}
+
+#[test]
+fn test_borsh_store_matches_packed_layout() {
+    let store = Store {
+        version: STORE_VERSION,
+        is_initialized: true,
+        buy_price: 5,
+        owner_pubkey: Pubkey::new_unique(),
+        fee_bps: 30,
+        is_paused: true,
+        last_trade_slot: 100,
+        ..Store::default()
+    };
+    let mut packed = vec![0; Store::LEN];
+    Store::pack(store, &mut packed).unwrap();
+    assert_eq!(to_vec(&store).unwrap(), packed);
+    assert_eq!(Store::try_from_slice(&packed).unwrap(), store);
+}
@@ -0,0 +1,438 @@
+//! Store market harness for program tests exercising sequences of operations
+
+#![allow(dead_code)]
+
+use solana_program::{
+    clock::{Clock, Epoch},
+    hash::Hash,
+    instruction::Instruction,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::{Account, WritableAccount},
+    signature::Keypair,
+    signer::Signer,
+    transaction::Transaction,
+};
+use solana_test::{instruction, processor::Processor, state};
+use spl_token::state::{Account as SplAccount, AccountState as SplAccountState};
+
+pub struct User {
+    pub keypair: Keypair,
+    pub payment_tokens: Pubkey,
+    pub store_tokens: Pubkey,
+    /// empty accounts, to be handed over to the program as offer vaults
+    pub spare_payment_tokens: Pubkey,
+    pub spare_store_tokens: Pubkey,
+}
+
+/// Initialized store with its owner settlement accounts and users,
+/// every token account except spare ones starts with `initial_tokens`
+pub struct Market {
+    pub context: ProgramTestContext,
+    pub banks_client: BanksClient,
+    pub payer: Keypair,
+    pub recent_blockhash: Hash,
+    pub program_id: Pubkey,
+    pub pda: Pubkey,
+    pub owner: Keypair,
+    pub store: Pubkey,
+    pub store_payment_tokens: Pubkey,
+    pub store_store_tokens: Pubkey,
+    /// owner account receiving payment tokens on buy
+    pub proceeds: Pubkey,
+    /// owner account receiving store tokens on sell
+    pub payout: Pubkey,
+    pub users: Vec<User>,
+    transactions: u64,
+}
+
+impl Market {
+    pub async fn start(users: usize, initial_tokens: u64, price: u64) -> Self {
+        let program_id = Pubkey::new_unique();
+        let store_token_mint = Pubkey::new_unique();
+        let payment_token_mint = Pubkey::new_unique();
+
+        let mut program_test =
+            ProgramTest::new("solana_test", program_id, processor!(Processor::process));
+
+        let owner = Keypair::new();
+        let user_keypairs: Vec<Keypair> = (0..users).map(|_| Keypair::new()).collect();
+        for wallet in std::iter::once(&owner).chain(&user_keypairs) {
+            program_test.add_account(
+                wallet.pubkey(),
+                Account {
+                    lamports: 1_000_000_000,
+                    ..Account::default()
+                },
+            );
+        }
+        let mut token_account = |owner: &Pubkey, mint: &Pubkey, amount: u64| {
+            let pubkey = Pubkey::new_unique();
+            program_test.add_account(pubkey, create_token_account(*owner, amount, *mint));
+            pubkey
+        };
+        let store_payment_tokens =
+            token_account(&owner.pubkey(), &payment_token_mint, initial_tokens);
+        let store_store_tokens = token_account(&owner.pubkey(), &store_token_mint, initial_tokens);
+        let proceeds = token_account(&owner.pubkey(), &payment_token_mint, initial_tokens);
+        let payout = token_account(&owner.pubkey(), &store_token_mint, initial_tokens);
+        let users = user_keypairs
+            .into_iter()
+            .map(|keypair| User {
+                payment_tokens: token_account(
+                    &keypair.pubkey(),
+                    &payment_token_mint,
+                    initial_tokens,
+                ),
+                store_tokens: token_account(&keypair.pubkey(), &store_token_mint, initial_tokens),
+                spare_payment_tokens: token_account(&keypair.pubkey(), &payment_token_mint, 0),
+                spare_store_tokens: token_account(&keypair.pubkey(), &store_token_mint, 0),
+                keypair,
+            })
+            .collect();
+
+        let context = program_test.start_with_context().await;
+        let mut banks_client = context.banks_client.clone();
+        let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+        let recent_blockhash = context.last_blockhash;
+        let rent = banks_client.get_rent().await.unwrap();
+
+        let store = Keypair::new();
+        let pda = instruction::find_store_authority_address(&program_id, &store.pubkey());
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                system_instruction::create_account(
+                    &owner.pubkey(),
+                    &store.pubkey(),
+                    rent.minimum_balance(state::Store::LEN),
+                    state::Store::LEN as u64,
+                    &program_id,
+                ),
+                instruction::initialyze_account_instruction(
+                    price,
+                    &program_id,
+                    &owner.pubkey(),
+                    &store.pubkey(),
+                    &store_payment_tokens,
+                    &store_store_tokens,
+                    &spl_token::id(),
+                )
+                .unwrap(),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &store, &owner], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        Self {
+            context,
+            banks_client,
+            payer,
+            recent_blockhash,
+            program_id,
+            pda,
+            owner,
+            store: store.pubkey(),
+            store_payment_tokens,
+            store_store_tokens,
+            proceeds,
+            payout,
+            users,
+            transactions: 0,
+        }
+    }
+
+    /// Sends instructions signed by `signer`, returns whether the transaction succeeded
+    pub async fn send(&mut self, instructions: &[Instruction], signer: &Keypair) -> bool {
+        let transaction = self.transaction(instructions, signer).await;
+        self.banks_client
+            .process_transaction(transaction)
+            .await
+            .is_ok()
+    }
+
+    /// Same as `send`, returns the transaction logs if it succeeded.
+    /// Precompile instructions are not verified, use `send` for those
+    pub async fn send_logged(
+        &mut self,
+        instructions: &[Instruction],
+        signer: &Keypair,
+    ) -> Option<Vec<String>> {
+        let transaction = self.transaction(instructions, signer).await;
+        let processed = self
+            .banks_client
+            .process_transaction_with_metadata(transaction)
+            .await
+            .unwrap();
+        processed.result.ok()?;
+        Some(processed.metadata?.log_messages)
+    }
+
+    /// Simulates instructions signed by `signer`, returns the return data they set
+    pub async fn return_data(&mut self, instructions: &[Instruction], signer: &Keypair) -> Vec<u8> {
+        self.recent_blockhash = self.banks_client.get_latest_blockhash().await.unwrap();
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&self.payer.pubkey()));
+        transaction.sign(&[&self.payer, signer], self.recent_blockhash);
+        let simulation = self
+            .banks_client
+            .simulate_transaction(transaction)
+            .await
+            .unwrap();
+        simulation.result.unwrap().unwrap();
+        simulation
+            .simulation_details
+            .unwrap()
+            .return_data
+            .map(|return_data| return_data.data)
+            .unwrap_or_default()
+    }
+
+    async fn transaction(&mut self, instructions: &[Instruction], signer: &Keypair) -> Transaction {
+        // long sequences outlive the blockhash the market started with
+        self.recent_blockhash = self.banks_client.get_latest_blockhash().await.unwrap();
+        // transfers a different lamports amount each time, so that repeated
+        // operations are different transactions within the same blockhash
+        self.transactions += 1;
+        let mut instructions = instructions.to_vec();
+        instructions.push(system_instruction::transfer(
+            &self.payer.pubkey(),
+            &self.owner.pubkey(),
+            self.transactions,
+        ));
+
+        let mut transaction =
+            Transaction::new_with_payer(&instructions, Some(&self.payer.pubkey()));
+        transaction.sign(&[&self.payer, signer], self.recent_blockhash);
+        transaction
+    }
+
+    /// Moves the clock to `slot`, later than the current one
+    pub fn warp_to_slot(&mut self, slot: u64) {
+        self.context.warp_to_slot(slot).unwrap();
+    }
+
+    /// Sets the clock timestamp, keeping the current slot
+    pub async fn set_unix_timestamp(&mut self, unix_timestamp: i64) {
+        let clock = self.banks_client.get_sysvar::<Clock>().await.unwrap();
+        self.context.set_sysvar(&Clock {
+            unix_timestamp,
+            ..clock
+        });
+    }
+
+    /// Initializes another store of the owner with `initial_tokens` in its token
+    /// accounts, returns the store with its payment and store token accounts
+    pub async fn add_store(&mut self, initial_tokens: u64, price: u64) -> (Pubkey, Pubkey, Pubkey) {
+        let accounts = [&self.store_payment_tokens, &self.store_store_tokens];
+        let mut token_accounts = Vec::new();
+        for pubkey in accounts.iter() {
+            let account = self
+                .banks_client
+                .get_account(**pubkey)
+                .await
+                .unwrap()
+                .unwrap();
+            let mint = SplAccount::unpack(&account.data).unwrap().mint;
+            let token_account = Pubkey::new_unique();
+            self.context.set_account(
+                &token_account,
+                &create_token_account(self.owner.pubkey(), initial_tokens, mint).into(),
+            );
+            token_accounts.push(token_account);
+        }
+
+        let rent = self.banks_client.get_rent().await.unwrap();
+        let store = Keypair::new();
+        let instructions = [
+            system_instruction::create_account(
+                &self.owner.pubkey(),
+                &store.pubkey(),
+                rent.minimum_balance(state::Store::LEN),
+                state::Store::LEN as u64,
+                &self.program_id,
+            ),
+            instruction::initialyze_account_instruction(
+                price,
+                &self.program_id,
+                &self.owner.pubkey(),
+                &store.pubkey(),
+                &token_accounts[0],
+                &token_accounts[1],
+                &spl_token::id(),
+            )
+            .unwrap(),
+        ];
+        let mut transaction =
+            Transaction::new_with_payer(&instructions, Some(&self.payer.pubkey()));
+        transaction.sign(&[&self.payer, &store, &self.owner], self.recent_blockhash);
+        self.banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        (store.pubkey(), token_accounts[0], token_accounts[1])
+    }
+
+    pub async fn buy(&mut self, user: usize, amount: u64, price: u64) -> bool {
+        let ix = instruction::buy_instruction(
+            amount,
+            price,
+            &self.program_id,
+            &self.users[user].keypair.pubkey(),
+            &self.store,
+            &self.proceeds,
+            &self.store_store_tokens,
+            &self.users[user].payment_tokens,
+            &self.users[user].store_tokens,
+            &self.pda,
+            &spl_token::id(),
+        )
+        .unwrap();
+        let signer = Keypair::from_bytes(&self.users[user].keypair.to_bytes()).unwrap();
+        self.send(&[ix], &signer).await
+    }
+
+    pub async fn sell(&mut self, user: usize, amount: u64, price: u64) -> bool {
+        let ix = instruction::sell_instruction(
+            amount,
+            price,
+            &self.program_id,
+            &self.users[user].keypair.pubkey(),
+            &self.store,
+            &self.store_payment_tokens,
+            &self.payout,
+            &self.users[user].payment_tokens,
+            &self.users[user].store_tokens,
+            &self.pda,
+            &spl_token::id(),
+        )
+        .unwrap();
+        let signer = Keypair::from_bytes(&self.users[user].keypair.to_bytes()).unwrap();
+        self.send(&[ix], &signer).await
+    }
+
+    pub async fn update_price(&mut self, price: u64) -> bool {
+        let ix = instruction::update_price_instruction(
+            price,
+            &self.program_id,
+            &self.owner.pubkey(),
+            &self.store,
+        )
+        .unwrap();
+        let signer = Keypair::from_bytes(&self.owner.to_bytes()).unwrap();
+        self.send(&[ix], &signer).await
+    }
+
+    /// Owner moves tokens from its settlement accounts into the store accounts
+    pub async fn deposit(&mut self, payment_tokens: u64, store_tokens: u64) -> bool {
+        let owner = self.owner.pubkey();
+        let ixs = [
+            spl_token::instruction::transfer(
+                &spl_token::id(),
+                &self.proceeds,
+                &self.store_payment_tokens,
+                &owner,
+                &[],
+                payment_tokens,
+            )
+            .unwrap(),
+            spl_token::instruction::transfer(
+                &spl_token::id(),
+                &self.payout,
+                &self.store_store_tokens,
+                &owner,
+                &[],
+                store_tokens,
+            )
+            .unwrap(),
+        ];
+        let signer = Keypair::from_bytes(&self.owner.to_bytes()).unwrap();
+        self.send(&ixs, &signer).await
+    }
+
+    pub async fn token_balance(&mut self, pubkey: &Pubkey) -> u64 {
+        let account = self
+            .banks_client
+            .get_account(*pubkey)
+            .await
+            .unwrap()
+            .unwrap();
+        SplAccount::unpack(&account.data).unwrap().amount
+    }
+
+    pub async fn store_info(&mut self) -> state::Store {
+        let account = self
+            .banks_client
+            .get_account(self.store)
+            .await
+            .unwrap()
+            .unwrap();
+        state::Store::unpack(&account.data).unwrap()
+    }
+
+    /// Total of payment and store tokens over all market token accounts
+    pub async fn token_totals(&mut self) -> (u64, u64) {
+        let mut payment_accounts = vec![self.store_payment_tokens, self.proceeds];
+        let mut store_accounts = vec![self.store_store_tokens, self.payout];
+        for user in &self.users {
+            payment_accounts.push(user.payment_tokens);
+            store_accounts.push(user.store_tokens);
+        }
+
+        let mut totals = (0, 0);
+        for pubkey in &payment_accounts {
+            totals.0 += self.token_balance(pubkey).await;
+        }
+        for pubkey in &store_accounts {
+            totals.1 += self.token_balance(pubkey).await;
+        }
+        totals
+    }
+}
+
+pub fn create_token_account(owner: Pubkey, amount: u64, mint: Pubkey) -> Account {
+    const DEFAULT_LAMPORTS_AMOUNT: u64 = 10000000000;
+
+    let mut token_account_vec = vec![0u8; SplAccount::LEN];
+    let token_account_data = SplAccount {
+        mint,
+        owner,
+        amount,
+        state: SplAccountState::Initialized,
+        ..SplAccount::default()
+    };
+    Pack::pack(token_account_data, &mut token_account_vec).unwrap();
+
+    Account::create(
+        DEFAULT_LAMPORTS_AMOUNT,
+        token_account_vec,
+        spl_token::id(),
+        false,
+        Epoch::default(),
+    )
+}
+
+/// Deterministic xorshift generator, so failing sequences can be replayed by seed
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Value in `0..bound`
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
@@ -0,0 +1,20 @@
+//! Workload for `cargo xtask cu-report`, which runs it against the BPF build
+//! with the `cu-profile` feature and reports compute units per checkpoint section.
+
+#![cfg(feature = "cu-profile")]
+
+mod common;
+
+use common::Market;
+
+#[tokio::test]
+async fn test_compute_units_workload() {
+    const PRICE: u64 = 3;
+    let mut market = Market::start(2, 1_000_000, PRICE).await;
+
+    for amount in [1, 100, 10_000] {
+        assert!(market.buy(0, amount, PRICE).await);
+        assert!(market.sell(1, amount, PRICE).await);
+    }
+    assert!(market.update_price(PRICE + 1).await);
+}
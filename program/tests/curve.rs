@@ -0,0 +1,116 @@
+mod common;
+
+use common::Market;
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_test::{
+    instruction::{self, Pricing},
+    math::quote_constant_product,
+    state::OrderSide,
+};
+
+#[tokio::test]
+async fn test_constant_product_pricing() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let user = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let set_pricing = |market: &Market, virtual_payment_reserve| {
+        instruction::set_pricing_instruction(
+            Pricing::ConstantProduct {
+                virtual_payment_reserve,
+                virtual_store_reserve: 0,
+            },
+            &market.program_id,
+            &owner.pubkey(),
+            &market.store,
+        )
+        .unwrap()
+    };
+    let ix = set_pricing(&market, 0);
+    assert!(!market.send(&[ix], &owner).await);
+    let proceeds_pubkey = market.store_info().await.proceeds_pubkey;
+    let ix = set_pricing(&market, 1_000);
+    assert!(market.send(&[ix], &owner).await);
+    let store_info = market.store_info().await;
+    // the configured settlement accounts are kept for the other pricing engines
+    assert_eq!(store_info.proceeds_pubkey, proceeds_pubkey);
+
+    let (store_payment_tokens, store_store_tokens) =
+        (market.store_payment_tokens, market.store_store_tokens);
+    let user_payment_tokens = market.users[0].payment_tokens;
+    let buy = |market: &Market, max_price| {
+        instruction::buy_instruction(
+            100,
+            max_price,
+            &market.program_id,
+            &user.pubkey(),
+            &market.store,
+            &store_payment_tokens,
+            &store_store_tokens,
+            &market.users[0].payment_tokens,
+            &market.users[0].store_tokens,
+            &market.pda,
+            &spl_token::id(),
+        )
+        .unwrap()
+    };
+    let sell = |market: &Market, min_price| {
+        instruction::sell_instruction(
+            100,
+            min_price,
+            &market.program_id,
+            &user.pubkey(),
+            &market.store,
+            &store_payment_tokens,
+            &store_store_tokens,
+            &market.users[0].payment_tokens,
+            &market.users[0].store_tokens,
+            &market.pda,
+            &spl_token::id(),
+        )
+        .unwrap()
+    };
+
+    // the reserves are 2000 payment and 1000 store tokens, 100 cost 2000 * 100 / 900 rounded up
+    let quote = quote_constant_product(&store_info, 1_000, 1_000, OrderSide::Buy, 100).unwrap();
+    assert_eq!(quote, 223);
+    let ix = buy(&market, 2);
+    assert!(!market.send(&[ix], &user).await);
+    let ix = buy(&market, 3);
+    assert!(market.send(&[ix], &user).await);
+    assert_eq!(
+        market.token_balance(&user_payment_tokens).await,
+        1_000 - quote
+    );
+    assert_eq!(
+        market.token_balance(&store_payment_tokens).await,
+        1_000 + quote
+    );
+    // trades settle to the reserves only
+    assert!(!market.buy(0, 100, PRICE).await);
+
+    let quote =
+        quote_constant_product(&store_info, 1_000 + 223, 900, OrderSide::Sell, 100).unwrap();
+    assert_eq!(quote, 222);
+    let ix = sell(&market, 3);
+    assert!(!market.send(&[ix], &user).await);
+    let ix = sell(&market, 2);
+    assert!(market.send(&[ix], &user).await);
+    assert_eq!(
+        market.token_balance(&user_payment_tokens).await,
+        1_000 - 223 + quote
+    );
+    assert_eq!(market.token_balance(&store_store_tokens).await, 1_000);
+
+    // back on fixed prices trades settle to the proceeds account again
+    let ix = instruction::set_pricing_instruction(
+        Pricing::Fixed,
+        &market.program_id,
+        &owner.pubkey(),
+        &market.store,
+    )
+    .unwrap();
+    assert!(market.send(&[ix], &owner).await);
+    assert!(market.buy(0, 10, PRICE).await);
+}
@@ -0,0 +1,280 @@
+mod common;
+
+use common::{create_token_account, Market};
+use solana_program::{
+    instruction::AccountMeta, program_pack::Pack, pubkey::Pubkey, system_instruction,
+    system_program,
+};
+use solana_program_test::tokio;
+use solana_sdk::{account::Account, signature::Keypair, signer::Signer};
+use solana_test::{
+    instruction,
+    state::{EscrowRecord, PurchaseLedger, FEATURE_ESCROW, FEATURE_SESSIONS},
+};
+
+#[tokio::test]
+async fn test_escrowed_session_buy_refund() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let payer = Keypair::from_bytes(&market.payer.to_bytes()).unwrap();
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let user = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let session_key = Keypair::new();
+    let program_id = market.program_id;
+    let funding = system_instruction::transfer(&payer.pubkey(), &user.pubkey(), 1_000_000_000);
+    assert!(market.send(&[funding], &payer).await);
+
+    let escrow_vault = Pubkey::new_unique();
+    let payment_mint = market.store_info().await.payment_token_mint;
+    market.context.set_account(
+        &escrow_vault,
+        &create_token_account(market.pda, 0, payment_mint).into(),
+    );
+    let setup = [
+        instruction::set_features_instruction(
+            FEATURE_ESCROW | FEATURE_SESSIONS,
+            &program_id,
+            &owner.pubkey(),
+            &market.store,
+        )
+        .unwrap(),
+        instruction::set_escrow_instruction(
+            100,
+            &program_id,
+            &owner.pubkey(),
+            &market.store,
+            Some(&escrow_vault),
+        )
+        .unwrap(),
+        instruction::set_wallet_cap_instruction(15, &program_id, &owner.pubkey(), &market.store)
+            .unwrap(),
+        instruction::set_trade_limits_instruction(
+            0,
+            Some(10),
+            &program_id,
+            &owner.pubkey(),
+            &market.store,
+        )
+        .unwrap(),
+    ];
+    assert!(market.send(&setup, &owner).await);
+
+    market.set_unix_timestamp(100).await;
+    let session = [
+        instruction::create_session_instruction(
+            100,
+            200,
+            &program_id,
+            &user.pubkey(),
+            &user.pubkey(),
+            &market.store,
+            &session_key.pubkey(),
+        )
+        .unwrap(),
+        instruction::approve_session_instruction(
+            1_000,
+            &program_id,
+            &user.pubkey(),
+            &market.store,
+            &session_key.pubkey(),
+            &market.users[0].payment_tokens,
+            &spl_token::id(),
+        )
+        .unwrap(),
+    ];
+    assert!(market.send(&session, &user).await);
+
+    // the session account can't pay for the escrow and ledger accounts, the payer does
+    let escrow = instruction::find_escrow_address(&program_id, &market.store, 0);
+    let ledger =
+        instruction::find_purchase_ledger_address(&program_id, &market.store, &user.pubkey());
+    let mut buy = instruction::session_buy_instruction(
+        2,
+        PRICE,
+        &program_id,
+        &session_key.pubkey(),
+        &user.pubkey(),
+        &market.store,
+        &escrow_vault,
+        &market.store_store_tokens,
+        &market.users[0].payment_tokens,
+        &market.users[0].store_tokens,
+        &market.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    buy.accounts.extend_from_slice(&[
+        AccountMeta::new(escrow, false),
+        AccountMeta::new(payer.pubkey(), true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(ledger, false),
+        AccountMeta::new(payer.pubkey(), true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]);
+    assert!(market.send(&[buy], &session_key).await);
+    assert_eq!(market.store_info().await.remaining_inventory, 8);
+    let ledger_amount = |account: Option<Account>| {
+        PurchaseLedger::unpack(&account.unwrap().data)
+            .unwrap()
+            .amount
+    };
+    assert_eq!(
+        ledger_amount(market.banks_client.get_account(ledger).await.unwrap()),
+        2
+    );
+
+    let escrow_account = market
+        .banks_client
+        .get_account(escrow)
+        .await
+        .unwrap()
+        .unwrap();
+    let escrow_info = EscrowRecord::unpack(&escrow_account.data).unwrap();
+    assert_eq!(escrow_info.payer_pubkey, payer.pubkey());
+    let approve = instruction::approve_refund_instruction(
+        2,
+        &user.pubkey(),
+        &market.users[0].store_tokens,
+        &market.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert!(market.send(&[approve], &user).await);
+    let refund = instruction::refund_escrow_instruction(
+        &program_id,
+        &owner.pubkey(),
+        &market.store,
+        &escrow,
+        &escrow_info,
+        &market.store_store_tokens,
+        &market.pda,
+        &spl_token::id(),
+        Some(&user.pubkey()),
+    )
+    .unwrap();
+    assert!(market.send(&[refund], &owner).await);
+
+    // the refunded tokens go back to the inventory and off the ledger
+    assert_eq!(market.store_info().await.remaining_inventory, 10);
+    assert_eq!(
+        ledger_amount(market.banks_client.get_account(ledger).await.unwrap()),
+        0
+    );
+    let user_payment_tokens = market.users[0].payment_tokens;
+    assert_eq!(market.token_balance(&user_payment_tokens).await, 1_000);
+}
+
+#[tokio::test]
+async fn test_escrowed_buy_refund_returns_fee() {
+    const PRICE: u64 = 10;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let payer = Keypair::from_bytes(&market.payer.to_bytes()).unwrap();
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let user = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let program_id = market.program_id;
+
+    let (escrow_vault, fee_vault) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let payment_mint = market.store_info().await.payment_token_mint;
+    for vault in [escrow_vault, fee_vault] {
+        market.context.set_account(
+            &vault,
+            &create_token_account(market.pda, 0, payment_mint).into(),
+        );
+    }
+    let setup = [
+        instruction::set_features_instruction(
+            FEATURE_ESCROW,
+            &program_id,
+            &owner.pubkey(),
+            &market.store,
+        )
+        .unwrap(),
+        instruction::set_escrow_instruction(
+            100,
+            &program_id,
+            &owner.pubkey(),
+            &market.store,
+            Some(&escrow_vault),
+        )
+        .unwrap(),
+        instruction::update_fee_instruction(
+            100,
+            &program_id,
+            &owner.pubkey(),
+            &market.store,
+            Some(&fee_vault),
+        )
+        .unwrap(),
+    ];
+    assert!(market.send(&setup, &owner).await);
+
+    let buy = instruction::buy_instruction(
+        10,
+        PRICE,
+        &program_id,
+        &user.pubkey(),
+        &market.store,
+        &escrow_vault,
+        &market.store_store_tokens,
+        &market.users[0].payment_tokens,
+        &market.users[0].store_tokens,
+        &market.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    let buy = instruction::with_escrow(
+        instruction::with_fee_vault(buy, &fee_vault),
+        0,
+        &payer.pubkey(),
+    );
+    // the market payer signs every transaction, it pays for the escrow account
+    assert!(market.send(&[buy], &user).await);
+    // 1% on top of 100 notional
+    let user_payment_tokens = market.users[0].payment_tokens;
+    assert_eq!(
+        market.token_balance(&user_payment_tokens).await,
+        1_000 - 101
+    );
+    assert_eq!(market.token_balance(&fee_vault).await, 1);
+
+    let escrow = instruction::find_escrow_address(&program_id, &market.store, 0);
+    let escrow_account = market
+        .banks_client
+        .get_account(escrow)
+        .await
+        .unwrap()
+        .unwrap();
+    let escrow_info = EscrowRecord::unpack(&escrow_account.data).unwrap();
+    assert_eq!((escrow_info.notional, escrow_info.fee), (100, 1));
+    let approve = instruction::approve_refund_instruction(
+        10,
+        &user.pubkey(),
+        &market.users[0].store_tokens,
+        &market.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert!(market.send(&[approve], &user).await);
+    let refund = instruction::refund_escrow_instruction(
+        &program_id,
+        &owner.pubkey(),
+        &market.store,
+        &escrow,
+        &escrow_info,
+        &market.store_store_tokens,
+        &market.pda,
+        &spl_token::id(),
+        None,
+    )
+    .unwrap();
+    // the fee is refunded from the fee vault along with the payment
+    assert!(!market.send(&[refund.clone()], &owner).await);
+    assert!(
+        market
+            .send(&[instruction::with_fee_vault(refund, &fee_vault)], &owner)
+            .await
+    );
+    assert_eq!(market.token_balance(&user_payment_tokens).await, 1_000);
+    assert_eq!(market.token_balance(&fee_vault).await, 0);
+    assert_eq!(market.store_info().await.fees_collected, 0);
+}
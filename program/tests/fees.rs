@@ -0,0 +1,199 @@
+mod common;
+
+use common::{create_token_account, Market};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_test::instruction;
+
+#[tokio::test]
+async fn test_fee_split_and_withdraw() {
+    const PRICE: u64 = 10;
+    let mut market = Market::start(1, 10_000, PRICE).await;
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let fee_vault = Pubkey::new_unique();
+    let payment_token_mint = market.store_info().await.payment_token_mint;
+    market.context.set_account(
+        &fee_vault,
+        &create_token_account(market.pda, 0, payment_token_mint).into(),
+    );
+
+    // a fee can't be charged without a vault
+    let ix = instruction::update_fee_instruction(
+        100,
+        &market.program_id,
+        &market.owner.pubkey(),
+        &market.store,
+        None,
+    )
+    .unwrap();
+    assert!(!market.send(&[ix], &owner).await);
+    let ix = instruction::update_fee_instruction(
+        100,
+        &market.program_id,
+        &market.owner.pubkey(),
+        &market.store,
+        Some(&fee_vault),
+    )
+    .unwrap();
+    assert!(market.send(&[ix], &owner).await);
+
+    // trades without the fee vault are rejected
+    assert!(!market.buy(0, 50, PRICE).await);
+
+    let user = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let user_payment_tokens = market.users[0].payment_tokens;
+    let before = market.token_balance(&user_payment_tokens).await;
+    let buy = instruction::buy_instruction(
+        50,
+        PRICE,
+        &market.program_id,
+        &market.users[0].keypair.pubkey(),
+        &market.store,
+        &market.proceeds,
+        &market.store_store_tokens,
+        &user_payment_tokens,
+        &market.users[0].store_tokens,
+        &market.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    let logs = market
+        .send_logged(&[instruction::with_fee_vault(buy, &fee_vault)], &user)
+        .await;
+    // each fill is logged with the accounts it moved tokens of, for bookkeeping
+    assert!(logs.unwrap().contains(&format!(
+        "Program log: Fill: store {} side buy amount 50 notional 500 fee 5 payment_vault {} store_vault {} fee_vault {} user {}",
+        market.store,
+        market.proceeds,
+        market.store_store_tokens,
+        fee_vault,
+        user.pubkey()
+    )));
+    // 1% on top of 500 notional
+    assert_eq!(
+        market.token_balance(&user_payment_tokens).await,
+        before - 505
+    );
+    assert_eq!(market.token_balance(&fee_vault).await, 5);
+
+    let sell = instruction::sell_instruction(
+        20,
+        PRICE,
+        &market.program_id,
+        &market.users[0].keypair.pubkey(),
+        &market.store,
+        &market.store_payment_tokens,
+        &market.payout,
+        &user_payment_tokens,
+        &market.users[0].store_tokens,
+        &market.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert!(
+        market
+            .send(&[instruction::with_fee_vault(sell, &fee_vault)], &user)
+            .await
+    );
+    // 1% deducted from 200 notional
+    assert_eq!(
+        market.token_balance(&user_payment_tokens).await,
+        before - 505 + 198
+    );
+    assert_eq!(market.token_balance(&fee_vault).await, 7);
+    assert_eq!(market.store_info().await.fees_collected, 7);
+
+    let proceeds = market.proceeds;
+    let proceeds_before = market.token_balance(&proceeds).await;
+    let ix = instruction::withdraw_fees_instruction(
+        7,
+        &market.program_id,
+        &market.owner.pubkey(),
+        &market.store,
+        &fee_vault,
+        &proceeds,
+        &market.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert!(market.send(&[ix], &owner).await);
+    assert_eq!(market.token_balance(&fee_vault).await, 0);
+    assert_eq!(market.token_balance(&proceeds).await, proceeds_before + 7);
+}
+
+#[tokio::test]
+async fn test_fee_schedule() {
+    const PRICE: u64 = 10;
+    let mut market = Market::start(1, 10_000, PRICE).await;
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let user = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let fee_vault = Pubkey::new_unique();
+    let payment_token_mint = market.store_info().await.payment_token_mint;
+    market.context.set_account(
+        &fee_vault,
+        &create_token_account(market.pda, 0, payment_token_mint).into(),
+    );
+    market.set_unix_timestamp(1_000).await;
+
+    let schedule = |market: &Market, effective_at| {
+        instruction::schedule_fee_instruction(
+            effective_at,
+            100,
+            &market.program_id,
+            &owner.pubkey(),
+            &market.store,
+        )
+        .unwrap()
+    };
+    // a fee vault is set up front, the change can't be effective already
+    let ix = schedule(&market, 2_000);
+    assert!(!market.send(&[ix], &owner).await);
+    let ix = instruction::update_fee_instruction(
+        0,
+        &market.program_id,
+        &owner.pubkey(),
+        &market.store,
+        Some(&fee_vault),
+    )
+    .unwrap();
+    assert!(market.send(&[ix], &owner).await);
+    let ix = schedule(&market, 1_000);
+    assert!(!market.send(&[ix], &owner).await);
+    let ix = schedule(&market, 2_000);
+    assert!(market.send(&[ix], &owner).await);
+    assert_eq!(market.store_info().await.fee_schedule.changes().len(), 1);
+
+    assert!(market.buy(0, 50, PRICE).await);
+    market.set_unix_timestamp(2_000).await;
+    // the fee is charged from the effective time on
+    assert!(!market.buy(0, 50, PRICE).await);
+    let user_payment_tokens = market.users[0].payment_tokens;
+    let before = market.token_balance(&user_payment_tokens).await;
+    let buy = instruction::buy_instruction(
+        50,
+        PRICE,
+        &market.program_id,
+        &user.pubkey(),
+        &market.store,
+        &market.proceeds,
+        &market.store_store_tokens,
+        &user_payment_tokens,
+        &market.users[0].store_tokens,
+        &market.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert!(
+        market
+            .send(&[instruction::with_fee_vault(buy, &fee_vault)], &user)
+            .await
+    );
+    assert_eq!(
+        market.token_balance(&user_payment_tokens).await,
+        before - 505
+    );
+    let store_info = market.store_info().await;
+    assert_eq!(store_info.fee_bps, 100);
+    assert!(store_info.fee_schedule.changes().is_empty());
+}
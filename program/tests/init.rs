@@ -0,0 +1,288 @@
+mod common;
+
+use common::{create_token_account, Market};
+use solana_program::{
+    bpf_loader_upgradeable, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+    system_instruction,
+};
+use solana_program_test::tokio;
+use solana_sdk::{account::Account, signature::Keypair, signer::Signer};
+use solana_test::{instruction, state::Store};
+use spl_token::state::{Account as SplAccount, Mint};
+
+#[tokio::test]
+async fn test_init_creates_vaults() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(0, 0, PRICE).await;
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let mints = [Keypair::new().pubkey(), Keypair::new().pubkey()];
+    for mint in mints.iter() {
+        let mut data = vec![0; Mint::LEN];
+        Mint {
+            mint_authority: COption::Some(owner.pubkey()),
+            is_initialized: true,
+            ..Mint::default()
+        }
+        .pack_into_slice(&mut data);
+        market.context.set_account(
+            mint,
+            &Account {
+                lamports: 1_000_000_000,
+                data,
+                owner: spl_token::id(),
+                ..Account::default()
+            }
+            .into(),
+        );
+    }
+
+    let store = Keypair::new();
+    let rent = market.banks_client.get_rent().await.unwrap();
+    let instructions = [
+        system_instruction::create_account(
+            &owner.pubkey(),
+            &store.pubkey(),
+            rent.minimum_balance(Store::LEN),
+            Store::LEN as u64,
+            &market.program_id,
+        ),
+        instruction::initialize_with_vaults_instruction(
+            PRICE,
+            &market.program_id,
+            &owner.pubkey(),
+            &store.pubkey(),
+            &mints[0],
+            &mints[1],
+            &spl_token::id(),
+        )
+        .unwrap(),
+    ];
+    let mut transaction = solana_sdk::transaction::Transaction::new_with_payer(
+        &instructions,
+        Some(&market.payer.pubkey()),
+    );
+    transaction.sign(&[&market.payer, &store, &owner], market.recent_blockhash);
+    market
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let account = market
+        .banks_client
+        .get_account(store.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    let store_info = Store::unpack(&account.data).unwrap();
+    let pda = instruction::find_store_authority_address(&market.program_id, &store.pubkey());
+    let vaults = [
+        store_info.native_tokens_to_auto_sell_pubkey,
+        store_info.store_tokens_to_auto_buy_pubkey,
+    ];
+    for (vault, mint) in vaults.iter().zip(mints.iter()) {
+        assert_eq!(
+            *vault,
+            instruction::find_vault_address(&market.program_id, &store.pubkey(), mint)
+        );
+        let account = market
+            .banks_client
+            .get_account(*vault)
+            .await
+            .unwrap()
+            .unwrap();
+        let vault_info = SplAccount::unpack(&account.data).unwrap();
+        assert_eq!(vault_info.owner, pda);
+        assert_eq!(vault_info.mint, *mint);
+    }
+    assert_eq!(store_info.payment_token_mint, mints[0]);
+}
+
+#[tokio::test]
+async fn test_closed_store_reinitialized_by_owner_only() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 100, PRICE).await;
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let user = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let close = instruction::close_store_instruction(
+        &market.program_id,
+        &owner.pubkey(),
+        &market.store,
+        &market.store_payment_tokens,
+        &market.store_store_tokens,
+        &market.pda,
+        &spl_token::id(),
+        &[],
+    )
+    .unwrap();
+    assert!(market.send(&[close], &owner).await);
+
+    let initialize = |owner: &Pubkey, payment_tokens, store_tokens| {
+        instruction::initialyze_account_instruction(
+            PRICE + 1,
+            &market.program_id,
+            owner,
+            &market.store,
+            payment_tokens,
+            store_tokens,
+            &spl_token::id(),
+        )
+        .unwrap()
+    };
+    let by_owner = initialize(
+        &owner.pubkey(),
+        &market.store_payment_tokens,
+        &market.store_store_tokens,
+    );
+    let by_user = initialize(
+        &user.pubkey(),
+        &market.users[0].payment_tokens,
+        &market.users[0].store_tokens,
+    );
+    let reactivate = instruction::reactivate_store_instruction(
+        &market.program_id,
+        &owner.pubkey(),
+        &market.store,
+    )
+    .unwrap();
+    // the tombstone rejects initialization until the owner reactivates the store
+    assert!(!market.send(&[by_owner.clone()], &owner).await);
+    assert!(market.send(&[reactivate], &owner).await);
+    assert!(!market.send(&[by_user], &user).await);
+    assert!(market.send(&[by_owner], &owner).await);
+    assert_eq!(market.store_info().await.buy_price, PRICE + 1);
+}
+
+#[tokio::test]
+async fn test_payment_mint_whitelist() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 100, PRICE).await;
+    let payer = Keypair::from_bytes(&market.payer.to_bytes()).unwrap();
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let program_id = market.program_id;
+    let store_info = market.store_info().await;
+
+    // only the upgrade authority initializes the config
+    set_program_data(&mut market, &payer.pubkey());
+    let init_config =
+        instruction::initialize_config_instruction(&program_id, &owner.pubkey()).unwrap();
+    assert!(!market.send(&[init_config], &owner).await);
+    let init_config =
+        instruction::initialize_config_instruction(&program_id, &payer.pubkey()).unwrap();
+    assert!(market.send(&[init_config], &payer).await);
+    let set_mints = |mints: &[Pubkey], authority: &Pubkey| {
+        instruction::set_payment_mints_instruction(mints, &program_id, authority).unwrap()
+    };
+    let other_mint = Pubkey::new_unique();
+    assert!(
+        !market
+            .send(&[set_mints(&[other_mint], &owner.pubkey())], &owner)
+            .await
+    );
+    assert!(
+        market
+            .send(&[set_mints(&[other_mint], &payer.pubkey())], &payer)
+            .await
+    );
+
+    let (payment_tokens, store_tokens) = (Pubkey::new_unique(), Pubkey::new_unique());
+    market.context.set_account(
+        &payment_tokens,
+        &create_token_account(owner.pubkey(), 0, store_info.payment_token_mint).into(),
+    );
+    market.context.set_account(
+        &store_tokens,
+        &create_token_account(owner.pubkey(), 0, store_info.store_token_mint).into(),
+    );
+    let store = Keypair::new();
+    let rent = market.banks_client.get_rent().await.unwrap();
+    // differently priced, so the retry is a different transaction
+    let initialize = |price, recent_blockhash| {
+        let mut transaction = solana_sdk::transaction::Transaction::new_with_payer(
+            &[
+                system_instruction::create_account(
+                    &owner.pubkey(),
+                    &store.pubkey(),
+                    rent.minimum_balance(Store::LEN),
+                    Store::LEN as u64,
+                    &program_id,
+                ),
+                instruction::initialyze_account_instruction(
+                    price,
+                    &program_id,
+                    &owner.pubkey(),
+                    &store.pubkey(),
+                    &payment_tokens,
+                    &store_tokens,
+                    &spl_token::id(),
+                )
+                .unwrap(),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &store, &owner], recent_blockhash);
+        transaction
+    };
+    let rejected = initialize(PRICE, market.recent_blockhash);
+    assert!(market
+        .banks_client
+        .process_transaction(rejected)
+        .await
+        .is_err());
+
+    let allowed = [other_mint, store_info.payment_token_mint];
+    assert!(
+        market
+            .send(&[set_mints(&allowed, &payer.pubkey())], &payer)
+            .await
+    );
+    market
+        .banks_client
+        .process_transaction(initialize(PRICE + 1, market.recent_blockhash))
+        .await
+        .unwrap();
+
+    // the authority is handed over in two steps
+    let propose = instruction::propose_config_authority_instruction(
+        &program_id,
+        &payer.pubkey(),
+        &owner.pubkey(),
+    )
+    .unwrap();
+    assert!(market.send(&[propose], &payer).await);
+    let accept =
+        instruction::accept_config_authority_instruction(&program_id, &payer.pubkey()).unwrap();
+    assert!(!market.send(&[accept], &payer).await);
+    let accept =
+        instruction::accept_config_authority_instruction(&program_id, &owner.pubkey()).unwrap();
+    assert!(market.send(&[accept], &owner).await);
+    assert!(
+        !market
+            .send(&[set_mints(&[], &payer.pubkey())], &payer)
+            .await
+    );
+    assert!(
+        market
+            .send(&[set_mints(&[], &owner.pubkey())], &owner)
+            .await
+    );
+}
+
+/// Stores the program data account of an upgradeable deployment with `authority` to upgrade
+fn set_program_data(market: &mut Market, authority: &Pubkey) {
+    // UpgradeableLoaderState::ProgramData, the slot and the upgrade authority
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.push(1);
+    data.extend_from_slice(authority.as_ref());
+    let program_data = Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: bpf_loader_upgradeable::id(),
+        ..Account::default()
+    };
+    market.context.set_account(
+        &instruction::find_program_data_address(&market.program_id),
+        &program_data.into(),
+    );
+}
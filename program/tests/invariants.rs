@@ -0,0 +1,55 @@
+mod common;
+
+use common::{Market, Rng};
+use solana_program_test::tokio;
+
+const USERS: usize = 3;
+const INITIAL_TOKENS: u64 = 1_000_000;
+const PRICE: u64 = 7;
+
+/// Checks after each operation of a random trade sequence that tokens of each mint are
+/// conserved and the store counters reconcile with the transfers actually made
+#[tokio::test]
+async fn test_settlement_invariants() {
+    let mut market = Market::start(USERS, INITIAL_TOKENS, PRICE).await;
+    let totals = market.token_totals().await;
+    let (store_store_tokens, store_payment_tokens) =
+        (market.store_store_tokens, market.store_payment_tokens);
+    let (proceeds, payout) = (market.proceeds, market.payout);
+
+    let mut rng = Rng::new(0x5eed);
+    let (mut bought, mut sold) = (0, 0);
+    let (mut buy_notional, mut sell_notional) = (0, 0);
+    for _ in 0..40 {
+        let user = rng.below(USERS as u64) as usize;
+        let amount = 1 + rng.below(2_000);
+        if rng.below(2) == 0 {
+            if market.buy(user, amount, PRICE).await {
+                bought += amount;
+                buy_notional += amount * PRICE;
+            }
+        } else if market.sell(user, amount, PRICE).await {
+            sold += amount;
+            sell_notional += amount * PRICE;
+        }
+
+        assert_eq!(market.token_totals().await, totals);
+
+        let store = market.store_info().await;
+        assert_eq!(store.sold_volume, bought);
+        assert_eq!(store.bought_volume, sold);
+        assert_eq!(
+            market.token_balance(&store_store_tokens).await,
+            INITIAL_TOKENS - bought
+        );
+        assert_eq!(
+            market.token_balance(&store_payment_tokens).await,
+            INITIAL_TOKENS - sell_notional
+        );
+        assert_eq!(
+            market.token_balance(&proceeds).await,
+            INITIAL_TOKENS + buy_notional
+        );
+        assert_eq!(market.token_balance(&payout).await, INITIAL_TOKENS + sold);
+    }
+}
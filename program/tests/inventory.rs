@@ -0,0 +1,86 @@
+mod common;
+
+use common::Market;
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_test::instruction;
+
+#[tokio::test]
+async fn test_inventory_low_logged_when_threshold_crossed() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let user = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let set_thresholds = instruction::set_inventory_thresholds_instruction(
+        995,
+        996,
+        &market.program_id,
+        &owner.pubkey(),
+        &market.store,
+    )
+    .unwrap();
+    assert!(market.send(&[set_thresholds], &owner).await);
+
+    let buy = |market: &Market, amount| {
+        instruction::buy_instruction(
+            amount,
+            PRICE,
+            &market.program_id,
+            &user.pubkey(),
+            &market.store,
+            &market.proceeds,
+            &market.store_store_tokens,
+            &market.users[0].payment_tokens,
+            &market.users[0].store_tokens,
+            &market.pda,
+            &spl_token::id(),
+        )
+        .unwrap()
+    };
+    let sell = instruction::sell_instruction(
+        1,
+        PRICE,
+        &market.program_id,
+        &user.pubkey(),
+        &market.store,
+        &market.store_payment_tokens,
+        &market.payout,
+        &market.users[0].payment_tokens,
+        &market.users[0].store_tokens,
+        &market.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    let inventory_low = |logs: Option<Vec<String>>| {
+        logs.unwrap()
+            .into_iter()
+            .filter(|log| log.contains("InventoryLow:"))
+            .collect::<Vec<_>>()
+    };
+
+    // down to the threshold, not below it
+    let logs = market.send_logged(&[buy(&market, 5)], &user).await;
+    assert!(inventory_low(logs).is_empty());
+
+    let logs = market.send_logged(&[buy(&market, 6)], &user).await;
+    assert_eq!(
+        inventory_low(logs),
+        vec![format!(
+            "Program log: InventoryLow: store {} account {} amount 989 threshold 995",
+            market.store, market.store_store_tokens
+        )]
+    );
+
+    // already below the threshold
+    let logs = market.send_logged(&[buy(&market, 1)], &user).await;
+    assert!(inventory_low(logs).is_empty());
+
+    let logs = market.send_logged(&[sell], &user).await;
+    assert_eq!(
+        inventory_low(logs),
+        vec![format!(
+            "Program log: InventoryLow: store {} account {} amount 995 threshold 996",
+            market.store, market.store_payment_tokens
+        )]
+    );
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::{create_token_account, Market};
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::tokio;
+use solana_sdk::{account::Account, signature::Keypair, signer::Signer};
+use solana_test::{
+    instruction,
+    state::{Store, STORE_VERSION},
+};
+
+#[tokio::test]
+async fn test_migrate_unversioned_store() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let store = market.store;
+
+    // the layout before the version header is the current one without its first byte,
+    // such stores have neither a sell price nor the nonce of the store PDA
+    let account = market
+        .banks_client
+        .get_account(store)
+        .await
+        .unwrap()
+        .unwrap();
+    let current_info = Store::unpack(&account.data).unwrap();
+    let mut data = account.data.clone();
+    Store::pack(
+        Store {
+            sell_price: 0,
+            authority_nonce: 0,
+            ..current_info
+        },
+        &mut data,
+    )
+    .unwrap();
+    let rent = market.banks_client.get_rent().await.unwrap();
+    market.context.set_account(
+        &store,
+        &Account {
+            lamports: rent.minimum_balance(Store::LEN - 1),
+            data: data[1..].to_vec(),
+            ..account
+        }
+        .into(),
+    );
+
+    // and their token accounts are owned by the legacy PDA of all stores
+    let legacy_pda = Pubkey::find_program_address(&[b"store"], &market.program_id).0;
+    let store_accounts = [market.store_payment_tokens, market.store_store_tokens];
+    for token_account in store_accounts {
+        let account = market
+            .banks_client
+            .get_account(token_account)
+            .await
+            .unwrap()
+            .unwrap();
+        let token_info = spl_token::state::Account::unpack(&account.data).unwrap();
+        market.context.set_account(
+            &token_account,
+            &create_token_account(legacy_pda, token_info.amount, token_info.mint).into(),
+        );
+    }
+    assert!(!market.buy(0, 10, PRICE).await);
+
+    let migrate =
+        instruction::migrate_store_instruction(&market.program_id, &owner.pubkey(), &store)
+            .unwrap();
+    // only token accounts of the store are handed over
+    let stranger = Pubkey::new_unique();
+    market.context.set_account(
+        &stranger,
+        &create_token_account(legacy_pda, 0, current_info.store_token_mint).into(),
+    );
+    let ix = instruction::with_legacy_accounts(migrate.clone(), &[stranger]);
+    assert!(!market.send(&[ix], &owner).await);
+    let ix = instruction::with_legacy_accounts(migrate.clone(), &store_accounts);
+    assert!(market.send(&[ix], &owner).await);
+    for token_account in store_accounts {
+        let account = market
+            .banks_client
+            .get_account(token_account)
+            .await
+            .unwrap()
+            .unwrap();
+        let token_info = spl_token::state::Account::unpack(&account.data).unwrap();
+        assert_eq!(token_info.owner, market.pda);
+    }
+    let account = market
+        .banks_client
+        .get_account(store)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.data.len(), Store::LEN);
+    assert!(rent.is_exempt(account.lamports, Store::LEN));
+    let store_info = Store::unpack(&account.data).unwrap();
+    assert_eq!(store_info.version, STORE_VERSION);
+    assert_eq!(store_info.buy_price, PRICE);
+    assert_eq!(store_info.sell_price, store_info.buy_price);
+    assert_eq!(store_info.authority_nonce, current_info.authority_nonce);
+    assert_eq!(store_info.owner_pubkey, owner.pubkey());
+
+    // migrating the current layout changes nothing
+    assert!(market.send(&[migrate], &owner).await);
+    assert_eq!(market.store_info().await, store_info);
+    assert!(market.buy(0, 10, PRICE).await);
+}
@@ -0,0 +1,1709 @@
+//! Fast tests of instruction checks, running the processor directly without a runtime.
+//! CPIs are not executed here, token movements are covered by the program-test tests.
+
+use std::sync::Once;
+
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_pack::Pack,
+    program_stubs,
+    pubkey::Pubkey,
+    rent::Rent,
+};
+use solana_test::{
+    error::StoreError,
+    instruction::{self, BalanceAssertion, BalanceCheck, StoreInstruction},
+    metadata,
+    processor::Processor,
+    state::{
+        Depth, DiscountTier, EscrowRecord, Invoice, Offer, OrderSide, PriceFeed, ProgramVersion,
+        Store, CAPABILITY_CONSTANT_PRODUCT, CAPABILITY_PER_STORE_PDA, FEATURE_ESCROW,
+        FEATURE_INVOICES, FEATURE_MEMBERSHIP, FEATURE_STRICT_DECODING,
+    },
+    store_interface::{self, Price},
+};
+
+struct Stubs;
+
+impl program_stubs::SyscallStubs for Stubs {
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Clock) = Clock::default() };
+        0
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        0
+    }
+}
+
+struct TestAccount {
+    key: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    owner: Pubkey,
+}
+
+impl TestAccount {
+    fn new(key: Pubkey, data: Vec<u8>, owner: Pubkey) -> Self {
+        Self {
+            key,
+            lamports: 1_000_000_000,
+            data,
+            owner,
+        }
+    }
+
+    fn wallet(key: Pubkey) -> Self {
+        Self::new(key, vec![], solana_program::system_program::id())
+    }
+
+    fn store(key: Pubkey, store: Store, program_id: Pubkey) -> Self {
+        let mut data = vec![0; Store::LEN];
+        store.pack_into_slice(&mut data);
+        Self::new(key, data, program_id)
+    }
+
+    fn token(key: Pubkey, owner: Pubkey, amount: u64) -> Self {
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account {
+            mint: Pubkey::new_from_array([1; 32]),
+            owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..spl_token::state::Account::default()
+        }
+        .pack_into_slice(&mut data);
+        Self::new(key, data, spl_token::id())
+    }
+}
+
+/// Runs the instruction with `accounts` looked up by its account metas
+fn process(instruction: &Instruction, accounts: &mut [TestAccount]) -> ProgramResult {
+    static STUBS: Once = Once::new();
+    STUBS.call_once(|| {
+        program_stubs::set_syscall_stubs(Box::new(Stubs));
+    });
+
+    let mut infos = Vec::new();
+    let mut remaining: Vec<&mut TestAccount> = accounts.iter_mut().collect();
+    for meta in &instruction.accounts {
+        let index = remaining
+            .iter()
+            .position(|account| account.key == meta.pubkey)
+            .unwrap_or_else(|| panic!("missing account {}", meta.pubkey));
+        let account = remaining.swap_remove(index);
+        infos.push(AccountInfo::new(
+            &account.key,
+            meta.is_signer,
+            meta.is_writable,
+            &mut account.lamports,
+            &mut account.data,
+            &account.owner,
+            false,
+            0,
+        ));
+    }
+    Processor::process(&instruction.program_id, &infos, &instruction.data)
+}
+
+struct Setup {
+    program_id: Pubkey,
+    owner: Pubkey,
+    store: Pubkey,
+    user: Pubkey,
+    pda: Pubkey,
+    accounts: Vec<TestAccount>,
+}
+
+const PRICE: u64 = 5;
+
+fn setup(store_info: Store) -> Setup {
+    let program_id = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let store = Pubkey::new_unique();
+    let user = Pubkey::new_unique();
+    let (pda, nonce) = Pubkey::find_program_address(&[b"store", store.as_ref()], &program_id);
+    let store_info = Store {
+        is_initialized: true,
+        authority_nonce: nonce,
+        buy_price: PRICE,
+        sell_price: PRICE,
+        owner_pubkey: owner,
+        native_tokens_to_auto_sell_pubkey: key(1),
+        store_tokens_to_auto_buy_pubkey: key(2),
+        ..store_info
+    };
+
+    let accounts = vec![
+        TestAccount::wallet(owner),
+        TestAccount::wallet(user),
+        TestAccount::wallet(pda),
+        TestAccount::store(store, store_info, program_id),
+        TestAccount::token(key(1), owner, 1_000),
+        TestAccount::token(key(2), pda, 1_000),
+        TestAccount::token(key(3), user, 1_000),
+        TestAccount::token(key(4), user, 1_000),
+        TestAccount::new(spl_token::id(), vec![], Pubkey::default()),
+    ];
+    Setup {
+        program_id,
+        owner,
+        store,
+        user,
+        pda,
+        accounts,
+    }
+}
+
+fn key(n: u8) -> Pubkey {
+    Pubkey::new_from_array([n + 100; 32])
+}
+
+fn buy(setup: &Setup, amount: u64, price: u64) -> Instruction {
+    instruction::buy_instruction(
+        amount,
+        price,
+        &setup.program_id,
+        &setup.user,
+        &setup.store,
+        &key(1),
+        &key(2),
+        &key(3),
+        &key(4),
+        &setup.pda,
+        &spl_token::id(),
+    )
+    .unwrap()
+}
+
+fn store_info(setup: &Setup) -> Store {
+    let account = setup
+        .accounts
+        .iter()
+        .find(|account| account.key == setup.store)
+        .unwrap();
+    Store::unpack(&account.data).unwrap()
+}
+
+#[test]
+fn test_update_price() {
+    let mut setup = setup(Store::default());
+    let ix =
+        instruction::update_price_instruction(7, &setup.program_id, &setup.owner, &setup.store)
+            .unwrap();
+    process(&ix, &mut setup.accounts).unwrap();
+    assert_eq!(store_info(&setup).buy_price, 7);
+
+    let ix = instruction::update_price_instruction(9, &setup.program_id, &setup.user, &setup.store)
+        .unwrap();
+    assert!(process(&ix, &mut setup.accounts).is_err());
+    assert_eq!(store_info(&setup).buy_price, 7);
+}
+
+#[test]
+fn test_publish_depth_quotes_both_sides() {
+    let mut setup = setup(Store {
+        price_denominator: 10,
+        ..Store::default()
+    });
+    let depth = instruction::find_depth_address(&setup.program_id, &setup.store);
+    let mut depth_account = TestAccount::new(depth, vec![0; Depth::LEN], setup.program_id);
+    depth_account.lamports = 0;
+    setup.accounts.extend(vec![
+        depth_account,
+        TestAccount::new(
+            solana_program::system_program::id(),
+            vec![],
+            Pubkey::default(),
+        ),
+    ]);
+    let ix = instruction::publish_depth_instruction(
+        &setup.program_id,
+        &setup.user,
+        &setup.store,
+        &key(2),
+        &key(1),
+    )
+    .unwrap();
+    process(&ix, &mut setup.accounts).unwrap();
+
+    let account = setup
+        .accounts
+        .iter()
+        .find(|account| account.key == depth)
+        .unwrap();
+    let depth_info = Depth::unpack(&account.data).unwrap();
+    assert_eq!(
+        (
+            depth_info.bid_price,
+            depth_info.ask_price,
+            depth_info.price_denominator
+        ),
+        (PRICE, PRICE, 10)
+    );
+    assert_eq!(
+        (depth_info.ask_liquidity, depth_info.bid_liquidity),
+        (1_000, 1_000)
+    );
+}
+
+#[test]
+fn test_update_prices_sets_each_side() {
+    let mut setup = setup(Store::default());
+    let update = |buy_price, sell_price| {
+        instruction::update_prices_instruction(
+            buy_price,
+            sell_price,
+            &setup.program_id,
+            &setup.owner,
+            &setup.store,
+        )
+        .unwrap()
+    };
+    let (inverted, spread) = (update(7, 8), update(7, 6));
+    assert_eq!(
+        process(&inverted, &mut setup.accounts),
+        Err(ProgramError::InvalidArgument)
+    );
+    process(&spread, &mut setup.accounts).unwrap();
+    assert_eq!(store_info(&setup).buy_price, 7);
+    assert_eq!(store_info(&setup).sell_price, 6);
+
+    let sell_at_buy_price = instruction::sell_instruction(
+        10,
+        7,
+        &setup.program_id,
+        &setup.user,
+        &setup.store,
+        &key(1),
+        &key(2),
+        &key(3),
+        &key(4),
+        &setup.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert_eq!(
+        process(&sell_at_buy_price, &mut setup.accounts),
+        Err(StoreError::AccountPriceMismatch.into())
+    );
+    process(&buy(&setup, 10, 7), &mut setup.accounts).unwrap();
+}
+
+#[test]
+fn test_withdraw_from_store_accounts_only() {
+    let mut setup = setup(Store::default());
+    let withdraw = |account| {
+        instruction::withdraw_instruction(
+            10,
+            &setup.program_id,
+            &setup.owner,
+            &setup.store,
+            account,
+            &key(3),
+            &setup.pda,
+            &spl_token::id(),
+        )
+        .unwrap()
+    };
+    let (from_store, from_user) = (withdraw(&key(2)), withdraw(&key(4)));
+    process(&from_store, &mut setup.accounts).unwrap();
+    assert_eq!(
+        process(&from_user, &mut setup.accounts),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn test_unknown_pricing_engine_rejects_trades() {
+    let mut setup = setup(Store {
+        pricing_engine: 1,
+        ..Store::default()
+    });
+    let ix = buy(&setup, 10, PRICE);
+    assert_eq!(
+        process(&ix, &mut setup.accounts),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn test_deposit_checks_mint() {
+    let mut setup = setup(Store::default());
+    let mut other_mint = TestAccount::token(key(5), setup.owner, 1_000);
+    let mut token_info = spl_token::state::Account::unpack(&other_mint.data).unwrap();
+    token_info.mint = Pubkey::new_unique();
+    token_info.pack_into_slice(&mut other_mint.data);
+    setup.accounts.extend(vec![
+        other_mint,
+        TestAccount::token(key(6), setup.owner, 1_000),
+    ]);
+
+    let deposit = |source| {
+        instruction::deposit_instruction(
+            10,
+            OrderSide::Buy,
+            &setup.program_id,
+            &setup.owner,
+            &setup.store,
+            source,
+            &key(2),
+            &spl_token::id(),
+        )
+        .unwrap()
+    };
+    let (same_mint, wrong_mint) = (deposit(&key(6)), deposit(&key(5)));
+    process(&same_mint, &mut setup.accounts).unwrap();
+    assert_eq!(
+        process(&wrong_mint, &mut setup.accounts),
+        Err(StoreError::MintMismatch.into())
+    );
+}
+
+#[test]
+fn test_buy_checks_store_mints() {
+    let mint = Pubkey::new_from_array([1; 32]);
+    let mut setup = setup(Store {
+        store_token_mint: mint,
+        payment_token_mint: mint,
+        ..Store::default()
+    });
+    let mut other_mint = TestAccount::token(key(5), setup.user, 0);
+    let mut token_info = spl_token::state::Account::unpack(&other_mint.data).unwrap();
+    token_info.mint = Pubkey::new_unique();
+    token_info.pack_into_slice(&mut other_mint.data);
+    setup.accounts.push(other_mint);
+
+    let wrong_mint = instruction::buy_instruction(
+        1,
+        PRICE,
+        &setup.program_id,
+        &setup.user,
+        &setup.store,
+        &key(1),
+        &key(2),
+        &key(3),
+        &key(5),
+        &setup.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert_eq!(
+        process(&wrong_mint, &mut setup.accounts),
+        Err(StoreError::MintMismatch.into())
+    );
+    process(&buy(&setup, 1, PRICE), &mut setup.accounts).unwrap();
+}
+
+#[test]
+fn test_set_gate_mint_requires_feature() {
+    let mut setup = setup(Store::default());
+    let ix = instruction::set_gate_mint_instruction(
+        &setup.program_id,
+        &setup.owner,
+        &setup.store,
+        &key(1),
+    )
+    .unwrap();
+    assert_eq!(
+        process(&ix, &mut setup.accounts),
+        Err(StoreError::FeatureDisabled.into())
+    );
+
+    let enable = instruction::set_features_instruction(
+        FEATURE_MEMBERSHIP,
+        &setup.program_id,
+        &setup.owner,
+        &setup.store,
+    )
+    .unwrap();
+    process(&enable, &mut setup.accounts).unwrap();
+    process(&ix, &mut setup.accounts).unwrap();
+}
+
+#[test]
+fn test_owner_rotation() {
+    let mut setup = setup(Store::default());
+    let (old_owner, new_owner) = (setup.owner, setup.user);
+    let accept =
+        instruction::accept_owner_instruction(&setup.program_id, &new_owner, &setup.store).unwrap();
+    assert_eq!(
+        process(&accept, &mut setup.accounts),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    let propose = instruction::propose_owner_instruction(
+        &setup.program_id,
+        &old_owner,
+        &setup.store,
+        &new_owner,
+    )
+    .unwrap();
+    process(&propose, &mut setup.accounts).unwrap();
+    process(&accept, &mut setup.accounts).unwrap();
+    assert_eq!(store_info(&setup).owner_pubkey, new_owner);
+    assert_eq!(store_info(&setup).pending_owner_pubkey, Pubkey::default());
+
+    let update = |owner| {
+        instruction::update_price_instruction(PRICE, &setup.program_id, owner, &setup.store)
+            .unwrap()
+    };
+    let (old_update, new_update) = (update(&old_owner), update(&new_owner));
+    assert_eq!(
+        process(&old_update, &mut setup.accounts),
+        Err(ProgramError::InvalidAccountData)
+    );
+    process(&new_update, &mut setup.accounts).unwrap();
+}
+
+#[test]
+fn test_release_accounts_closes_store() {
+    let mut setup = setup(Store::default());
+    let release = |signer| {
+        instruction::release_accounts_instruction(
+            &setup.program_id,
+            signer,
+            &setup.store,
+            &key(1),
+            &key(2),
+            &setup.pda,
+            &spl_token::id(),
+        )
+        .unwrap()
+    };
+    let (by_user, by_owner) = (release(&setup.user), release(&setup.owner));
+    assert_eq!(
+        process(&by_user, &mut setup.accounts),
+        Err(ProgramError::InvalidAccountData)
+    );
+    process(&by_owner, &mut setup.accounts).unwrap();
+    assert!(store_info(&setup).is_closed);
+
+    let ix = buy(&setup, 10, PRICE);
+    assert_eq!(
+        process(&ix, &mut setup.accounts),
+        Err(StoreError::StoreClosed.into())
+    );
+}
+
+#[test]
+fn test_close_store() {
+    let mut setup = setup(Store::default());
+    let ix = instruction::close_store_instruction(
+        &setup.program_id,
+        &setup.owner,
+        &setup.store,
+        &key(1),
+        &key(2),
+        &setup.pda,
+        &spl_token::id(),
+        &[],
+    )
+    .unwrap();
+    process(&ix, &mut setup.accounts).unwrap();
+
+    let store_account = setup
+        .accounts
+        .iter()
+        .find(|account| account.key == setup.store)
+        .unwrap();
+    assert_eq!(
+        store_account.lamports,
+        Rent::default().minimum_balance(Store::LEN)
+    );
+    let tombstone = Store::unpack_unchecked(&store_account.data).unwrap();
+    assert!(tombstone.is_closed);
+    assert!(!tombstone.is_initialized);
+    assert_eq!(tombstone.owner_pubkey, setup.owner);
+    assert_eq!(
+        process(&ix, &mut setup.accounts),
+        Err(ProgramError::UninitializedAccount)
+    );
+
+    let reactivate = |signer| {
+        instruction::reactivate_store_instruction(&setup.program_id, signer, &setup.store).unwrap()
+    };
+    let (by_user, by_owner) = (reactivate(&setup.user), reactivate(&setup.owner));
+    assert_eq!(
+        process(&by_user, &mut setup.accounts),
+        Err(ProgramError::InvalidAccountData)
+    );
+    process(&by_owner, &mut setup.accounts).unwrap();
+    let store_account = setup
+        .accounts
+        .iter()
+        .find(|account| account.key == setup.store)
+        .unwrap();
+    let reserved = Store::unpack_unchecked(&store_account.data).unwrap();
+    assert!(!reserved.is_closed);
+    assert_eq!(reserved.owner_pubkey, setup.owner);
+    assert_eq!(
+        process(&by_owner, &mut setup.accounts),
+        Err(StoreError::StoreNotDeleted.into())
+    );
+}
+
+#[test]
+fn test_update_price_ratio_rounds_buy_up() {
+    let mut setup = setup(Store {
+        cost_basis_amount: 3,
+        cost_basis: 3,
+        ..Store::default()
+    });
+    let update = |denominator| {
+        instruction::update_price_ratio_instruction(
+            3,
+            3,
+            denominator,
+            &setup.program_id,
+            &setup.owner,
+            &setup.store,
+        )
+        .unwrap()
+    };
+    let (zero, halves) = (update(0), update(2));
+    assert_eq!(
+        process(&zero, &mut setup.accounts),
+        Err(ProgramError::InvalidArgument)
+    );
+    process(&halves, &mut setup.accounts).unwrap();
+    assert_eq!(store_info(&setup).denominator(), 2);
+
+    process(&buy(&setup, 3, 3), &mut setup.accounts).unwrap();
+    assert_eq!(store_info(&setup).realized_pnl, 5 - 3);
+}
+
+#[test]
+fn test_update_prices_batch() {
+    let mut setup = setup(Store::default());
+    let (own_store, foreign_store) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let store_of = |owner_pubkey| Store {
+        is_initialized: true,
+        owner_pubkey,
+        ..Store::default()
+    };
+    setup.accounts.extend(vec![
+        TestAccount::store(own_store, store_of(setup.owner), setup.program_id),
+        TestAccount::store(foreign_store, store_of(setup.user), setup.program_id),
+    ]);
+
+    let ix = instruction::update_prices_batch_instruction(
+        &setup.program_id,
+        &setup.owner,
+        &[(setup.store, 7), (own_store, 8)],
+    )
+    .unwrap();
+    process(&ix, &mut setup.accounts).unwrap();
+    assert_eq!(store_info(&setup).buy_price, 7);
+
+    let ix = instruction::update_prices_batch_instruction(
+        &setup.program_id,
+        &setup.owner,
+        &[(own_store, 9), (foreign_store, 9)],
+    )
+    .unwrap();
+    assert_eq!(
+        process(&ix, &mut setup.accounts),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn test_attest_price_chains_hashes() {
+    let mut setup = setup(Store::default());
+    let feed = instruction::find_price_feed_address(&setup.program_id, &setup.store);
+    setup.accounts.extend(vec![
+        TestAccount::new(feed, vec![0; PriceFeed::LEN], setup.program_id),
+        TestAccount::new(
+            solana_program::system_program::id(),
+            vec![],
+            Pubkey::default(),
+        ),
+    ]);
+    let feed_info = |setup: &Setup| {
+        let account = setup
+            .accounts
+            .iter()
+            .find(|account| account.key == feed)
+            .unwrap();
+        PriceFeed::unpack(&account.data).unwrap()
+    };
+
+    for price in [7, 8] {
+        let ix = instruction::attest_price_instruction(
+            price,
+            &setup.program_id,
+            &setup.owner,
+            &setup.store,
+        )
+        .unwrap();
+        process(&ix, &mut setup.accounts).unwrap();
+    }
+    // clock stub is always at slot 0
+    let mut expected = PriceFeed {
+        is_initialized: true,
+        store_pubkey: setup.store,
+        ..PriceFeed::default()
+    };
+    expected.attest(7, 0);
+    expected.attest(8, 0);
+    assert_eq!(feed_info(&setup), expected);
+    assert_eq!(store_info(&setup).buy_price, 8);
+}
+
+#[test]
+fn test_buy_rejects_price_above_max() {
+    let mut setup = setup(Store {
+        cost_basis_amount: 1,
+        ..Store::default()
+    });
+    let ix = buy(&setup, 1, PRICE - 1);
+    assert_eq!(
+        process(&ix, &mut setup.accounts),
+        Err(StoreError::AccountPriceMismatch.into())
+    );
+
+    process(&buy(&setup, 1, PRICE + 1), &mut setup.accounts).unwrap();
+    assert_eq!(store_info(&setup).realized_pnl, PRICE as i64);
+}
+
+#[test]
+fn test_buy_rejects_amount_off_lot() {
+    let mut setup = setup(Store {
+        lot_size: 10,
+        ..Store::default()
+    });
+    let ix = buy(&setup, 15, PRICE);
+    assert_eq!(
+        process(&ix, &mut setup.accounts),
+        Err(StoreError::InvalidLotAmount.into())
+    );
+}
+
+#[test]
+fn test_trade_rejects_notional_overflow() {
+    let mut setup = setup(Store::default());
+    let amount = u64::MAX / PRICE + 1;
+    let sell = instruction::sell_instruction(
+        amount,
+        PRICE,
+        &setup.program_id,
+        &setup.user,
+        &setup.store,
+        &key(1),
+        &key(2),
+        &key(3),
+        &key(4),
+        &setup.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    for ix in [buy(&setup, amount, PRICE), sell] {
+        assert_eq!(
+            process(&ix, &mut setup.accounts),
+            Err(StoreError::MathOverflow.into())
+        );
+    }
+    process(&buy(&setup, amount - 1, PRICE), &mut setup.accounts).unwrap();
+}
+
+#[test]
+fn test_buy_within_trade_limits() {
+    let mut setup = setup(Store::default());
+    let limits = instruction::set_trade_limits_instruction(
+        10,
+        Some(15),
+        &setup.program_id,
+        &setup.owner,
+        &setup.store,
+    )
+    .unwrap();
+    process(&limits, &mut setup.accounts).unwrap();
+
+    assert_eq!(
+        process(&buy(&setup, 11, PRICE), &mut setup.accounts),
+        Err(StoreError::TradeLimitExceeded.into())
+    );
+    process(&buy(&setup, 10, PRICE), &mut setup.accounts).unwrap();
+    assert_eq!(store_info(&setup).remaining_inventory, 5);
+    assert_eq!(
+        process(&buy(&setup, 6, PRICE), &mut setup.accounts),
+        Err(StoreError::InventoryExhausted.into())
+    );
+    process(&buy(&setup, 5, PRICE), &mut setup.accounts).unwrap();
+    assert_eq!(store_info(&setup).remaining_inventory, 0);
+}
+
+#[test]
+fn test_buy_within_trading_hours() {
+    let mut setup = setup(Store::default());
+    let set_hours = |hour: usize| {
+        let mut hours = [0; 21];
+        hours[hour / 8] |= 1 << (hour % 8);
+        instruction::set_trading_hours_instruction(
+            hours,
+            &setup.program_id,
+            &setup.owner,
+            &setup.store,
+        )
+        .unwrap()
+    };
+    // the test clock is at the unix epoch, Thursday 00:00 UTC
+    let (closed, open) = (set_hours(3 * 24 + 1), set_hours(3 * 24));
+    process(&closed, &mut setup.accounts).unwrap();
+    assert_eq!(
+        process(&buy(&setup, 1, PRICE), &mut setup.accounts),
+        Err(StoreError::OutsideTradingHours.into())
+    );
+    process(&open, &mut setup.accounts).unwrap();
+    process(&buy(&setup, 1, PRICE), &mut setup.accounts).unwrap();
+}
+
+#[test]
+fn test_guardian_pauses_only() {
+    let mut setup = setup(Store::default());
+    let guardian = Pubkey::new_unique();
+    setup.accounts.push(TestAccount::wallet(guardian));
+    let pause = |authority| {
+        instruction::pause_instruction(&setup.program_id, authority, &setup.store).unwrap()
+    };
+    let resume = |authority| {
+        instruction::resume_instruction(&setup.program_id, authority, &setup.store).unwrap()
+    };
+    let set_guardian = instruction::set_guardian_instruction(
+        &setup.program_id,
+        &setup.owner,
+        &setup.store,
+        &guardian,
+    )
+    .unwrap();
+    let (user_pause, guardian_pause) = (pause(&setup.user), pause(&guardian));
+    let (guardian_resume, owner_resume) = (resume(&guardian), resume(&setup.owner));
+
+    assert_eq!(
+        process(&guardian_pause, &mut setup.accounts),
+        Err(ProgramError::InvalidAccountData)
+    );
+    process(&set_guardian, &mut setup.accounts).unwrap();
+    assert_eq!(
+        process(&user_pause, &mut setup.accounts),
+        Err(ProgramError::InvalidAccountData)
+    );
+    process(&guardian_pause, &mut setup.accounts).unwrap();
+    assert_eq!(
+        process(&buy(&setup, 1, PRICE), &mut setup.accounts),
+        Err(StoreError::StorePaused.into())
+    );
+    assert_eq!(
+        process(&guardian_resume, &mut setup.accounts),
+        Err(ProgramError::InvalidAccountData)
+    );
+    process(&owner_resume, &mut setup.accounts).unwrap();
+    process(&buy(&setup, 1, PRICE), &mut setup.accounts).unwrap();
+}
+
+#[test]
+fn test_close_released_store_hands_over_fee_and_escrow_vaults() {
+    let mut setup = setup(Store {
+        is_closed: true,
+        fee_vault_pubkey: key(5),
+        escrow_pubkey: key(6),
+        ..Store::default()
+    });
+    setup.accounts.extend(vec![
+        TestAccount::token(key(5), setup.pda, 50),
+        TestAccount::token(key(6), setup.pda, 10),
+    ]);
+    let close = |vaults: &[Pubkey]| {
+        instruction::close_store_instruction(
+            &setup.program_id,
+            &setup.owner,
+            &setup.store,
+            &key(1),
+            &key(2),
+            &setup.pda,
+            &spl_token::id(),
+            vaults,
+        )
+        .unwrap()
+    };
+    // the tombstone doesn't keep the vaults, they are required
+    assert_eq!(
+        process(&close(&[]), &mut setup.accounts),
+        Err(ProgramError::NotEnoughAccountKeys)
+    );
+    // escrowed payments keep the store open
+    assert_eq!(
+        process(&close(&[key(5), key(6)]), &mut setup.accounts),
+        Err(StoreError::EscrowVaultNotEmpty.into())
+    );
+
+    setup.accounts.retain(|account| account.key != key(6));
+    setup
+        .accounts
+        .push(TestAccount::token(key(6), setup.pda, 0));
+    process(&close(&[key(5), key(6)]), &mut setup.accounts).unwrap();
+    let store_account = setup
+        .accounts
+        .iter()
+        .find(|account| account.key == setup.store)
+        .unwrap();
+    assert!(
+        !Store::unpack_unchecked(&store_account.data)
+            .unwrap()
+            .is_initialized
+    );
+}
+
+#[test]
+fn test_wind_down_rejects_buys_and_close_until_deadline() {
+    let mut setup = setup(Store::default());
+    let close = instruction::close_store_instruction(
+        &setup.program_id,
+        &setup.owner,
+        &setup.store,
+        &key(1),
+        &key(2),
+        &setup.pda,
+        &spl_token::id(),
+        &[],
+    )
+    .unwrap();
+    // the test clock is at the unix epoch
+    let wind_down = |deadline| {
+        instruction::begin_wind_down_instruction(
+            deadline,
+            &setup.program_id,
+            &setup.owner,
+            &setup.store,
+        )
+        .unwrap()
+    };
+    let (past, earlier, deadline) = (wind_down(0), wind_down(30), wind_down(60));
+    assert_eq!(
+        process(&past, &mut setup.accounts),
+        Err(ProgramError::InvalidArgument)
+    );
+    process(&deadline, &mut setup.accounts).unwrap();
+    assert_eq!(store_info(&setup).wind_down_deadline, 60);
+    // the deadline may only be extended
+    assert_eq!(
+        process(&earlier, &mut setup.accounts),
+        Err(ProgramError::InvalidArgument)
+    );
+    assert_eq!(
+        process(&buy(&setup, 1, PRICE), &mut setup.accounts),
+        Err(StoreError::StoreWindingDown.into())
+    );
+    assert_eq!(
+        process(&close, &mut setup.accounts),
+        Err(StoreError::StoreWindingDown.into())
+    );
+}
+
+#[test]
+fn test_wind_down_rejects_sells_after_deadline() {
+    let mut setup = setup(Store {
+        wind_down_deadline: -1,
+        ..Store::default()
+    });
+    let sell = instruction::sell_instruction(
+        1,
+        PRICE,
+        &setup.program_id,
+        &setup.user,
+        &setup.store,
+        &key(1),
+        &key(2),
+        &key(3),
+        &key(4),
+        &setup.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert_eq!(
+        process(&sell, &mut setup.accounts),
+        Err(StoreError::WindDownEnded.into())
+    );
+    let close = instruction::close_store_instruction(
+        &setup.program_id,
+        &setup.owner,
+        &setup.store,
+        &key(1),
+        &key(2),
+        &setup.pda,
+        &spl_token::id(),
+        &[],
+    )
+    .unwrap();
+    process(&close, &mut setup.accounts).unwrap();
+}
+
+#[test]
+fn test_strict_decoding() {
+    let mut setup = setup(Store::default());
+    let mut trailing = buy(&setup, 1, PRICE);
+    trailing.data.push(0);
+    process(&trailing, &mut setup.accounts).unwrap();
+
+    let set_features = instruction::set_features_instruction(
+        FEATURE_STRICT_DECODING,
+        &setup.program_id,
+        &setup.owner,
+        &setup.store,
+    )
+    .unwrap();
+    process(&set_features, &mut setup.accounts).unwrap();
+    assert_eq!(
+        process(&trailing, &mut setup.accounts),
+        Err(StoreError::NonCanonicalInstruction.into())
+    );
+    assert_eq!(
+        process(&buy(&setup, 0, PRICE), &mut setup.accounts),
+        Err(StoreError::ZeroAmount.into())
+    );
+    process(&buy(&setup, 1, PRICE), &mut setup.accounts).unwrap();
+    // the Borsh encoding is canonical too
+    let mut borsh = buy(&setup, 1, PRICE);
+    borsh.data = StoreInstruction::unpack(&borsh.data).unwrap().pack_borsh();
+    process(&borsh, &mut setup.accounts).unwrap();
+    // only the store the instruction names is checked
+    let mut version = instruction::get_version_instruction(&setup.program_id).unwrap();
+    version.data.push(0);
+    version
+        .accounts
+        .push(AccountMeta::new_readonly(setup.store, false));
+    process(&version, &mut setup.accounts).unwrap();
+}
+
+#[test]
+fn test_trade_rejects_substituted_store_accounts() {
+    let mut setup = setup(Store::default());
+    setup
+        .accounts
+        .push(TestAccount::token(key(5), setup.pda, 1_000));
+    let buy = instruction::buy_instruction(
+        1,
+        PRICE,
+        &setup.program_id,
+        &setup.user,
+        &setup.store,
+        &key(1),
+        &key(5),
+        &key(3),
+        &key(4),
+        &setup.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    let sell = instruction::sell_instruction(
+        1,
+        PRICE,
+        &setup.program_id,
+        &setup.user,
+        &setup.store,
+        &key(5),
+        &key(2),
+        &key(3),
+        &key(4),
+        &setup.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    for ix in [buy, sell] {
+        assert_eq!(
+            process(&ix, &mut setup.accounts),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
+
+#[test]
+fn test_buy_rejects_migrated_store() {
+    let mut setup = setup(Store {
+        redirect_pubkey: Pubkey::new_unique(),
+        ..Store::default()
+    });
+    let ix = buy(&setup, 1, PRICE);
+    assert_eq!(
+        process(&ix, &mut setup.accounts),
+        Err(StoreError::StoreMigrated.into())
+    );
+}
+
+#[test]
+fn test_buy_requires_cosigner_above_threshold() {
+    let cosigner = Pubkey::new_unique();
+    let mut setup = setup(Store {
+        cosigner_pubkey: cosigner,
+        cosign_threshold: 10 * PRICE,
+        ..Store::default()
+    });
+    let ix = buy(&setup, 11, PRICE);
+    assert_eq!(
+        process(&ix, &mut setup.accounts),
+        Err(StoreError::CosignerRequired.into())
+    );
+}
+
+#[test]
+fn test_buy_rejects_foreign_settlement_account() {
+    let mut setup = setup(Store::default());
+    // user account instead of an owner account to receive payment
+    let ix = instruction::buy_instruction(
+        1,
+        PRICE,
+        &setup.program_id,
+        &setup.user,
+        &setup.store,
+        &key(3),
+        &key(2),
+        &key(3),
+        &key(4),
+        &setup.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    setup
+        .accounts
+        .push(TestAccount::token(key(3), setup.user, 0));
+    assert_eq!(
+        process(&ix, &mut setup.accounts),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn test_assert_balances() {
+    let mut setup = setup(Store::default());
+    let assertion = |check, amount| {
+        instruction::assert_balances_instruction(
+            &setup.program_id,
+            &[(key(3), BalanceAssertion { check, amount })],
+        )
+        .unwrap()
+    };
+    let (at_least, exactly, too_much) = (
+        assertion(BalanceCheck::AtLeast, 1_000),
+        assertion(BalanceCheck::Exactly, 1_000),
+        assertion(BalanceCheck::AtLeast, 1_001),
+    );
+
+    process(&at_least, &mut setup.accounts).unwrap();
+    process(&exactly, &mut setup.accounts).unwrap();
+    assert_eq!(
+        process(&too_much, &mut setup.accounts),
+        Err(StoreError::BalanceAssertionFailed.into())
+    );
+}
+
+#[test]
+fn test_store_interface() {
+    let mut setup = setup(Store {
+        lot_size: 10,
+        ..Store::default()
+    });
+    let program_id = setup.program_id;
+    let store = setup.store;
+    let mut accounts = setup.accounts.iter_mut();
+    let store_account = accounts.find(|account| account.key == store).unwrap();
+    let vault_account = accounts.find(|account| account.key == key(1)).unwrap();
+    let store_info = AccountInfo::new(
+        &store_account.key,
+        false,
+        false,
+        &mut store_account.lamports,
+        &mut store_account.data,
+        &store_account.owner,
+        false,
+        0,
+    );
+    let vault_info = AccountInfo::new(
+        &vault_account.key,
+        false,
+        false,
+        &mut vault_account.lamports,
+        &mut vault_account.data,
+        &vault_account.owner,
+        false,
+        0,
+    );
+
+    assert_eq!(
+        store_interface::get_price(&program_id, &store_info),
+        Ok(Price {
+            price: PRICE,
+            sell_price: PRICE,
+            price_denominator: 1,
+            lot_size: 10
+        })
+    );
+    assert_eq!(
+        store_interface::get_payment_mint(&program_id, &store_info, &vault_info),
+        Ok(Pubkey::new_from_array([1; 32]))
+    );
+    assert_eq!(
+        store_interface::get_store_mint(&program_id, &store_info, &vault_info),
+        Err(ProgramError::InvalidAccountData)
+    );
+    assert_eq!(
+        store_interface::get_price(&Pubkey::new_unique(), &store_info),
+        Err(ProgramError::IncorrectProgramId)
+    );
+}
+
+#[test]
+fn test_buy_requires_membership_of_gated_store() {
+    let gate_mint = Pubkey::new_from_array([1; 32]);
+    let mut setup = setup(Store {
+        gate_mint_pubkey: gate_mint,
+        ..Store::default()
+    });
+    let without_membership = buy(&setup, 1, PRICE);
+    assert_eq!(
+        process(&without_membership, &mut setup.accounts),
+        Err(StoreError::MembershipRequired.into())
+    );
+
+    let membership = Pubkey::new_unique();
+    setup
+        .accounts
+        .push(TestAccount::token(membership, setup.user, 1));
+    let with_membership = instruction::with_membership(buy(&setup, 1, PRICE), &membership);
+    process(&with_membership, &mut setup.accounts).unwrap();
+}
+
+/// Metaplex metadata account data of `mint` in verified `collection`
+fn nft_metadata(mint: &Pubkey, collection: &Pubkey) -> Vec<u8> {
+    let mut data = vec![4];
+    data.extend_from_slice(Pubkey::new_unique().as_ref());
+    data.extend_from_slice(mint.as_ref());
+    for len in [32u32, 10, 200] {
+        data.extend_from_slice(&len.to_le_bytes());
+        data.extend(std::iter::repeat(0).take(len as usize));
+    }
+    data.extend_from_slice(&500u16.to_le_bytes());
+    data.extend_from_slice(&[1, 1, 0, 0, 0]);
+    data.extend_from_slice(Pubkey::new_unique().as_ref());
+    data.extend_from_slice(&[1, 100]);
+    data.extend_from_slice(&[0, 1, 1, 255, 0, 1, 1]);
+    data.extend_from_slice(collection.as_ref());
+    data.resize(679, 0);
+    data
+}
+
+#[test]
+fn test_buy_with_collection_discount() {
+    let mut setup = setup(Store {
+        cost_basis_amount: 2,
+        cost_basis: 2,
+        ..Store::default()
+    });
+    let (nft, collection, holder) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let discount =
+        instruction::find_discount_tier_address(&setup.program_id, &setup.store, &collection);
+    let mut holder_account = TestAccount::token(holder, setup.user, 1);
+    spl_token::state::Account {
+        mint: nft,
+        ..spl_token::state::Account::unpack(&holder_account.data).unwrap()
+    }
+    .pack_into_slice(&mut holder_account.data);
+    let mut discount_data = vec![0; DiscountTier::LEN];
+    DiscountTier {
+        is_initialized: true,
+        store_pubkey: setup.store,
+        collection_pubkey: collection,
+        discount_bps: 5_000,
+    }
+    .pack_into_slice(&mut discount_data);
+    setup.accounts.extend(vec![
+        holder_account,
+        TestAccount::new(
+            metadata::find_metadata_address(&nft),
+            nft_metadata(&nft, &collection),
+            metadata::id(),
+        ),
+        TestAccount::new(discount, discount_data, setup.program_id),
+    ]);
+
+    let ix = instruction::with_discount(buy(&setup, 2, PRICE), &holder, &nft, &collection);
+    process(&ix, &mut setup.accounts).unwrap();
+    assert_eq!(store_info(&setup).realized_pnl, 5 - 2);
+
+    let other_collection = Pubkey::new_unique();
+    let mut other_discount_data = vec![0; DiscountTier::LEN];
+    DiscountTier {
+        is_initialized: true,
+        store_pubkey: setup.store,
+        collection_pubkey: other_collection,
+        discount_bps: 5_000,
+    }
+    .pack_into_slice(&mut other_discount_data);
+    setup.accounts.push(TestAccount::new(
+        instruction::find_discount_tier_address(&setup.program_id, &setup.store, &other_collection),
+        other_discount_data,
+        setup.program_id,
+    ));
+    let ix = instruction::with_discount(buy(&setup, 2, PRICE), &holder, &nft, &other_collection);
+    assert_eq!(
+        process(&ix, &mut setup.accounts),
+        Err(StoreError::InvalidDiscount.into())
+    );
+}
+
+#[test]
+fn test_escrowed_buy_refund() {
+    let mut setup = setup(Store {
+        features: FEATURE_ESCROW,
+        store_tokens_to_auto_buy_pubkey: key(2),
+        is_inventory_limited: true,
+        remaining_inventory: 10,
+        ..Store::default()
+    });
+    // the escrow rent is paid by another wallet than the one moving the tokens
+    let payer = Pubkey::new_unique();
+    let escrow_vault = key(5);
+    let escrow = instruction::find_escrow_address(&setup.program_id, &setup.store, 0);
+    let mut escrow_account = TestAccount::new(escrow, vec![0; EscrowRecord::LEN], setup.program_id);
+    escrow_account.lamports = 0;
+    setup.accounts.extend(vec![
+        TestAccount::token(escrow_vault, setup.pda, 0),
+        escrow_account,
+        TestAccount::wallet(payer),
+        TestAccount::new(
+            solana_program::system_program::id(),
+            vec![],
+            Pubkey::default(),
+        ),
+    ]);
+    let ix = instruction::set_escrow_instruction(
+        10,
+        &setup.program_id,
+        &setup.owner,
+        &setup.store,
+        Some(&escrow_vault),
+    )
+    .unwrap();
+    process(&ix, &mut setup.accounts).unwrap();
+
+    let ix = instruction::buy_instruction(
+        2,
+        PRICE,
+        &setup.program_id,
+        &setup.user,
+        &setup.store,
+        &escrow_vault,
+        &key(2),
+        &key(3),
+        &key(4),
+        &setup.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert_eq!(
+        process(&ix, &mut setup.accounts),
+        Err(ProgramError::NotEnoughAccountKeys)
+    );
+    process(
+        &instruction::with_escrow(ix, 0, &payer),
+        &mut setup.accounts,
+    )
+    .unwrap();
+    assert_eq!(store_info(&setup).escrow_sequence, 1);
+    assert_eq!(store_info(&setup).remaining_inventory, 8);
+
+    let escrow_info = {
+        let account = setup
+            .accounts
+            .iter()
+            .find(|account| account.key == escrow)
+            .unwrap();
+        EscrowRecord::unpack(&account.data).unwrap()
+    };
+    assert_eq!(
+        escrow_info,
+        EscrowRecord {
+            is_initialized: true,
+            store_pubkey: setup.store,
+            payer_pubkey: payer,
+            vault_pubkey: escrow_vault,
+            refund_pubkey: key(3),
+            tokens_pubkey: key(4),
+            amount: 2,
+            notional: 2 * PRICE,
+            fee: 0,
+            release_at: 10,
+        }
+    );
+
+    let release = instruction::release_escrow_instruction(
+        &setup.program_id,
+        &setup.store,
+        &escrow,
+        &escrow_info,
+        &key(1),
+        &setup.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert_eq!(
+        process(&release, &mut setup.accounts),
+        Err(StoreError::EscrowLocked.into())
+    );
+
+    let refund = instruction::refund_escrow_instruction(
+        &setup.program_id,
+        &setup.owner,
+        &setup.store,
+        &escrow,
+        &escrow_info,
+        &key(2),
+        &setup.pda,
+        &spl_token::id(),
+        None,
+    )
+    .unwrap();
+    process(&refund, &mut setup.accounts).unwrap();
+    let store = store_info(&setup);
+    assert_eq!(store.bought_volume, 2);
+    assert_eq!(store.remaining_inventory, 10);
+}
+
+#[test]
+fn test_sweep_escrow_after_escheat_period() {
+    let mut setup = setup(Store {
+        escheat_period: 200,
+        ..Store::default()
+    });
+    let (escrow, escrow_vault) = (key(6), key(5));
+    // the test clock is at the unix epoch, the refund window ended 100 seconds ago
+    let escrow_info = EscrowRecord {
+        is_initialized: true,
+        store_pubkey: setup.store,
+        payer_pubkey: setup.user,
+        vault_pubkey: escrow_vault,
+        refund_pubkey: key(3),
+        tokens_pubkey: key(4),
+        amount: 2,
+        notional: 2 * PRICE,
+        fee: 0,
+        release_at: -100,
+    };
+    let mut data = vec![0; EscrowRecord::LEN];
+    escrow_info.pack_into_slice(&mut data);
+    setup.accounts.extend(vec![
+        TestAccount::token(escrow_vault, setup.pda, 2 * PRICE),
+        TestAccount::new(escrow, data, setup.program_id),
+    ]);
+    let sweep = instruction::sweep_escrow_instruction(
+        &setup.program_id,
+        &setup.store,
+        &setup.owner,
+        &escrow,
+        &escrow_info,
+        &key(1),
+        &setup.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    let set_period = instruction::set_escheat_period_instruction(
+        50,
+        &setup.program_id,
+        &setup.owner,
+        &setup.store,
+    )
+    .unwrap();
+
+    assert_eq!(
+        process(&sweep, &mut setup.accounts),
+        Err(StoreError::EscheatPeriodNotElapsed.into())
+    );
+    process(&set_period, &mut setup.accounts).unwrap();
+    process(&sweep, &mut setup.accounts).unwrap();
+    let lamports = |key| {
+        setup
+            .accounts
+            .iter()
+            .find(|account| account.key == key)
+            .unwrap()
+            .lamports
+    };
+    // the escrow rent goes to the store owner
+    assert_eq!(lamports(escrow), 0);
+    assert_eq!(lamports(setup.owner), 2_000_000_000);
+}
+
+/// Adds the uncreated invoice account of the store and the system program
+fn add_invoice(setup: &mut Setup, reference: &[u8; 32]) -> Pubkey {
+    let invoice = instruction::find_invoice_address(&setup.program_id, &setup.store, reference);
+    let mut invoice_account = TestAccount::new(invoice, vec![0; Invoice::LEN], setup.program_id);
+    invoice_account.lamports = 0;
+    setup.accounts.extend(vec![
+        invoice_account,
+        TestAccount::new(
+            solana_program::system_program::id(),
+            vec![],
+            Pubkey::default(),
+        ),
+    ]);
+    invoice
+}
+
+/// Creates invoice of `amount` in the store and returns its PayInvoice
+fn create_invoice(setup: &mut Setup, amount: u64) -> Instruction {
+    let reference = [7; 32];
+    let invoice = add_invoice(setup, &reference);
+    let ix = instruction::create_invoice_instruction(
+        amount,
+        reference,
+        &setup.program_id,
+        &setup.owner,
+        &setup.store,
+    )
+    .unwrap();
+    process(&ix, &mut setup.accounts).unwrap();
+
+    instruction::pay_invoice_instruction(
+        &setup.program_id,
+        &setup.user,
+        &setup.store,
+        &invoice,
+        &key(1),
+        &key(2),
+        &key(3),
+        &key(4),
+        &setup.pda,
+        &spl_token::id(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_invoice_is_paid_once() {
+    let mut setup = setup(Store {
+        features: FEATURE_INVOICES,
+        store_tokens_to_auto_buy_pubkey: key(2),
+        ..Store::default()
+    });
+    let pay = create_invoice(&mut setup, 3);
+    process(&pay, &mut setup.accounts).unwrap();
+    let store = store_info(&setup);
+    assert_eq!(store.sold_volume, 3);
+    assert_eq!(store.trade_count, 1);
+    assert_eq!(
+        process(&pay, &mut setup.accounts),
+        Err(StoreError::InvoicePaid.into())
+    );
+}
+
+#[test]
+fn test_invoice_payment_rejected_during_wind_down() {
+    let mut setup = setup(Store {
+        features: FEATURE_INVOICES,
+        store_tokens_to_auto_buy_pubkey: key(2),
+        ..Store::default()
+    });
+    let pay = create_invoice(&mut setup, 3);
+    let wind_down =
+        instruction::begin_wind_down_instruction(60, &setup.program_id, &setup.owner, &setup.store)
+            .unwrap();
+    process(&wind_down, &mut setup.accounts).unwrap();
+    assert_eq!(
+        process(&pay, &mut setup.accounts),
+        Err(StoreError::StoreWindingDown.into())
+    );
+    assert_eq!(store_info(&setup).sold_volume, 0);
+}
+
+#[test]
+fn test_invoice_payment_rejected_past_inventory() {
+    let mut setup = setup(Store {
+        features: FEATURE_INVOICES,
+        store_tokens_to_auto_buy_pubkey: key(2),
+        ..Store::default()
+    });
+    let pay = create_invoice(&mut setup, 3);
+    let limits = instruction::set_trade_limits_instruction(
+        0,
+        Some(2),
+        &setup.program_id,
+        &setup.owner,
+        &setup.store,
+    )
+    .unwrap();
+    process(&limits, &mut setup.accounts).unwrap();
+    assert_eq!(
+        process(&pay, &mut setup.accounts),
+        Err(StoreError::InventoryExhausted.into())
+    );
+    assert_eq!(store_info(&setup).remaining_inventory, 2);
+}
+
+#[test]
+fn test_invoice_payment_rejected_until_settled() {
+    // the test clock is at the unix epoch, past the cutoff of the unsettled day
+    let mut setup = setup(Store {
+        features: FEATURE_INVOICES,
+        store_tokens_to_auto_buy_pubkey: key(2),
+        is_settling_daily: true,
+        settled_day: -1,
+        ..Store::default()
+    });
+    let pay = create_invoice(&mut setup, 3);
+    assert_eq!(
+        process(&pay, &mut setup.accounts),
+        Err(StoreError::SettlementPending.into())
+    );
+    assert_eq!(store_info(&setup).sold_volume, 0);
+}
+
+#[test]
+fn test_invoice_within_trade_limits() {
+    let mut setup = setup(Store {
+        features: FEATURE_INVOICES,
+        max_trade_amount: 5,
+        is_inventory_limited: true,
+        remaining_inventory: 4,
+        ..Store::default()
+    });
+    add_invoice(&mut setup, &[7; 32]);
+    let create = |amount| {
+        instruction::create_invoice_instruction(
+            amount,
+            [7; 32],
+            &setup.program_id,
+            &setup.owner,
+            &setup.store,
+        )
+        .unwrap()
+    };
+    let (over_limit, over_inventory) = (create(6), create(5));
+    assert_eq!(
+        process(&over_limit, &mut setup.accounts),
+        Err(StoreError::TradeLimitExceeded.into())
+    );
+    assert_eq!(
+        process(&over_inventory, &mut setup.accounts),
+        Err(StoreError::InventoryExhausted.into())
+    );
+}
+
+#[test]
+fn test_buy_offer_accepted_once() {
+    let mut setup = setup(Store::default());
+    let offer = instruction::find_offer_address(&setup.program_id, &key(5));
+    let mut offer_account = TestAccount::new(offer, vec![0; Offer::LEN], setup.program_id);
+    offer_account.lamports = 0;
+    setup.accounts.extend(vec![
+        offer_account,
+        TestAccount::token(key(5), setup.user, 0),
+        TestAccount::token(key(6), setup.owner, 0),
+        TestAccount::new(
+            solana_program::system_program::id(),
+            vec![],
+            Pubkey::default(),
+        ),
+    ]);
+    let create = |expires_at| {
+        instruction::create_buy_offer_instruction(
+            2,
+            3,
+            expires_at,
+            &setup.program_id,
+            &setup.user,
+            &key(5),
+            &key(3),
+            &key(4),
+            &spl_token::id(),
+        )
+        .unwrap()
+    };
+    let (expired, create) = (create(0), create(100));
+    assert_eq!(
+        process(&expired, &mut setup.accounts),
+        Err(StoreError::OfferExpired.into())
+    );
+    process(&create, &mut setup.accounts).unwrap();
+
+    let offer_account = setup
+        .accounts
+        .iter()
+        .find(|account| account.key == offer)
+        .unwrap();
+    let offer_info = Offer::unpack(&offer_account.data).unwrap();
+    assert_eq!(offer_info.maker_pubkey, setup.user);
+    assert_eq!(offer_info.receive_pubkey, key(4));
+    assert_eq!(offer_info.notional(), Ok(6));
+
+    let accept = instruction::accept_offer_instruction(
+        &setup.program_id,
+        &setup.owner,
+        &offer_info,
+        &key(1),
+        &key(6),
+        &spl_token::id(),
+    )
+    .unwrap();
+    process(&accept, &mut setup.accounts).unwrap();
+    assert_eq!(
+        process(&accept, &mut setup.accounts),
+        Err(ProgramError::UninitializedAccount)
+    );
+}
+
+#[test]
+fn test_get_version() {
+    let ix = instruction::get_version_instruction(&Pubkey::new_unique()).unwrap();
+    process(&ix, &mut []).unwrap();
+
+    let version = ProgramVersion::current();
+    assert_eq!(
+        format!("{}.{}.{}", version.major, version.minor, version.patch),
+        env!("CARGO_PKG_VERSION")
+    );
+    assert!(version.has_capability(CAPABILITY_PER_STORE_PDA));
+    assert!(version.has_capability(CAPABILITY_CONSTANT_PRODUCT));
+    let mut data = vec![0; ProgramVersion::LEN];
+    version.pack_into_slice(&mut data);
+    assert_eq!(ProgramVersion::unpack_from_slice(&data), Ok(version));
+}
+
+#[test]
+fn test_buy_with_sol_instructions() {
+    let setup = setup(Store::default());
+    let temporary = Pubkey::new_unique();
+    let build = |amount| {
+        instruction::buy_with_sol_instructions(
+            amount,
+            PRICE,
+            &Rent::default(),
+            &setup.program_id,
+            &setup.user,
+            &setup.store,
+            &key(1),
+            &key(2),
+            &temporary,
+            &key(4),
+            &setup.pda,
+        )
+    };
+    assert_eq!(build(u64::MAX), Err(StoreError::MathOverflow.into()));
+
+    let instructions = build(10).unwrap();
+    assert_eq!(instructions.len(), 4);
+    assert_eq!(instructions[2].program_id, setup.program_id);
+    assert_eq!(instructions[2].accounts[4].pubkey, temporary);
+    assert_eq!(
+        instructions[3].accounts[0].pubkey, temporary,
+        "the temporary account is closed after the buy"
+    );
+}
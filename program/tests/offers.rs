@@ -0,0 +1,200 @@
+mod common;
+
+use common::Market;
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_test::{instruction, state::Offer};
+
+const INITIAL_TOKENS: u64 = 1_000;
+
+async fn offer_info(market: &mut Market, vault: &Pubkey) -> Option<Offer> {
+    let offer = instruction::find_offer_address(&market.program_id, vault);
+    let account = market.banks_client.get_account(offer).await.unwrap()?;
+    Some(Offer::unpack(&account.data).unwrap())
+}
+
+fn user_signer(market: &Market, user: usize) -> Keypair {
+    Keypair::from_bytes(&market.users[user].keypair.to_bytes()).unwrap()
+}
+
+#[tokio::test]
+async fn test_sell_offer_accepted() {
+    let mut market = Market::start(2, INITIAL_TOKENS, 10).await;
+    let (maker, taker) = (&market.users[0], &market.users[1]);
+    let (vault, maker_store_tokens, maker_payment_tokens) = (
+        maker.spare_store_tokens,
+        maker.store_tokens,
+        maker.payment_tokens,
+    );
+    let (taker_pubkey, taker_payment_tokens, taker_store_tokens) = (
+        taker.keypair.pubkey(),
+        taker.payment_tokens,
+        taker.store_tokens,
+    );
+
+    let create = instruction::create_sell_offer_instruction(
+        100,
+        3,
+        i64::MAX,
+        &market.program_id,
+        &maker.keypair.pubkey(),
+        &vault,
+        &maker_store_tokens,
+        &maker_payment_tokens,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert!(market.send(&[create], &user_signer(&market, 0)).await);
+    assert_eq!(market.token_balance(&vault).await, 100);
+    assert_eq!(
+        market.token_balance(&maker_store_tokens).await,
+        INITIAL_TOKENS - 100
+    );
+
+    let offer = offer_info(&mut market, &vault).await.unwrap();
+    let accept = instruction::accept_offer_instruction(
+        &market.program_id,
+        &taker_pubkey,
+        &offer,
+        &taker_payment_tokens,
+        &taker_store_tokens,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert!(
+        market
+            .send(&[accept.clone()], &user_signer(&market, 1))
+            .await
+    );
+    assert_eq!(
+        market.token_balance(&taker_store_tokens).await,
+        INITIAL_TOKENS + 100
+    );
+    assert_eq!(
+        market.token_balance(&taker_payment_tokens).await,
+        INITIAL_TOKENS - 300
+    );
+    assert_eq!(
+        market.token_balance(&maker_payment_tokens).await,
+        INITIAL_TOKENS + 300
+    );
+    assert!(offer_info(&mut market, &vault).await.is_none());
+    assert!(!market.send(&[accept], &user_signer(&market, 1)).await);
+}
+
+#[tokio::test]
+async fn test_buy_offer_canceled() {
+    let mut market = Market::start(2, INITIAL_TOKENS, 10).await;
+    let maker = &market.users[0];
+    let (vault, maker_payment_tokens, maker_store_tokens) = (
+        maker.spare_payment_tokens,
+        maker.payment_tokens,
+        maker.store_tokens,
+    );
+    let taker_store_tokens = market.users[1].store_tokens;
+    let taker_payment_tokens = market.users[1].payment_tokens;
+    let taker_pubkey = market.users[1].keypair.pubkey();
+
+    let create = instruction::create_buy_offer_instruction(
+        10,
+        2,
+        i64::MAX,
+        &market.program_id,
+        &market.users[0].keypair.pubkey(),
+        &vault,
+        &maker_payment_tokens,
+        &maker_store_tokens,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert!(market.send(&[create], &user_signer(&market, 0)).await);
+    assert_eq!(market.token_balance(&vault).await, 20);
+
+    let offer = offer_info(&mut market, &vault).await.unwrap();
+    let cancel = instruction::cancel_offer_instruction(
+        &market.program_id,
+        &offer,
+        &maker_payment_tokens,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert!(market.send(&[cancel], &user_signer(&market, 0)).await);
+    assert_eq!(
+        market.token_balance(&maker_payment_tokens).await,
+        INITIAL_TOKENS
+    );
+    assert!(offer_info(&mut market, &vault).await.is_none());
+
+    let accept = instruction::accept_offer_instruction(
+        &market.program_id,
+        &taker_pubkey,
+        &offer,
+        &taker_store_tokens,
+        &taker_payment_tokens,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert!(!market.send(&[accept], &user_signer(&market, 1)).await);
+}
+
+#[tokio::test]
+async fn test_dusted_offer_canceled() {
+    let mut market = Market::start(2, INITIAL_TOKENS, 10).await;
+    let maker = &market.users[0];
+    let (vault, maker_store_tokens, maker_payment_tokens) = (
+        maker.spare_store_tokens,
+        maker.store_tokens,
+        maker.payment_tokens,
+    );
+    let other_store_tokens = market.users[1].store_tokens;
+    let other_pubkey = market.users[1].keypair.pubkey();
+
+    let create = instruction::create_sell_offer_instruction(
+        100,
+        3,
+        i64::MAX,
+        &market.program_id,
+        &market.users[0].keypair.pubkey(),
+        &vault,
+        &maker_store_tokens,
+        &maker_payment_tokens,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert!(market.send(&[create], &user_signer(&market, 0)).await);
+
+    // anyone can send tokens to the vault, they must not keep it from closing
+    let dust = spl_token::instruction::transfer(
+        &spl_token::id(),
+        &other_store_tokens,
+        &vault,
+        &other_pubkey,
+        &[],
+        1,
+    )
+    .unwrap();
+    assert!(market.send(&[dust], &user_signer(&market, 1)).await);
+    assert_eq!(market.token_balance(&vault).await, 101);
+
+    let offer = offer_info(&mut market, &vault).await.unwrap();
+    let cancel = instruction::cancel_offer_instruction(
+        &market.program_id,
+        &offer,
+        &maker_store_tokens,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert!(market.send(&[cancel], &user_signer(&market, 0)).await);
+    assert_eq!(
+        market.token_balance(&maker_store_tokens).await,
+        INITIAL_TOKENS + 1
+    );
+    assert!(offer_info(&mut market, &vault).await.is_none());
+    assert!(market
+        .banks_client
+        .get_account(vault)
+        .await
+        .unwrap()
+        .is_none());
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use common::Market;
+use solana_program::{clock::Clock, program_pack::Pack};
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_test::{
+    instruction,
+    state::{PnlReport, Valuation, VALUATION_STORE_PRICE},
+};
+
+#[tokio::test]
+async fn test_report_pnl_after_buy_and_sell() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let payer = Keypair::from_bytes(&market.payer.to_bytes()).unwrap();
+    let report = instruction::report_pnl_instruction(&market.program_id, &market.store).unwrap();
+
+    // the store buys 10 for 50, then sells 4 of them for 28 with cost 20
+    assert!(market.sell(0, 10, PRICE).await);
+    assert!(market.update_price(7).await);
+    assert!(market.buy(0, 4, 7).await);
+
+    let pnl = PnlReport::unpack_from_slice(&market.return_data(&[report], &payer).await).unwrap();
+    assert_eq!(
+        pnl,
+        PnlReport {
+            realized_pnl: 28 - 20,
+            unrealized_pnl: 6 * 7 - 30,
+            bought_volume: 10,
+            sold_volume: 4,
+            cost_basis_amount: 6,
+            cost_basis: 30,
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_valuation_feeds_report_pnl() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let payer = Keypair::from_bytes(&market.payer.to_bytes()).unwrap();
+    assert!(market.sell(0, 10, PRICE).await);
+
+    let epoch = market
+        .banks_client
+        .get_sysvar::<Clock>()
+        .await
+        .unwrap()
+        .epoch;
+    let record = instruction::record_valuation_instruction(
+        epoch,
+        &market.program_id,
+        &payer.pubkey(),
+        &market.store,
+        &market.store_store_tokens,
+        &market.store_payment_tokens,
+        false,
+    )
+    .unwrap();
+    assert!(market.send(&[record.clone()], &payer).await);
+    // recorded once per epoch, a later price doesn't change it
+    assert!(market.update_price(7).await);
+    assert!(!market.send(&[record], &payer).await);
+
+    let valuation_pubkey =
+        instruction::find_valuation_address(&market.program_id, &market.store, epoch);
+    let account = market
+        .banks_client
+        .get_account(valuation_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    let valuation = Valuation::unpack(&account.data).unwrap();
+    let store_store_tokens = market.store_store_tokens;
+    let store_tokens = market.token_balance(&store_store_tokens).await;
+    assert_eq!(valuation.price, PRICE);
+    assert_eq!(valuation.source, VALUATION_STORE_PRICE);
+    assert_eq!(valuation.store_tokens, store_tokens);
+    assert_eq!(valuation.inventory_value, store_tokens * PRICE);
+    assert_eq!(valuation.unrealized_pnl, 0);
+
+    // the report marks the cost basis at the valuation price, not the current one
+    let report = instruction::with_valuation(
+        instruction::report_pnl_instruction(&market.program_id, &market.store).unwrap(),
+        &valuation_pubkey,
+    );
+    let pnl = PnlReport::unpack_from_slice(&market.return_data(&[report], &payer).await).unwrap();
+    assert_eq!(pnl.unrealized_pnl, 0);
+}
+
+#[tokio::test]
+async fn test_trade_statistics() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    assert!(market.buy(0, 10, PRICE).await);
+    market.warp_to_slot(100);
+    assert!(market.sell(0, 4, PRICE).await);
+
+    let store_info = market.store_info().await;
+    assert_eq!(store_info.total_buy_volume, 10 * PRICE);
+    assert_eq!(store_info.total_sell_volume, 4 * PRICE);
+    assert_eq!(store_info.trade_count, 2);
+    assert_eq!(store_info.last_trade_slot, 100);
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use common::Market;
+use solana_program::program_pack::Pack;
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_test::{
+    instruction,
+    state::{OrderSide, TradePreview},
+};
+
+#[tokio::test]
+async fn test_preview_trade_matches_trade() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let user = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let (user_payment_tokens, user_store_tokens) =
+        (market.users[0].payment_tokens, market.users[0].store_tokens);
+    let lot_size = instruction::update_lot_size_instruction(
+        10,
+        &market.program_id,
+        &owner.pubkey(),
+        &market.store,
+    )
+    .unwrap();
+    assert!(market.send(&[lot_size], &owner).await);
+
+    let preview = |market: &Market, side: OrderSide, amount: u64| {
+        let (store_payment_tokens, store_store_tokens) = match side {
+            OrderSide::Buy => (market.proceeds, market.store_store_tokens),
+            OrderSide::Sell => (market.store_payment_tokens, market.payout),
+        };
+        instruction::preview_trade_instruction(
+            side,
+            amount,
+            PRICE,
+            &market.program_id,
+            &user.pubkey(),
+            &market.store,
+            &store_payment_tokens,
+            &store_store_tokens,
+            &user_payment_tokens,
+            &user_store_tokens,
+            &market.pda,
+            &spl_token::id(),
+        )
+        .unwrap()
+    };
+    let (preview_buy, preview_sell, preview_off_lot) = (
+        preview(&market, OrderSide::Buy, 200),
+        preview(&market, OrderSide::Sell, 200),
+        preview(&market, OrderSide::Buy, 15),
+    );
+    let mut bad_side = preview_buy.clone();
+    bad_side.data[1] = 2;
+
+    let buy_preview =
+        TradePreview::unpack_from_slice(&market.return_data(&[preview_buy], &user).await).unwrap();
+    let (payment_tokens, store_tokens) = (
+        market.token_balance(&user_payment_tokens).await,
+        market.token_balance(&user_store_tokens).await,
+    );
+    assert!(market.buy(0, 200, PRICE).await);
+    assert_eq!(
+        (buy_preview.user_pays, buy_preview.user_receives),
+        (
+            payment_tokens - market.token_balance(&user_payment_tokens).await,
+            market.token_balance(&user_store_tokens).await - store_tokens
+        )
+    );
+    assert_eq!(buy_preview.user_pays, 1_000);
+
+    let sell_preview =
+        TradePreview::unpack_from_slice(&market.return_data(&[preview_sell], &user).await).unwrap();
+    let (payment_tokens, store_tokens) = (
+        market.token_balance(&user_payment_tokens).await,
+        market.token_balance(&user_store_tokens).await,
+    );
+    assert!(market.sell(0, 200, PRICE).await);
+    assert_eq!(
+        (sell_preview.user_pays, sell_preview.user_receives),
+        (
+            store_tokens - market.token_balance(&user_store_tokens).await,
+            market.token_balance(&user_payment_tokens).await - payment_tokens
+        )
+    );
+    assert!(!sell_preview.cosigner_required);
+
+    assert!(!market.send(&[preview_off_lot], &user).await);
+    assert!(!market.buy(0, 15, PRICE).await);
+    assert!(!market.send(&[bad_side], &user).await);
+}
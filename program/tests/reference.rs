@@ -0,0 +1,79 @@
+mod common;
+
+use common::Market;
+use solana_program::pubkey::Pubkey;
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_test::instruction;
+
+#[tokio::test]
+async fn test_buy_with_reference_logs_it() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let user = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let user_store_tokens = market.users[0].store_tokens;
+    let reference = Pubkey::new_unique();
+    let buy = instruction::buy_instruction(
+        2,
+        PRICE,
+        &market.program_id,
+        &user.pubkey(),
+        &market.store,
+        &market.proceeds,
+        &market.store_store_tokens,
+        &market.users[0].payment_tokens,
+        &user_store_tokens,
+        &market.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    let referenced = instruction::with_reference(buy, &reference).unwrap();
+
+    let logs = market.send_logged(&[referenced.clone()], &user).await;
+    assert!(logs.unwrap().contains(&format!(
+        "Program log: ReferencedTrade: store {} side buy amount 2 notional 10 reference {}",
+        market.store, reference
+    )));
+    assert_eq!(market.token_balance(&user_store_tokens).await, 1_002);
+
+    // the reference is no store account, sent without it the trade is rejected
+    let mut unreferenced = referenced;
+    unreferenced.accounts.remove(0);
+    assert!(!market.send(&[unreferenced], &user).await);
+    assert_eq!(market.token_balance(&user_store_tokens).await, 1_002);
+}
+
+#[tokio::test]
+async fn test_tagged_sell_logs_tag() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let user = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let user_payment_tokens = market.users[0].payment_tokens;
+    let sell = instruction::sell_instruction(
+        2,
+        PRICE,
+        &market.program_id,
+        &user.pubkey(),
+        &market.store,
+        &market.store_payment_tokens,
+        &market.payout,
+        &user_payment_tokens,
+        &market.users[0].store_tokens,
+        &market.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    let tagged = instruction::with_tag(sell, 42).unwrap();
+
+    let before = market.token_balance(&user_payment_tokens).await;
+    let logs = market.send_logged(&[tagged], &user).await;
+    assert!(logs.unwrap().contains(&format!(
+        "Program log: TaggedTrade: store {} side sell amount 2 notional 10 tag 42",
+        market.store
+    )));
+    // settled the same as untagged
+    assert_eq!(
+        market.token_balance(&user_payment_tokens).await,
+        before + 10
+    );
+}
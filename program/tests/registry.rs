@@ -0,0 +1,115 @@
+mod common;
+
+use common::Market;
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_test::{
+    instruction,
+    state::{StoreRegistry, STORE_NAMESPACE_OFFSET},
+};
+
+#[tokio::test]
+async fn test_registry_lists_owner_stores() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(0, 1_000, PRICE).await;
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let (second_store, _, _) = market.add_store(1_000, PRICE).await;
+    let (third_store, _, _) = market.add_store(1_000, PRICE).await;
+
+    let program_id = market.program_id;
+    let register = |store| {
+        instruction::register_store_instruction(&program_id, &owner.pubkey(), store).unwrap()
+    };
+    let instructions = [
+        register(&market.store),
+        register(&second_store),
+        register(&third_store),
+    ];
+    assert!(market.send(&instructions, &owner).await);
+    // registered once
+    let again = register(&second_store);
+    assert!(!market.send(&[again], &owner).await);
+
+    let unregister = instruction::unregister_store_instruction(
+        &market.program_id,
+        &owner.pubkey(),
+        &market.store,
+    )
+    .unwrap();
+    assert!(market.send(&[unregister], &owner).await);
+
+    let registry_pubkey = instruction::find_registry_address(&market.program_id, &owner.pubkey());
+    let account = market
+        .banks_client
+        .get_account(registry_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    let registry = StoreRegistry::unpack(&account.data).unwrap();
+    assert_eq!(registry.owner_pubkey, owner.pubkey());
+    assert_eq!(registry.stores(), &[second_store, third_store]);
+}
+
+#[tokio::test]
+async fn test_namespaced_registry() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(0, 1_000, PRICE).await;
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let (partner_store, _, _) = market.add_store(1_000, PRICE).await;
+    let program_id = market.program_id;
+    let namespace = Pubkey::new_unique();
+    let set_namespace = instruction::set_namespace_instruction(
+        &namespace,
+        &program_id,
+        &owner.pubkey(),
+        &partner_store,
+    )
+    .unwrap();
+    assert!(market.send(&[set_namespace], &owner).await);
+
+    // the namespaced store goes to the registry of its namespace only
+    let register = |store| {
+        instruction::register_store_instruction(&program_id, &owner.pubkey(), store).unwrap()
+    };
+    let (register_store, register_partner_store) =
+        (register(&market.store), register(&partner_store));
+    assert!(!market.send(&[register_partner_store.clone()], &owner).await);
+    let register_partner_store =
+        instruction::with_registry_namespace(register_partner_store, &namespace);
+    assert!(
+        market
+            .send(&[register_store, register_partner_store], &owner)
+            .await
+    );
+
+    for (namespace, stores) in [
+        (Pubkey::default(), [market.store]),
+        (namespace, [partner_store]),
+    ] {
+        let registry_pubkey =
+            instruction::find_namespaced_registry_address(&program_id, &owner.pubkey(), &namespace);
+        let account = market
+            .banks_client
+            .get_account(registry_pubkey)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            StoreRegistry::unpack(&account.data).unwrap().stores(),
+            &stores
+        );
+    }
+
+    // discovery filters match the namespace at its offset
+    let account = market
+        .banks_client
+        .get_account(partner_store)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        &account.data[STORE_NAMESPACE_OFFSET..STORE_NAMESPACE_OFFSET + 32],
+        namespace.as_ref()
+    );
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use common::Market;
+use solana_program::{program_pack::Pack, pubkey::Pubkey, system_instruction};
+use solana_program_test::tokio;
+use solana_sdk::{account::Account, signature::Keypair, signer::Signer};
+use solana_test::{
+    instruction,
+    state::{Session, FEATURE_SESSIONS},
+};
+
+#[tokio::test]
+async fn test_session_trades_within_limit_until_expired_or_revoked() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let user = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let session_key = Keypair::new();
+    let payer = Keypair::from_bytes(&market.payer.to_bytes()).unwrap();
+    let funding = system_instruction::transfer(&payer.pubkey(), &user.pubkey(), 1_000_000_000);
+    assert!(market.send(&[funding], &payer).await);
+    let (foreign_store, _, foreign_store_tokens) = market.add_store(1_000, PRICE).await;
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let enable_sessions: Vec<_> = [market.store, foreign_store]
+        .iter()
+        .map(|store| {
+            instruction::set_features_instruction(
+                FEATURE_SESSIONS,
+                &market.program_id,
+                &owner.pubkey(),
+                store,
+            )
+            .unwrap()
+        })
+        .collect();
+    assert!(market.send(&enable_sessions, &owner).await);
+
+    let user_store_tokens = market.users[0].store_tokens;
+    let session = instruction::find_session_address(
+        &market.program_id,
+        &market.store,
+        &user.pubkey(),
+        &session_key.pubkey(),
+    );
+    let create = |market: &Market, expires_at| {
+        instruction::create_session_instruction(
+            20,
+            expires_at,
+            &market.program_id,
+            &user.pubkey(),
+            &user.pubkey(),
+            &market.store,
+            &session_key.pubkey(),
+        )
+        .unwrap()
+    };
+    let approve = instruction::approve_session_instruction(
+        1_000,
+        &market.program_id,
+        &user.pubkey(),
+        &market.store,
+        &session_key.pubkey(),
+        &market.users[0].payment_tokens,
+        &spl_token::id(),
+    )
+    .unwrap();
+    let session_buy = |market: &Market, amount, store: Pubkey, store_tokens: Pubkey| {
+        let mut ix = instruction::session_buy_instruction(
+            amount,
+            PRICE,
+            &market.program_id,
+            &session_key.pubkey(),
+            &user.pubkey(),
+            &store,
+            &market.proceeds,
+            &store_tokens,
+            &market.users[0].payment_tokens,
+            &market.users[0].store_tokens,
+            &instruction::find_store_authority_address(&market.program_id, &store),
+            &spl_token::id(),
+        )
+        .unwrap();
+        // the session of the market store, used with any store
+        ix.accounts[1].pubkey = session;
+        ix
+    };
+    let revoke = instruction::revoke_session_instruction(
+        &market.program_id,
+        &user.pubkey(),
+        &market.store,
+        &session_key.pubkey(),
+    )
+    .unwrap();
+    let session_info =
+        |account: Option<Account>| account.map(|account| Session::unpack(&account.data).unwrap());
+
+    market.set_unix_timestamp(100).await;
+    let ix = create(&market, 100);
+    assert!(!market.send(&[ix], &user).await);
+    let ix = create(&market, 200);
+    assert!(market.send(&[ix, approve], &user).await);
+
+    let ix = session_buy(&market, 3, market.store, market.store_store_tokens);
+    assert!(market.send(&[ix], &session_key).await);
+    assert_eq!(
+        session_info(market.banks_client.get_account(session).await.unwrap())
+            .unwrap()
+            .spent_notional,
+        15
+    );
+    assert_eq!(market.token_balance(&user_store_tokens).await, 1_003);
+    // above the notional limit
+    let ix = session_buy(&market, 2, market.store, market.store_store_tokens);
+    assert!(!market.send(&[ix], &session_key).await);
+    // the session is bound to its store
+    let ix = session_buy(&market, 1, foreign_store, foreign_store_tokens);
+    assert!(!market.send(&[ix], &session_key).await);
+    assert_eq!(
+        session_info(market.banks_client.get_account(session).await.unwrap())
+            .unwrap()
+            .spent_notional,
+        15
+    );
+
+    let buy_after = session_buy(&market, 1, market.store, market.store_store_tokens);
+    market.set_unix_timestamp(200).await;
+    assert!(!market.send(&[buy_after.clone()], &session_key).await);
+
+    market.set_unix_timestamp(100).await;
+    assert!(market.send(&[revoke], &user).await);
+    assert!(session_info(market.banks_client.get_account(session).await.unwrap()).is_none());
+    assert!(!market.send(&[buy_after], &session_key).await);
+}
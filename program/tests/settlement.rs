@@ -0,0 +1,106 @@
+mod common;
+
+use common::Market;
+use solana_program::program_pack::Pack;
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_test::{
+    instruction,
+    state::{OrderSide, SettlementReport, DAY_SECONDS},
+};
+
+#[tokio::test]
+async fn test_daily_settlement() {
+    const PRICE: u64 = 5;
+    const CUTOFF: u32 = 3_600;
+    const DAY: i64 = 10;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let payer = Keypair::from_bytes(&market.payer.to_bytes()).unwrap();
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let program_id = market.program_id;
+
+    // the cutoff of the day passed already, the first one to settle is tomorrow's
+    market.set_unix_timestamp(DAY * DAY_SECONDS + 4_000).await;
+    let set_cutoff = instruction::set_settlement_cutoff_instruction(
+        Some(CUTOFF),
+        &program_id,
+        &owner.pubkey(),
+        &market.store,
+    )
+    .unwrap();
+    assert!(market.send(&[set_cutoff], &owner).await);
+    assert!(market.buy(0, 10, PRICE).await);
+
+    let settle = |market: &Market, day| {
+        instruction::settle_day_instruction(
+            day,
+            &program_id,
+            &payer.pubkey(),
+            &market.store,
+            &market.store_store_tokens,
+            &market.store_payment_tokens,
+        )
+        .unwrap()
+    };
+    let ix = settle(&market, DAY);
+    assert!(!market.send(&[ix], &payer).await);
+
+    // trades are frozen from the next cutoff until the day is settled
+    market
+        .set_unix_timestamp((DAY + 1) * DAY_SECONDS + CUTOFF as i64)
+        .await;
+    assert!(!market.buy(0, 10, PRICE).await);
+    assert!(!market.sell(0, 10, PRICE).await);
+    // and so are the store vault balances the settlement snapshots
+    let withdraw = instruction::withdraw_instruction(
+        10,
+        &program_id,
+        &owner.pubkey(),
+        &market.store,
+        &market.store_store_tokens,
+        &market.payout,
+        &market.pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert!(!market.send(&[withdraw], &owner).await);
+    let deposit = instruction::deposit_instruction(
+        10,
+        OrderSide::Sell,
+        &program_id,
+        &owner.pubkey(),
+        &market.store,
+        &market.proceeds,
+        &market.store_payment_tokens,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert!(!market.send(&[deposit], &owner).await);
+    let ix = settle(&market, DAY);
+    assert!(!market.send(&[ix], &payer).await);
+    let ix = settle(&market, DAY + 1);
+    assert!(market.send(&[ix], &payer).await);
+    assert!(market.buy(0, 10, PRICE).await);
+    // settled once a day
+    let ix = settle(&market, DAY + 1);
+    assert!(!market.send(&[ix], &payer).await);
+
+    let report_pubkey =
+        instruction::find_settlement_report_address(&program_id, &market.store, DAY + 1);
+    let account = market
+        .banks_client
+        .get_account(report_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    let report = SettlementReport::unpack(&account.data).unwrap();
+    let store_store_tokens = market.store_store_tokens;
+    assert_eq!(report.store_pubkey, market.store);
+    assert_eq!((report.day, report.previous_day), (DAY + 1, DAY));
+    assert_eq!(report.trade_count, 1);
+    assert_eq!(report.total_buy_volume, 10 * PRICE);
+    assert_eq!(
+        report.store_tokens,
+        market.token_balance(&store_store_tokens).await + 10
+    );
+}
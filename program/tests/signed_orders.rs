@@ -0,0 +1,269 @@
+mod common;
+
+use std::convert::TryFrom;
+
+use common::Market;
+use solana_program::{
+    instruction::Instruction, program_pack::Pack, pubkey::Pubkey, system_instruction,
+};
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_test::{
+    instruction::{self, Pricing},
+    state::{OrderSide, SignedOrder},
+};
+
+const INITIAL_TOKENS: u64 = 1_000;
+const PRICE: u64 = 5;
+
+/// Market with user 0 tokens delegated to its order authority
+async fn start() -> Market {
+    let mut market = Market::start(1, INITIAL_TOKENS, PRICE).await;
+    let user = &market.users[0];
+    let approve = instruction::approve_order_authority_instruction(
+        INITIAL_TOKENS,
+        &market.program_id,
+        &user.keypair.pubkey(),
+        &user.payment_tokens,
+        &spl_token::id(),
+    )
+    .unwrap();
+    let signer = Keypair::from_bytes(&user.keypair.to_bytes()).unwrap();
+    assert!(market.send(&[approve], &signer).await);
+    market
+}
+
+fn order(market: &Market, nonce: u64) -> SignedOrder {
+    SignedOrder {
+        store_pubkey: market.store,
+        side: OrderSide::Buy,
+        amount: 10,
+        limit_price: PRICE,
+        expires_at: i64::MAX,
+        nonce,
+    }
+}
+
+/// Ed25519 instruction with `signer` signature of `order` for user 0 wallet, and the settlement
+fn settle(market: &Market, signer: &Keypair, order: &SignedOrder) -> Vec<Instruction> {
+    settle_to(market, signer, order, &market.proceeds)
+}
+
+/// Same as `settle`, the store receives payment tokens to `store_payment_tokens`
+fn settle_to(
+    market: &Market,
+    signer: &Keypair,
+    order: &SignedOrder,
+    store_payment_tokens: &Pubkey,
+) -> Vec<Instruction> {
+    let user = &market.users[0];
+    let wallet = user.keypair.pubkey();
+    let signature = signer.sign_message(&instruction::signed_order_message(order));
+    vec![
+        instruction::signed_order_ed25519_instruction(
+            &wallet,
+            &<[u8; 64]>::try_from(signature.as_ref()).unwrap(),
+            order,
+        ),
+        instruction::settle_signed_order_instruction(
+            &market.program_id,
+            &wallet,
+            order.nonce,
+            &market.payer.pubkey(),
+            &market.store,
+            store_payment_tokens,
+            &market.store_store_tokens,
+            &user.payment_tokens,
+            &user.store_tokens,
+            &market.pda,
+            &spl_token::id(),
+        )
+        .unwrap(),
+    ]
+}
+
+/// Sets `index`-th offset of the ed25519 instruction
+fn set_offset(ed25519_ix: &mut Instruction, index: usize, value: u16) {
+    ed25519_ix.data[2 + index * 2..4 + index * 2].copy_from_slice(&value.to_le_bytes());
+}
+
+#[tokio::test]
+async fn test_settle_signed_order_checks_signature() {
+    let mut market = start().await;
+    let wallet = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let relayer = Keypair::from_bytes(&market.payer.to_bytes()).unwrap();
+    let user_store_tokens = market.users[0].store_tokens;
+
+    let wrong_signer = settle(&market, &Keypair::new(), &order(&market, 1));
+
+    let mut tampered = settle(&market, &wallet, &order(&market, 2));
+    // order amount follows the store key and the side in the message
+    let amount_offset = tampered[0].data.len() - SignedOrder::LEN + 33;
+    tampered[0].data[amount_offset] += 1;
+
+    let mut public_key_at_signature = settle(&market, &wallet, &order(&market, 3));
+    let signature_offset = u16::from_le_bytes([
+        public_key_at_signature[0].data[2],
+        public_key_at_signature[0].data[3],
+    ]);
+    set_offset(&mut public_key_at_signature[0], 2, signature_offset);
+
+    let mut short_message = settle(&market, &wallet, &order(&market, 4));
+    set_offset(&mut short_message[0], 5, SignedOrder::LEN as u16 - 8);
+
+    // the signature is verified against the data of the settle instruction
+    let mut other_instruction = settle(&market, &wallet, &order(&market, 5));
+    set_offset(&mut other_instruction[0], 1, 1);
+
+    let mut not_preceding = settle(&market, &wallet, &order(&market, 6));
+    not_preceding.insert(
+        1,
+        system_instruction::transfer(&relayer.pubkey(), &market.owner.pubkey(), 1),
+    );
+
+    let missing = settle(&market, &wallet, &order(&market, 7)).split_off(1);
+
+    for instructions in [
+        &wrong_signer,
+        &tampered,
+        &public_key_at_signature,
+        &short_message,
+        &other_instruction,
+        &not_preceding,
+        &missing,
+    ] {
+        assert!(!market.send(instructions, &relayer).await);
+    }
+    assert_eq!(
+        market.token_balance(&user_store_tokens).await,
+        INITIAL_TOKENS
+    );
+
+    let valid = settle(&market, &wallet, &order(&market, 8));
+    assert!(market.send(&valid, &relayer).await);
+    assert_eq!(
+        market.token_balance(&user_store_tokens).await,
+        INITIAL_TOKENS + 10
+    );
+}
+
+#[tokio::test]
+async fn test_signed_order_nonce_used_once() {
+    let mut market = start().await;
+    let wallet = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let relayer = Keypair::from_bytes(&market.payer.to_bytes()).unwrap();
+    let user_store_tokens = market.users[0].store_tokens;
+
+    let settle_first = settle(&market, &wallet, &order(&market, 1));
+    assert!(market.send(&settle_first, &relayer).await);
+    // replays differ only in the harness transfer, as a new transaction would
+    assert!(!market.send(&settle_first, &relayer).await);
+    assert_eq!(
+        market.token_balance(&user_store_tokens).await,
+        INITIAL_TOKENS + 10
+    );
+
+    let cancel = instruction::cancel_signed_order_instruction(
+        2,
+        &market.program_id,
+        &wallet.pubkey(),
+        &wallet.pubkey(),
+    )
+    .unwrap();
+    assert!(market.send(&[cancel.clone()], &wallet).await);
+    assert!(!market.send(&[cancel], &wallet).await);
+    let settle_cancelled = settle(&market, &wallet, &order(&market, 2));
+    assert!(!market.send(&settle_cancelled, &relayer).await);
+    assert_eq!(
+        market.token_balance(&user_store_tokens).await,
+        INITIAL_TOKENS + 10
+    );
+
+    // nonces of the same bitmap page stay usable
+    let settle_other = settle(&market, &wallet, &order(&market, 3));
+    assert!(market.send(&settle_other, &relayer).await);
+}
+
+#[tokio::test]
+async fn test_signed_order_at_auction_price() {
+    let mut market = start().await;
+    let wallet = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let relayer = Keypair::from_bytes(&market.payer.to_bytes()).unwrap();
+    let user_payment_tokens = market.users[0].payment_tokens;
+    let set_pricing = instruction::set_pricing_instruction(
+        Pricing::DutchAuction {
+            start_price: 20,
+            floor_price: 10,
+            start_slot: 100,
+            end_slot: 200,
+        },
+        &market.program_id,
+        &owner.pubkey(),
+        &market.store,
+    )
+    .unwrap();
+    assert!(market.send(&[set_pricing], &owner).await);
+
+    // the order limit is checked against the auction price, not the fixed store price
+    let below_auction = SignedOrder {
+        limit_price: 19,
+        ..order(&market, 1)
+    };
+    let settle_below = settle(&market, &wallet, &below_auction);
+    assert!(!market.send(&settle_below, &relayer).await);
+    let at_auction = SignedOrder {
+        limit_price: 20,
+        ..order(&market, 2)
+    };
+    let settle_at = settle(&market, &wallet, &at_auction);
+    assert!(market.send(&settle_at, &relayer).await);
+    assert_eq!(
+        market.token_balance(&user_payment_tokens).await,
+        INITIAL_TOKENS - 10 * 20
+    );
+}
+
+#[tokio::test]
+async fn test_signed_order_on_curve() {
+    let mut market = start().await;
+    let wallet = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let relayer = Keypair::from_bytes(&market.payer.to_bytes()).unwrap();
+    let (user_payment_tokens, store_payment_tokens) =
+        (market.users[0].payment_tokens, market.store_payment_tokens);
+    let set_pricing = instruction::set_pricing_instruction(
+        Pricing::ConstantProduct {
+            virtual_payment_reserve: INITIAL_TOKENS,
+            virtual_store_reserve: 0,
+        },
+        &market.program_id,
+        &owner.pubkey(),
+        &market.store,
+    )
+    .unwrap();
+    assert!(market.send(&[set_pricing], &owner).await);
+
+    // the reserves are 2000 payment and 1000 store tokens, 10 cost 2000 * 10 / 990 rounded up,
+    // above the limit of 2 and below the fixed store price
+    let below_curve = SignedOrder {
+        limit_price: 2,
+        ..order(&market, 1)
+    };
+    let settle_below = settle_to(&market, &wallet, &below_curve, &store_payment_tokens);
+    assert!(!market.send(&settle_below, &relayer).await);
+    let within_curve = SignedOrder {
+        limit_price: 3,
+        ..order(&market, 2)
+    };
+    let settle_within = settle_to(&market, &wallet, &within_curve, &store_payment_tokens);
+    assert!(market.send(&settle_within, &relayer).await);
+    assert_eq!(
+        market.token_balance(&user_payment_tokens).await,
+        INITIAL_TOKENS - 21
+    );
+    assert_eq!(
+        market.token_balance(&store_payment_tokens).await,
+        INITIAL_TOKENS + 21
+    );
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use std::env;
+
+use common::{Market, Rng};
+use solana_program_test::tokio;
+
+const USERS: usize = 5;
+const INITIAL_TOKENS: u64 = 10_000_000;
+
+fn env_or(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Random sequence of buys, sells, price updates and owner deposits across users,
+/// checking tokens of each mint are conserved throughout.
+/// Set `SIMULATION_STEPS` for longer runs and `SIMULATION_SEED` to replay a sequence.
+#[tokio::test]
+async fn test_random_trade_sequence() {
+    let steps = env_or("SIMULATION_STEPS", 200);
+    let seed = env_or("SIMULATION_SEED", 0x51_3a1);
+    println!("simulation seed {} steps {}", seed, steps);
+
+    let mut price = 10;
+    let mut market = Market::start(USERS, INITIAL_TOKENS, price).await;
+    let totals = market.token_totals().await;
+
+    let mut rng = Rng::new(seed);
+    for step in 0..steps {
+        let user = rng.below(USERS as u64) as usize;
+        let amount = 1 + rng.below(20_000);
+        match rng.below(10) {
+            0..=3 => {
+                market.buy(user, amount, price).await;
+            }
+            4..=7 => {
+                market.sell(user, amount, price).await;
+            }
+            8 => {
+                let new_price = 1 + rng.below(50);
+                if market.update_price(new_price).await {
+                    price = new_price;
+                }
+            }
+            _ => {
+                market.deposit(rng.below(100_000), rng.below(10_000)).await;
+            }
+        }
+        // a buy with max price below the store price must be rejected
+        if rng.below(20) == 0 {
+            assert!(!market.buy(user, 1, price - 1).await, "step {}", step);
+        }
+
+        assert_eq!(market.token_totals().await, totals, "step {}", step);
+    }
+
+    let store = market.store_info().await;
+    assert_eq!(store.buy_price, price);
+}
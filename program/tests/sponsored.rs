@@ -0,0 +1,47 @@
+mod common;
+
+use common::Market;
+use solana_program::program_pack::Pack;
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_test::{instruction, state::WaitlistEntry};
+
+/// User signs trades and account-creating instructions, while the harness payer pays
+/// the transaction fees and the rent, as a relayer would
+#[tokio::test]
+async fn test_relayer_pays_for_user_instructions() {
+    let mut market = Market::start(1, 1_000, 5).await;
+    let wallet = market.users[0].keypair.pubkey();
+    let relayer = market.payer.pubkey();
+    let lamports = market.banks_client.get_balance(wallet).await.unwrap();
+
+    assert!(market.buy(0, 10, 5).await);
+    assert!(market.sell(0, 10, 5).await);
+
+    let join = instruction::join_waitlist_instruction(
+        5_000,
+        &market.program_id,
+        &wallet,
+        &relayer,
+        &market.store,
+        &market.store_store_tokens,
+    )
+    .unwrap();
+    let signer = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    assert!(market.send(&[join], &signer).await);
+
+    let waitlist = instruction::find_waitlist_address(&market.program_id, &market.store, &wallet);
+    let account = market
+        .banks_client
+        .get_account(waitlist)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.owner, market.program_id);
+    assert_eq!(WaitlistEntry::unpack(&account.data).unwrap().amount, 5_000);
+
+    assert_eq!(
+        market.banks_client.get_balance(wallet).await.unwrap(),
+        lamports
+    );
+}
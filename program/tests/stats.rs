@@ -0,0 +1,162 @@
+mod common;
+
+use common::{create_token_account, Market};
+use solana_program::{program_pack::Pack, pubkey::Pubkey, system_instruction};
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer, transaction::Transaction};
+use solana_test::{
+    instruction,
+    state::{MintStats, ProgramStats, Store},
+};
+
+#[tokio::test]
+async fn test_program_stats() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let payer = Keypair::from_bytes(&market.payer.to_bytes()).unwrap();
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let user = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let program_id = market.program_id;
+    let init_stats =
+        instruction::initialize_stats_instruction(&program_id, &payer.pubkey()).unwrap();
+    assert!(market.send(&[init_stats], &payer).await);
+
+    let store_info = market.store_info().await;
+    let (payment_tokens, store_tokens) = (Pubkey::new_unique(), Pubkey::new_unique());
+    market.context.set_account(
+        &payment_tokens,
+        &create_token_account(owner.pubkey(), 1_000, store_info.payment_token_mint).into(),
+    );
+    market.context.set_account(
+        &store_tokens,
+        &create_token_account(owner.pubkey(), 1_000, store_info.store_token_mint).into(),
+    );
+    let payment_mint = store_info.payment_token_mint;
+    let store = Keypair::new();
+    let rent = market.banks_client.get_rent().await.unwrap();
+    let init = instruction::initialyze_account_instruction(
+        PRICE,
+        &program_id,
+        &owner.pubkey(),
+        &store.pubkey(),
+        &payment_tokens,
+        &store_tokens,
+        &spl_token::id(),
+    )
+    .unwrap();
+    // once the program stats exist, every store initialized is tracked
+    for (init, succeeds) in [
+        (init.clone(), false),
+        (instruction::with_stats(init, &payment_mint), true),
+    ] {
+        let instructions = [
+            system_instruction::create_account(
+                &owner.pubkey(),
+                &store.pubkey(),
+                rent.minimum_balance(Store::LEN),
+                Store::LEN as u64,
+                &program_id,
+            ),
+            init,
+        ];
+        let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &store, &owner], market.recent_blockhash);
+        let result = market.banks_client.process_transaction(transaction).await;
+        assert_eq!(result.is_ok(), succeeds);
+    }
+
+    let pda = instruction::find_store_authority_address(&program_id, &store.pubkey());
+    let buy = instruction::buy_instruction(
+        10,
+        PRICE,
+        &program_id,
+        &user.pubkey(),
+        &store.pubkey(),
+        &market.proceeds,
+        &store_tokens,
+        &market.users[0].payment_tokens,
+        &market.users[0].store_tokens,
+        &pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    let sell = instruction::sell_instruction(
+        4,
+        PRICE,
+        &program_id,
+        &user.pubkey(),
+        &store.pubkey(),
+        &payment_tokens,
+        &market.payout,
+        &market.users[0].payment_tokens,
+        &market.users[0].store_tokens,
+        &pda,
+        &spl_token::id(),
+    )
+    .unwrap();
+    // trades of a tracked store must update the stats
+    assert!(!market.send(&[buy.clone()], &user).await);
+    assert!(
+        market
+            .send(&[instruction::with_stats(buy, &payment_mint)], &user)
+            .await
+    );
+    assert!(
+        market
+            .send(&[instruction::with_stats(sell, &payment_mint)], &user)
+            .await
+    );
+    // trades of untracked stores don't
+    assert!(market.buy(0, 10, PRICE).await);
+
+    let stats = program_stats(&mut market).await;
+    assert_eq!(stats.store_count, 1);
+    let mint_stats = mint_stats(&mut market, &payment_mint).await;
+    assert_eq!(mint_stats.store_count, 1);
+    assert_eq!(mint_stats.volume, 14 * PRICE);
+    assert_eq!(mint_stats.fees, 0);
+
+    // deleting the store stops counting it
+    let close = instruction::close_store_instruction(
+        &program_id,
+        &owner.pubkey(),
+        &store.pubkey(),
+        &payment_tokens,
+        &store_tokens,
+        &pda,
+        &spl_token::id(),
+        &[],
+    )
+    .unwrap();
+    assert!(!market.send(&[close.clone()], &owner).await);
+    assert!(
+        market
+            .send(&[instruction::with_stats(close, &payment_mint)], &owner)
+            .await
+    );
+    assert_eq!(program_stats(&mut market).await.store_count, 0);
+    let mint_stats = mint_stats(&mut market, &payment_mint).await;
+    assert_eq!(mint_stats.store_count, 0);
+    assert_eq!(mint_stats.volume, 14 * PRICE);
+}
+
+async fn program_stats(market: &mut Market) -> ProgramStats {
+    let account = market
+        .banks_client
+        .get_account(instruction::find_stats_address(&market.program_id))
+        .await
+        .unwrap()
+        .unwrap();
+    ProgramStats::unpack(&account.data).unwrap()
+}
+
+async fn mint_stats(market: &mut Market, payment_mint: &Pubkey) -> MintStats {
+    let stats_pubkey = instruction::find_mint_stats_address(&market.program_id, payment_mint);
+    let account = market
+        .banks_client
+        .get_account(stats_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    MintStats::unpack(&account.data).unwrap()
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use common::Market;
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_test::instruction;
+
+#[tokio::test]
+async fn test_trading_window() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let set_window = |market: &Market, open_at_slot, close_at_slot| {
+        instruction::set_trading_window_instruction(
+            open_at_slot,
+            close_at_slot,
+            &market.program_id,
+            &owner.pubkey(),
+            &market.store,
+        )
+        .unwrap()
+    };
+    // the window closes after it opens
+    let ix = set_window(&market, 100, 100);
+    assert!(!market.send(&[ix], &owner).await);
+    let ix = set_window(&market, 100, 200);
+    assert!(market.send(&[ix], &owner).await);
+    let store_info = market.store_info().await;
+    assert_eq!(
+        (store_info.open_at_slot, store_info.close_at_slot),
+        (100, 200)
+    );
+
+    assert!(!market.buy(0, 10, PRICE).await);
+    assert!(!market.sell(0, 10, PRICE).await);
+    market.warp_to_slot(100);
+    assert!(market.buy(0, 10, PRICE).await);
+    assert!(market.sell(0, 10, PRICE).await);
+    market.warp_to_slot(200);
+    assert!(!market.buy(0, 10, PRICE).await);
+    assert!(!market.sell(0, 10, PRICE).await);
+
+    // the owner reopens the market without an end
+    let ix = set_window(&market, 0, 0);
+    assert!(market.send(&[ix], &owner).await);
+    assert!(market.buy(0, 10, PRICE).await);
+}
+
+#[tokio::test]
+async fn test_trading_window_set_at_init() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let program_id = market.program_id;
+    let close = instruction::close_store_instruction(
+        &program_id,
+        &owner.pubkey(),
+        &market.store,
+        &market.store_payment_tokens,
+        &market.store_store_tokens,
+        &market.pda,
+        &spl_token::id(),
+        &[],
+    )
+    .unwrap();
+    let reactivate =
+        instruction::reactivate_store_instruction(&program_id, &owner.pubkey(), &market.store)
+            .unwrap();
+    assert!(market.send(&[close, reactivate], &owner).await);
+
+    let initialize = |open_at_slot, close_at_slot| {
+        instruction::with_trading_window(
+            instruction::initialyze_account_instruction(
+                PRICE,
+                &program_id,
+                &owner.pubkey(),
+                &market.store,
+                &market.store_payment_tokens,
+                &market.store_store_tokens,
+                &spl_token::id(),
+            )
+            .unwrap(),
+            open_at_slot,
+            close_at_slot,
+        )
+        .unwrap()
+    };
+    let ix = initialize(100, 50);
+    assert!(!market.send(&[ix], &owner).await);
+    let ix = initialize(100, 200);
+    assert!(market.send(&[ix], &owner).await);
+    let store_info = market.store_info().await;
+    assert_eq!(
+        (store_info.open_at_slot, store_info.close_at_slot),
+        (100, 200)
+    );
+
+    assert!(!market.buy(0, 10, PRICE).await);
+    market.warp_to_slot(100);
+    assert!(market.buy(0, 10, PRICE).await);
+}
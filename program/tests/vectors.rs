@@ -0,0 +1,234 @@
+//! Instruction encoding vectors shared with clients.
+//!
+//! Each line of `vectors/instructions.txt` is an instruction name, its data in hex
+//! and its accounts as `pubkey:flags`, where flags are `s` for signer and `w` for writable.
+//! Regenerate with `UPDATE_VECTORS=1 cargo test --test vectors` after intentional layout changes.
+
+use std::{env, fs, path::PathBuf};
+
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_test::{
+    instruction::{self, BalanceAssertion, BalanceCheck},
+    state::{OrderSide, SignedOrder},
+};
+
+fn key(n: u8) -> Pubkey {
+    Pubkey::new_from_array([n; 32])
+}
+
+fn vectors() -> Vec<(&'static str, Instruction)> {
+    let program_id = key(1);
+    let owner = key(2);
+    let store = key(3);
+    let store_payment_tokens = key(4);
+    let store_store_tokens = key(5);
+    let user = key(6);
+    let user_payment_tokens = key(7);
+    let user_store_tokens = key(8);
+    let pda = key(9);
+    let cosigner = key(10);
+
+    vec![
+        (
+            "initialize_account",
+            instruction::initialyze_account_instruction(
+                100,
+                &program_id,
+                &owner,
+                &store,
+                &store_payment_tokens,
+                &store_store_tokens,
+                &spl_token::id(),
+            )
+            .unwrap(),
+        ),
+        (
+            "update_price",
+            instruction::update_price_instruction(200, &program_id, &owner, &store).unwrap(),
+        ),
+        (
+            "update_lot_size",
+            instruction::update_lot_size_instruction(10, &program_id, &owner, &store).unwrap(),
+        ),
+        (
+            "set_inventory_thresholds",
+            instruction::set_inventory_thresholds_instruction(
+                1_000,
+                50_000,
+                &program_id,
+                &owner,
+                &store,
+            )
+            .unwrap(),
+        ),
+        (
+            "set_cosigner",
+            instruction::set_cosigner_instruction(
+                1_000_000,
+                &program_id,
+                &owner,
+                &store,
+                &cosigner,
+            )
+            .unwrap(),
+        ),
+        (
+            "buy",
+            instruction::buy_instruction(
+                3,
+                200,
+                &program_id,
+                &user,
+                &store,
+                &store_payment_tokens,
+                &store_store_tokens,
+                &user_payment_tokens,
+                &user_store_tokens,
+                &pda,
+                &spl_token::id(),
+            )
+            .unwrap(),
+        ),
+        (
+            "buy_tagged",
+            instruction::with_tag(
+                instruction::buy_instruction(
+                    3,
+                    200,
+                    &program_id,
+                    &user,
+                    &store,
+                    &store_payment_tokens,
+                    &store_store_tokens,
+                    &user_payment_tokens,
+                    &user_store_tokens,
+                    &pda,
+                    &spl_token::id(),
+                )
+                .unwrap(),
+                7,
+            )
+            .unwrap(),
+        ),
+        (
+            "sell_with_cosigner",
+            instruction::with_cosigner(
+                instruction::sell_instruction(
+                    6,
+                    200,
+                    &program_id,
+                    &user,
+                    &store,
+                    &store_payment_tokens,
+                    &store_store_tokens,
+                    &user_payment_tokens,
+                    &user_store_tokens,
+                    &pda,
+                    &spl_token::id(),
+                )
+                .unwrap(),
+                &cosigner,
+            ),
+        ),
+        (
+            "report_pnl",
+            instruction::report_pnl_instruction(&program_id, &store).unwrap(),
+        ),
+        (
+            "preview_trade",
+            instruction::preview_trade_instruction(
+                OrderSide::Sell,
+                6,
+                200,
+                &program_id,
+                &user,
+                &store,
+                &store_payment_tokens,
+                &store_store_tokens,
+                &user_payment_tokens,
+                &user_store_tokens,
+                &pda,
+                &spl_token::id(),
+            )
+            .unwrap(),
+        ),
+        (
+            "assert_balances",
+            instruction::assert_balances_instruction(
+                &program_id,
+                &[
+                    (
+                        user_payment_tokens,
+                        BalanceAssertion {
+                            check: BalanceCheck::AtLeast,
+                            amount: 1,
+                        },
+                    ),
+                    (
+                        user_store_tokens,
+                        BalanceAssertion {
+                            check: BalanceCheck::Exactly,
+                            amount: 600,
+                        },
+                    ),
+                ],
+            )
+            .unwrap(),
+        ),
+        (
+            "signed_order_ed25519",
+            instruction::signed_order_ed25519_instruction(
+                &user,
+                &[11; 64],
+                &SignedOrder {
+                    store_pubkey: store,
+                    side: OrderSide::Buy,
+                    amount: 100,
+                    limit_price: 200,
+                    expires_at: 1_700_000_000,
+                    nonce: 7,
+                },
+            ),
+        ),
+    ]
+}
+
+fn render(name: &str, instruction: &Instruction) -> String {
+    let data: String = instruction
+        .data
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let accounts: Vec<String> = instruction
+        .accounts
+        .iter()
+        .map(|meta| {
+            format!(
+                "{}:{}{}",
+                meta.pubkey,
+                if meta.is_signer { "s" } else { "-" },
+                if meta.is_writable { "w" } else { "-" }
+            )
+        })
+        .collect();
+    format!("{} {} {}", name, data, accounts.join(" "))
+}
+
+#[test]
+fn test_instruction_vectors() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/vectors/instructions.txt");
+    let rendered: String = vectors()
+        .iter()
+        .map(|(name, instruction)| render(name, instruction) + "\n")
+        .collect();
+
+    if env::var_os("UPDATE_VECTORS").is_some() {
+        fs::write(&path, &rendered).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap();
+    for (expected, rendered) in expected.lines().zip(rendered.lines()) {
+        assert_eq!(expected, rendered);
+    }
+    assert_eq!(expected.lines().count(), rendered.lines().count());
+}
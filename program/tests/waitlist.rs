@@ -0,0 +1,108 @@
+mod common;
+
+use common::Market;
+use solana_program::{program_pack::Pack, pubkey::Pubkey, system_instruction};
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_test::{instruction, state::WaitlistEntry};
+
+#[tokio::test]
+async fn test_waitlist_join_leave_and_fill_order() {
+    let mut market = Market::start(2, 1_000, 5).await;
+    let users: Vec<Keypair> = market
+        .users
+        .iter()
+        .map(|user| Keypair::from_bytes(&user.keypair.to_bytes()).unwrap())
+        .collect();
+    let payer = Keypair::from_bytes(&market.payer.to_bytes()).unwrap();
+    let funding: Vec<_> = users
+        .iter()
+        .map(|user| system_instruction::transfer(&payer.pubkey(), &user.pubkey(), 1_000_000_000))
+        .collect();
+    assert!(market.send(&funding, &payer).await);
+
+    let join = |market: &Market, user: &Keypair, amount| {
+        instruction::join_waitlist_instruction(
+            amount,
+            &market.program_id,
+            &user.pubkey(),
+            &user.pubkey(),
+            &market.store,
+            &market.store_store_tokens,
+        )
+        .unwrap()
+    };
+    let leave = instruction::leave_waitlist_instruction(
+        &market.program_id,
+        &users[0].pubkey(),
+        &market.store,
+    )
+    .unwrap();
+    let waitlist = |market: &Market, user: &Keypair| {
+        instruction::find_waitlist_address(&market.program_id, &market.store, &user.pubkey())
+    };
+    let entries = [waitlist(&market, &users[0]), waitlist(&market, &users[1])];
+
+    // the store can still cover it
+    let ix = join(&market, &users[0], 1_000);
+    assert!(!market.send(&[ix], &users[0]).await);
+
+    market.warp_to_slot(10);
+    let ix = join(&market, &users[0], 1_500);
+    assert!(market.send(&[ix], &users[0]).await);
+    market.warp_to_slot(12);
+    let ix = join(&market, &users[1], 1_200);
+    assert!(market.send(&[ix], &users[1]).await);
+    market.warp_to_slot(15);
+    let ix = join(&market, &users[0], 2_000);
+    assert!(market.send(&[ix], &users[0]).await);
+
+    let mut registrations = Vec::new();
+    for entry in &entries {
+        let account = market
+            .banks_client
+            .get_account(*entry)
+            .await
+            .unwrap()
+            .unwrap();
+        registrations.push(WaitlistEntry::unpack(&account.data).unwrap());
+    }
+    assert_eq!(
+        (registrations[0].amount, registrations[0].buyer_pubkey),
+        (2_000, users[0].pubkey())
+    );
+    assert_eq!(registrations[1].amount, 1_200);
+    // joining again kept the earlier registration first in the fill order
+    registrations.sort_by_key(|entry| entry.slot);
+    assert_eq!(
+        registrations
+            .iter()
+            .map(|entry| entry.buyer_pubkey)
+            .collect::<Vec<Pubkey>>(),
+        vec![users[0].pubkey(), users[1].pubkey()]
+    );
+
+    let balance = market
+        .banks_client
+        .get_balance(users[0].pubkey())
+        .await
+        .unwrap();
+    let rent = market.banks_client.get_balance(entries[0]).await.unwrap();
+    assert!(market.send(&[leave.clone()], &users[0]).await);
+    assert!(market
+        .banks_client
+        .get_account(entries[0])
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        market
+            .banks_client
+            .get_balance(users[0].pubkey())
+            .await
+            .unwrap(),
+        balance + rent
+    );
+    // leaving twice finds no entry
+    assert!(!market.send(&[leave], &users[0]).await);
+}
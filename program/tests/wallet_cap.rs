@@ -0,0 +1,147 @@
+mod common;
+
+use common::Market;
+use solana_program::{program_pack::Pack, pubkey::Pubkey, system_instruction};
+use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_test::{instruction, state::PurchaseLedger};
+
+#[tokio::test]
+async fn test_per_wallet_cap() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(1, 1_000, PRICE).await;
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let user = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let program_id = market.program_id;
+    let set_cap =
+        instruction::set_wallet_cap_instruction(15, &program_id, &owner.pubkey(), &market.store)
+            .unwrap();
+    assert!(market.send(&[set_cap], &owner).await);
+    assert_eq!(market.store_info().await.per_wallet_cap, 15);
+
+    let buy = |amount| {
+        instruction::buy_instruction(
+            amount,
+            PRICE,
+            &program_id,
+            &user.pubkey(),
+            &market.store,
+            &market.proceeds,
+            &market.store_store_tokens,
+            &market.users[0].payment_tokens,
+            &market.users[0].store_tokens,
+            &market.pda,
+            &spl_token::id(),
+        )
+        .unwrap()
+    };
+    let (first_buy, second_buy, last_buy) = (buy(10), buy(6), buy(5));
+    // the ledger is required once the store caps purchases
+    assert!(!market.send(&[first_buy.clone()], &user).await);
+
+    assert!(
+        market
+            .send(
+                &[instruction::with_purchase_ledger(
+                    first_buy,
+                    &user.pubkey(),
+                    &user.pubkey()
+                )],
+                &user
+            )
+            .await
+    );
+    let ledger_pubkey =
+        instruction::find_purchase_ledger_address(&program_id, &market.store, &user.pubkey());
+    let ledger_account = market
+        .banks_client
+        .get_account(ledger_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    let ledger = PurchaseLedger::unpack(&ledger_account.data).unwrap();
+    assert_eq!(ledger.buyer_pubkey, user.pubkey());
+    assert_eq!(ledger.amount, 10);
+
+    // 10 + 6 goes over the cap, 10 + 5 reaches it
+    assert!(
+        !market
+            .send(
+                &[instruction::with_purchase_ledger(
+                    second_buy,
+                    &user.pubkey(),
+                    &user.pubkey()
+                )],
+                &user
+            )
+            .await
+    );
+    assert!(
+        market
+            .send(
+                &[instruction::with_purchase_ledger(
+                    last_buy,
+                    &user.pubkey(),
+                    &user.pubkey()
+                )],
+                &user
+            )
+            .await
+    );
+    assert!(!market.buy(0, 1, PRICE).await);
+    let user_store_tokens = market.users[0].store_tokens;
+    assert_eq!(market.token_balance(&user_store_tokens).await, 1_000 + 15);
+}
+
+#[tokio::test]
+async fn test_purchase_ledger_of_recipient() {
+    const PRICE: u64 = 5;
+    let mut market = Market::start(2, 1_000, PRICE).await;
+    let payer = Keypair::from_bytes(&market.payer.to_bytes()).unwrap();
+    let owner = Keypair::from_bytes(&market.owner.to_bytes()).unwrap();
+    let user = Keypair::from_bytes(&market.users[0].keypair.to_bytes()).unwrap();
+    let recipient = market.users[1].keypair.pubkey();
+    let program_id = market.program_id;
+    let set_cap =
+        instruction::set_wallet_cap_instruction(15, &program_id, &owner.pubkey(), &market.store)
+            .unwrap();
+    assert!(market.send(&[set_cap], &owner).await);
+
+    // lamports sent to the ledger address beforehand don't block its creation
+    let ledger_pubkey =
+        instruction::find_purchase_ledger_address(&program_id, &market.store, &recipient);
+    let prefund = system_instruction::transfer(&payer.pubkey(), &ledger_pubkey, 1);
+    assert!(market.send(&[prefund], &payer).await);
+
+    let buy = |amount, wallet: &Pubkey| {
+        let ix = instruction::buy_instruction(
+            amount,
+            PRICE,
+            &program_id,
+            &user.pubkey(),
+            &market.store,
+            &market.proceeds,
+            &market.store_store_tokens,
+            &market.users[0].payment_tokens,
+            &market.users[1].store_tokens,
+            &market.pda,
+            &spl_token::id(),
+        )
+        .unwrap();
+        instruction::with_purchase_ledger(ix, wallet, &user.pubkey())
+    };
+    // the cap counts the wallet receiving store tokens, not the one paying
+    assert!(!market.send(&[buy(10, &user.pubkey())], &user).await);
+    assert!(market.send(&[buy(10, &recipient)], &user).await);
+    let ledger_account = market
+        .banks_client
+        .get_account(ledger_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(ledger_account.owner, program_id);
+    let ledger = PurchaseLedger::unpack(&ledger_account.data).unwrap();
+    assert_eq!(ledger.buyer_pubkey, recipient);
+    assert_eq!(ledger.amount, 10);
+    assert!(!market.send(&[buy(6, &recipient)], &user).await);
+}
@@ -0,0 +1,388 @@
+//! Build and deployment tasks, run with `cargo xtask <command>`
+//!
+//! Program keypairs are kept per cluster in `deploy/<cluster>/program-keypair.json`,
+//! so the same workspace can deploy separate program instances to each environment.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{exit, Command},
+};
+
+const USAGE: &str = "usage: cargo xtask <command>
+
+commands:
+    build-bpf                      build the program shared object
+    deploy --cluster <cluster>     build and deploy a new program instance
+    upgrade --cluster <cluster>    build and upgrade the deployed program
+    verify --cluster <cluster>     check deployed program matches the local build
+    verify-program --program-id <id> --cluster <cluster> --tag <tag>
+                                   build the repository at a git tag and check
+                                   the deployed program matches it
+    program-info --program-id <id> --cluster <cluster>
+                                   show upgrade authority and last deploy slot
+    cu-report                      profile compute units of the BPF build into
+                                   target/cu-report.md
+
+cluster is a solana cli moniker or url: localhost, devnet, testnet, mainnet-beta";
+
+const PROGRAM_SO: &str = "solana_test.so";
+const SYSTEM_PROGRAM: &str = "11111111111111111111111111111111";
+
+type Result<T> = std::result::Result<T, String>;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("error: {}", e);
+        exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<()> {
+    match args.first().map(String::as_str) {
+        Some("build-bpf") => build_bpf(),
+        Some("deploy") => {
+            let cluster = cluster_arg(&args[1..])?;
+            build_bpf()?;
+            deploy(&cluster, true)?;
+            verify(&cluster)
+        }
+        Some("upgrade") => {
+            let cluster = cluster_arg(&args[1..])?;
+            build_bpf()?;
+            deploy(&cluster, false)?;
+            verify(&cluster)
+        }
+        Some("verify") => verify(&cluster_arg(&args[1..])?),
+        Some("verify-program") => verify_program(
+            &option(&args[1..], "--program-id")?,
+            &option(&args[1..], "--cluster")?,
+            &option(&args[1..], "--tag")?,
+        ),
+        Some("program-info") => program_info(
+            &option(&args[1..], "--program-id")?,
+            &option(&args[1..], "--cluster")?,
+        ),
+        Some("cu-report") => cu_report(),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn cluster_arg(args: &[String]) -> Result<String> {
+    match args {
+        [flag, cluster] if flag == "--cluster" => Ok(cluster.clone()),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn option(args: &[String], name: &str) -> Result<String> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+        .ok_or_else(|| USAGE.to_string())
+}
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask is in the workspace")
+        .to_path_buf()
+}
+
+fn program_so() -> PathBuf {
+    workspace_root()
+        .join("target")
+        .join("deploy")
+        .join(PROGRAM_SO)
+}
+
+/// Keypair directory name for the cluster, urls are reduced to their host
+fn environment_name(cluster: &str) -> String {
+    let name = cluster
+        .trim_start_matches("https://")
+        .trim_start_matches("http://");
+    name.split(['/', ':']).next().unwrap_or(name).to_string()
+}
+
+fn program_keypair(cluster: &str) -> PathBuf {
+    workspace_root()
+        .join("deploy")
+        .join(environment_name(cluster))
+        .join("program-keypair.json")
+}
+
+fn build_bpf() -> Result<()> {
+    build_bpf_at(&workspace_root())
+}
+
+fn build_bpf_at(root: &Path) -> Result<()> {
+    run_command(
+        Command::new("cargo")
+            .arg("build-bpf")
+            .arg("--manifest-path")
+            .arg(root.join("program").join("Cargo.toml"))
+            .arg("--bpf-out-dir")
+            .arg(root.join("target").join("deploy")),
+    )
+}
+
+fn deploy(cluster: &str, new_instance: bool) -> Result<()> {
+    let keypair = program_keypair(cluster);
+    match (new_instance, keypair.exists()) {
+        (true, true) => {
+            return Err(format!(
+                "{} exists, use upgrade to update the deployed program",
+                keypair.display()
+            ))
+        }
+        (true, false) => {
+            fs::create_dir_all(keypair.parent().unwrap()).map_err(|e| e.to_string())?;
+            run_command(
+                Command::new("solana-keygen")
+                    .args(["new", "--no-bip39-passphrase", "--silent", "--outfile"])
+                    .arg(&keypair),
+            )?;
+        }
+        (false, false) => {
+            return Err(format!(
+                "{} not found, use deploy to create the program",
+                keypair.display()
+            ))
+        }
+        (false, true) => {}
+    }
+
+    run_command(
+        Command::new("solana")
+            .args(["program", "deploy", "--url", cluster, "--program-id"])
+            .arg(&keypair)
+            .arg(program_so()),
+    )
+}
+
+fn verify(cluster: &str) -> Result<()> {
+    let program_id = output_of(
+        Command::new("solana-keygen")
+            .arg("pubkey")
+            .arg(program_keypair(cluster)),
+    )?;
+    compare_deployed(&program_id, cluster, &program_so(), "the local build")
+}
+
+/// Builds the repository at `tag` in a separate worktree and compares it with the deployed program.
+/// Builds are only byte for byte reproducible with the same solana toolchain version.
+fn verify_program(program_id: &str, cluster: &str, tag: &str) -> Result<()> {
+    let root = workspace_root();
+    let worktree = root.join("target").join("verify").join(tag);
+    if !worktree.exists() {
+        run_command(
+            Command::new("git")
+                .arg("-C")
+                .arg(&root)
+                .args(["worktree", "add", "--detach"])
+                .arg(&worktree)
+                .arg(tag),
+        )?;
+    }
+    build_bpf_at(&worktree)?;
+
+    let so = worktree.join("target").join("deploy").join(PROGRAM_SO);
+    compare_deployed(program_id, cluster, &so, &format!("tag {}", tag))
+}
+
+/// Compares the deployed program data with the build at `so`,
+/// program data account may be larger than the build and is padded with zeros
+fn compare_deployed(program_id: &str, cluster: &str, so: &Path, build: &str) -> Result<()> {
+    let dump = workspace_root()
+        .join("target")
+        .join("deploy")
+        .join(format!("{}.dump.so", environment_name(cluster)));
+    run_command(
+        Command::new("solana")
+            .args(["program", "dump", "--url", cluster, program_id])
+            .arg(&dump),
+    )?;
+
+    let local = fs::read(so).map_err(|e| e.to_string())?;
+    let deployed = fs::read(&dump).map_err(|e| e.to_string())?;
+    let matches = deployed.len() >= local.len()
+        && deployed[..local.len()] == local[..]
+        && deployed[local.len()..].iter().all(|b| *b == 0);
+    if !matches {
+        return Err(format!(
+            "program {} on {} does not match {}",
+            program_id, cluster, build
+        ));
+    }
+    println!("program {} on {} matches {}", program_id, cluster, build);
+    Ok(())
+}
+
+/// Shows who can change the deployed program, warning when a single key can upgrade it
+fn program_info(program_id: &str, cluster: &str) -> Result<()> {
+    let show = output_of(Command::new("solana").args([
+        "program", "show", "--url", cluster, "--output", "json", program_id,
+    ]))?;
+    let authority = json_field(&show, "authority").unwrap_or_else(|| "none".to_string());
+    let last_deploy_slot = json_field(&show, "lastDeploySlot").unwrap_or_default();
+    println!("program: {}", program_id);
+    println!("upgrade authority: {}", authority);
+    println!("last deploy slot: {}", last_deploy_slot);
+
+    if authority == "none" {
+        println!("program is immutable");
+        return Ok(());
+    }
+    let account = output_of(
+        Command::new("solana").args(["account", "--url", cluster, "--output", "json", &authority]),
+    )?;
+    if json_field(&account, "owner").as_deref() == Some(SYSTEM_PROGRAM) {
+        println!("warning: program can be upgraded by a single key");
+    }
+    Ok(())
+}
+
+/// Value of the first `"name": value` in solana cli json output
+fn json_field(json: &str, name: &str) -> Option<String> {
+    let key = format!("\"{}\"", name);
+    let rest = json[json.find(&key)? + key.len()..]
+        .trim_start()
+        .strip_prefix(':')?
+        .trim_start();
+    let value = match rest.strip_prefix('"') {
+        Some(quoted) => &quoted[..quoted.find('"')?],
+        None => rest.split([',', '}']).next()?.trim(),
+    };
+    Some(value.to_string())
+}
+
+/// Runs the compute units workload test against the BPF build with the `cu-profile` feature
+/// and reports units spent in each section, the part of an instruction ending at a checkpoint
+fn cu_report() -> Result<()> {
+    let root = workspace_root();
+    let output = Command::new("cargo")
+        .arg("test-bpf")
+        .arg("--manifest-path")
+        .arg(root.join("program").join("Cargo.toml"))
+        .args([
+            "--features",
+            "cu-profile",
+            "--test",
+            "compute_units",
+            "--",
+            "--nocapture",
+        ])
+        .env(
+            "RUST_LOG",
+            env::var("RUST_LOG").unwrap_or_else(|_| {
+                "solana_program_runtime=debug,solana_runtime::message_processor=debug".to_string()
+            }),
+        )
+        .output()
+        .map_err(|e| format!("cargo test-bpf: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "cargo test-bpf failed: {}\n{}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    let logs = String::from_utf8_lossy(&output.stderr).to_string()
+        + &String::from_utf8_lossy(&output.stdout);
+
+    let report = render_cu_report(&cu_sections(&logs));
+    let path = root.join("target").join("cu-report.md");
+    fs::write(&path, &report).map_err(|e| e.to_string())?;
+    print!("{}", report);
+    println!("written to {}", path.display());
+    Ok(())
+}
+
+struct CuSection {
+    name: String,
+    calls: u64,
+    total: u64,
+    max: u64,
+}
+
+/// Units spent before each checkpoint since the previous one of the same top level instruction
+fn cu_sections(logs: &str) -> Vec<CuSection> {
+    let mut sections: Vec<CuSection> = Vec::new();
+    let mut checkpoint: Option<String> = None;
+    let mut previous_remaining: Option<u64> = None;
+    for line in logs.lines() {
+        if line.contains(" invoke [1]") {
+            previous_remaining = None;
+        } else if let Some(i) = line.find("Program log: CU checkpoint: ") {
+            checkpoint = Some(
+                line[i + "Program log: CU checkpoint: ".len()..]
+                    .trim()
+                    .to_string(),
+            );
+        } else if let Some(i) = line.find("Program consumption: ") {
+            let remaining = line[i + "Program consumption: ".len()..]
+                .split_whitespace()
+                .next()
+                .and_then(|units| units.parse::<u64>().ok());
+            let (name, remaining) = match (checkpoint.take(), remaining) {
+                (Some(name), Some(remaining)) => (name, remaining),
+                _ => continue,
+            };
+            if let Some(previous) = previous_remaining.replace(remaining) {
+                let units = previous.saturating_sub(remaining);
+                match sections.iter_mut().find(|section| section.name == name) {
+                    Some(section) => {
+                        section.calls += 1;
+                        section.total += units;
+                        section.max = section.max.max(units);
+                    }
+                    None => sections.push(CuSection {
+                        name,
+                        calls: 1,
+                        total: units,
+                        max: units,
+                    }),
+                }
+            }
+        }
+    }
+    sections
+}
+
+fn render_cu_report(sections: &[CuSection]) -> String {
+    let mut report = String::from(
+        "# Compute units per section\n\n| section | calls | average | max |\n|---|---:|---:|---:|\n",
+    );
+    for section in sections {
+        report += &format!(
+            "| {} | {} | {} | {} |\n",
+            section.name,
+            section.calls,
+            section.total / section.calls,
+            section.max
+        );
+    }
+    report
+}
+
+fn run_command(command: &mut Command) -> Result<()> {
+    let status = command
+        .status()
+        .map_err(|e| format!("{:?}: {}", command, e))?;
+    if !status.success() {
+        return Err(format!("{:?} failed: {}", command, status));
+    }
+    Ok(())
+}
+
+fn output_of(command: &mut Command) -> Result<String> {
+    let output = command
+        .output()
+        .map_err(|e| format!("{:?}: {}", command, e))?;
+    if !output.status.success() {
+        return Err(format!("{:?} failed: {}", command, output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}